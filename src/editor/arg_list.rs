@@ -0,0 +1,195 @@
+use crate::error::{EditorError, Result};
+use std::path::{Path, PathBuf};
+
+/// 引数リスト（起動時に指定したファイルの一覧と、編集中の位置）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArgList {
+    files: Vec<PathBuf>,
+    index: usize,
+}
+
+impl ArgList {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self { files, index: 0 }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// 編集中のファイルの位置（先頭が0）
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Path> {
+        self.files.get(index).map(PathBuf::as_path)
+    }
+
+    /// `:next`などで編集するファイルを移したときに位置を記録する
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index.min(self.files.len().saturating_sub(1));
+    }
+
+    /// `:[count]next`: `count`個先のファイルの位置
+    pub fn next(&self, count: usize) -> Result<usize> {
+        let index = self.index + count;
+        if index >= self.files.len() {
+            return Err(EditorError::invalid_command("Cannot go beyond last file"));
+        }
+        Ok(index)
+    }
+
+    /// `:[count]previous`: `count`個前のファイルの位置
+    pub fn previous(&self, count: usize) -> Result<usize> {
+        if self.files.is_empty() {
+            return Err(EditorError::invalid_command("Cannot go before first file"));
+        }
+        self.index
+            .checked_sub(count)
+            .ok_or_else(|| EditorError::invalid_command("Cannot go before first file"))
+    }
+
+    /// `:argadd`: `files`を`position`の位置（範囲外なら末尾）に追加する
+    pub fn add(&mut self, position: usize, files: Vec<PathBuf>) {
+        let position = position.min(self.files.len());
+        if position <= self.index && !self.files.is_empty() {
+            self.index += files.len();
+        }
+        self.files.splice(position..position, files);
+    }
+
+    /// `:argdelete {pattern}`: パターン（`*`と`?`を使える）に一致するファイルを取り除く
+    pub fn delete(&mut self, pattern: &str) -> Result<()> {
+        let before = self.files.len();
+        let mut index = 0;
+        let current = self.index;
+        self.files.retain(|file| {
+            let keep = !matches_pattern(pattern, &file.to_string_lossy());
+            if !keep && index < current {
+                self.index -= 1;
+            }
+            index += 1;
+            keep
+        });
+        if self.files.len() == before {
+            return Err(EditorError::invalid_command(format!(
+                "No match: {}",
+                pattern
+            )));
+        }
+        self.set_index(self.index);
+        Ok(())
+    }
+
+    /// `:[N]argdelete`: `index`のファイルを取り除く
+    pub fn remove(&mut self, index: usize) -> Result<()> {
+        if index >= self.files.len() {
+            return Err(EditorError::invalid_command("Invalid range"));
+        }
+        self.files.remove(index);
+        if index < self.index {
+            self.index -= 1;
+        }
+        self.set_index(self.index);
+        Ok(())
+    }
+
+    /// `:args`で表示する一覧（編集中のファイルは`[]`で囲む）
+    pub fn display(&self) -> String {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| {
+                if index == self.index {
+                    format!("[{}]", file.display())
+                } else {
+                    file.display().to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// `*`（任意の文字列）と`?`（任意の1文字）を使ったパターンに`name`全体が一致するか
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // `matched[j]`: パターンの先頭から見た部分が`name`の先頭`j`文字に一致するか
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for &ch in &pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match ch {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                _ => j > 0 && matched[j - 1] && name[j - 1] == ch,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arg_list(files: &[&str]) -> ArgList {
+        ArgList::new(files.iter().map(PathBuf::from).collect())
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut args = arg_list(&["a.rs", "b.rs", "c.rs"]);
+        assert_eq!(args.display(), "[a.rs] b.rs c.rs");
+        assert_eq!(args.next(2).unwrap(), 2);
+        assert!(args.next(3).is_err());
+        assert!(args.previous(1).is_err());
+
+        args.set_index(1);
+        assert_eq!(args.display(), "a.rs [b.rs] c.rs");
+        assert_eq!(args.previous(1).unwrap(), 0);
+        assert!(ArgList::default().previous(0).is_err());
+    }
+
+    #[test]
+    fn test_add_and_delete() {
+        let mut args = arg_list(&["a.rs", "b.rs"]);
+        args.set_index(1);
+        args.add(2, vec![PathBuf::from("c.rs")]);
+        args.add(0, vec![PathBuf::from("README.md")]);
+        assert_eq!(args.display(), "README.md a.rs [b.rs] c.rs");
+
+        args.delete("?.rs").unwrap();
+        assert_eq!(args.display(), "[README.md]");
+        assert!(args.delete("?.rs").is_err());
+
+        let mut args = arg_list(&["a.rs", "b.rs", "c.rs"]);
+        args.set_index(2);
+        args.remove(0).unwrap();
+        assert_eq!(args.display(), "b.rs [c.rs]");
+        args.remove(1).unwrap();
+        assert_eq!(args.display(), "[b.rs]");
+        assert!(args.remove(1).is_err());
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*", "src/main.rs"));
+        assert!(matches_pattern("src/*.rs", "src/main.rs"));
+        assert!(matches_pattern("m?in.rs", "main.rs"));
+        assert!(!matches_pattern("*.md", "main.rs"));
+        assert!(!matches_pattern("main", "main.rs"));
+    }
+}
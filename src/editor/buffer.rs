@@ -1,7 +1,14 @@
-use crate::editor::cursor::Position;
+use crate::editor::comment;
+use crate::editor::cursor::{Position, virtual_column};
+use crate::editor::mark::Marks;
+use crate::editor::options::{self, Options};
 use crate::error::{EditorError, Result};
+use crate::io::FileStamp;
 use std::path::PathBuf;
 
+/// undo/redoで戻す1つの操作
+///
+/// `InsertLine`と`DeleteLine`の`content`は、`position.row`から連続する行を`\n`でつないだもの。
 #[derive(Debug, Clone)]
 pub struct Action {
     pub action_type: ActionType,
@@ -17,13 +24,54 @@ pub enum ActionType {
     DeleteLine,
 }
 
+/// 1回のundo/redoで戻す操作のまとまり
+type UndoGroup = Vec<Action>;
+
+/// 読み込んだファイルの状態（通常のファイル以外はステータスラインに表示する）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileState {
+    #[default]
+    Loaded,
+    /// まだ存在しないファイル（最初の保存で作る）
+    New,
+    /// 読み込む権限がなく、空のバッファとして開いた
+    PermissionDenied,
+    /// ディレクトリのエントリの一覧
+    Directory,
+    /// 読み込んだ後に外部で削除された
+    Deleted,
+}
+
+impl FileState {
+    /// ステータスラインに表示する印
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            FileState::Loaded => None,
+            FileState::New => Some("[New]"),
+            FileState::PermissionDenied => Some("[Permission Denied]"),
+            FileState::Directory => Some("[Directory]"),
+            FileState::Deleted => Some("[Deleted]"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Buffer {
     lines: Vec<String>,
     file_path: Option<PathBuf>,
     modified: bool,
-    undo_stack: Vec<Action>,
-    redo_stack: Vec<Action>,
+    undo_stack: Vec<UndoGroup>,
+    redo_stack: Vec<UndoGroup>,
+    // 保存した時点のundo履歴の深さ（redoの履歴を捨てて戻れなくなったら`None`）
+    saved_depth: Option<usize>,
+    // begin_undo_group()のネスト数と、その間に記録された操作
+    group_depth: usize,
+    open_group: UndoGroup,
+    marks: Marks,
+    options: Options,
+    file_state: FileState,
+    // 読み込み・保存した時点のファイルの状態
+    stamp: Option<FileStamp>,
 }
 
 impl Buffer {
@@ -34,6 +82,13 @@ impl Buffer {
             modified: false,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            saved_depth: Some(0),
+            group_depth: 0,
+            open_group: Vec::new(),
+            marks: Marks::new(),
+            options: Options::default(),
+            file_state: FileState::Loaded,
+            stamp: None,
         }
     }
 
@@ -46,18 +101,44 @@ impl Buffer {
 
         Self {
             lines,
-            file_path: None,
-            modified: false,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            ..Self::new()
         }
     }
 
     pub fn with_file_path(mut self, path: PathBuf) -> Self {
+        self.options.filetype = options::detect_filetype(&path).map(str::to_string);
         self.file_path = Some(path);
         self
     }
 
+    /// `:w {file}`/`:saveas`: バッファのファイル名を付け替える（ファイルタイプも判定し直す）
+    pub fn set_file_path(&mut self, path: PathBuf) {
+        self.options.filetype = options::detect_filetype(&path).map(str::to_string);
+        self.file_path = Some(path);
+    }
+
+    pub fn with_file_state(mut self, state: FileState) -> Self {
+        self.file_state = state;
+        self
+    }
+
+    pub fn file_state(&self) -> FileState {
+        self.file_state
+    }
+
+    pub fn set_file_state(&mut self, state: FileState) {
+        self.file_state = state;
+    }
+
+    /// 読み込み・保存した時点のファイルの状態（外部で変更されたかの確認に使う）
+    pub fn stamp(&self) -> Option<&FileStamp> {
+        self.stamp.as_ref()
+    }
+
+    pub fn set_stamp(&mut self, stamp: Option<FileStamp>) {
+        self.stamp = stamp;
+    }
+
     pub fn line_count(&self) -> usize {
         self.lines.len()
     }
@@ -69,6 +150,10 @@ impl Buffer {
             .ok_or_else(|| EditorError::out_of_bounds(index, 0))
     }
 
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
     pub fn line_length(&self, index: usize) -> Result<usize> {
         self.lines
             .get(index)
@@ -76,6 +161,28 @@ impl Buffer {
             .ok_or_else(|| EditorError::out_of_bounds(index, 0))
     }
 
+    /// 行頭の空白を除いた最初の文字の列
+    pub fn first_non_blank(&self, index: usize) -> Result<usize> {
+        let line = self.line(index)?;
+        Ok(line.len() - line.trim_start_matches([' ', '\t']).len())
+    }
+
+    pub fn marks(&self) -> &Marks {
+        &self.marks
+    }
+
+    pub fn marks_mut(&mut self) -> &mut Marks {
+        &mut self.marks
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
     pub fn is_modified(&self) -> bool {
         self.modified
     }
@@ -85,26 +192,7 @@ impl Buffer {
     }
 
     pub fn insert_char(&mut self, pos: Position, ch: char) -> Result<()> {
-        if pos.row >= self.lines.len() {
-            return Err(EditorError::out_of_bounds(pos.row, pos.col));
-        }
-
-        let line = &mut self.lines[pos.row];
-        if pos.col > line.len() {
-            return Err(EditorError::out_of_bounds(pos.row, pos.col));
-        }
-
-        let action = Action {
-            action_type: ActionType::Insert,
-            position: pos,
-            content: ch.to_string(),
-        };
-
-        line.insert(pos.col, ch);
-        self.modified = true;
-        self.push_action(action);
-
-        Ok(())
+        self.insert_str(pos, &ch.to_string())
     }
 
     pub fn delete_char(&mut self, pos: Position) -> Result<Option<char>> {
@@ -112,36 +200,35 @@ impl Buffer {
             return Err(EditorError::out_of_bounds(pos.row, pos.col));
         }
 
-        let line = &mut self.lines[pos.row];
-        if pos.col >= line.len() {
+        let Some(ch) = self.lines[pos.row]
+            .get(pos.col..)
+            .and_then(|rest| rest.chars().next())
+        else {
             return Ok(None);
-        }
-
-        let deleted_char = line.remove(pos.col);
-        let action = Action {
-            action_type: ActionType::Delete,
-            position: pos,
-            content: deleted_char.to_string(),
         };
 
-        self.modified = true;
-        self.push_action(action);
-
-        Ok(Some(deleted_char))
+        self.delete_str(pos, ch.len_utf8())?;
+        Ok(Some(ch))
     }
 
     pub fn insert_line(&mut self, row: usize) -> Result<()> {
+        self.insert_line_with(row, String::new())
+    }
+
+    /// `row`に内容付きの行を挿入する
+    pub fn insert_line_with(&mut self, row: usize, content: String) -> Result<()> {
         if row > self.lines.len() {
             return Err(EditorError::out_of_bounds(row, 0));
         }
 
-        self.lines.insert(row, String::new());
+        self.lines.insert(row, content.clone());
+        self.marks.lines_inserted(row, 1);
         self.modified = true;
 
         let action = Action {
             action_type: ActionType::InsertLine,
             position: Position::new(row, 0),
-            content: String::new(),
+            content,
         };
         self.push_action(action);
 
@@ -154,21 +241,14 @@ impl Buffer {
         }
 
         if self.lines.len() == 1 {
+            // 最後の1行は削除せず空にする（undoで元に戻せるよう文字削除として記録）
             let content = self.lines[0].clone();
-            self.lines[0].clear();
-            self.modified = true;
-
-            let action = Action {
-                action_type: ActionType::DeleteLine,
-                position: Position::new(row, 0),
-                content: content.clone(),
-            };
-            self.push_action(action);
-
+            self.delete_str(Position::new(0, 0), content.len())?;
             return Ok(Some(content));
         }
 
         let deleted_line = self.lines.remove(row);
+        self.marks.lines_deleted(row, 1);
         self.modified = true;
 
         let action = Action {
@@ -181,8 +261,219 @@ impl Buffer {
         Ok(Some(deleted_line))
     }
 
+    /// `start`から`end`までの行を削除する（`end`を含む）
+    pub fn delete_lines(&mut self, start: usize, end: usize) -> Result<Vec<String>> {
+        if start > end || end >= self.lines.len() {
+            return Err(EditorError::out_of_bounds(end, 0));
+        }
+
+        // すべての行を消すときは、最後の1行を削除せず空にする
+        let whole = start == 0 && end == self.lines.len() - 1;
+        let drain_end = if whole { end } else { end + 1 };
+
+        self.begin_undo_group();
+        let mut deleted: Vec<String> = self.lines.drain(start..drain_end).collect();
+        if !deleted.is_empty() {
+            self.marks.lines_deleted(start, deleted.len());
+            self.modified = true;
+            self.push_action(Action {
+                action_type: ActionType::DeleteLine,
+                position: Position::new(start, 0),
+                content: deleted.join("\n"),
+            });
+        }
+        let result = if whole {
+            let content = self.lines[0].clone();
+            self.delete_str(Position::new(0, 0), content.len())
+                .map(|_| deleted.push(content))
+        } else {
+            Ok(())
+        };
+        self.end_undo_group();
+
+        result.map(|_| deleted)
+    }
+
+    /// 行内の`pos`に文字列（改行を含まない）を挿入する
+    pub fn insert_str(&mut self, pos: Position, text: &str) -> Result<()> {
+        let line = self.line(pos.row)?;
+        if pos.col > line.len() || !line.is_char_boundary(pos.col) {
+            return Err(EditorError::out_of_bounds(pos.row, pos.col));
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        self.lines[pos.row].insert_str(pos.col, text);
+        self.modified = true;
+        self.push_action(Action {
+            action_type: ActionType::Insert,
+            position: pos,
+            content: text.to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// 行内の`pos`から`len`バイトを削除する
+    pub fn delete_str(&mut self, pos: Position, len: usize) -> Result<String> {
+        let line = self.line(pos.row)?;
+        let end = pos.col + len;
+        if end > line.len() || !line.is_char_boundary(pos.col) || !line.is_char_boundary(end) {
+            return Err(EditorError::out_of_bounds(pos.row, end));
+        }
+        if len == 0 {
+            return Ok(String::new());
+        }
+
+        let deleted: String = self.lines[pos.row].drain(pos.col..end).collect();
+        self.modified = true;
+        self.push_action(Action {
+            action_type: ActionType::Delete,
+            position: pos,
+            content: deleted.clone(),
+        });
+
+        Ok(deleted)
+    }
+
+    /// 行内の`pos`から`len`バイトを`text`で置き換え、置き換えたテキストを返す
+    pub fn replace_str(&mut self, pos: Position, len: usize, text: &str) -> Result<String> {
+        self.begin_undo_group();
+        let result = self
+            .delete_str(pos, len)
+            .and_then(|deleted| self.insert_str(pos, text).map(|_| deleted));
+        self.end_undo_group();
+        result
+    }
+
+    /// 改行を含む文字列を`pos`に挿入し、挿入したテキスト直後の位置を返す
+    pub fn insert_text(&mut self, pos: Position, text: &str) -> Result<Position> {
+        let line = self.line(pos.row)?;
+        if pos.col > line.len() || !line.is_char_boundary(pos.col) {
+            return Err(EditorError::out_of_bounds(pos.row, pos.col));
+        }
+
+        let mut parts = text.split('\n');
+        let first = parts.next().unwrap_or("");
+        let rest: Vec<&str> = parts.collect();
+
+        self.begin_undo_group();
+        let result = (|| {
+            if rest.is_empty() {
+                self.insert_str(pos, first)?;
+                return Ok(Position::new(pos.row, pos.col + first.len()));
+            }
+
+            // 挿入位置より後ろの部分は最後の行の末尾に移す
+            let tail_len = self.lines[pos.row].len() - pos.col;
+            let tail = self.delete_str(pos, tail_len)?;
+            self.insert_str(pos, first)?;
+
+            let mut row = pos.row;
+            for part in &rest {
+                row += 1;
+                self.insert_line_with(row, part.to_string())?;
+            }
+            let end = Position::new(row, self.lines[row].len());
+            self.insert_str(end, &tail)?;
+            Ok(end)
+        })();
+        self.end_undo_group();
+
+        result
+    }
+
+    /// `start`から`end`の直前までのテキスト（行をまたぐ場合は改行を含む）
+    pub fn text_range(&self, start: Position, end: Position) -> Result<String> {
+        let end = self.clamp_end(end);
+        if end <= start {
+            return Ok(String::new());
+        }
+
+        let first = self.line(start.row)?;
+        if start.row == end.row {
+            return first
+                .get(start.col..end.col)
+                .map(|s| s.to_string())
+                .ok_or_else(|| EditorError::out_of_bounds(end.row, end.col));
+        }
+
+        let mut text = first
+            .get(start.col..)
+            .ok_or_else(|| EditorError::out_of_bounds(start.row, start.col))?
+            .to_string();
+        for row in start.row + 1..end.row {
+            text.push('\n');
+            text.push_str(self.line(row)?);
+        }
+        text.push('\n');
+        text.push_str(
+            self.line(end.row)?
+                .get(..end.col)
+                .ok_or_else(|| EditorError::out_of_bounds(end.row, end.col))?,
+        );
+        Ok(text)
+    }
+
+    /// `start`から`end`の直前までを削除し、削除したテキストを返す（1回のundo単位）
+    pub fn delete_range(&mut self, start: Position, end: Position) -> Result<String> {
+        let end = self.clamp_end(end);
+        let deleted = self.text_range(start, end)?;
+        if deleted.is_empty() {
+            return Ok(deleted);
+        }
+
+        if start.row == end.row {
+            self.delete_str(start, end.col - start.col)?;
+            return Ok(deleted);
+        }
+
+        self.begin_undo_group();
+        let result = (|| {
+            let tail_len = self.lines[start.row].len() - start.col;
+            self.delete_str(start, tail_len)?;
+            if end.row > start.row + 1 {
+                self.delete_lines(start.row + 1, end.row - 1)?;
+            }
+
+            // 終了行の残りを開始行に連結する
+            let last = self.delete_line(start.row + 1)?.unwrap_or_default();
+            self.insert_str(start, &last[end.col..])
+        })();
+        self.end_undo_group();
+
+        result.map(|_| deleted)
+    }
+
+    /// 範囲の終端をバッファ末尾までに制限する
+    fn clamp_end(&self, end: Position) -> Position {
+        let last = self.lines.len() - 1;
+        if end.row > last {
+            Position::new(last, self.lines[last].len())
+        } else {
+            Position::new(end.row, end.col.min(self.lines[end.row].len()))
+        }
+    }
+
+    /// 以降の変更を1回のundoで戻せるようにまとめる（ネスト可能）
+    pub fn begin_undo_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    pub fn end_undo_group(&mut self) {
+        if self.group_depth == 0 {
+            return;
+        }
+        self.group_depth -= 1;
+        if self.group_depth == 0 && !self.open_group.is_empty() {
+            let group = std::mem::take(&mut self.open_group);
+            self.undo_stack.push(group);
+        }
+    }
+
     pub fn can_undo(&self) -> bool {
-        !self.undo_stack.is_empty()
+        !self.undo_stack.is_empty() || !self.open_group.is_empty()
     }
 
     pub fn can_redo(&self) -> bool {
@@ -190,87 +481,298 @@ impl Buffer {
     }
 
     pub fn undo(&mut self) -> Result<()> {
-        let action = self.undo_stack.pop().ok_or(EditorError::EmptyUndoStack)?;
+        self.close_undo_group();
+        let group = self.undo_stack.pop().ok_or(EditorError::EmptyUndoStack)?;
 
+        for action in group.iter().rev() {
+            self.revert(action);
+        }
+
+        self.redo_stack.push(group);
+        self.modified = self.saved_depth != Some(self.undo_stack.len());
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<()> {
+        self.close_undo_group();
+        let group = self.redo_stack.pop().ok_or(EditorError::EmptyRedoStack)?;
+
+        for action in &group {
+            self.apply(action);
+        }
+
+        self.undo_stack.push(group);
+        self.modified = self.saved_depth != Some(self.undo_stack.len());
+        Ok(())
+    }
+
+    /// undo/redoの前に開いたままのグループを確定する
+    fn close_undo_group(&mut self) {
+        if self.group_depth > 0 {
+            self.group_depth = 1;
+            self.end_undo_group();
+        }
+    }
+
+    fn apply(&mut self, action: &Action) {
+        let pos = action.position;
         match action.action_type {
             ActionType::Insert => {
-                let pos = action.position;
-                if pos.row < self.lines.len() && pos.col < self.lines[pos.row].len() {
-                    self.lines[pos.row].remove(pos.col);
+                if pos.row < self.lines.len() && pos.col <= self.lines[pos.row].len() {
+                    self.lines[pos.row].insert_str(pos.col, &action.content);
                 }
             }
             ActionType::Delete => {
-                let pos = action.position;
-                if pos.row < self.lines.len() && pos.col <= self.lines[pos.row].len() {
-                    if let Some(ch) = action.content.chars().next() {
-                        self.lines[pos.row].insert(pos.col, ch);
-                    }
+                let end = pos.col + action.content.len();
+                if pos.row < self.lines.len() && end <= self.lines[pos.row].len() {
+                    self.lines[pos.row].replace_range(pos.col..end, "");
                 }
             }
             ActionType::InsertLine => {
-                let pos = action.position;
-                if pos.row < self.lines.len() {
-                    self.lines.remove(pos.row);
+                if pos.row <= self.lines.len() {
+                    let lines = action.content.split('\n').map(str::to_string);
+                    let count = action.content.split('\n').count();
+                    self.lines.splice(pos.row..pos.row, lines);
+                    self.marks.lines_inserted(pos.row, count);
                 }
             }
             ActionType::DeleteLine => {
-                let pos = action.position;
-                if pos.row <= self.lines.len() {
-                    self.lines.insert(pos.row, action.content.clone());
+                let count = action.content.split('\n').count();
+                if pos.row + count <= self.lines.len() {
+                    self.lines.drain(pos.row..pos.row + count);
+                    self.marks.lines_deleted(pos.row, count);
                 }
             }
         }
+    }
 
-        self.redo_stack.push(action);
-        self.modified = !self.undo_stack.is_empty();
-        Ok(())
+    fn revert(&mut self, action: &Action) {
+        let inverse = action.action_type.inverse();
+        self.apply(&Action {
+            action_type: inverse,
+            position: action.position,
+            content: action.content.clone(),
+        });
     }
 
-    pub fn redo(&mut self) -> Result<()> {
-        let action = self.redo_stack.pop().ok_or(EditorError::EmptyRedoStack)?;
+    /// `start`行から`end`行までを1行に連結する（全体で1回のundo単位）
+    ///
+    /// `spaces`なら`J`と同じく次の行の先頭の空白を取り除いて空白1つ（`joinspaces`では文末の後に2つ）で
+    /// つなぎ、`formatoptions`に`j`があればコメントリーダーも取り除く。`false`なら`gJ`と同じくそのままつなぐ。
+    /// 最後につないだ位置の列を返す。
+    pub fn join_lines(&mut self, start: usize, end: usize, spaces: bool) -> Result<usize> {
+        let end = end.min(self.lines.len().saturating_sub(1));
+        let mut col = self.line_length(start)?;
+
+        self.begin_undo_group();
+        let result = (|| {
+            for _ in start..end {
+                let current = self.line(start)?;
+                let next = self.line(start + 1)?;
+                let joined = if spaces {
+                    join_text(current, next, &self.options)
+                } else {
+                    next.to_string()
+                };
+                col = current.len();
+
+                self.delete_line(start + 1)?;
+                self.insert_str(Position::new(start, col), &joined)?;
+            }
+            Ok(col)
+        })();
+        self.end_undo_group();
+        result
+    }
 
-        match action.action_type {
-            ActionType::Insert => {
-                let pos = action.position;
-                if pos.row < self.lines.len() && pos.col <= self.lines[pos.row].len() {
-                    if let Some(ch) = action.content.chars().next() {
-                        self.lines[pos.row].insert(pos.col, ch);
-                    }
+    /// `start`行から`end`行までを`lines`で置き換える（1回のundo単位、内容が同じ行はそのまま残す）
+    ///
+    /// `lines`が空なら範囲の行を削除する。
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: Vec<String>) -> Result<()> {
+        let count = end - start + 1;
+        let new_count = lines.len();
+
+        self.begin_undo_group();
+        let result = (|| {
+            for (offset, line) in lines.into_iter().enumerate() {
+                let row = start + offset;
+                if offset >= count {
+                    self.insert_line_with(row, line)?;
+                } else if self.line(row)? != line {
+                    let len = self.line_length(row)?;
+                    self.replace_str(Position::new(row, 0), len, &line)?;
                 }
             }
-            ActionType::Delete => {
-                let pos = action.position;
-                if pos.row < self.lines.len() && pos.col < self.lines[pos.row].len() {
-                    self.lines[pos.row].remove(pos.col);
-                }
+            if new_count < count {
+                self.delete_lines(start + new_count, end)?;
             }
-            ActionType::InsertLine => {
-                let pos = action.position;
-                if pos.row <= self.lines.len() {
-                    self.lines.insert(pos.row, action.content.clone());
+            Ok(())
+        })();
+        self.end_undo_group();
+        result
+    }
+
+    /// `start`行から`end`行までのインデントを`amount`段増減する（負の値なら減らす）
+    ///
+    /// インデントは`shiftwidth`と`expandtab`に従って付け直す。空行はそのまま残す。
+    pub fn shift_lines(&mut self, start: usize, end: usize, amount: isize) -> Result<()> {
+        let shift = self.options.shift_width() * amount.unsigned_abs();
+
+        self.begin_undo_group();
+        let result = (|| {
+            for row in start..=end {
+                let line = self.line(row)?;
+                if line.is_empty() {
+                    continue;
                 }
-            }
-            ActionType::DeleteLine => {
-                let pos = action.position;
-                if pos.row < self.lines.len() {
-                    self.lines.remove(pos.row);
+                let len = self.first_non_blank(row)?;
+                let width = virtual_column(line, len, self.options.tabstop);
+                let width = if amount > 0 {
+                    width + shift
+                } else {
+                    width.saturating_sub(shift)
+                };
+                let indent = self.options.indent_string(width);
+                if line[..len] != indent {
+                    self.replace_str(Position::new(row, 0), len, &indent)?;
                 }
             }
+            Ok(())
+        })();
+        self.end_undo_group();
+        result
+    }
+
+    /// `:retab[!]`: タブを含む空白の並びを、見た目を保ったまま`expandtab`に従って付け直す
+    ///
+    /// `bang`なら空白だけの並びも対象にする。`tabstop`を指定すると、その幅で付け直してオプションも変更する。
+    pub fn retab(
+        &mut self,
+        start: usize,
+        end: usize,
+        tabstop: Option<usize>,
+        bang: bool,
+    ) -> Result<()> {
+        let old_tabstop = self.options.tabstop;
+        let mut options = self.options.clone();
+        if let Some(tabstop) = tabstop.filter(|&tabstop| tabstop > 0) {
+            options.tabstop = tabstop;
         }
 
-        self.undo_stack.push(action);
-        self.modified = true;
-        Ok(())
+        self.begin_undo_group();
+        let result = (|| {
+            for row in start..=end {
+                let line = self.line(row)?;
+                let retabbed = retab_line(line, old_tabstop, &options, bang);
+                if retabbed != line {
+                    self.replace_str(Position::new(row, 0), line.len(), &retabbed)?;
+                }
+            }
+            Ok(())
+        })();
+        self.end_undo_group();
+        self.options.tabstop = options.tabstop;
+        result
     }
 
     pub fn mark_saved(&mut self) {
         self.modified = false;
+        self.saved_depth = Some(self.undo_stack.len() + usize::from(!self.open_group.is_empty()));
+        self.file_state = FileState::Loaded;
     }
 
     fn push_action(&mut self, action: Action) {
-        self.undo_stack.push(action);
+        // 保存した状態がredoの履歴にあれば、捨てると戻れなくなる
+        if self
+            .saved_depth
+            .is_some_and(|depth| depth > self.undo_stack.len())
+        {
+            self.saved_depth = None;
+        }
         self.redo_stack.clear();
+        if self.group_depth > 0 {
+            self.open_group.push(action);
+        } else {
+            self.undo_stack.push(vec![action]);
+        }
+    }
+}
+
+impl ActionType {
+    fn inverse(&self) -> Self {
+        match self {
+            ActionType::Insert => ActionType::Delete,
+            ActionType::Delete => ActionType::Insert,
+            ActionType::InsertLine => ActionType::DeleteLine,
+            ActionType::DeleteLine => ActionType::InsertLine,
+        }
+    }
+}
+
+/// `J`で`current`の後ろにつなぐテキスト（区切りの空白を含む）
+fn join_text(current: &str, next: &str, options: &Options) -> String {
+    let mut next = next;
+    if options.has_format_option('j')
+        && comment::leader(current, &options.comments).is_some()
+        && let Some(leader) = comment::leader(next, &options.comments)
+        && !leader.part.is_first_only()
+    {
+        next = &next[leader.len..];
     }
+    let next = next.trim_start_matches([' ', '\t']);
+
+    // 行末が空白なら空白を追加しないが、その前の文末には`joinspaces`の空白を足す
+    let mut last = current.chars().rev();
+    let (end1, end2) = (last.next(), last.next());
+    let mut spaces = 0;
+    if !current.is_empty() && !next.is_empty() && !next.starts_with(')') && end1 != Some('\t') {
+        let end = if end1 == Some(' ') {
+            end2
+        } else {
+            spaces += 1;
+            end1
+        };
+        if options.joinspaces && matches!(end, Some('.' | '?' | '!')) {
+            spaces += 1;
+        }
+    }
+    format!("{}{}", " ".repeat(spaces), next)
+}
+
+/// 行内の空白の並びを`options`に従って付け直す（`old_tabstop`は現在のタブ幅）
+fn retab_line(line: &str, old_tabstop: usize, options: &Options, bang: bool) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    let mut vcol = 0;
+    let advance = |vcol: usize, ch: char| {
+        if ch == '\t' {
+            (vcol / old_tabstop + 1) * old_tabstop
+        } else {
+            vcol + 1
+        }
+    };
+
+    while let Some(ch) = chars.next() {
+        if ch != ' ' && ch != '\t' {
+            result.push(ch);
+            vcol += 1;
+            continue;
+        }
+
+        let from = vcol;
+        let mut run = ch.to_string();
+        vcol = advance(vcol, ch);
+        while let Some(ch) = chars.next_if(|&ch| ch == ' ' || ch == '\t') {
+            run.push(ch);
+            vcol = advance(vcol, ch);
+        }
+        if run.contains('\t') || bang {
+            result.push_str(&options.whitespace(from, vcol));
+        } else {
+            result.push_str(&run);
+        }
+    }
+    result
 }
 
 impl Default for Buffer {
@@ -399,6 +901,16 @@ mod tests {
         assert_eq!(buffer3.line_length(2).unwrap(), 6);
     }
 
+    #[test]
+    fn test_first_non_blank() {
+        let buffer = Buffer::from_content("    indented\n\tfn main()\nplain\n   ");
+        assert_eq!(buffer.first_non_blank(0).unwrap(), 4);
+        assert_eq!(buffer.first_non_blank(1).unwrap(), 1);
+        assert_eq!(buffer.first_non_blank(2).unwrap(), 0);
+        assert_eq!(buffer.first_non_blank(3).unwrap(), 3);
+        assert!(buffer.first_non_blank(4).is_err());
+    }
+
     #[test]
     fn test_line_length_out_of_bounds() {
         let buffer = Buffer::from_content("Hello");
@@ -419,6 +931,31 @@ mod tests {
         assert!(!buffer.is_modified());
     }
 
+    #[test]
+    fn test_undo_to_saved_state() {
+        let mut buffer = Buffer::from_content("Hello");
+        buffer.insert_char(Position::new(0, 5), '!').unwrap();
+        buffer.mark_saved();
+        buffer.insert_char(Position::new(0, 6), '?').unwrap();
+
+        // 保存より前の履歴が残っていても、保存した状態に戻れば変更なし
+        buffer.undo().unwrap();
+        assert!(!buffer.is_modified());
+        buffer.undo().unwrap();
+        assert!(buffer.is_modified());
+        buffer.redo().unwrap();
+        assert!(!buffer.is_modified());
+        buffer.redo().unwrap();
+        assert!(buffer.is_modified());
+
+        // 保存した状態をredoの履歴ごと捨てると戻れない
+        buffer.undo().unwrap();
+        buffer.undo().unwrap();
+        buffer.insert_char(Position::new(0, 0), '>').unwrap();
+        buffer.undo().unwrap();
+        assert!(buffer.is_modified());
+    }
+
     #[test]
     fn test_undo_delete_char() {
         let mut buffer = Buffer::from_content("Hello");
@@ -462,6 +999,183 @@ mod tests {
         assert!(buffer.can_redo());
     }
 
+    #[test]
+    fn test_undo_delete_only_line() {
+        let mut buffer = Buffer::from_content("Hello");
+
+        buffer.delete_line(0).unwrap();
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "Hello");
+    }
+
+    #[test]
+    fn test_insert_and_delete_multibyte_char() {
+        let mut buffer = Buffer::from_content("あい");
+
+        buffer.insert_char(Position::new(0, 3), 'x').unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "あxい");
+
+        let deleted = buffer.delete_char(Position::new(0, 4)).unwrap();
+        assert_eq!(deleted, Some('い'));
+        assert_eq!(buffer.line(0).unwrap(), "あx");
+
+        // 文字境界以外への挿入はエラー
+        assert!(buffer.insert_char(Position::new(0, 1), 'y').is_err());
+    }
+
+    #[test]
+    fn test_text_range() {
+        let buffer = Buffer::from_content("Line 1\nLine 2\nLine 3");
+
+        let text = buffer
+            .text_range(Position::new(0, 2), Position::new(0, 4))
+            .unwrap();
+        assert_eq!(text, "ne");
+
+        let text = buffer
+            .text_range(Position::new(0, 5), Position::new(2, 4))
+            .unwrap();
+        assert_eq!(text, "1\nLine 2\nLine");
+
+        // バッファ末尾を超える終端はクランプされる
+        let text = buffer
+            .text_range(Position::new(2, 5), Position::new(3, 0))
+            .unwrap();
+        assert_eq!(text, "3");
+    }
+
+    #[test]
+    fn test_delete_range_single_line() {
+        let mut buffer = Buffer::from_content("Hello, World!");
+
+        let deleted = buffer
+            .delete_range(Position::new(0, 5), Position::new(0, 12))
+            .unwrap();
+        assert_eq!(deleted, ", World");
+        assert_eq!(buffer.line(0).unwrap(), "Hello!");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "Hello, World!");
+    }
+
+    #[test]
+    fn test_delete_range_multiple_lines_is_single_undo_step() {
+        let mut buffer = Buffer::from_content("fn main() {\n    body\n}");
+
+        let deleted = buffer
+            .delete_range(Position::new(0, 11), Position::new(2, 0))
+            .unwrap();
+        assert_eq!(deleted, "\n    body\n");
+        assert_eq!(buffer.to_string(), "fn main() {}");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "fn main() {\n    body\n}");
+        assert!(!buffer.can_undo());
+
+        buffer.redo().unwrap();
+        assert_eq!(buffer.to_string(), "fn main() {}");
+    }
+
+    #[test]
+    fn test_replace_str() {
+        let mut buffer = Buffer::from_content("Hello, World!");
+
+        let replaced = buffer.replace_str(Position::new(0, 7), 5, "Rust").unwrap();
+        assert_eq!(replaced, "World");
+        assert_eq!(buffer.line(0).unwrap(), "Hello, Rust!");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "Hello, World!");
+    }
+
+    #[test]
+    fn test_insert_text_with_newlines() {
+        let mut buffer = Buffer::from_content("Hello World");
+
+        let end = buffer
+            .insert_text(Position::new(0, 5), ",\nnew line\nand")
+            .unwrap();
+        assert_eq!(end, Position::new(2, 3));
+        assert_eq!(buffer.to_string(), "Hello,\nnew line\nand World");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "Hello World");
+    }
+
+    #[test]
+    fn test_delete_lines() {
+        let mut buffer = Buffer::from_content("a\nb\nc\nd");
+
+        let deleted = buffer.delete_lines(1, 2).unwrap();
+        assert_eq!(deleted, vec!["b", "c"]);
+        assert_eq!(buffer.to_string(), "a\nd");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nc\nd");
+
+        assert!(buffer.delete_lines(2, 9).is_err());
+
+        // 範囲全体が1回の操作として記録され、redoでも同じ行を消す
+        buffer.marks_mut().set('a', Position::new(3, 0));
+        buffer.delete_lines(0, 2).unwrap();
+        assert_eq!(buffer.to_string(), "d");
+        assert_eq!(buffer.marks().get('a'), Some(Position::new(0, 0)));
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nc\nd");
+        assert_eq!(buffer.marks().get('a'), Some(Position::new(3, 0)));
+        buffer.redo().unwrap();
+        assert_eq!(buffer.to_string(), "d");
+
+        // すべての行を消すと空の1行が残る
+        let mut buffer = Buffer::from_content("a\nb\nc");
+        let deleted = buffer.delete_lines(0, 2).unwrap();
+        assert_eq!(deleted, vec!["a", "b", "c"]);
+        assert_eq!(buffer.to_string(), "");
+        assert_eq!(buffer.line_count(), 1);
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nc");
+        buffer.redo().unwrap();
+        assert_eq!(buffer.to_string(), "");
+    }
+
+    #[test]
+    fn test_marks_follow_line_edits() {
+        let mut buffer = Buffer::from_content("a\nb\nc\nd");
+        buffer.marks_mut().set('<', Position::new(2, 0));
+
+        buffer.delete_line(0).unwrap();
+        assert_eq!(buffer.marks().get('<'), Some(Position::new(1, 0)));
+
+        buffer.insert_text(Position::new(0, 1), "\nx\ny").unwrap();
+        assert_eq!(buffer.marks().get('<'), Some(Position::new(3, 0)));
+
+        // undoでも行の増減に追従する
+        buffer.undo().unwrap();
+        assert_eq!(buffer.marks().get('<'), Some(Position::new(1, 0)));
+    }
+
+    #[test]
+    fn test_undo_group() {
+        let mut buffer = Buffer::from_content("");
+
+        buffer.begin_undo_group();
+        for (i, ch) in "abc".chars().enumerate() {
+            buffer.insert_char(Position::new(0, i), ch).unwrap();
+        }
+        buffer.end_undo_group();
+        buffer.insert_char(Position::new(0, 3), 'd').unwrap();
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "abc");
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "");
+        assert!(!buffer.can_undo());
+    }
+
     #[test]
     fn test_empty_undo_stack() {
         let mut buffer = Buffer::new();
@@ -475,6 +1189,115 @@ mod tests {
         let result = buffer.redo();
         assert!(matches!(result, Err(EditorError::EmptyRedoStack)));
     }
+
+    #[test]
+    fn test_join_lines() {
+        let mut buffer = Buffer::from_content("one\n    two\n\n(x\n)\nend.  \nz");
+
+        // 空行との間には空白を入れない
+        let col = buffer.join_lines(0, 2, true).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "one two");
+        assert_eq!(col, 7);
+        // `)`の前には空白を入れない
+        buffer.join_lines(0, 2, true).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "one two (x)");
+        // 行末の空白はそのまま残す
+        buffer.join_lines(1, 2, true).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "end.  z");
+
+        // 1回のundoで戻る
+        buffer.undo().unwrap();
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "one two");
+        assert_eq!(buffer.line(1).unwrap(), "(x");
+
+        let mut buffer = Buffer::from_content("a\n  b");
+        assert_eq!(buffer.join_lines(0, 5, false).unwrap(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "a  b");
+    }
+
+    #[test]
+    fn test_replace_lines() {
+        let mut buffer = Buffer::from_content("a\nb\nc\nd");
+
+        buffer
+            .replace_lines(
+                1,
+                2,
+                vec!["b".to_string(), "x".to_string(), "y".to_string()],
+            )
+            .unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nx\ny\nd");
+
+        buffer.replace_lines(1, 3, Vec::new()).unwrap();
+        assert_eq!(buffer.to_string(), "a\nd");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nx\ny\nd");
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_join_lines_with_options() {
+        let mut buffer = Buffer::from_content("Hi.\nthere?\n yes\n// a\n   // b\n/* c\n * d");
+        buffer.options_mut().joinspaces = true;
+
+        buffer.join_lines(0, 2, true).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "Hi.  there?  yes");
+
+        buffer.join_lines(1, 2, true).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "// a b");
+        buffer.join_lines(2, 3, true).unwrap();
+        assert_eq!(buffer.line(2).unwrap(), "/* c d");
+
+        buffer.options_mut().set("fo-=j").unwrap();
+        buffer.insert_line_with(2, "// x".to_string()).unwrap();
+        buffer.join_lines(1, 2, true).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "// a b // x");
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut buffer = Buffer::from_content("a\n\n\t  b");
+
+        buffer.shift_lines(0, 2, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    a");
+        assert_eq!(buffer.line(1).unwrap(), "");
+        assert_eq!(buffer.line(2).unwrap(), " ".repeat(14) + "b");
+
+        buffer.options_mut().expandtab = false;
+        buffer.shift_lines(2, 2, -3).unwrap();
+        assert_eq!(buffer.line(2).unwrap(), "  b");
+        buffer.shift_lines(0, 0, 2).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t    a");
+
+        // 範囲全体で1回のundo
+        buffer.undo().unwrap();
+        buffer.undo().unwrap();
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a");
+        assert_eq!(buffer.line(2).unwrap(), "\t  b");
+    }
+
+    #[test]
+    fn test_retab() {
+        let mut buffer = Buffer::from_content("\tif x {\n    \ty\t= 1\n        z");
+
+        buffer.retab(0, 2, Some(4), false).unwrap();
+        assert_eq!(buffer.options().tabstop, 4);
+        assert_eq!(buffer.line(0).unwrap(), "        if x {");
+        assert_eq!(buffer.line(1).unwrap(), "        y       = 1");
+        // タブを含まない空白は`!`なしでは変更しない
+        assert_eq!(buffer.line(2).unwrap(), "        z");
+
+        buffer.options_mut().expandtab = false;
+        buffer.retab(2, 2, None, false).unwrap();
+        assert_eq!(buffer.line(2).unwrap(), "        z");
+        buffer.retab(0, 2, None, true).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t\tif x {");
+        assert_eq!(buffer.line(2).unwrap(), "\t\tz");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,570 @@
+use crate::editor::buffer::{Buffer, FileState};
+use crate::editor::cursor::Position;
+use crate::error::{EditorError, Result};
+use crate::io::{FileStamp, FileSystem, OpenedFile};
+use std::path::Path;
+
+/// バッファリストの1項目
+#[derive(Debug)]
+pub struct BufferEntry {
+    /// `:ls`や`:b N`で使う番号（1始まりで、削除しても再利用しない）
+    pub number: usize,
+    pub buffer: Buffer,
+    /// `:bdelete`したバッファは一覧に表示しない
+    pub listed: bool,
+    /// 最後にバッファを離れたときのカーソル位置
+    pub cursor: Position,
+}
+
+impl BufferEntry {
+    /// `:ls`などで表示する名前
+    pub fn name(&self) -> String {
+        self.buffer
+            .file_path()
+            .map_or("[No Name]".to_string(), |path| path.display().to_string())
+    }
+}
+
+/// 読み込んだバッファの一覧と、カレントバッファ・オルタネートバッファ（`#`）
+#[derive(Debug)]
+pub struct BufferList {
+    entries: Vec<BufferEntry>,
+    current: usize,
+    alternate: Option<usize>,
+    next_number: usize,
+}
+
+impl BufferList {
+    pub fn new(buffer: Buffer) -> Self {
+        Self {
+            entries: vec![BufferEntry {
+                number: 1,
+                buffer,
+                listed: true,
+                cursor: Position::origin(),
+            }],
+            current: 1,
+            alternate: None,
+            next_number: 2,
+        }
+    }
+
+    pub fn current(&self) -> &Buffer {
+        &self.current_entry().buffer
+    }
+
+    pub fn current_mut(&mut self) -> &mut Buffer {
+        let index = self.index(self.current).expect("current buffer exists");
+        &mut self.entries[index].buffer
+    }
+
+    pub fn current_number(&self) -> usize {
+        self.current
+    }
+
+    pub fn alternate_number(&self) -> Option<usize> {
+        self.alternate
+    }
+
+    pub fn get(&self, number: usize) -> Option<&BufferEntry> {
+        self.entries.iter().find(|entry| entry.number == number)
+    }
+
+    pub fn get_mut(&mut self, number: usize) -> Option<&mut BufferEntry> {
+        self.entries.iter_mut().find(|entry| entry.number == number)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &BufferEntry> {
+        self.entries.iter()
+    }
+
+    /// カレントバッファ以外の一覧に表示するバッファ（補完の候補に使う）
+    pub fn others(&self) -> Vec<&Buffer> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.listed && entry.number != self.current)
+            .map(|entry| &entry.buffer)
+            .collect()
+    }
+
+    /// 保存していない変更がある最初のバッファ
+    pub fn first_modified(&self) -> Option<&BufferEntry> {
+        self.entries.iter().find(|entry| entry.buffer.is_modified())
+    }
+
+    /// バッファを一覧の末尾に追加して番号を返す
+    pub fn add(&mut self, buffer: Buffer) -> usize {
+        let number = self.next_number;
+        self.next_number += 1;
+        self.entries.push(BufferEntry {
+            number,
+            buffer,
+            listed: true,
+            cursor: Position::origin(),
+        });
+        number
+    }
+
+    /// `path`のバッファの番号（読み込んでいなければファイルを読み込んで追加する）
+    pub fn open(&mut self, path: &Path) -> Result<usize> {
+        if let Some(entry) = self.entries.iter().find(|entry| {
+            entry
+                .buffer
+                .file_path()
+                .is_some_and(|other| same_file(other, path))
+        }) {
+            return Ok(entry.number);
+        }
+        Ok(self.add(load(path)?))
+    }
+
+    /// `:b`の引数をバッファ番号にする（番号、`%`、`#`、名前の一部）
+    pub fn resolve(&self, arg: &str) -> Result<usize> {
+        match arg.trim() {
+            "" | "%" => Ok(self.current),
+            "#" => self
+                .alternate
+                .filter(|&number| self.get(number).is_some())
+                .ok_or_else(|| EditorError::invalid_command("No alternate file")),
+            arg if arg.chars().all(|ch| ch.is_ascii_digit()) => {
+                let number = arg.parse().unwrap_or(0);
+                self.get(number).map(|entry| entry.number).ok_or_else(|| {
+                    EditorError::invalid_command(format!("Buffer {} does not exist", number))
+                })
+            }
+            arg => {
+                // 完全に一致する名前を優先し、なければ名前の一部で探す
+                if let Some(entry) = self.entries.iter().find(|entry| entry.name() == arg) {
+                    return Ok(entry.number);
+                }
+                let matches: Vec<usize> = self
+                    .entries
+                    .iter()
+                    .filter(|entry| {
+                        entry.buffer.file_path().is_some() && entry.name().contains(arg)
+                    })
+                    .map(|entry| entry.number)
+                    .collect();
+                match matches.as_slice() {
+                    [number] => Ok(*number),
+                    [] => Err(EditorError::invalid_command(format!(
+                        "No matching buffer for {}",
+                        arg
+                    ))),
+                    _ => Err(EditorError::invalid_command(format!(
+                        "More than one match for {}",
+                        arg
+                    ))),
+                }
+            }
+        }
+    }
+
+    /// `:bnext`/`:bprevious`: 一覧に表示するバッファを`count`個先（後ろ）に進めた番号
+    pub fn cycle(&self, count: usize, forward: bool) -> usize {
+        let listed: Vec<usize> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.listed || entry.number == self.current)
+            .map(|entry| entry.number)
+            .collect();
+        let position = listed
+            .iter()
+            .position(|&number| number == self.current)
+            .unwrap_or(0);
+        let len = listed.len();
+        let count = count % len;
+        let index = if forward {
+            (position + count) % len
+        } else {
+            (position + len - count) % len
+        };
+        listed[index]
+    }
+
+    /// カレントバッファを離れてよいか（`hidden`でなければ変更を保存している必要がある）
+    pub fn check_abandon(&self, hidden: bool, force: bool) -> Result<()> {
+        if self.current().is_modified() && !hidden && !force {
+            return Err(EditorError::invalid_command(
+                "No write since last change (add ! to override)",
+            ));
+        }
+        Ok(())
+    }
+
+    /// `number`のバッファに切り替え、そのバッファで最後に使っていたカーソル位置に戻す
+    ///
+    /// `hidden`でなければ、離れるバッファの変更は破棄する。
+    pub fn switch(&mut self, number: usize, cursor: &mut Position, hidden: bool) -> Result<()> {
+        if number == self.current {
+            return Ok(());
+        }
+        let target = self.index(number).ok_or_else(|| {
+            EditorError::invalid_command(format!("Buffer {} does not exist", number))
+        })?;
+
+        let previous = self.current;
+        if !hidden && self.current().is_modified() {
+            self.reload(previous)?;
+        }
+        if let Some(index) = self.index(previous) {
+            self.entries[index].cursor = *cursor;
+        }
+
+        let entry = &mut self.entries[target];
+        entry.listed = true;
+        *cursor = clamp(&entry.buffer, entry.cursor);
+        self.alternate = Some(previous);
+        self.current = number;
+        Ok(())
+    }
+
+    /// ウィンドウの移動でカレントバッファを変える（オルタネートバッファは変えない）
+    ///
+    /// `cursor`は離れるバッファでのカーソル位置。
+    pub fn focus(&mut self, number: usize, cursor: Position) {
+        if self.get(number).is_none() {
+            return;
+        }
+        if let Some(index) = self.index(self.current) {
+            self.entries[index].cursor = cursor;
+        }
+        self.current = number;
+    }
+
+    /// ファイルを読み直して変更を破棄する（ファイル名がなければ空にする）
+    pub fn reload(&mut self, number: usize) -> Result<()> {
+        let index = self.index(number).ok_or_else(|| {
+            EditorError::invalid_command(format!("Buffer {} does not exist", number))
+        })?;
+        let buffer = match self.entries[index].buffer.file_path() {
+            Some(path) => load(&path.clone())?,
+            None => Buffer::new(),
+        };
+        self.entries[index].buffer = buffer;
+        Ok(())
+    }
+
+    /// `:bdelete`（`wipe`なら`:bwipeout`）: バッファを一覧から外す
+    ///
+    /// カレントバッファならオルタネートバッファか次のバッファに切り替える（なければ空のバッファを作る）。
+    pub fn delete(
+        &mut self,
+        number: usize,
+        wipe: bool,
+        force: bool,
+        cursor: &mut Position,
+    ) -> Result<()> {
+        let entry = self.get(number).ok_or_else(|| {
+            EditorError::invalid_command(format!("Buffer {} does not exist", number))
+        })?;
+        if entry.buffer.is_modified() && !force {
+            return Err(EditorError::invalid_command(format!(
+                "No write since last change for buffer {} (add ! to override)",
+                number
+            )));
+        }
+
+        if number == self.current {
+            let next = self
+                .alternate
+                .filter(|&alternate| {
+                    alternate != number && self.get(alternate).is_some_and(|entry| entry.listed)
+                })
+                .or_else(|| Some(self.cycle(1, true)).filter(|&next| next != number));
+            let next = match next {
+                Some(next) => next,
+                None => self.add(Buffer::new()),
+            };
+            self.switch(next, cursor, true)?;
+        }
+
+        if wipe {
+            self.entries.retain(|entry| entry.number != number);
+            if self.alternate == Some(number) {
+                self.alternate = None;
+            }
+        } else {
+            // 一覧から外したバッファは読み込み直した状態に戻す
+            if self
+                .get(number)
+                .is_some_and(|entry| entry.buffer.is_modified())
+            {
+                self.reload(number)?;
+            }
+            if let Some(index) = self.index(number) {
+                self.entries[index].listed = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// `:ls`の各行（`all`なら一覧から外したバッファも含める）
+    ///
+    /// `cursor`はカレントバッファのカーソル位置。
+    pub fn list(&self, all: bool, cursor: Position) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|entry| all || entry.listed)
+            .map(|entry| {
+                let is_current = entry.number == self.current;
+                let marker = if is_current {
+                    '%'
+                } else if Some(entry.number) == self.alternate {
+                    '#'
+                } else {
+                    ' '
+                };
+                let line = if is_current {
+                    cursor.row
+                } else {
+                    entry.cursor.row
+                } + 1;
+                format!(
+                    "{:>3}{}{}{} {} {:<30} line {}",
+                    entry.number,
+                    if entry.listed { ' ' } else { 'u' },
+                    marker,
+                    if is_current { 'a' } else { 'h' },
+                    if entry.buffer.is_modified() { '+' } else { ' ' },
+                    format!("\"{}\"", entry.name()),
+                    line
+                )
+            })
+            .collect()
+    }
+
+    fn current_entry(&self) -> &BufferEntry {
+        self.get(self.current).expect("current buffer exists")
+    }
+
+    fn index(&self, number: usize) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.number == number)
+    }
+}
+
+/// ファイルを読み込んだバッファ（存在しなければ空の新しいバッファ、ディレクトリならエントリの一覧）
+pub fn load(path: &Path) -> Result<Buffer> {
+    let buffer = match FileSystem::open(path)? {
+        OpenedFile::Text { content, writable } => {
+            let mut buffer = Buffer::from_content(&content);
+            buffer.options_mut().readonly = !writable;
+            buffer.set_stamp(FileStamp::read(path).ok());
+            buffer
+        }
+        OpenedFile::New => Buffer::new().with_file_state(FileState::New),
+        OpenedFile::PermissionDenied => {
+            let mut buffer = Buffer::new().with_file_state(FileState::PermissionDenied);
+            buffer.options_mut().readonly = true;
+            buffer
+        }
+        OpenedFile::Directory(entries) => {
+            // 先頭に親ディレクトリ、サブディレクトリは`/`を付けて並べる
+            let names: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let name = entry
+                        .file_name()
+                        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+                    if entry.is_dir() {
+                        format!("{}/", name)
+                    } else {
+                        name
+                    }
+                })
+                .collect();
+            let listing = std::iter::once("../".to_string())
+                .chain(names)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut buffer = Buffer::from_content(&listing).with_file_state(FileState::Directory);
+            buffer.options_mut().readonly = true;
+            buffer
+        }
+    };
+    Ok(buffer.with_file_path(path.to_path_buf()))
+}
+
+/// 同じファイルを指すパスか（存在するファイルは正規化して比べる）
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// 編集で短くなっていても収まるようにしたカーソル位置
+fn clamp(buffer: &Buffer, cursor: Position) -> Position {
+    let row = cursor.row.min(buffer.line_count() - 1);
+    let len = buffer.line_length(row).unwrap_or(0);
+    Position::new(row, cursor.col.min(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fs::test_helpers::TestFileHelper;
+
+    #[test]
+    fn test_open_and_switch() {
+        let helper = TestFileHelper::new();
+        let first = helper.create_test_file("first.txt", "one\ntwo\nthree");
+        let second = helper.create_test_file("second.txt", "alpha");
+
+        let mut buffers = BufferList::new(Buffer::new());
+        let mut cursor = Position::origin();
+        let number = buffers.open(&first).unwrap();
+        assert_eq!(number, 2);
+        buffers.switch(number, &mut cursor, false).unwrap();
+        assert_eq!(buffers.current().line(0).unwrap(), "one");
+        assert_eq!(buffers.alternate_number(), Some(1));
+
+        // 同じファイルは読み込み直さない
+        assert_eq!(buffers.open(&first).unwrap(), 2);
+
+        // 離れたときのカーソル位置に戻る
+        cursor = Position::new(2, 3);
+        let number = buffers.open(&second).unwrap();
+        buffers.switch(number, &mut cursor, false).unwrap();
+        assert_eq!(cursor, Position::origin());
+        let alternate = buffers.resolve("#").unwrap();
+        buffers.switch(alternate, &mut cursor, false).unwrap();
+        assert_eq!(cursor, Position::new(2, 3));
+        assert_eq!(buffers.current_number(), 2);
+
+        // ウィンドウの移動ではオルタネートバッファは変わらない
+        buffers.focus(1, cursor);
+        assert_eq!(buffers.current_number(), 1);
+        assert_eq!(buffers.alternate_number(), Some(3));
+        assert_eq!(buffers.get(2).unwrap().cursor, Position::new(2, 3));
+
+        // 存在しないファイルは空の新しいバッファになる
+        let missing = helper.get_path("missing.txt");
+        let number = buffers.open(&missing).unwrap();
+        assert_eq!(buffers.get(number).unwrap().buffer.to_string(), "");
+    }
+
+    #[test]
+    fn test_load() {
+        let helper = TestFileHelper::new();
+        let file = helper.create_test_file("file.txt", "text");
+        helper.create_test_file("sub/nested.txt", "");
+
+        let buffer = load(&file).unwrap();
+        assert_eq!(buffer.file_state(), FileState::Loaded);
+        assert!(!buffer.options().readonly);
+
+        // 存在しないファイルはパスを持つ新しいバッファになり、保存すると通常の状態に戻る
+        let missing = helper.get_path("missing.rs");
+        let mut buffer = load(&missing).unwrap();
+        assert_eq!(buffer.file_state(), FileState::New);
+        assert_eq!(buffer.file_path(), Some(&missing));
+        assert_eq!(buffer.options().filetype.as_deref(), Some("rust"));
+        buffer.mark_saved();
+        assert_eq!(buffer.file_state(), FileState::Loaded);
+
+        // ディレクトリはエントリの一覧を読み込み専用で表示する
+        let buffer = load(helper.temp_dir.path()).unwrap();
+        assert_eq!(buffer.file_state(), FileState::Directory);
+        assert_eq!(buffer.to_string(), "../\nfile.txt\nsub/");
+        assert!(buffer.options().readonly);
+
+        #[cfg(unix)]
+        assert!(load(Path::new("/dev/null")).is_err());
+    }
+
+    #[test]
+    fn test_abandon_modified_buffer() {
+        let helper = TestFileHelper::new();
+        let path = helper.create_test_file("file.txt", "text");
+
+        let mut buffers = BufferList::new(Buffer::new());
+        let mut cursor = Position::origin();
+        let number = buffers.open(&path).unwrap();
+        buffers.switch(number, &mut cursor, false).unwrap();
+        buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+
+        assert!(buffers.check_abandon(false, false).is_err());
+        assert!(buffers.check_abandon(true, false).is_ok());
+        assert!(buffers.check_abandon(false, true).is_ok());
+
+        // `hidden`なら変更を残したまま隠す
+        buffers.switch(1, &mut cursor, true).unwrap();
+        assert!(buffers.get(number).unwrap().buffer.is_modified());
+        assert_eq!(buffers.first_modified().unwrap().number, number);
+
+        // `hidden`でなければ変更を破棄する
+        buffers.switch(number, &mut cursor, true).unwrap();
+        buffers.switch(1, &mut cursor, false).unwrap();
+        assert_eq!(buffers.get(number).unwrap().buffer.to_string(), "text");
+        assert!(buffers.first_modified().is_none());
+    }
+
+    #[test]
+    fn test_resolve_and_cycle() {
+        let mut buffers = BufferList::new(Buffer::new());
+        buffers.add(Buffer::new().with_file_path("src/main.rs".into()));
+        buffers.add(Buffer::new().with_file_path("src/lib.rs".into()));
+        buffers.add(Buffer::new().with_file_path("README.md".into()));
+
+        assert_eq!(buffers.resolve("3").unwrap(), 3);
+        assert_eq!(buffers.resolve("main").unwrap(), 2);
+        assert_eq!(buffers.resolve("README.md").unwrap(), 4);
+        assert!(buffers.resolve("src").is_err());
+        assert!(buffers.resolve("nothing").is_err());
+        assert!(buffers.resolve("9").is_err());
+        assert!(buffers.resolve("#").is_err());
+
+        assert_eq!(buffers.cycle(1, true), 2);
+        assert_eq!(buffers.cycle(1, false), 4);
+        assert_eq!(buffers.cycle(5, true), 2);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut buffers = BufferList::new(Buffer::new());
+        let mut cursor = Position::origin();
+        let second = buffers.add(Buffer::from_content("second"));
+        buffers.switch(second, &mut cursor, false).unwrap();
+
+        // カレントバッファを削除するとオルタネートバッファに切り替える
+        buffers.delete(second, false, false, &mut cursor).unwrap();
+        assert_eq!(buffers.current_number(), 1);
+        assert!(!buffers.get(second).unwrap().listed);
+        assert_eq!(buffers.list(false, cursor).len(), 1);
+        assert_eq!(buffers.list(true, cursor).len(), 2);
+        assert_eq!(buffers.cycle(1, true), 1);
+
+        // 変更があれば`!`が必要
+        buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+        assert!(buffers.delete(1, true, false, &mut cursor).is_err());
+
+        // 最後のバッファを消すと空のバッファを作る
+        buffers.delete(1, true, true, &mut cursor).unwrap();
+        assert!(buffers.get(1).is_none());
+        assert_eq!(buffers.current_number(), second + 1);
+        assert_eq!(buffers.alternate_number(), None);
+    }
+
+    #[test]
+    fn test_list() {
+        let mut buffers = BufferList::new(Buffer::new().with_file_path("a.txt".into()));
+        let mut cursor = Position::new(4, 0);
+        let second = buffers.add(Buffer::from_content("x"));
+        buffers.switch(second, &mut cursor, false).unwrap();
+        buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'y')
+            .unwrap();
+
+        let lines = buffers.list(false, cursor);
+        assert_eq!(lines[0], format!("  1 #h   {:<30} line 5", "\"a.txt\""));
+        assert_eq!(lines[1], format!("  2 %a + {:<30} line 1", "\"[No Name]\""));
+    }
+}
@@ -0,0 +1,139 @@
+/// `comments`オプションの1項目（`b:#`なら`flags`が`"b"`、`text`が`"#"`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentPart {
+    pub flags: String,
+    pub text: String,
+}
+
+impl CommentPart {
+    fn has_flag(&self, flag: char) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// 3部構成のコメントの開始（`s`）・中間（`m`）・終了（`e`）
+    pub fn is_start(&self) -> bool {
+        self.has_flag('s')
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.has_flag('e')
+    }
+
+    /// 最初の行にだけ付くリーダー（箇条書きの`-`など）
+    pub fn is_first_only(&self) -> bool {
+        self.has_flag('f')
+    }
+}
+
+/// 行頭で見つかったコメントリーダー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leader<'a> {
+    pub part: CommentPart,
+    /// リーダーの前のインデント
+    pub indent: &'a str,
+    /// インデント、リーダー、続く空白までを含む長さ
+    pub len: usize,
+}
+
+/// `comments`オプションを項目に分ける（`s1:/*,mb:*,ex:*/,://`など）
+pub fn parse(comments: &str) -> Vec<CommentPart> {
+    comments
+        .split(',')
+        .filter_map(|item| {
+            let (flags, text) = item.split_once(':')?;
+            (!text.is_empty()).then(|| CommentPart {
+                flags: flags.to_string(),
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 行頭のコメントリーダーを探す（3部構成の終了部分はリーダーとみなさない）
+pub fn leader<'a>(line: &'a str, comments: &str) -> Option<Leader<'a>> {
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let rest = &line[indent_len..];
+
+    parse(comments).into_iter().find_map(|part| {
+        if part.is_end() {
+            return None;
+        }
+        let after = rest.strip_prefix(part.text.as_str())?;
+        // `b`フラグはリーダーの後に空白（または行末）が必要
+        if part.has_flag('b') && !(after.is_empty() || after.starts_with([' ', '\t'])) {
+            return None;
+        }
+        let len = line.len() - after.trim_start_matches([' ', '\t']).len();
+        Some(Leader {
+            part,
+            indent: &line[..indent_len],
+            len,
+        })
+    })
+}
+
+/// 折り返した次の行に付けるリーダー（コメントでなければインデントだけ）
+///
+/// 3部構成の開始（`/*`）なら中間部分（` * `）、最初の行だけのリーダー（`- `）なら同じ幅の空白にする。
+pub fn continuation(line: &str, comments: &str) -> String {
+    let Some(leader) = leader(line, comments) else {
+        let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+        return line[..indent_len].to_string();
+    };
+    let indent_len = leader.indent.len();
+    let blank = |len: usize| format!("{}{}", leader.indent, " ".repeat(len));
+
+    if leader.part.is_start() {
+        let parts = parse(comments);
+        let middle = parts
+            .iter()
+            .skip_while(|part| **part != leader.part)
+            .find(|part| part.has_flag('m'));
+        // `s1`の数字は開始部分に対する中間部分の字下げ
+        let offset: usize = leader
+            .part
+            .flags
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse()
+            .unwrap_or(0);
+        return match middle {
+            Some(middle) => format!("{}{} ", blank(offset), middle.text),
+            None => blank(leader.len - indent_len),
+        };
+    }
+    if leader.part.is_first_only() {
+        return blank(leader.len - indent_len);
+    }
+    line[..leader.len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    const COMMENTS: &str = "s1:/*,mb:*,ex:*/,://,b:#,:%,n:>,fb:-";
+
+    #[test]
+    fn test_leader() {
+        let leader = super::leader("    // text", COMMENTS).unwrap();
+        assert_eq!(leader.part.text, "//");
+        assert_eq!(leader.indent, "    ");
+        assert_eq!(leader.len, 7);
+
+        assert_eq!(super::leader(" * text", COMMENTS).unwrap().len, 3);
+        assert!(super::leader("/* text", COMMENTS).unwrap().part.is_start());
+        // `b`フラグの項目は空白が続かなければ一致しない
+        assert!(super::leader("#include", COMMENTS).is_none());
+        assert!(super::leader("*/", COMMENTS).is_none());
+        assert!(super::leader("text // no", COMMENTS).is_none());
+    }
+
+    #[test]
+    fn test_continuation() {
+        assert_eq!(super::continuation("  // text", COMMENTS), "  // ");
+        assert_eq!(super::continuation("  /* text", COMMENTS), "   * ");
+        assert_eq!(super::continuation("   * text", COMMENTS), "   * ");
+        assert_eq!(super::continuation("- item", COMMENTS), "  ");
+        assert_eq!(super::continuation("\ttext", COMMENTS), "\t");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::{EditorError, Result};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub row: usize,
     pub col: usize,
@@ -87,10 +87,151 @@ impl Default for Position {
     }
 }
 
+/// 垂直移動で維持する希望列（Vimの`curswant`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curswant {
+    /// 表示上の列番号
+    Column(usize),
+    /// `$`で設定される「常に行末」
+    EndOfLine,
+}
+
+impl Curswant {
+    pub fn from_position(line: &str, pos: Position, tabstop: usize) -> Self {
+        Self::Column(virtual_column(line, pos.col, tabstop))
+    }
+
+    /// 希望列を`line`上のバイト列に変換してカーソルへ反映する
+    pub fn apply(&self, line: &str, pos: &mut Position, tabstop: usize) {
+        pos.col = match self {
+            Self::EndOfLine => line.len(),
+            Self::Column(want) => byte_column(line, *want, tabstop),
+        };
+    }
+}
+
+impl Default for Curswant {
+    fn default() -> Self {
+        Self::Column(0)
+    }
+}
+
+/// 画面上で1文字が占める位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// 行内のバイト位置
+    pub byte: usize,
+    pub ch: char,
+    /// 画面上の開始列
+    pub col: usize,
+    /// 画面上の幅（タブは次の`tabstop`の倍数まで、全角文字は2、結合文字は0）
+    pub width: usize,
+}
+
+/// 行の各文字が画面上で占める位置
+pub fn cells(line: &str, tabstop: usize) -> impl Iterator<Item = Cell> + '_ {
+    let tabstop = tabstop.max(1);
+    line.char_indices().scan(0, move |vcol, (byte, ch)| {
+        let width = if ch == '\t' {
+            tabstop - *vcol % tabstop
+        } else {
+            char_width(ch)
+        };
+        let cell = Cell {
+            byte,
+            ch,
+            col: *vcol,
+            width,
+        };
+        *vcol += width;
+        Some(cell)
+    })
+}
+
+/// バイト列`col`の文字が始まる画面上の列番号（行末以降は行の幅）
+pub fn virtual_column(line: &str, col: usize, tabstop: usize) -> usize {
+    cells(line, tabstop)
+        .find(|cell| cell.byte >= col)
+        .map(|cell| cell.col)
+        .unwrap_or_else(|| {
+            cells(line, tabstop)
+                .last()
+                .map_or(0, |cell| cell.col + cell.width)
+        })
+}
+
+/// 画面上の列番号`want`を覆う文字のバイト列（行の幅を超えれば行の長さ）
+pub fn byte_column(line: &str, want: usize, tabstop: usize) -> usize {
+    cells(line, tabstop)
+        .find(|cell| cell.width > 0 && want < cell.col + cell.width)
+        .map_or(line.len(), |cell| cell.byte)
+}
+
+/// 文字の画面上の幅（CJKなどの全角文字は2、結合文字は0）
+pub fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F
+        | 0x20D0..=0x20FF
+        | 0x3099..=0x309A
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_virtual_column_width() {
+        let width = |text: &str, tabstop| virtual_column(text, text.len(), tabstop);
+        assert_eq!(width("abc", 8), 3);
+        assert_eq!(width("日本語", 8), 6);
+        assert_eq!(width("a\tｂ", 4), 6);
+        // 結合文字（濁点）は幅を持たない
+        assert_eq!(width("か\u{3099}", 8), 2);
+        assert_eq!(virtual_column("日本語", "日本".len(), 8), 4);
+    }
+
+    #[test]
+    fn test_byte_column() {
+        // 全角文字の2列目も同じ文字を指す
+        assert_eq!(byte_column("日本語", 2, 8), "日".len());
+        assert_eq!(byte_column("日本語", 3, 8), "日".len());
+        assert_eq!(byte_column("日本語", 6, 8), "日本語".len());
+        assert_eq!(byte_column("a\tb", 5, 8), 1);
+        assert_eq!(byte_column("a\tb", 8, 8), 2);
+        assert_eq!(byte_column("か\u{3099}x", 2, 8), "か\u{3099}".len());
+    }
+
+    #[test]
+    fn test_virtual_column_with_tabs() {
+        let line = "a\tb\t\tc";
+        assert_eq!(virtual_column(line, 1, 4), 1);
+        assert_eq!(virtual_column(line, 2, 4), 4);
+        assert_eq!(virtual_column(line, 5, 4), 12);
+        assert_eq!(virtual_column(line, 5, 8), 24);
+        assert_eq!(virtual_column("日\tx", "日\t".len(), 4), 4);
+    }
+
     #[test]
     fn test_position_new() {
         let pos = Position::new(5, 10);
@@ -226,4 +367,53 @@ mod tests {
         assert_eq!(pos.col, 5);
         assert_eq!(pos.row, 5);
     }
+
+    #[test]
+    fn test_curswant_restores_column_after_short_line() {
+        let want = Curswant::from_position("Long line", Position::new(0, 7), 8);
+        assert_eq!(want, Curswant::Column(7));
+
+        // 空行ではクランプされる
+        let mut pos = Position::new(1, 7);
+        want.apply("", &mut pos, 8);
+        assert_eq!(pos.col, 0);
+
+        // 長い行に戻ると元の列が復元される
+        let mut pos = Position::new(2, 0);
+        want.apply("Another long line", &mut pos, 8);
+        assert_eq!(pos.col, 7);
+    }
+
+    #[test]
+    fn test_curswant_end_of_line() {
+        let mut pos = Position::new(0, 0);
+        Curswant::EndOfLine.apply("Hello", &mut pos, 8);
+        assert_eq!(pos.col, 5);
+
+        Curswant::EndOfLine.apply("Hello, World!", &mut pos, 8);
+        assert_eq!(pos.col, 13);
+    }
+
+    #[test]
+    fn test_curswant_screen_column() {
+        // 表示列で保持するため、全角文字の行では同じ表示列を覆う文字に移る
+        let want = Curswant::from_position("abcd", Position::new(0, 2), 8);
+        assert_eq!(want, Curswant::Column(2));
+        let mut pos = Position::new(1, 0);
+        want.apply("あいう", &mut pos, 8);
+        assert_eq!(pos.col, "あ".len());
+
+        let want = Curswant::from_position("あいう", Position::new(0, "あい".len()), 8);
+        assert_eq!(want, Curswant::Column(4));
+        want.apply("abcdef", &mut pos, 8);
+        assert_eq!(pos.col, 4);
+
+        // タブの後ろの列はタブの幅を含めて数える
+        let want = Curswant::from_position("\tx", Position::new(0, 1), 8);
+        assert_eq!(want, Curswant::Column(8));
+        want.apply("0123456789", &mut pos, 8);
+        assert_eq!(pos.col, 8);
+        want.apply("ab\tcd", &mut pos, 8);
+        assert_eq!(pos.col, 3);
+    }
 }
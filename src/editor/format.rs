@@ -0,0 +1,277 @@
+use crate::editor::buffer::Buffer;
+use crate::editor::comment::{self, CommentPart};
+use crate::editor::cursor::{char_width, virtual_column};
+use crate::editor::options::Options;
+use crate::error::Result;
+
+/// `width`を超える`line`を折り返す位置（1行目の終わりと、次の行に送るテキストの開始位置）
+///
+/// 空白の前で折り返し、`formatoptions`に`m`があれば全角文字の間でも折り返す。
+/// `body_start`より前（インデントやコメントリーダー）では折り返さない。
+/// 収まる位置がなければ最初に折り返せる位置を使う。
+pub fn break_point(
+    line: &str,
+    body_start: usize,
+    width: usize,
+    options: &Options,
+) -> Option<(usize, usize)> {
+    if virtual_column(line, line.len(), options.tabstop) <= width {
+        return None;
+    }
+    let multibyte = options.has_format_option('m');
+    let is_blank = |ch: char| ch == ' ' || ch == '\t';
+
+    let mut found = None;
+    let mut prev: Option<char> = None;
+    for (index, ch) in line.char_indices() {
+        let breakable = match prev {
+            Some(prev) if index > body_start => {
+                (is_blank(ch) && !is_blank(prev))
+                    || (multibyte
+                        && !is_blank(ch)
+                        && !is_blank(prev)
+                        && char_width(ch) != 0
+                        && (char_width(ch) == 2 || char_width(prev) == 2))
+            }
+            _ => false,
+        };
+        if breakable && !line[body_start..index].trim().is_empty() {
+            let fits = virtual_column(line, index, options.tabstop) <= width;
+            if fits || found.is_none() {
+                found = Some(index);
+            }
+            if !fits {
+                break;
+            }
+        }
+        prev = Some(ch);
+    }
+
+    let index = found?;
+    let next = line.len() - line[index..].trim_start_matches(is_blank).len();
+    (next < line.len()).then(|| (line[..index].trim_end_matches(is_blank).len(), next))
+}
+
+/// `gq`/`gw`: `start`行から`end`行までを段落ごとに`textwidth`で整形し直す
+///
+/// 段落は空行（コメントリーダーだけの行を含む）や種類の違うリーダーで区切る。
+/// 2行目以降には段落の2行目のインデントとリーダーを引き継ぐ。整形した最後の行を返す。
+pub fn format_lines(buffer: &mut Buffer, start: usize, end: usize) -> Result<usize> {
+    let options = buffer.options().clone();
+    let mut end = end.min(buffer.line_count().saturating_sub(1));
+    let mut row = start;
+
+    buffer.begin_undo_group();
+    let result = (|| {
+        while row <= end {
+            let (_, part, blank) = split_prefix(buffer.line(row)?, &options);
+            if blank {
+                row += 1;
+                continue;
+            }
+
+            let mut last = row;
+            while last < end {
+                let (_, next, blank) = split_prefix(buffer.line(last + 1)?, &options);
+                if blank || !continues(part.as_ref(), next.as_ref()) {
+                    break;
+                }
+                last += 1;
+            }
+
+            let lines = (row..=last)
+                .map(|row| buffer.line(row).map(str::to_string))
+                .collect::<Result<Vec<_>>>()?;
+            let formatted = format_paragraph(&lines, &options);
+            let count = formatted.len();
+            buffer.replace_lines(row, last, formatted)?;
+
+            end = end + count - (last - row + 1);
+            row += count;
+        }
+        Ok(end)
+    })();
+    buffer.end_undo_group();
+    result
+}
+
+/// 行頭のインデントとコメントリーダーの長さ、リーダーの種類、本文が空かどうか
+///
+/// コメントリーダーは`formatoptions`に`q`があるときだけ認識する。
+fn split_prefix(line: &str, options: &Options) -> (usize, Option<CommentPart>, bool) {
+    let leader = options
+        .has_format_option('q')
+        .then(|| comment::leader(line, &options.comments))
+        .flatten();
+    let len = match &leader {
+        Some(leader) => leader.len,
+        None => line.len() - line.trim_start_matches([' ', '\t']).len(),
+    };
+    (
+        len,
+        leader.map(|leader| leader.part),
+        line[len..].trim().is_empty(),
+    )
+}
+
+/// `first`のリーダーで始まる段落に、`next`のリーダーの行が続くか
+fn continues(first: Option<&CommentPart>, next: Option<&CommentPart>) -> bool {
+    match (first, next) {
+        (None, None) => true,
+        // 箇条書きの2行目以降はリーダーなしで字下げされている
+        (Some(first), None) => first.is_first_only(),
+        (Some(first), Some(next)) => {
+            !next.is_start()
+                && !next.is_first_only()
+                && (next.text == first.text || first.is_start())
+        }
+        (None, Some(_)) => false,
+    }
+}
+
+/// 段落の行を連結し、`format_width`に収まるよう折り返す
+fn format_paragraph(lines: &[String], options: &Options) -> Vec<String> {
+    let first = &lines[0][..split_prefix(&lines[0], options).0];
+    let rest = match lines.get(1) {
+        Some(second) => second[..split_prefix(second, options).0].to_string(),
+        None if options.has_format_option('q') => {
+            comment::continuation(&lines[0], &options.comments)
+        }
+        None => first.to_string(),
+    };
+
+    let mut text = String::new();
+    for line in lines {
+        let body = line[split_prefix(line, options).0..].trim_end_matches([' ', '\t']);
+        if let (Some(last), Some(next)) = (text.chars().last(), body.chars().next()) {
+            text.push_str(separator(last, next, options));
+        }
+        text.push_str(body);
+    }
+
+    let width = options.format_width();
+    let mut formatted = Vec::new();
+    let mut line = format!("{}{}", first, text);
+    let mut body_start = first.len();
+    while let Some((end, next)) = break_point(&line, body_start, width, options) {
+        let wrapped = format!("{}{}", rest, &line[next..]);
+        line.truncate(end);
+        formatted.push(std::mem::replace(&mut line, wrapped));
+        body_start = rest.len();
+    }
+    formatted.push(line);
+    formatted
+}
+
+/// 連結する行の間に入れる空白（`J`と同じく`joinspaces`に従う）
+///
+/// `formatoptions`に`B`があれば全角文字どうしの間には空白を入れない。
+fn separator(last: char, next: char, options: &Options) -> &'static str {
+    if next == ')'
+        || (options.has_format_option('B') && char_width(last) == 2 && char_width(next) == 2)
+    {
+        ""
+    } else if options.joinspaces && matches!(last, '.' | '?' | '!') {
+        "  "
+    } else {
+        " "
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str, textwidth: usize, formatoptions: &str) -> (Vec<String>, usize) {
+        let mut buffer = Buffer::from_content(content);
+        buffer.options_mut().textwidth = textwidth;
+        buffer.options_mut().formatoptions = formatoptions.to_string();
+        let end = buffer.line_count() - 1;
+        let last = format_lines(&mut buffer, 0, end).unwrap();
+        let lines = (0..buffer.line_count())
+            .map(|row| buffer.line(row).unwrap().to_string())
+            .collect();
+        (lines, last)
+    }
+
+    #[test]
+    fn test_break_point() {
+        let options = Options {
+            textwidth: 10,
+            ..Options::default()
+        };
+        assert_eq!(break_point("aaa bbb ccc", 0, 10, &options), Some((7, 8)));
+        assert_eq!(break_point("aaa bbb", 0, 10, &options), None);
+        // 収まらない長い単語はその直後で折り返す
+        assert_eq!(
+            break_point("aaaaaaaaaaaa bb", 0, 10, &options),
+            Some((12, 13))
+        );
+        // インデントやリーダーの直後では折り返さない
+        assert_eq!(break_point("//   aaaaaaaaaaaa", 5, 10, &options), None);
+    }
+
+    #[test]
+    fn test_format_paragraphs() {
+        let (lines, last) = format(
+            "  one two three\n  four five six seven\n\nshort\nlines",
+            12,
+            "tcq",
+        );
+        assert_eq!(
+            lines,
+            [
+                "  one two",
+                "  three four",
+                "  five six",
+                "  seven",
+                "",
+                "short lines"
+            ]
+        );
+        assert_eq!(last, 5);
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let (lines, _) = format(
+            "  // alpha beta gamma delta\n  // epsilon\n# other",
+            16,
+            "q",
+        );
+        assert_eq!(
+            lines,
+            [
+                "  // alpha beta",
+                "  // gamma delta",
+                "  // epsilon",
+                "# other"
+            ]
+        );
+
+        let (lines, _) = format("/* alpha beta gamma delta", 14, "q");
+        assert_eq!(lines, ["/* alpha beta", " * gamma delta"]);
+
+        // `q`がなければリーダーも本文として扱う
+        let (lines, _) = format("// aa bb\n// cc", 79, "");
+        assert_eq!(lines, ["// aa bb // cc"]);
+    }
+
+    #[test]
+    fn test_format_bullets() {
+        let (lines, _) = format("- one two three\n- four", 10, "q");
+        assert_eq!(lines, ["- one two", "  three", "- four"]);
+    }
+
+    #[test]
+    fn test_format_wide_characters() {
+        // 全角文字は2列と数え、`m`があれば文字の間で折り返す
+        let (lines, _) = format("日本語の文章を整形する", 10, "m");
+        assert_eq!(lines, ["日本語の文", "章を整形す", "る"]);
+
+        let (lines, _) = format("日本語の\n文章", 20, "mB");
+        assert_eq!(lines, ["日本語の文章"]);
+        let (lines, _) = format("日本語の\n文章", 20, "m");
+        assert_eq!(lines, ["日本語の 文章"]);
+    }
+}
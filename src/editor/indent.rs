@@ -0,0 +1,195 @@
+use crate::editor::buffer::Buffer;
+use crate::editor::cursor::{Position, virtual_column};
+use crate::editor::options::Options;
+use crate::error::Result;
+
+/// ファイルタイプごとのインデント規則（Vimの`indentexpr`に相当）
+pub trait Indenter {
+    /// `row`行目に置くインデントの幅（`None`なら`autoindent`と同じく前の行に合わせる）
+    ///
+    /// 幅は`buffer`のオプションの`shiftwidth`と`tabstop`に従って計算する。
+    fn indent(&self, buffer: &Buffer, row: usize) -> Option<usize>;
+
+    /// 行頭に入力したときに行のインデントを付け直す文字（Vimの`indentkeys`）
+    fn reindent_keys(&self) -> &[char] {
+        &[]
+    }
+}
+
+/// 括弧の開閉でインデントを増減する（`smartindent`やC系の言語）
+pub struct BracketIndent;
+
+impl Indenter for BracketIndent {
+    fn indent(&self, buffer: &Buffer, row: usize) -> Option<usize> {
+        let options = buffer.options();
+        let shiftwidth = options.shift_width();
+        let current = buffer.line(row).unwrap_or("").trim_start();
+        if current.starts_with(CLOSERS)
+            && let Some(width) = matching_open_indent(buffer, row)
+        {
+            return Some(width);
+        }
+
+        let prev = buffer.line(previous_non_blank(buffer, row)?).ok()?;
+        let width = indent_width(prev, options.tabstop);
+        Some(if prev.trim_end().ends_with(OPENERS) {
+            width + shiftwidth
+        } else {
+            width
+        })
+    }
+
+    fn reindent_keys(&self) -> &[char] {
+        &CLOSERS
+    }
+}
+
+/// 行末の`:`でインデントを増やし、`return`などの後で減らす（Python）
+pub struct ColonIndent;
+
+impl Indenter for ColonIndent {
+    fn indent(&self, buffer: &Buffer, row: usize) -> Option<usize> {
+        let options = buffer.options();
+        let shiftwidth = options.shift_width();
+        let prev = buffer.line(previous_non_blank(buffer, row)?).ok()?;
+        let width = indent_width(prev, options.tabstop);
+        let keyword = prev.split_whitespace().next().unwrap_or("");
+
+        Some(if prev.trim_end().ends_with(':') {
+            width + shiftwidth
+        } else if matches!(keyword, "return" | "pass" | "break" | "continue" | "raise") {
+            width.saturating_sub(shiftwidth)
+        } else {
+            width
+        })
+    }
+}
+
+const OPENERS: [char; 3] = ['{', '(', '['];
+const CLOSERS: [char; 3] = ['}', ')', ']'];
+
+/// ファイルタイプに対応するインデント規則
+pub fn indenter_for(filetype: &str) -> Option<&'static dyn Indenter> {
+    match filetype {
+        "rust" | "c" | "cpp" | "java" | "javascript" | "typescript" | "go" | "json" => {
+            Some(&BracketIndent)
+        }
+        "python" => Some(&ColonIndent),
+        _ => None,
+    }
+}
+
+/// 使用するインデント規則（ファイルタイプの規則がなければ`smartindent`）
+pub fn active_indenter(options: &Options) -> Option<&'static dyn Indenter> {
+    options
+        .filetype
+        .as_deref()
+        .and_then(indenter_for)
+        .or_else(|| {
+            options
+                .smartindent
+                .then_some(&BracketIndent as &dyn Indenter)
+        })
+}
+
+/// 行頭の空白の画面上の幅
+pub fn indent_width(line: &str, tabstop: usize) -> usize {
+    let len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    virtual_column(line, len, tabstop)
+}
+
+/// 新しく開いた`row`行目に付けるインデント
+///
+/// インデント規則があればそれに従い、なければ`autoindent`で`reference`行の行頭の空白をコピーする。
+pub fn open_line_indent(buffer: &Buffer, row: usize, reference: usize) -> String {
+    let options = buffer.options();
+    if let Some(width) = active_indenter(options).and_then(|rule| rule.indent(buffer, row)) {
+        return options.indent_string(width);
+    }
+    if !options.autoindent {
+        return String::new();
+    }
+    let line = buffer.line(reference).unwrap_or("");
+    line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string()
+}
+
+/// インデント規則に従って`row`行目のインデントを付け直す
+pub fn reindent(buffer: &mut Buffer, row: usize) -> Result<()> {
+    let Some(width) = active_indenter(buffer.options()).and_then(|rule| rule.indent(buffer, row))
+    else {
+        return Ok(());
+    };
+    let len = buffer.first_non_blank(row)?;
+    let indent = buffer.options().indent_string(width);
+    buffer.replace_str(Position::new(row, 0), len, &indent)?;
+    Ok(())
+}
+
+fn previous_non_blank(buffer: &Buffer, row: usize) -> Option<usize> {
+    (0..row)
+        .rev()
+        .find(|&row| buffer.line(row).is_ok_and(|line| !line.trim().is_empty()))
+}
+
+/// `row`行目の先頭の閉じ括弧に対応する開き括弧がある行のインデント
+fn matching_open_indent(buffer: &Buffer, row: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for row in (0..row).rev() {
+        let line = buffer.line(row).ok()?;
+        for ch in line.chars().rev() {
+            if CLOSERS.contains(&ch) {
+                depth += 1;
+            } else if OPENERS.contains(&ch) {
+                if depth == 0 {
+                    return Some(indent_width(line, buffer.options().tabstop));
+                }
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bracket_indent() {
+        let buffer = Buffer::from_content("fn main() {\n    if x {\n\n        y\n}");
+
+        assert_eq!(BracketIndent.indent(&buffer, 1), Some(4));
+        assert_eq!(BracketIndent.indent(&buffer, 2), Some(8));
+        // 空行は飛ばして直前の行に合わせる
+        assert_eq!(BracketIndent.indent(&buffer, 3), Some(8));
+        // 閉じ括弧は対応する開き括弧の行に揃える
+        assert_eq!(BracketIndent.indent(&buffer, 4), Some(4));
+        assert_eq!(BracketIndent.indent(&buffer, 0), None);
+    }
+
+    #[test]
+    fn test_colon_indent() {
+        let buffer = Buffer::from_content("def f():\n    return 1\n");
+
+        assert_eq!(ColonIndent.indent(&buffer, 1), Some(4));
+        assert_eq!(ColonIndent.indent(&buffer, 2), Some(0));
+    }
+
+    #[test]
+    fn test_open_line_indent_falls_back_to_autoindent() {
+        let mut buffer = Buffer::from_content("\t  text {\n");
+        assert_eq!(open_line_indent(&buffer, 1, 0), "\t  ");
+
+        // タブは`tabstop`（既定は8）の位置まで進む
+        buffer.options_mut().smartindent = true;
+        assert_eq!(open_line_indent(&buffer, 1, 0), " ".repeat(14));
+
+        buffer.options_mut().expandtab = false;
+        assert_eq!(open_line_indent(&buffer, 1, 0), "\t      ");
+        buffer.options_mut().expandtab = true;
+
+        buffer.options_mut().smartindent = false;
+        buffer.options_mut().autoindent = false;
+        assert_eq!(open_line_indent(&buffer, 1, 0), "");
+    }
+}
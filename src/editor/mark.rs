@@ -0,0 +1,100 @@
+use crate::editor::cursor::Position;
+use std::collections::BTreeMap;
+
+/// バッファ内のマーク（`'<`や`'>`など）
+///
+/// 行の挿入・削除に合わせて位置を移動し、編集後も同じ行を指し続ける。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Marks {
+    positions: BTreeMap<char, Position>,
+    // 最後のビジュアルモードの種類（`v`、`V`、`Ctrl-V`の文字）
+    visual_mode: Option<char>,
+}
+
+impl Marks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: char) -> Option<Position> {
+        self.positions.get(&name).copied()
+    }
+
+    pub fn set(&mut self, name: char, position: Position) {
+        self.positions.insert(name, position);
+    }
+
+    /// 最後のビジュアル選択（モードと`'<`、`'>`の位置）を記録する
+    pub fn set_visual(&mut self, mode: char, start: Position, end: Position) {
+        self.visual_mode = Some(mode);
+        self.set('<', start);
+        self.set('>', end);
+    }
+
+    pub fn visual(&self) -> Option<(char, Position, Position)> {
+        Some((self.visual_mode?, self.get('<')?, self.get('>')?))
+    }
+
+    /// `row`の位置に`count`行挿入された
+    pub fn lines_inserted(&mut self, row: usize, count: usize) {
+        for position in self.positions.values_mut() {
+            if position.row >= row {
+                position.row += count;
+            }
+        }
+    }
+
+    /// `row`から`count`行削除された（削除された行のマークは削除位置に移す）
+    pub fn lines_deleted(&mut self, row: usize, count: usize) {
+        for position in self.positions.values_mut() {
+            if position.row >= row + count {
+                position.row -= count;
+            } else if position.row >= row {
+                *position = Position::new(row, 0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut marks = Marks::new();
+        assert_eq!(marks.get('<'), None);
+        assert_eq!(marks.visual(), None);
+
+        marks.set_visual('V', Position::new(1, 0), Position::new(3, 2));
+        assert_eq!(marks.get('<'), Some(Position::new(1, 0)));
+        assert_eq!(
+            marks.visual(),
+            Some(('V', Position::new(1, 0), Position::new(3, 2)))
+        );
+    }
+
+    #[test]
+    fn test_marks_follow_inserted_lines() {
+        let mut marks = Marks::new();
+        marks.set('a', Position::new(2, 3));
+        marks.set('b', Position::new(5, 1));
+
+        marks.lines_inserted(3, 2);
+        assert_eq!(marks.get('a'), Some(Position::new(2, 3)));
+        assert_eq!(marks.get('b'), Some(Position::new(7, 1)));
+    }
+
+    #[test]
+    fn test_marks_follow_deleted_lines() {
+        let mut marks = Marks::new();
+        marks.set('a', Position::new(1, 3));
+        marks.set('b', Position::new(3, 4));
+        marks.set('c', Position::new(6, 1));
+
+        marks.lines_deleted(2, 3);
+        assert_eq!(marks.get('a'), Some(Position::new(1, 3)));
+        assert_eq!(marks.get('b'), Some(Position::new(2, 0)));
+        assert_eq!(marks.get('c'), Some(Position::new(3, 1)));
+    }
+}
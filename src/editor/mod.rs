@@ -1,8 +1,29 @@
+pub mod arg_list;
 pub mod buffer;
+pub mod buffer_list;
+pub mod comment;
 pub mod cursor;
+pub mod format;
+pub mod indent;
+pub mod mark;
+pub mod motion;
+pub mod options;
+pub mod tab;
+pub mod text_object;
+pub mod viewport;
+pub mod window;
 
-pub use buffer::Buffer;
-pub use cursor::Position;
+pub use arg_list::ArgList;
+pub use buffer::{Buffer, FileState};
+pub use buffer_list::{BufferEntry, BufferList};
+pub use cursor::{Curswant, Position};
+pub use indent::Indenter;
+pub use mark::Marks;
+pub use options::{GlobalOptions, Options};
+pub use tab::TabPages;
+pub use text_object::{TextObject, TextObjectKind, TextRange};
+pub use viewport::Viewport;
+pub use window::{Direction, Layout, Rect, SplitDirection, Window};
 
 #[cfg(test)]
 mod tests {
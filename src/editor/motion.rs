@@ -0,0 +1,163 @@
+use crate::editor::buffer::Buffer;
+use crate::editor::cursor::Position;
+use crate::editor::text_object::{CharClass, char_class, next_boundary, prev_boundary};
+
+/// 段落の境界になる空行か（空白だけの行は含めない）
+fn is_empty_line(buffer: &Buffer, row: usize) -> bool {
+    buffer.line(row).map(str::is_empty).unwrap_or(true)
+}
+
+/// `}`: 段落の後ろの空行へ進む（なければ最後の行の末尾）
+pub fn paragraph_forward(buffer: &Buffer, row: usize) -> Position {
+    let last = buffer.line_count() - 1;
+    let mut row = row.min(last);
+    // 空行の並びを越えてから、次の空行まで進む
+    while row < last && is_empty_line(buffer, row) {
+        row += 1;
+    }
+    while row < last && !is_empty_line(buffer, row) {
+        row += 1;
+    }
+    if is_empty_line(buffer, row) {
+        Position::new(row, 0)
+    } else {
+        Position::new(row, buffer.line_length(row).unwrap_or(0))
+    }
+}
+
+/// `{`: 段落の前の空行へ戻る（なければ最初の行の先頭）
+pub fn paragraph_backward(buffer: &Buffer, row: usize) -> Position {
+    let mut row = row.min(buffer.line_count() - 1);
+    while row > 0 && is_empty_line(buffer, row) {
+        row -= 1;
+    }
+    while row > 0 && !is_empty_line(buffer, row) {
+        row -= 1;
+    }
+    Position::new(row, 0)
+}
+
+/// `w`: 次の単語の先頭へ進む（空行も単語として止まり、最後の単語では行末へ）
+pub fn word_forward(buffer: &Buffer, position: Position) -> Position {
+    let (mut row, mut col) = (position.row, position.col);
+    let line = buffer.line(row).unwrap_or("");
+    if let Some(ch) = line.get(col..).and_then(|rest| rest.chars().next()) {
+        let class = char_class(ch, false);
+        if class != CharClass::Blank {
+            col = run_end(line, col, class);
+        }
+    }
+    loop {
+        let line = buffer.line(row).unwrap_or("");
+        col = run_end(line, col, CharClass::Blank);
+        if col < line.len() || row + 1 >= buffer.line_count() {
+            return Position::new(row, col);
+        }
+        row += 1;
+        col = 0;
+        if is_empty_line(buffer, row) {
+            return Position::new(row, 0);
+        }
+    }
+}
+
+/// `e`: 単語の末尾の文字へ進む（空行では止まらない）
+pub fn word_end(buffer: &Buffer, position: Position) -> Position {
+    let (mut row, mut col) = (position.row, position.col);
+    col = next_boundary(buffer.line(row).unwrap_or(""), col);
+    loop {
+        let line = buffer.line(row).unwrap_or("");
+        col = run_end(line, col, CharClass::Blank);
+        if let Some(ch) = line[col..].chars().next() {
+            let end = run_end(line, col, char_class(ch, false));
+            return Position::new(row, prev_boundary(line, end));
+        }
+        if row + 1 >= buffer.line_count() {
+            return position;
+        }
+        row += 1;
+        col = 0;
+    }
+}
+
+/// `b`: 前の単語の先頭へ戻る（空行も単語として止まる）
+pub fn word_backward(buffer: &Buffer, position: Position) -> Position {
+    let (mut row, mut col) = (position.row, position.col);
+    loop {
+        let line = buffer.line(row).unwrap_or("");
+        let before = &line[..col.min(line.len())];
+        if let Some((index, ch)) = before.char_indices().rfind(|(_, ch)| !ch.is_whitespace()) {
+            let class = char_class(ch, false);
+            let start = before[..index]
+                .char_indices()
+                .rfind(|(_, ch)| char_class(*ch, false) != class)
+                .map_or(0, |(i, ch)| i + ch.len_utf8());
+            return Position::new(row, start);
+        }
+        if row == 0 {
+            return Position::new(0, 0);
+        }
+        row -= 1;
+        col = buffer.line_length(row).unwrap_or(0);
+        if col == 0 {
+            return Position::new(row, 0);
+        }
+    }
+}
+
+/// `col`から始まる`class`の文字の並びの終わり
+fn run_end(line: &str, col: usize, class: CharClass) -> usize {
+    line[col..]
+        .char_indices()
+        .find(|(_, ch)| char_class(*ch, false) != class)
+        .map_or(line.len(), |(i, _)| col + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraph_motions() {
+        let buffer = Buffer::from_content("one\ntwo\n\n\nthree\n  \nfour");
+
+        assert_eq!(paragraph_forward(&buffer, 0), Position::new(2, 0));
+        // 空行の並びの上では次の段落の後ろまで進む
+        assert_eq!(paragraph_forward(&buffer, 2), Position::new(6, 4));
+        assert_eq!(paragraph_forward(&buffer, 6), Position::new(6, 4));
+
+        assert_eq!(paragraph_backward(&buffer, 6), Position::new(3, 0));
+        assert_eq!(paragraph_backward(&buffer, 3), Position::new(0, 0));
+        assert_eq!(paragraph_backward(&buffer, 1), Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let buffer = Buffer::from_content("foo.bar  baz\n\n  qux");
+        let forward = |row, col| word_forward(&buffer, Position::new(row, col));
+        let end = |row, col| word_end(&buffer, Position::new(row, col));
+        let backward = |row, col| word_backward(&buffer, Position::new(row, col));
+
+        assert_eq!(forward(0, 0), Position::new(0, 3));
+        assert_eq!(forward(0, 3), Position::new(0, 4));
+        assert_eq!(forward(0, 4), Position::new(0, 9));
+        // 空行でも止まり、最後の単語からは行末へ
+        assert_eq!(forward(0, 9), Position::new(1, 0));
+        assert_eq!(forward(1, 0), Position::new(2, 2));
+        assert_eq!(forward(2, 2), Position::new(2, 5));
+
+        assert_eq!(end(0, 0), Position::new(0, 2));
+        assert_eq!(end(0, 2), Position::new(0, 3));
+        assert_eq!(end(0, 9), Position::new(0, 11));
+        // 空行を越えて次の単語の末尾へ
+        assert_eq!(end(0, 11), Position::new(2, 4));
+        assert_eq!(end(2, 4), Position::new(2, 4));
+
+        assert_eq!(backward(2, 2), Position::new(1, 0));
+        assert_eq!(backward(1, 0), Position::new(0, 9));
+        assert_eq!(backward(0, 11), Position::new(0, 9));
+        assert_eq!(backward(0, 4), Position::new(0, 3));
+        assert_eq!(backward(0, 3), Position::new(0, 0));
+        assert_eq!(backward(0, 0), Position::new(0, 0));
+    }
+}
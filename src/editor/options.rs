@@ -0,0 +1,605 @@
+use crate::error::{EditorError, Result};
+use crate::io::{BackupCopy, WriteOptions};
+use std::path::Path;
+
+/// バッファごとのオプション（`:set`で変更する）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// 新しい行に前の行のインデントをコピーする
+    pub autoindent: bool,
+    /// 括弧に合わせてインデントを増減する
+    pub smartindent: bool,
+    /// ファイルタイプ（拡張子から判定し、インデント規則の選択に使う）
+    pub filetype: Option<String>,
+    /// タブ文字の表示幅
+    pub tabstop: usize,
+    /// インデント1段の幅（0なら`tabstop`）
+    pub shiftwidth: usize,
+    /// Insertモードの`Tab`/`Backspace`で増減する空白の幅（0なら無効）
+    pub softtabstop: usize,
+    /// インデントや`Tab`をタブ文字でなく空白で入力する
+    pub expandtab: bool,
+    /// 行の連結で`.`、`?`、`!`の後に空白を2つ入れる
+    pub joinspaces: bool,
+    /// 自動で折り返す行の幅（0なら折り返さない）
+    pub textwidth: usize,
+    /// 自動整形の動作を表すフラグ（`j`なら連結時にコメントリーダーを取り除く）
+    pub formatoptions: String,
+    /// コメントリーダーの一覧（Vimの`comments`と同じ書式）
+    pub comments: String,
+    /// `~`を大文字・小文字を切り替えるオペレータにする
+    pub tildeop: bool,
+    /// `Ctrl-A`/`Ctrl-X`で扱う数値の形式（`bin`、`octal`、`hex`、`alpha`、`unsigned`）
+    pub nrformats: String,
+    /// `!`なしでは保存しない（書き込めないファイルを開くと有効になる）
+    pub readonly: bool,
+}
+
+const DEFAULT_COMMENTS: &str = "s1:/*,mb:*,ex:*/,://,b:#,:%,:XCOMM,n:>,fb:-";
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            autoindent: true,
+            smartindent: false,
+            filetype: None,
+            tabstop: 8,
+            shiftwidth: 4,
+            softtabstop: 0,
+            expandtab: true,
+            joinspaces: false,
+            textwidth: 0,
+            formatoptions: "tcqj".to_string(),
+            comments: DEFAULT_COMMENTS.to_string(),
+            tildeop: false,
+            nrformats: "bin,hex".to_string(),
+            readonly: false,
+        }
+    }
+}
+
+impl Options {
+    /// 実際に使うインデント1段の幅
+    pub fn shift_width(&self) -> usize {
+        if self.shiftwidth == 0 {
+            self.tabstop
+        } else {
+            self.shiftwidth
+        }
+    }
+
+    /// 画面上の列`from`から`to`までを埋める空白（`noexpandtab`ではタブ文字を使う）
+    pub fn whitespace(&self, from: usize, to: usize) -> String {
+        if self.expandtab || to <= from {
+            return " ".repeat(to.saturating_sub(from));
+        }
+        let first_stop = (from / self.tabstop + 1) * self.tabstop;
+        if first_stop > to {
+            return " ".repeat(to - from);
+        }
+        let tabs = 1 + (to - first_stop) / self.tabstop;
+        let spaces = (to - first_stop) % self.tabstop;
+        format!("{}{}", "\t".repeat(tabs), " ".repeat(spaces))
+    }
+
+    /// `gq`で整形する行の幅（`textwidth`が0なら79）
+    pub fn format_width(&self) -> usize {
+        if self.textwidth == 0 {
+            79
+        } else {
+            self.textwidth
+        }
+    }
+
+    pub fn has_format_option(&self, flag: char) -> bool {
+        self.formatoptions.contains(flag)
+    }
+
+    /// 幅`width`のインデント
+    pub fn indent_string(&self, width: usize) -> String {
+        self.whitespace(0, width)
+    }
+
+    /// `:set`の引数1つを適用する（`ai`、`noai`、`ai!`、`ts=4`、`fo+=j`、`ft=rust`など）
+    pub fn set(&mut self, arg: &str) -> Result<()> {
+        match SetArg::parse(arg) {
+            SetArg::Value { name, op, value } => match name {
+                "filetype" | "ft" if op.is_none() => {
+                    self.filetype = (!value.is_empty()).then(|| value.to_string());
+                }
+                "tabstop" | "ts" => {
+                    let tabstop = number(self.tabstop, arg, value, op)?;
+                    if tabstop == 0 {
+                        return Err(invalid_argument(arg));
+                    }
+                    self.tabstop = tabstop;
+                }
+                "shiftwidth" | "sw" => self.shiftwidth = number(self.shiftwidth, arg, value, op)?,
+                "softtabstop" | "sts" => {
+                    self.softtabstop = number(self.softtabstop, arg, value, op)?
+                }
+                "textwidth" | "tw" => self.textwidth = number(self.textwidth, arg, value, op)?,
+                "formatoptions" | "fo" => {
+                    modify_list(&mut self.formatoptions, value, op, None);
+                }
+                "comments" | "com" => modify_list(&mut self.comments, value, op, Some(',')),
+                "nrformats" | "nf" => modify_list(&mut self.nrformats, value, op, Some(',')),
+                _ => return Err(unknown_option(name)),
+            },
+            SetArg::Flag { name, value } => {
+                let flag = match name {
+                    "autoindent" | "ai" => &mut self.autoindent,
+                    "smartindent" | "si" => &mut self.smartindent,
+                    "expandtab" | "et" => &mut self.expandtab,
+                    "joinspaces" | "js" => &mut self.joinspaces,
+                    "tildeop" | "top" => &mut self.tildeop,
+                    "readonly" | "ro" => &mut self.readonly,
+                    _ => return Err(unknown_option(name)),
+                };
+                *flag = value.unwrap_or(!*flag);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// エディタ全体のオプション（バッファを切り替えても変わらない）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalOptions {
+    /// 変更を保存していないバッファも隠して別のバッファに切り替えられる
+    pub hidden: bool,
+    /// タブラインを表示するか（0: 表示しない、1: タブページが2つ以上のとき、2: 常に）
+    pub showtabline: usize,
+    /// 保存時に元のファイルを置き換えるか上書きするか
+    pub backupcopy: BackupCopy,
+    /// 保存後もバックアップ（ファイル名に`backupext`を付けた名前）を残す
+    pub backup: bool,
+    /// 保存が終わるまでバックアップを作っておく
+    pub writebackup: bool,
+    /// バックアップを作るディレクトリのカンマ区切りの候補（`.`はファイルと同じディレクトリ）
+    pub backupdir: String,
+    /// バックアップのファイル名に付ける文字列
+    pub backupext: String,
+    /// 外部で変更されたファイルを、バッファに変更がなければ確認せずに読み直す
+    pub autoread: bool,
+    /// 変更をスワップファイルに書き出す
+    pub swapfile: bool,
+    /// スワップファイルを作るディレクトリのカンマ区切りの候補（`.`はファイルと同じディレクトリ）
+    pub directory: String,
+    /// キー入力がこの時間（ミリ秒）なければスワップファイルに書き出す
+    pub updatetime: usize,
+    /// この数のキーを入力するたびにスワップファイルに書き出す（0ならスワップファイルを作らない）
+    pub updatecount: usize,
+    /// カーソルの上下に最低限表示しておく行数
+    pub scrolloff: usize,
+    /// 折り返さない行でカーソルの左右に最低限表示しておく列数
+    pub sidescrolloff: usize,
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            showtabline: 1,
+            backupcopy: BackupCopy::Auto,
+            backup: false,
+            writebackup: true,
+            backupdir: ".".to_string(),
+            backupext: "~".to_string(),
+            autoread: false,
+            swapfile: true,
+            directory: ".".to_string(),
+            updatetime: 4000,
+            updatecount: 200,
+            scrolloff: 5,
+            sidescrolloff: 0,
+        }
+    }
+}
+
+impl GlobalOptions {
+    const NAMES: &[&str] = &[
+        "hidden",
+        "hid",
+        "showtabline",
+        "stal",
+        "backupcopy",
+        "bkc",
+        "backup",
+        "bk",
+        "writebackup",
+        "wb",
+        "backupdir",
+        "bdir",
+        "backupext",
+        "bex",
+        "autoread",
+        "ar",
+        "swapfile",
+        "swf",
+        "directory",
+        "dir",
+        "updatetime",
+        "ut",
+        "updatecount",
+        "uc",
+        "scrolloff",
+        "so",
+        "sidescrolloff",
+        "siso",
+    ];
+
+    /// `:set`の引数がエディタ全体のオプションを対象にしているか
+    pub fn is_global(arg: &str) -> bool {
+        Self::NAMES.contains(&SetArg::parse(arg).name())
+    }
+
+    /// `:set`の引数1つを適用する
+    pub fn set(&mut self, arg: &str) -> Result<()> {
+        match SetArg::parse(arg) {
+            SetArg::Value {
+                name: "showtabline" | "stal",
+                op,
+                value,
+            } => {
+                let showtabline = number(self.showtabline, arg, value, op)?;
+                if showtabline > 2 {
+                    return Err(invalid_argument(arg));
+                }
+                self.showtabline = showtabline;
+                Ok(())
+            }
+            SetArg::Value {
+                name: "backupcopy" | "bkc",
+                op: None,
+                value,
+            } => {
+                self.backupcopy = BackupCopy::parse(value).ok_or_else(|| invalid_argument(arg))?;
+                Ok(())
+            }
+            SetArg::Value {
+                name: "backupdir" | "bdir",
+                op,
+                value,
+            } => {
+                modify_list(&mut self.backupdir, value, op, Some(','));
+                Ok(())
+            }
+            // 空やディレクトリを含む名前では元のファイルと区別できない
+            SetArg::Value {
+                name: "backupext" | "bex",
+                op: None,
+                value,
+            } if !value.is_empty() && !value.contains(std::path::is_separator) => {
+                self.backupext = value.to_string();
+                Ok(())
+            }
+            SetArg::Value {
+                name: "directory" | "dir",
+                op,
+                value,
+            } => {
+                modify_list(&mut self.directory, value, op, Some(','));
+                Ok(())
+            }
+            SetArg::Value {
+                name: "updatetime" | "ut",
+                op,
+                value,
+            } => {
+                self.updatetime = number(self.updatetime, arg, value, op)?;
+                Ok(())
+            }
+            SetArg::Value {
+                name: "updatecount" | "uc",
+                op,
+                value,
+            } => {
+                self.updatecount = number(self.updatecount, arg, value, op)?;
+                Ok(())
+            }
+            SetArg::Value {
+                name: "scrolloff" | "so",
+                op,
+                value,
+            } => {
+                self.scrolloff = number(self.scrolloff, arg, value, op)?;
+                Ok(())
+            }
+            SetArg::Value {
+                name: "sidescrolloff" | "siso",
+                op,
+                value,
+            } => {
+                self.sidescrolloff = number(self.sidescrolloff, arg, value, op)?;
+                Ok(())
+            }
+            SetArg::Value { name, .. } if Self::NAMES.contains(&name) => Err(invalid_argument(arg)),
+            SetArg::Value { name, .. } => Err(unknown_option(name)),
+            SetArg::Flag { name, value } => {
+                let flag = match name {
+                    "hidden" | "hid" => &mut self.hidden,
+                    "backup" | "bk" => &mut self.backup,
+                    "writebackup" | "wb" => &mut self.writebackup,
+                    "autoread" | "ar" => &mut self.autoread,
+                    "swapfile" | "swf" => &mut self.swapfile,
+                    _ => return Err(unknown_option(name)),
+                };
+                *flag = value.unwrap_or(!*flag);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl GlobalOptions {
+    /// 保存（`FileSystem::save`）に渡すオプション
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            backupcopy: self.backupcopy,
+            backup: self.backup,
+            writebackup: self.writebackup,
+            backupdir: self.backupdir.clone(),
+            backupext: self.backupext.clone(),
+        }
+    }
+}
+
+/// `:set`の引数1つ（`name`、`noname`、`name!`、`name=value`、`name+=value`、`name-=value`）
+enum SetArg<'a> {
+    /// `value`が`None`なら反転する
+    Flag { name: &'a str, value: Option<bool> },
+    Value {
+        name: &'a str,
+        op: Option<char>,
+        value: &'a str,
+    },
+}
+
+impl<'a> SetArg<'a> {
+    fn parse(arg: &'a str) -> Self {
+        if let Some((name, value)) = arg.split_once('=') {
+            // `+=`/`-=`は数値なら加減算、文字列なら追加・削除
+            let (name, op) = match name.strip_suffix(['+', '-']) {
+                Some(stripped) => (stripped, name.chars().last()),
+                None => (name, None),
+            };
+            return SetArg::Value { name, op, value };
+        }
+
+        let (name, value) = if let Some(name) = arg.strip_suffix('!') {
+            (name, None)
+        } else if let Some(name) = arg.strip_prefix("no") {
+            (name, Some(false))
+        } else {
+            (arg, Some(true))
+        };
+        SetArg::Flag { name, value }
+    }
+
+    fn name(&self) -> &'a str {
+        match self {
+            SetArg::Flag { name, .. } | SetArg::Value { name, .. } => name,
+        }
+    }
+}
+
+/// 数値オプションの新しい値
+fn number(current: usize, arg: &str, value: &str, op: Option<char>) -> Result<usize> {
+    let value = value.parse::<usize>().map_err(|_| invalid_argument(arg))?;
+    Ok(match op {
+        Some('+') => current + value,
+        Some(_) => current.saturating_sub(value),
+        None => value,
+    })
+}
+
+/// 文字列オプションに`value`を設定・追加・削除する（`separator`がなければフラグの並び）
+fn modify_list(current: &mut String, value: &str, op: Option<char>, separator: Option<char>) {
+    match (op, separator) {
+        (None, _) => *current = value.to_string(),
+        (Some('+'), Some(separator)) => {
+            if !current.split(separator).any(|item| item == value) {
+                if !current.is_empty() {
+                    current.push(separator);
+                }
+                current.push_str(value);
+            }
+        }
+        (Some('+'), None) => {
+            let flags: String = value.chars().filter(|&ch| !current.contains(ch)).collect();
+            current.push_str(&flags);
+        }
+        (Some(_), Some(separator)) => {
+            let items: Vec<&str> = current
+                .split(separator)
+                .filter(|item| *item != value)
+                .collect();
+            *current = items.join(&separator.to_string());
+        }
+        (Some(_), None) => current.retain(|ch| !value.contains(ch)),
+    }
+}
+
+fn unknown_option(name: &str) -> EditorError {
+    EditorError::config(format!("Unknown option: {}", name))
+}
+
+fn invalid_argument(arg: &str) -> EditorError {
+    EditorError::config(format!("Invalid argument: {}", arg))
+}
+
+/// 拡張子からファイルタイプを判定する
+pub fn detect_filetype(path: &Path) -> Option<&'static str> {
+    let filetype = match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" => "cpp",
+        "java" => "java",
+        "js" | "mjs" => "javascript",
+        "ts" => "typescript",
+        "go" => "go",
+        "json" => "json",
+        "py" => "python",
+        _ => return None,
+    };
+    Some(filetype)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_flags() {
+        let mut options = Options::default();
+
+        options.set("noai").unwrap();
+        assert!(!options.autoindent);
+        options.set("autoindent").unwrap();
+        assert!(options.autoindent);
+        options.set("si!").unwrap();
+        assert!(options.smartindent);
+        options.set("ft=python").unwrap();
+        assert_eq!(options.filetype.as_deref(), Some("python"));
+
+        assert!(options.set("nosuchoption").is_err());
+        assert!(options.set("ai=1").is_err());
+    }
+
+    #[test]
+    fn test_set_tab_options() {
+        let mut options = Options::default();
+
+        options.set("ts=4").unwrap();
+        options.set("sw=0").unwrap();
+        options.set("sts=2").unwrap();
+        options.set("noet").unwrap();
+        assert_eq!(options.tabstop, 4);
+        assert_eq!(options.shift_width(), 4);
+        assert_eq!(options.softtabstop, 2);
+        assert!(!options.expandtab);
+
+        options.set("sw+=2").unwrap();
+        assert_eq!(options.shift_width(), 2);
+
+        assert_eq!(options.format_width(), 79);
+        options.set("tw=60").unwrap();
+        assert_eq!(options.format_width(), 60);
+
+        assert!(options.set("ts=0").is_err());
+        assert!(options.set("sw=x").is_err());
+    }
+
+    #[test]
+    fn test_set_list_options() {
+        let mut options = Options::default();
+
+        options.set("fo-=j").unwrap();
+        assert!(!options.has_format_option('j'));
+        options.set("fo+=jt").unwrap();
+        assert_eq!(options.formatoptions, "tcqj");
+
+        options.set("com=://").unwrap();
+        options.set("com+=b:#").unwrap();
+        options.set("com+=b:#").unwrap();
+        assert_eq!(options.comments, "://,b:#");
+        options.set("com-=://").unwrap();
+        assert_eq!(options.comments, "b:#");
+
+        options.set("nf+=alpha").unwrap();
+        options.set("nf-=bin").unwrap();
+        assert_eq!(options.nrformats, "hex,alpha");
+
+        assert!(options.set("ft+=rust").is_err());
+    }
+
+    #[test]
+    fn test_global_options() {
+        let mut options = GlobalOptions::default();
+        assert!(GlobalOptions::is_global("hidden"));
+        assert!(GlobalOptions::is_global("nohid"));
+        assert!(!GlobalOptions::is_global("ai"));
+
+        options.set("hid").unwrap();
+        assert!(options.hidden);
+        options.set("hidden!").unwrap();
+        assert!(!options.hidden);
+
+        assert!(options.set("hidden=1").is_err());
+        assert!(options.set("ai").is_err());
+
+        assert_eq!(options.showtabline, 1);
+        assert!(GlobalOptions::is_global("stal=2"));
+        options.set("stal=2").unwrap();
+        assert_eq!(options.showtabline, 2);
+        options.set("showtabline-=2").unwrap();
+        assert_eq!(options.showtabline, 0);
+        assert!(options.set("stal=3").is_err());
+        assert!(options.set("stal").is_err());
+
+        assert_eq!(options.write_options(), WriteOptions::default());
+        options.set("bkc=no").unwrap();
+        options.set("bk").unwrap();
+        options.set("nowb").unwrap();
+        options.set("bdir+=~/backup").unwrap();
+        options.set("bex=.bak").unwrap();
+        assert_eq!(
+            options.write_options(),
+            WriteOptions {
+                backupcopy: BackupCopy::No,
+                backup: true,
+                writebackup: false,
+                backupdir: ".,~/backup".to_string(),
+                backupext: ".bak".to_string(),
+            }
+        );
+        assert!(options.set("bex=").is_err());
+        assert!(options.set("bex=/x").is_err());
+        options.set("backupdir-=.").unwrap();
+        assert_eq!(options.backupdir, "~/backup");
+        assert!(options.set("bkc=always").is_err());
+        assert!(options.set("bkc+=yes").is_err());
+
+        options.set("ar").unwrap();
+        assert!(options.autoread);
+        options.set("noswf").unwrap();
+        assert!(!options.swapfile);
+        options.set("dir=~/swap,.").unwrap();
+        assert_eq!(options.directory, "~/swap,.");
+        options.set("ut=1000").unwrap();
+        options.set("uc-=100").unwrap();
+        assert_eq!((options.updatetime, options.updatecount), (1000, 100));
+
+        assert_eq!((options.scrolloff, options.sidescrolloff), (5, 0));
+        assert!(GlobalOptions::is_global("so=0"));
+        options.set("so=0").unwrap();
+        options.set("sidescrolloff=3").unwrap();
+        options.set("siso+=1").unwrap();
+        assert_eq!((options.scrolloff, options.sidescrolloff), (0, 4));
+        assert!(options.set("so").is_err());
+        assert!(options.set("siso=x").is_err());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let mut options = Options {
+            tabstop: 4,
+            expandtab: false,
+            ..Options::default()
+        };
+        assert_eq!(options.indent_string(10), "\t\t  ");
+        assert_eq!(options.whitespace(2, 9), "\t\t ");
+        assert_eq!(options.whitespace(1, 3), "  ");
+
+        options.expandtab = true;
+        assert_eq!(options.indent_string(6), "      ");
+    }
+
+    #[test]
+    fn test_detect_filetype() {
+        assert_eq!(detect_filetype(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(detect_filetype(Path::new("a.py")), Some("python"));
+        assert_eq!(detect_filetype(Path::new("README")), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::editor::buffer_list::BufferList;
+use crate::editor::window::Layout;
+use crate::error::{EditorError, Result};
+
+/// タブページの一覧（タブページごとにウィンドウの配置を持つ）とカレントタブページ
+#[derive(Debug, Clone)]
+pub struct TabPages {
+    tabs: Vec<Layout>,
+    current: usize,
+}
+
+impl TabPages {
+    /// `layout`のタブページ1つだけの一覧
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            tabs: vec![layout],
+            current: 0,
+        }
+    }
+
+    /// カレントタブページのウィンドウの配置
+    pub fn layout(&self) -> &Layout {
+        &self.tabs[self.current]
+    }
+
+    pub fn layout_mut(&mut self) -> &mut Layout {
+        &mut self.tabs[self.current]
+    }
+
+    /// カレントタブページの位置（先頭が0）
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    pub fn count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Layout> {
+        self.tabs.iter()
+    }
+
+    /// `:tabnew`: `index`の位置（範囲外なら末尾）に新しいタブページを作ってカレントにする
+    pub fn insert(&mut self, index: usize, layout: Layout) {
+        let index = index.min(self.tabs.len());
+        self.tabs.insert(index, layout);
+        self.current = index;
+    }
+
+    /// `gt`/`:tabnext N`: `index`のタブページに移る（範囲外なら何もしない）
+    pub fn focus(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.current = index;
+        }
+    }
+
+    /// `gt`/`gT`: `count`個先（前）のタブページの位置（端では反対側に回る）
+    pub fn cycle(&self, count: usize, forward: bool) -> usize {
+        let len = self.tabs.len();
+        let count = count % len;
+        if forward {
+            (self.current + count) % len
+        } else {
+            (self.current + len - count) % len
+        }
+    }
+
+    /// `:tabclose`: `index`のタブページを閉じる（カレントなら右隣、末尾なら左隣に移る）
+    pub fn close(&mut self, index: usize) -> Result<Layout> {
+        if self.tabs.len() == 1 {
+            return Err(EditorError::invalid_command("Cannot close last tab page"));
+        }
+        if index >= self.tabs.len() {
+            return Err(EditorError::invalid_command("Invalid tab page"));
+        }
+        let layout = self.tabs.remove(index);
+        if index < self.current || self.current == self.tabs.len() {
+            self.current -= 1;
+        }
+        Ok(layout)
+    }
+
+    /// `:tabonly`: カレントタブページ以外を閉じる
+    pub fn only(&mut self) {
+        let layout = self.tabs.swap_remove(self.current);
+        self.tabs = vec![layout];
+        self.current = 0;
+    }
+
+    /// `:tabmove`: カレントタブページを`index`の位置（範囲外なら末尾）に移す
+    pub fn move_current(&mut self, index: usize) {
+        let index = index.min(self.tabs.len() - 1);
+        let layout = self.tabs.remove(self.current);
+        self.tabs.insert(index, layout);
+        self.current = index;
+    }
+
+    /// `:bdelete`で削除したバッファを表示しているウィンドウを閉じる
+    ///
+    /// カレントウィンドウは残し、すべてのウィンドウが閉じるタブページはタブページごと閉じる。
+    pub fn close_buffer(&mut self, number: usize) -> Result<()> {
+        let current = self.layout().current_id();
+        let mut index = 0;
+        while index < self.tabs.len() {
+            let is_current = index == self.current;
+            let stale: Vec<usize> = self.tabs[index]
+                .windows()
+                .filter(|window| window.buffer == number && !(is_current && window.id == current))
+                .map(|window| window.id)
+                .collect();
+            if stale.len() == self.tabs[index].count() {
+                self.close(index)?;
+                continue;
+            }
+            for id in stale {
+                self.tabs[index].close(id)?;
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// タブラインに表示するラベル（ウィンドウの数、変更のあるバッファがあれば`+`、カレントウィンドウのバッファ名）
+    pub fn label(layout: &Layout, buffers: &BufferList) -> String {
+        let mut prefix = String::new();
+        if layout.count() > 1 {
+            prefix.push_str(&layout.count().to_string());
+        }
+        let modified = layout.windows().any(|window| {
+            buffers
+                .get(window.buffer)
+                .is_some_and(|entry| entry.buffer.is_modified())
+        });
+        if modified {
+            prefix.push('+');
+        }
+        let name = buffers
+            .get(layout.current().buffer)
+            .and_then(|entry| entry.buffer.file_path())
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or("[No Name]");
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} {}", prefix, name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::buffer::Buffer;
+    use crate::editor::cursor::Position;
+    use crate::editor::window::{Rect, SplitDirection};
+    use std::path::PathBuf;
+
+    fn tabs(buffers: &[usize]) -> TabPages {
+        let mut tabs = TabPages::new(Layout::new(buffers[0]));
+        for &buffer in &buffers[1..] {
+            tabs.insert(tabs.count(), Layout::new(buffer));
+        }
+        tabs
+    }
+
+    fn buffers_of(tabs: &TabPages) -> Vec<usize> {
+        tabs.iter().map(|layout| layout.current().buffer).collect()
+    }
+
+    #[test]
+    fn test_insert_and_cycle() {
+        let mut tabs = tabs(&[1, 2, 3]);
+        assert_eq!(tabs.index(), 2);
+
+        // 新しいタブページはカレントタブページの後ろに作る
+        tabs.focus(0);
+        tabs.insert(tabs.index() + 1, Layout::new(4));
+        assert_eq!(buffers_of(&tabs), [1, 4, 2, 3]);
+        assert_eq!(tabs.layout().current().buffer, 4);
+
+        assert_eq!(tabs.cycle(1, true), 2);
+        assert_eq!(tabs.cycle(3, true), 0);
+        assert_eq!(tabs.cycle(2, false), 3);
+        tabs.focus(9);
+        assert_eq!(tabs.index(), 1);
+    }
+
+    #[test]
+    fn test_close_and_only() {
+        let mut tabs = tabs(&[1, 2, 3]);
+        tabs.focus(1);
+        tabs.close(1).unwrap();
+        assert_eq!(buffers_of(&tabs), [1, 3]);
+        assert_eq!(tabs.layout().current().buffer, 3);
+
+        // 末尾のタブページを閉じると左隣に移る
+        tabs.close(1).unwrap();
+        assert_eq!(tabs.index(), 0);
+        assert!(tabs.close(0).is_err());
+
+        let mut tabs = self::tabs(&[1, 2, 3]);
+        tabs.focus(1);
+        tabs.only();
+        assert_eq!(buffers_of(&tabs), [2]);
+    }
+
+    #[test]
+    fn test_move_current() {
+        let mut tabs = tabs(&[1, 2, 3, 4]);
+        tabs.focus(1);
+        tabs.move_current(2);
+        assert_eq!(buffers_of(&tabs), [1, 3, 2, 4]);
+        assert_eq!(tabs.index(), 2);
+        tabs.move_current(usize::MAX);
+        assert_eq!(buffers_of(&tabs), [1, 3, 4, 2]);
+        tabs.move_current(0);
+        assert_eq!(buffers_of(&tabs), [2, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_close_buffer() {
+        let area = Rect::new(0, 0, 20, 40);
+        let mut tabs = tabs(&[1, 2, 1]);
+        tabs.focus(1);
+        tabs.layout_mut()
+            .split(SplitDirection::Horizontal, area)
+            .unwrap();
+        tabs.layout_mut().current_mut().buffer = 1;
+
+        // カレントウィンドウは残し、バッファ1だけのタブページは閉じる
+        tabs.close_buffer(1).unwrap();
+        assert_eq!(tabs.count(), 1);
+        assert_eq!(tabs.layout().count(), 2);
+        assert_eq!(tabs.layout().current().buffer, 1);
+
+        tabs.close_buffer(2).unwrap();
+        assert_eq!(tabs.layout().count(), 1);
+    }
+
+    #[test]
+    fn test_label() {
+        let mut buffers = BufferList::new(Buffer::new());
+        let path = PathBuf::from("src/main.rs");
+        let number = buffers.add(Buffer::from_content("fn main() {}").with_file_path(path));
+        let mut layout = Layout::new(number);
+        assert_eq!(TabPages::label(&layout, &buffers), "main.rs");
+
+        layout
+            .split(SplitDirection::Vertical, Rect::new(0, 0, 20, 40))
+            .unwrap();
+        layout.current_mut().buffer = 1;
+        buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+        assert_eq!(TabPages::label(&layout, &buffers), "2+ [No Name]");
+    }
+}
@@ -0,0 +1,1070 @@
+use crate::editor::buffer::Buffer;
+use crate::editor::cursor::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObjectKind {
+    Word,
+    BigWord,
+    Sentence,
+    Paragraph,
+    Block { open: char, close: char },
+    Quote(char),
+    Tag,
+}
+
+/// `iw`や`a(`などのテキストオブジェクト（`inner`が`i`、それ以外は`a`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextObject {
+    pub kind: TextObjectKind,
+    pub inner: bool,
+}
+
+impl TextObject {
+    pub fn inner(kind: TextObjectKind) -> Self {
+        Self { kind, inner: true }
+    }
+
+    pub fn around(kind: TextObjectKind) -> Self {
+        Self { kind, inner: false }
+    }
+
+    /// `i`/`a`に続くキーからテキストオブジェクトを決める
+    pub fn from_key(inner: bool, key: char) -> Option<Self> {
+        let kind = match key {
+            'w' => TextObjectKind::Word,
+            'W' => TextObjectKind::BigWord,
+            's' => TextObjectKind::Sentence,
+            'p' => TextObjectKind::Paragraph,
+            '(' | ')' | 'b' => TextObjectKind::Block {
+                open: '(',
+                close: ')',
+            },
+            '{' | '}' | 'B' => TextObjectKind::Block {
+                open: '{',
+                close: '}',
+            },
+            '[' | ']' => TextObjectKind::Block {
+                open: '[',
+                close: ']',
+            },
+            '<' | '>' => TextObjectKind::Block {
+                open: '<',
+                close: '>',
+            },
+            '"' | '\'' | '`' => TextObjectKind::Quote(key),
+            't' => TextObjectKind::Tag,
+            _ => return None,
+        };
+        Some(Self { kind, inner })
+    }
+}
+
+/// テキストオブジェクトが表す範囲
+///
+/// 文字単位では`start`から`end`の直前まで、行単位（`linewise`）では
+/// `start.row`から`end.row`までの行全体を表す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    pub start: Position,
+    pub end: Position,
+    pub linewise: bool,
+}
+
+impl TextRange {
+    pub fn charwise(start: Position, end: Position) -> Self {
+        Self {
+            start,
+            end,
+            linewise: false,
+        }
+    }
+
+    pub fn lines(start_row: usize, end_row: usize) -> Self {
+        Self {
+            start: Position::new(start_row, 0),
+            end: Position::new(end_row, 0),
+            linewise: true,
+        }
+    }
+
+    /// 範囲に含まれる最後の行
+    pub fn last_row(&self) -> usize {
+        if !self.linewise && self.end.col == 0 && self.end.row > self.start.row {
+            self.end.row - 1
+        } else {
+            self.end.row
+        }
+    }
+
+    /// 範囲に含まれる最後の文字の位置（ビジュアルモードの終端）
+    pub fn last_position(&self, buffer: &Buffer) -> Position {
+        if self.linewise {
+            let len = buffer.line_length(self.end.row).unwrap_or(0);
+            return Position::new(self.end.row, len);
+        }
+        if self.end <= self.start {
+            return self.start;
+        }
+        if self.end.col > 0 {
+            let line = buffer.line(self.end.row).unwrap_or("");
+            return Position::new(self.end.row, prev_boundary(line, self.end.col));
+        }
+        // 行頭で終わる範囲は直前の行の改行までを含む
+        let row = self.end.row - 1;
+        Position::new(row, buffer.line_length(row).unwrap_or(0))
+    }
+}
+
+/// カーソル位置のテキストオブジェクトの範囲を求める
+pub fn select(
+    buffer: &Buffer,
+    cursor: Position,
+    object: TextObject,
+    count: usize,
+) -> Option<TextRange> {
+    let count = count.max(1);
+    match object.kind {
+        TextObjectKind::Word => select_word(buffer, cursor, false, object.inner, count),
+        TextObjectKind::BigWord => select_word(buffer, cursor, true, object.inner, count),
+        TextObjectKind::Sentence => select_sentence(buffer, cursor, object.inner, count),
+        TextObjectKind::Paragraph => select_paragraph(buffer, cursor, object.inner, count),
+        TextObjectKind::Block { open, close } => {
+            select_block(buffer, cursor, open, close, object.inner, count)
+        }
+        TextObjectKind::Quote(quote) => select_quote(buffer, cursor, quote, object.inner, count),
+        TextObjectKind::Tag => select_tag(buffer, cursor, object.inner, count),
+    }
+}
+
+/// ビジュアルモードでテキストオブジェクトを選択・拡張する
+///
+/// 選択済みの範囲で同じオブジェクトを繰り返すと、単語や段落は次の要素まで、
+/// 括弧やタグは外側のペアまで範囲を広げる。戻り値は（開始, 終端）で終端を含む。
+pub fn select_visual(
+    buffer: &Buffer,
+    anchor: Position,
+    cursor: Position,
+    object: TextObject,
+    count: usize,
+) -> Option<(Position, Position)> {
+    let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+    if start == end {
+        let range = select(buffer, cursor, object, count)?;
+        return Some((range.start, range.last_position(buffer)));
+    }
+
+    match object.kind {
+        TextObjectKind::Block { .. } | TextObjectKind::Tag => {
+            // 現在の選択より広くなるまで外側のペアを探す
+            let mut level = count.max(1);
+            loop {
+                let range = select(buffer, start, object, level)?;
+                let last = range.last_position(buffer);
+                if range.start <= start && last >= end && (range.start, last) != (start, end) {
+                    return Some((range.start, last));
+                }
+                level += 1;
+            }
+        }
+        TextObjectKind::Quote(_) => {
+            let range = select(buffer, cursor, object, count)?;
+            Some((range.start, range.last_position(buffer)))
+        }
+        _ => {
+            let next = next_position(buffer, end)?;
+            let range = select(buffer, next, object, count)?;
+            Some((start, range.last_position(buffer)))
+        }
+    }
+}
+
+fn next_position(buffer: &Buffer, pos: Position) -> Option<Position> {
+    let line = buffer.line(pos.row).ok()?;
+    if pos.col < line.len() {
+        let next = next_boundary(line, pos.col);
+        if next < line.len() {
+            return Some(Position::new(pos.row, next));
+        }
+    }
+    (pos.row + 1 < buffer.line_count()).then(|| Position::new(pos.row + 1, 0))
+}
+
+pub(crate) fn prev_boundary(line: &str, col: usize) -> usize {
+    line[..col.min(line.len())]
+        .char_indices()
+        .next_back()
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+pub(crate) fn next_boundary(line: &str, col: usize) -> usize {
+    line[col..]
+        .chars()
+        .next()
+        .map(|ch| col + ch.len_utf8())
+        .unwrap_or(line.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CharClass {
+    Blank,
+    Keyword,
+    Punctuation,
+}
+
+pub(crate) fn char_class(ch: char, big_word: bool) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Blank
+    } else if big_word || ch.is_alphanumeric() || ch == '_' {
+        CharClass::Keyword
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn select_word(
+    buffer: &Buffer,
+    cursor: Position,
+    big_word: bool,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    let line = buffer.line(cursor.row).ok()?;
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    if chars.is_empty() {
+        return None;
+    }
+
+    let class = |i: usize| char_class(chars[i].1, big_word);
+    let run_end = |i: usize| {
+        let mut j = i;
+        while j < chars.len() && class(j) == class(i) {
+            j += 1;
+        }
+        j
+    };
+    let run_start = |i: usize| {
+        let mut j = i;
+        while j > 0 && class(j - 1) == class(i) {
+            j -= 1;
+        }
+        j
+    };
+
+    let index = chars
+        .iter()
+        .rposition(|(i, _)| *i <= cursor.col)
+        .unwrap_or(0);
+    let mut start = run_start(index);
+    let mut end = start;
+
+    if inner {
+        // 空白の並びも1つの単語として数える
+        for _ in 0..count {
+            if end >= chars.len() {
+                break;
+            }
+            end = run_end(end);
+        }
+    } else if class(index) == CharClass::Blank {
+        // 空白上では空白とそれに続く単語
+        for _ in 0..count {
+            if end >= chars.len() {
+                break;
+            }
+            end = run_end(end);
+            if end < chars.len() {
+                end = run_end(end);
+            }
+        }
+    } else {
+        for _ in 0..count {
+            if end >= chars.len() {
+                break;
+            }
+            end = run_end(end);
+            if end < chars.len() && class(end) == CharClass::Blank {
+                end = run_end(end);
+            }
+        }
+        // 後ろに空白がなければ前の空白を含める
+        let has_trailing_blank = end > 0 && class(end - 1) == CharClass::Blank;
+        if !has_trailing_blank && start > 0 && class(start - 1) == CharClass::Blank {
+            start = run_start(start - 1);
+        }
+    }
+
+    let col = |i: usize| chars.get(i).map(|(c, _)| *c).unwrap_or(line.len());
+    Some(TextRange::charwise(
+        Position::new(cursor.row, col(start)),
+        Position::new(cursor.row, col(end)),
+    ))
+}
+
+fn is_blank_line(buffer: &Buffer, row: usize) -> bool {
+    buffer
+        .line(row)
+        .map(|line| line.trim().is_empty())
+        .unwrap_or(true)
+}
+
+/// `row`を含む、空行かどうかが同じ行の並び
+fn line_run(buffer: &Buffer, row: usize) -> (usize, usize) {
+    let blank = is_blank_line(buffer, row);
+    let mut start = row;
+    while start > 0 && is_blank_line(buffer, start - 1) == blank {
+        start -= 1;
+    }
+    let mut end = row;
+    while end + 1 < buffer.line_count() && is_blank_line(buffer, end + 1) == blank {
+        end += 1;
+    }
+    (start, end)
+}
+
+fn select_paragraph(
+    buffer: &Buffer,
+    cursor: Position,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    let last_row = buffer.line_count() - 1;
+    let (mut start, mut end) = line_run(buffer, cursor.row.min(last_row));
+
+    if inner {
+        for _ in 1..count {
+            if end >= last_row {
+                break;
+            }
+            end = line_run(buffer, end + 1).1;
+        }
+        return Some(TextRange::lines(start, end));
+    }
+
+    let started_on_blank = is_blank_line(buffer, cursor.row);
+    for i in 0..count {
+        if i > 0 {
+            if end >= last_row {
+                break;
+            }
+            end = line_run(buffer, end + 1).1;
+        }
+        // 段落と後続の空行（空行上では空行と後続の段落）をまとめる
+        if end < last_row {
+            end = line_run(buffer, end + 1).1;
+        } else if !started_on_blank && i == 0 && start > 0 {
+            start = line_run(buffer, start - 1).0;
+        }
+    }
+    Some(TextRange::lines(start, end))
+}
+
+/// バッファの行を改行で連結したテキストと、行頭のオフセット
+///
+/// 行番号をそのまま添字に使えるよう、範囲より前の行の行頭は0で埋める。
+struct FlatText {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl FlatText {
+    fn new(buffer: &Buffer, rows: std::ops::RangeInclusive<usize>) -> Self {
+        let first_row = *rows.start();
+        let mut text = String::new();
+        let mut line_starts = vec![0; first_row];
+        for row in rows {
+            if row > first_row {
+                text.push('\n');
+            }
+            line_starts.push(text.len());
+            text.push_str(buffer.line(row).unwrap_or(""));
+        }
+        Self { text, line_starts }
+    }
+
+    fn whole(buffer: &Buffer) -> Self {
+        Self::new(buffer, 0..=buffer.line_count() - 1)
+    }
+
+    fn offset(&self, pos: Position) -> usize {
+        let start = self.line_starts[pos.row];
+        let line_end = self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |i| start + i);
+        (start + pos.col).min(line_end)
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let row = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        Position::new(row, offset - self.line_starts[row])
+    }
+}
+
+fn is_sentence_end(text: &str, index: usize) -> Option<usize> {
+    let rest = &text[index..];
+    let mut chars = rest.char_indices();
+    let (_, ch) = chars.next()?;
+    if !matches!(ch, '.' | '!' | '?') {
+        return None;
+    }
+    for (i, ch) in chars {
+        match ch {
+            ')' | ']' | '"' | '\'' => continue,
+            c if c.is_whitespace() => return Some(index + i),
+            _ => return None,
+        }
+    }
+    Some(text.len())
+}
+
+fn select_sentence(
+    buffer: &Buffer,
+    cursor: Position,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    if is_blank_line(buffer, cursor.row) {
+        return select_paragraph(buffer, cursor, inner, count);
+    }
+
+    // 文は段落内で区切る
+    let (first_row, last_row) = line_run(buffer, cursor.row);
+    let flat = FlatText::new(buffer, first_row..=last_row);
+    let text = flat.text.as_str();
+
+    // 文の本体と直後の空白を交互に並べた区間
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    let mut start = text.len() - text.trim_start().len();
+    if start > 0 {
+        segments.push((0, start));
+    }
+    let mut index = start;
+    while index < text.len() {
+        if let Some(end) = is_sentence_end(text, index) {
+            let next = end + (text[end..].len() - text[end..].trim_start().len());
+            segments.push((start, end));
+            if next > end {
+                segments.push((end, next));
+            }
+            start = next;
+            index = next;
+            continue;
+        }
+        index = next_boundary(text, index);
+    }
+    if start < text.len() {
+        let end = text.trim_end().len().max(start);
+        segments.push((start, end));
+        if end < text.len() {
+            segments.push((end, text.len()));
+        }
+    }
+
+    let offset = flat.offset(cursor);
+    let current = segments
+        .iter()
+        .position(|&(s, e)| offset >= s && offset < e)
+        .unwrap_or(segments.len().saturating_sub(1));
+    let is_blank = |i: usize| text[segments[i].0..segments[i].1].trim().is_empty();
+
+    let (mut first, mut last) = (current, current);
+    if inner {
+        last = (current + count - 1).min(segments.len() - 1);
+    } else {
+        let mut remaining = count;
+        if is_blank(current) {
+            // 空白上では空白と後続の文
+            last = (current + 1).min(segments.len() - 1);
+            remaining -= 1;
+        }
+        for _ in 0..remaining {
+            if last + 1 < segments.len() && is_blank(last + 1) {
+                last += 1;
+            }
+            if remaining > 1 && last + 1 < segments.len() {
+                last += 1;
+            }
+        }
+        let has_trailing_blank = is_blank(last);
+        if !has_trailing_blank && first > 0 && is_blank(first - 1) {
+            first -= 1;
+        }
+    }
+
+    Some(TextRange::charwise(
+        flat.position(segments[first].0),
+        flat.position(segments[last].1),
+    ))
+}
+
+/// `offset`を囲む`open`の位置（`level`番目に内側のもの）
+fn find_enclosing_open(text: &[u8], offset: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = offset;
+    loop {
+        let byte = text[i];
+        if byte == close && i != offset {
+            depth += 1;
+        } else if byte == open {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+        if i == 0 {
+            return None;
+        }
+        i -= 1;
+    }
+}
+
+fn find_matching_close(text: &[u8], open_at: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, &byte) in text.iter().enumerate().skip(open_at + 1) {
+        if byte == open {
+            depth += 1;
+        } else if byte == close {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+fn select_block(
+    buffer: &Buffer,
+    cursor: Position,
+    open: char,
+    close: char,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    let flat = FlatText::whole(buffer);
+    let bytes = flat.text.as_bytes();
+    if bytes.is_empty() {
+        return None;
+    }
+    let (open, close) = (open as u8, close as u8);
+    let offset = flat.offset(cursor).min(bytes.len() - 1);
+
+    // カーソルが閉じ括弧上なら、それに対応する括弧を対象にする
+    let mut open_at = if bytes[offset] == close {
+        let mut depth = 0usize;
+        let mut found = None;
+        for i in (0..offset).rev() {
+            if bytes[i] == close {
+                depth += 1;
+            } else if bytes[i] == open {
+                if depth == 0 {
+                    found = Some(i);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        found?
+    } else {
+        find_enclosing_open(bytes, offset, open, close)?
+    };
+    for _ in 1..count {
+        open_at = find_enclosing_open(bytes, open_at.checked_sub(1)?, open, close)?;
+    }
+    let close_at = find_matching_close(bytes, open_at, open, close)?;
+
+    if !inner {
+        return Some(TextRange::charwise(
+            flat.position(open_at),
+            flat.position(close_at + 1),
+        ));
+    }
+
+    let open_pos = flat.position(open_at);
+    let close_pos = flat.position(close_at);
+    let after_open = &flat.text[open_at + 1..flat.offset(Position::new(open_pos.row, usize::MAX))];
+    let before_close = &flat.text[flat.line_starts[close_pos.row]..close_at];
+
+    // 複数行のブロックで括弧が行末・行頭にある場合は中の行を行単位で扱う
+    if close_pos.row > open_pos.row
+        && after_open.trim().is_empty()
+        && before_close.trim().is_empty()
+    {
+        if close_pos.row == open_pos.row + 1 {
+            let empty = Position::new(close_pos.row, close_pos.col);
+            return Some(TextRange::charwise(empty, empty));
+        }
+        return Some(TextRange::lines(open_pos.row + 1, close_pos.row - 1));
+    }
+
+    Some(TextRange::charwise(flat.position(open_at + 1), close_pos))
+}
+
+fn select_quote(
+    buffer: &Buffer,
+    cursor: Position,
+    quote: char,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    let line = buffer.line(cursor.row).ok()?;
+    let bytes = line.as_bytes();
+    let quote = quote as u8;
+
+    // バックスラッシュでエスケープされていない引用符の位置
+    let quotes: Vec<usize> = (0..bytes.len())
+        .filter(|&i| {
+            bytes[i] == quote
+                && bytes[..i].iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 0
+        })
+        .collect();
+
+    let before = quotes.iter().filter(|&&i| i < cursor.col).count();
+    let (open, close) = if quotes.contains(&cursor.col) {
+        if before % 2 == 0 {
+            (cursor.col, *quotes.get(before + 1)?)
+        } else {
+            (quotes[before - 1], cursor.col)
+        }
+    } else if before % 2 == 1 {
+        (quotes[before - 1], *quotes.get(before)?)
+    } else {
+        // 引用符の外側では右側の最初の文字列を対象にする
+        (*quotes.get(before)?, *quotes.get(before + 1)?)
+    };
+
+    let row = cursor.row;
+    if inner && count < 2 {
+        return Some(TextRange::charwise(
+            Position::new(row, open + 1),
+            Position::new(row, close),
+        ));
+    }
+    if inner {
+        // `2i"`は引用符を含むが空白は含まない
+        return Some(TextRange::charwise(
+            Position::new(row, open),
+            Position::new(row, close + 1),
+        ));
+    }
+
+    let mut start = open;
+    let mut end = close + 1;
+    let trailing = line[end..].len() - line[end..].trim_start().len();
+    if trailing > 0 {
+        end += trailing;
+    } else {
+        start = line[..start].trim_end().len();
+    }
+    Some(TextRange::charwise(
+        Position::new(row, start),
+        Position::new(row, end),
+    ))
+}
+
+/// 開始タグと終了タグのペア（各タグの開始・終了オフセット）
+#[derive(Debug, Clone, Copy)]
+struct TagPair {
+    open_start: usize,
+    open_end: usize,
+    close_start: usize,
+    close_end: usize,
+}
+
+fn parse_tags(text: &str) -> Vec<TagPair> {
+    let mut pairs = Vec::new();
+    let mut stack: Vec<(String, usize, usize)> = Vec::new();
+    let mut index = 0;
+
+    while let Some(found) = text[index..].find('<') {
+        let start = index + found;
+        let Some(len) = text[start..].find('>') else {
+            break;
+        };
+        let end = start + len + 1;
+        let content = &text[start + 1..end - 1];
+        index = end;
+
+        if content.starts_with('!') || content.starts_with('?') || content.ends_with('/') {
+            continue;
+        }
+        if let Some(name) = content.strip_prefix('/') {
+            let name = name.trim();
+            // 対応しない開始タグは読み飛ばす
+            if let Some(pos) = stack.iter().rposition(|(open, _, _)| open == name) {
+                let (_, open_start, open_end) = stack[pos].clone();
+                stack.truncate(pos);
+                pairs.push(TagPair {
+                    open_start,
+                    open_end,
+                    close_start: start,
+                    close_end: end,
+                });
+            }
+        } else {
+            let name = content.split_whitespace().next().unwrap_or("").to_string();
+            if !name.is_empty() {
+                stack.push((name, start, end));
+            }
+        }
+    }
+    pairs
+}
+
+fn select_tag(buffer: &Buffer, cursor: Position, inner: bool, count: usize) -> Option<TextRange> {
+    let flat = FlatText::whole(buffer);
+    let offset = flat.offset(cursor);
+
+    let mut enclosing: Vec<TagPair> = parse_tags(&flat.text)
+        .into_iter()
+        .filter(|pair| pair.open_start <= offset && offset < pair.close_end)
+        .collect();
+    // 内側のタグから順に並べる
+    enclosing.sort_by_key(|pair| std::cmp::Reverse(pair.open_start));
+    let pair = enclosing.get(count - 1)?;
+
+    let (start, end) = if inner {
+        (pair.open_end, pair.close_start)
+    } else {
+        (pair.open_start, pair.close_end)
+    };
+    Some(TextRange::charwise(
+        flat.position(start),
+        flat.position(end),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_of(buffer: &Buffer, range: TextRange) -> String {
+        if range.linewise {
+            return (range.start.row..=range.end.row)
+                .map(|row| buffer.line(row).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        buffer.text_range(range.start, range.end).unwrap()
+    }
+
+    fn select_text(content: &str, cursor: Position, keys: &str, count: usize) -> Option<String> {
+        let buffer = Buffer::from_content(content);
+        let mut chars = keys.chars();
+        let inner = chars.next() == Some('i');
+        let object = TextObject::from_key(inner, chars.next().unwrap()).unwrap();
+        select(&buffer, cursor, object, count).map(|range| text_of(&buffer, range))
+    }
+
+    #[test]
+    fn test_from_key() {
+        assert_eq!(
+            TextObject::from_key(true, 'w'),
+            Some(TextObject::inner(TextObjectKind::Word))
+        );
+        assert_eq!(
+            TextObject::from_key(false, 'b'),
+            Some(TextObject::around(TextObjectKind::Block {
+                open: '(',
+                close: ')'
+            }))
+        );
+        assert_eq!(
+            TextObject::from_key(true, '"'),
+            Some(TextObject::inner(TextObjectKind::Quote('"')))
+        );
+        assert_eq!(TextObject::from_key(true, 'x'), None);
+    }
+
+    #[test]
+    fn test_inner_word() {
+        let line = "let foo_bar = baz.qux;";
+        assert_eq!(
+            select_text(line, Position::new(0, 5), "iw", 1).unwrap(),
+            "foo_bar"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 17), "iw", 1).unwrap(),
+            "."
+        );
+        // 空白も単語として数える
+        assert_eq!(
+            select_text(line, Position::new(0, 4), "iw", 3).unwrap(),
+            "foo_bar ="
+        );
+    }
+
+    #[test]
+    fn test_around_word() {
+        let line = "one two three";
+        assert_eq!(
+            select_text(line, Position::new(0, 5), "aw", 1).unwrap(),
+            "two "
+        );
+        // 末尾の単語は前の空白を含む
+        assert_eq!(
+            select_text(line, Position::new(0, 9), "aw", 1).unwrap(),
+            " three"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 0), "aw", 2).unwrap(),
+            "one two "
+        );
+        // 空白上では空白と後続の単語
+        assert_eq!(
+            select_text(line, Position::new(0, 3), "aw", 1).unwrap(),
+            " two"
+        );
+    }
+
+    #[test]
+    fn test_big_word() {
+        let line = "call foo.bar(x) now";
+        assert_eq!(
+            select_text(line, Position::new(0, 7), "iW", 1).unwrap(),
+            "foo.bar(x)"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 7), "aW", 1).unwrap(),
+            "foo.bar(x) "
+        );
+    }
+
+    #[test]
+    fn test_sentence() {
+        let content = "First one. Second one is here!  Third.";
+        assert_eq!(
+            select_text(content, Position::new(0, 14), "is", 1).unwrap(),
+            "Second one is here!"
+        );
+        assert_eq!(
+            select_text(content, Position::new(0, 14), "as", 1).unwrap(),
+            "Second one is here!  "
+        );
+        // 最後の文は前の空白を含む
+        assert_eq!(
+            select_text(content, Position::new(0, 34), "as", 1).unwrap(),
+            "  Third."
+        );
+        assert_eq!(
+            select_text(content, Position::new(0, 0), "is", 3).unwrap(),
+            "First one. Second one is here!"
+        );
+    }
+
+    #[test]
+    fn test_sentence_across_lines() {
+        let content = "A sentence that\nspans lines. Next.\n\nOther paragraph.";
+        assert_eq!(
+            select_text(content, Position::new(0, 3), "is", 1).unwrap(),
+            "A sentence that\nspans lines."
+        );
+    }
+
+    #[test]
+    fn test_paragraph() {
+        let content = "a\nb\n\n\nc\nd\n\ne";
+        let buffer = Buffer::from_content(content);
+        let ip = TextObject::inner(TextObjectKind::Paragraph);
+        let ap = TextObject::around(TextObjectKind::Paragraph);
+
+        assert_eq!(
+            select(&buffer, Position::new(0, 0), ip, 1),
+            Some(TextRange::lines(0, 1))
+        );
+        assert_eq!(
+            select(&buffer, Position::new(1, 0), ap, 1),
+            Some(TextRange::lines(0, 3))
+        );
+        assert_eq!(
+            select(&buffer, Position::new(2, 0), ip, 1),
+            Some(TextRange::lines(2, 3))
+        );
+        // 空行上では空行と後続の段落
+        assert_eq!(
+            select(&buffer, Position::new(2, 0), ap, 1),
+            Some(TextRange::lines(2, 5))
+        );
+        // 最後の段落は前の空行を含む
+        assert_eq!(
+            select(&buffer, Position::new(7, 0), ap, 1),
+            Some(TextRange::lines(6, 7))
+        );
+        assert_eq!(
+            select(&buffer, Position::new(0, 0), ap, 2),
+            Some(TextRange::lines(0, 6))
+        );
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let line = "call(a, (b + c), d)";
+        assert_eq!(
+            select_text(line, Position::new(0, 10), "i(", 1).unwrap(),
+            "b + c"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 10), "a)", 1).unwrap(),
+            "(b + c)"
+        );
+        // カウントで外側の括弧
+        assert_eq!(
+            select_text(line, Position::new(0, 10), "ib", 2).unwrap(),
+            "a, (b + c), d"
+        );
+        // 括弧上でもその括弧のペア
+        assert_eq!(
+            select_text(line, Position::new(0, 4), "i(", 1).unwrap(),
+            "a, (b + c), d"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 14), "i(", 1).unwrap(),
+            "b + c"
+        );
+        assert_eq!(select_text(line, Position::new(0, 0), "i(", 1), None);
+    }
+
+    #[test]
+    fn test_other_brackets() {
+        let line = "x = [1, {k: <v>}];";
+        assert_eq!(
+            select_text(line, Position::new(0, 5), "i[", 1).unwrap(),
+            "1, {k: <v>}"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 10), "a{", 1).unwrap(),
+            "{k: <v>}"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 13), "i<", 1).unwrap(),
+            "v"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 13), "iB", 1).unwrap(),
+            "k: <v>"
+        );
+    }
+
+    #[test]
+    fn test_multiline_block_is_linewise() {
+        let content = "fn main() {\n    let x = 1;\n    let y = 2;\n}";
+        let buffer = Buffer::from_content(content);
+        let object = TextObject::inner(TextObjectKind::Block {
+            open: '{',
+            close: '}',
+        });
+
+        let range = select(&buffer, Position::new(1, 4), object, 1).unwrap();
+        assert_eq!(range, TextRange::lines(1, 2));
+
+        let around = TextObject::around(TextObjectKind::Block {
+            open: '{',
+            close: '}',
+        });
+        let range = select(&buffer, Position::new(1, 4), around, 1).unwrap();
+        assert_eq!(
+            text_of(&buffer, range),
+            "{\n    let x = 1;\n    let y = 2;\n}"
+        );
+    }
+
+    #[test]
+    fn test_quotes() {
+        let line = r#"say("hello world", 'x', "a\"b")"#;
+        assert_eq!(
+            select_text(line, Position::new(0, 8), "i\"", 1).unwrap(),
+            "hello world"
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 8), "a\"", 1).unwrap(),
+            "\"hello world\""
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 8), "i\"", 2).unwrap(),
+            "\"hello world\""
+        );
+        assert_eq!(
+            select_text(line, Position::new(0, 20), "i'", 1).unwrap(),
+            "x"
+        );
+        // エスケープされた引用符は区切りにならない
+        assert_eq!(
+            select_text(line, Position::new(0, 26), "i\"", 1).unwrap(),
+            r#"a\"b"#
+        );
+        // 引用符の外側では右側の文字列
+        assert_eq!(
+            select_text(line, Position::new(0, 0), "i\"", 1).unwrap(),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn test_around_quote_whitespace() {
+        let line = "echo 'one' two";
+        assert_eq!(
+            select_text(line, Position::new(0, 6), "a'", 1).unwrap(),
+            "'one' "
+        );
+        let line = "echo `cmd`";
+        assert_eq!(
+            select_text(line, Position::new(0, 6), "a`", 1).unwrap(),
+            " `cmd`"
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        let content = "<div class=\"x\">\n  <p>Hello <b>world</b></p>\n  <br/>\n</div>";
+        assert_eq!(
+            select_text(content, Position::new(1, 16), "it", 1).unwrap(),
+            "world"
+        );
+        assert_eq!(
+            select_text(content, Position::new(1, 16), "at", 1).unwrap(),
+            "<b>world</b>"
+        );
+        assert_eq!(
+            select_text(content, Position::new(1, 16), "it", 2).unwrap(),
+            "Hello <b>world</b>"
+        );
+        assert_eq!(
+            select_text(content, Position::new(1, 6), "it", 2).unwrap(),
+            "\n  <p>Hello <b>world</b></p>\n  <br/>\n"
+        );
+        assert_eq!(select_text(content, Position::new(1, 6), "it", 3), None);
+    }
+
+    #[test]
+    fn test_visual_word_expansion() {
+        let buffer = Buffer::from_content("one two three");
+        let iw = TextObject::inner(TextObjectKind::Word);
+
+        let (start, end) =
+            select_visual(&buffer, Position::new(0, 5), Position::new(0, 5), iw, 1).unwrap();
+        assert_eq!((start, end), (Position::new(0, 4), Position::new(0, 6)));
+
+        // 繰り返すと次の単語（空白）まで広がる
+        let (start, end) = select_visual(&buffer, start, end, iw, 1).unwrap();
+        assert_eq!((start, end), (Position::new(0, 4), Position::new(0, 7)));
+        let (start, end) = select_visual(&buffer, start, end, iw, 1).unwrap();
+        assert_eq!((start, end), (Position::new(0, 4), Position::new(0, 12)));
+    }
+
+    #[test]
+    fn test_visual_block_expansion() {
+        let buffer = Buffer::from_content("f(a, (bc), d)");
+        let ib = TextObject::inner(TextObjectKind::Block {
+            open: '(',
+            close: ')',
+        });
+
+        let (start, end) =
+            select_visual(&buffer, Position::new(0, 6), Position::new(0, 6), ib, 1).unwrap();
+        assert_eq!((start, end), (Position::new(0, 6), Position::new(0, 7)));
+
+        // 選択済みの範囲を含む外側の括弧へ広がる
+        let (start, end) = select_visual(&buffer, start, end, ib, 1).unwrap();
+        assert_eq!((start, end), (Position::new(0, 2), Position::new(0, 11)));
+    }
+}
@@ -0,0 +1,378 @@
+use crate::editor::cursor::{Position, cells};
+
+/// 画面に表示しているバッファの範囲（先頭行・左端の表示列）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub top: usize,
+    pub left: usize,
+    pub height: usize,
+    pub width: usize,
+    pub scrolloff: usize,
+    pub sidescrolloff: usize,
+}
+
+impl Viewport {
+    pub fn new(height: usize, width: usize) -> Self {
+        Self {
+            top: 0,
+            left: 0,
+            height: height.max(1),
+            width: width.max(1),
+            scrolloff: 5,
+            sidescrolloff: 0,
+        }
+    }
+
+    pub fn resize(&mut self, height: usize, width: usize) {
+        self.height = height.max(1);
+        self.width = width.max(1);
+    }
+
+    /// 画面最下行に表示されるバッファ行
+    pub fn bottom(&self) -> usize {
+        self.top + self.height - 1
+    }
+
+    /// 画面の高さに収まるよう制限した`scrolloff`
+    fn effective_scrolloff(&self) -> usize {
+        self.scrolloff.min((self.height - 1) / 2)
+    }
+
+    fn effective_sidescrolloff(&self) -> usize {
+        self.sidescrolloff.min((self.width - 1) / 2)
+    }
+
+    /// カーソル（行と表示列）が`scrolloff`を保って見えるようにスクロールする
+    pub fn scroll_to_cursor(&mut self, row: usize, display_col: usize, line_count: usize) {
+        let so = self.effective_scrolloff();
+        let last_line = line_count.saturating_sub(1);
+
+        if row < self.top + so {
+            self.top = row.saturating_sub(so);
+        }
+        let lowest = (row + so).min(last_line.max(row));
+        if lowest > self.bottom() {
+            self.top = lowest + 1 - self.height;
+        }
+
+        let sso = self.effective_sidescrolloff();
+        if display_col < self.left + sso {
+            self.left = display_col.saturating_sub(sso);
+        }
+        if display_col + sso >= self.left + self.width {
+            self.left = display_col + sso + 1 - self.width;
+        }
+    }
+
+    /// ビューポート内で`scrolloff`を満たすようにカーソル行を移動する
+    pub fn keep_cursor_visible(&self, cursor: &mut Position, line_count: usize) {
+        let so = self.effective_scrolloff();
+        let last_line = line_count.saturating_sub(1);
+
+        let min_row = if self.top == 0 { 0 } else { self.top + so };
+        let max_row = if self.bottom() >= last_line {
+            last_line
+        } else {
+            self.bottom() - so
+        };
+
+        cursor.row = cursor.row.max(min_row.min(max_row)).min(max_row);
+    }
+
+    /// `Ctrl-E`/`Ctrl-Y`: 画面を行単位でスクロールする（カーソルは画面内に留まる）
+    pub fn scroll_lines(&mut self, delta: isize, cursor: &mut Position, line_count: usize) {
+        let last_line = line_count.saturating_sub(1);
+        self.top = self.top.saturating_add_signed(delta).min(last_line);
+        self.keep_cursor_visible(cursor, line_count);
+    }
+
+    /// `Ctrl-F`: 1画面分（2行の重なりを残して）前方へスクロールする
+    pub fn page_down(&mut self, cursor: &mut Position, line_count: usize) {
+        let amount = self.height.saturating_sub(2).max(1);
+        let last_line = line_count.saturating_sub(1);
+        self.top = (self.top + amount).min(last_line);
+        self.keep_cursor_visible(cursor, line_count);
+    }
+
+    /// `Ctrl-B`: 1画面分後方へスクロールする
+    pub fn page_up(&mut self, cursor: &mut Position, line_count: usize) {
+        let amount = self.height.saturating_sub(2).max(1);
+        self.top = self.top.saturating_sub(amount);
+        self.keep_cursor_visible(cursor, line_count);
+    }
+
+    /// `Ctrl-D`: 半画面分、画面とカーソルを前方へ移動する
+    pub fn half_page_down(&mut self, cursor: &mut Position, line_count: usize) {
+        let amount = (self.height / 2).max(1);
+        let last_line = line_count.saturating_sub(1);
+        let max_top = line_count.saturating_sub(self.height).max(self.top);
+
+        self.top = (self.top + amount).min(max_top);
+        cursor.row = (cursor.row + amount).min(last_line);
+    }
+
+    /// `Ctrl-U`: 半画面分、画面とカーソルを後方へ移動する
+    pub fn half_page_up(&mut self, cursor: &mut Position) {
+        let amount = (self.height / 2).max(1);
+        self.top = self.top.saturating_sub(amount);
+        cursor.row = cursor.row.saturating_sub(amount);
+    }
+
+    /// `zz`: `row`を画面中央に表示する
+    pub fn center_on(&mut self, row: usize) {
+        self.top = row.saturating_sub((self.height - 1) / 2);
+    }
+
+    /// `zt`: `row`を画面上端（`scrolloff`を考慮）に表示する
+    pub fn top_on(&mut self, row: usize) {
+        self.top = row.saturating_sub(self.effective_scrolloff());
+    }
+
+    /// `zb`: `row`を画面下端（`scrolloff`を考慮）に表示する
+    pub fn bottom_on(&mut self, row: usize) {
+        self.top = (row + self.effective_scrolloff() + 1).saturating_sub(self.height);
+    }
+
+    /// `H`: 画面上端の行
+    pub fn screen_top(&self, line_count: usize) -> usize {
+        let so = if self.top == 0 {
+            0
+        } else {
+            self.effective_scrolloff()
+        };
+        (self.top + so).min(line_count.saturating_sub(1))
+    }
+
+    /// `M`: 画面中央の行（表示中の最終行までの中央）
+    pub fn screen_middle(&self, line_count: usize) -> usize {
+        let last_visible = self.bottom().min(line_count.saturating_sub(1));
+        (self.top + last_visible) / 2
+    }
+
+    /// `L`: 画面下端の行
+    pub fn screen_bottom(&self, line_count: usize) -> usize {
+        let last_line = line_count.saturating_sub(1);
+        if self.bottom() >= last_line {
+            last_line
+        } else {
+            self.bottom() - self.effective_scrolloff()
+        }
+    }
+
+    /// バッファ上の位置を画面上の位置（行, 表示列）に変換する
+    pub fn to_screen(&self, row: usize, display_col: usize) -> Option<(usize, usize)> {
+        if row < self.top || row > self.bottom() || display_col < self.left {
+            return None;
+        }
+        let screen_col = display_col - self.left;
+        (screen_col < self.width).then_some((row - self.top, screen_col))
+    }
+
+    /// 行のうち横スクロール位置と画面幅に収まる部分
+    ///
+    /// タブは空白に展開し、画面の端にかかる全角文字は見える幅の空白にする。
+    pub fn visible_text(&self, line: &str, tabstop: usize) -> String {
+        let right = self.left + self.width;
+        cells(line, tabstop)
+            .filter(|cell| cell.col + cell.width > self.left && cell.col < right)
+            .map(|cell| {
+                let end = cell.col + cell.width;
+                if cell.ch == '\t' || cell.col < self.left || end > right {
+                    " ".repeat(end.min(right) - cell.col.max(self.left))
+                } else {
+                    cell.ch.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(24, 80)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(height: usize, scrolloff: usize) -> Viewport {
+        let mut viewport = Viewport::new(height, 80);
+        viewport.scrolloff = scrolloff;
+        viewport
+    }
+
+    #[test]
+    fn test_scroll_to_cursor_down_and_up() {
+        let mut vp = viewport(10, 0);
+
+        vp.scroll_to_cursor(15, 0, 100);
+        assert_eq!(vp.top, 6);
+        assert_eq!(vp.bottom(), 15);
+
+        vp.scroll_to_cursor(3, 0, 100);
+        assert_eq!(vp.top, 3);
+    }
+
+    #[test]
+    fn test_scroll_to_cursor_with_scrolloff() {
+        let mut vp = viewport(10, 3);
+
+        vp.scroll_to_cursor(7, 0, 100);
+        assert_eq!(vp.top, 1); // 7行目の下に3行を確保
+
+        vp.scroll_to_cursor(2, 0, 100);
+        assert_eq!(vp.top, 0);
+    }
+
+    #[test]
+    fn test_scrolloff_near_buffer_end() {
+        let mut vp = viewport(10, 3);
+
+        // 最終行付近では存在しない行のためにスクロールしない
+        vp.scroll_to_cursor(19, 0, 20);
+        assert_eq!(vp.top, 10);
+        assert_eq!(vp.bottom(), 19);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_with_sidescrolloff() {
+        let mut vp = Viewport::new(10, 20);
+        vp.sidescrolloff = 2;
+
+        vp.scroll_to_cursor(0, 25, 1);
+        assert_eq!(vp.left, 8);
+
+        vp.scroll_to_cursor(0, 9, 1);
+        assert_eq!(vp.left, 7);
+
+        vp.scroll_to_cursor(0, 0, 1);
+        assert_eq!(vp.left, 0);
+    }
+
+    #[test]
+    fn test_scroll_lines_keeps_cursor_on_screen() {
+        let mut vp = viewport(10, 0);
+        let mut cursor = Position::new(0, 4);
+
+        vp.scroll_lines(3, &mut cursor, 100);
+        assert_eq!(vp.top, 3);
+        assert_eq!(cursor.row, 3);
+
+        vp.scroll_lines(-2, &mut cursor, 100);
+        assert_eq!(vp.top, 1);
+        assert_eq!(cursor.row, 3); // 画面内なので移動しない
+        assert_eq!(cursor.col, 4);
+    }
+
+    #[test]
+    fn test_page_down_and_up() {
+        let mut vp = viewport(10, 0);
+        let mut cursor = Position::new(0, 0);
+
+        vp.page_down(&mut cursor, 100);
+        assert_eq!(vp.top, 8);
+        assert_eq!(cursor.row, 8);
+
+        vp.page_down(&mut cursor, 100);
+        assert_eq!(vp.top, 16);
+
+        vp.page_up(&mut cursor, 100);
+        assert_eq!(vp.top, 8);
+        assert_eq!(cursor.row, 16); // 画面内なので移動しない
+    }
+
+    #[test]
+    fn test_page_down_stops_at_last_line() {
+        let mut vp = viewport(10, 0);
+        let mut cursor = Position::new(0, 0);
+
+        vp.page_down(&mut cursor, 5);
+        assert_eq!(vp.top, 4);
+        assert_eq!(cursor.row, 4);
+    }
+
+    #[test]
+    fn test_half_page_scroll() {
+        let mut vp = viewport(10, 0);
+        let mut cursor = Position::new(2, 0);
+
+        vp.half_page_down(&mut cursor, 100);
+        assert_eq!(vp.top, 5);
+        assert_eq!(cursor.row, 7);
+
+        vp.half_page_up(&mut cursor);
+        assert_eq!(vp.top, 0);
+        assert_eq!(cursor.row, 2);
+    }
+
+    #[test]
+    fn test_half_page_down_at_buffer_end() {
+        let mut vp = viewport(10, 0);
+        let mut cursor = Position::new(5, 0);
+
+        // 全行が表示済みなら画面は動かずカーソルのみ移動
+        vp.half_page_down(&mut cursor, 8);
+        assert_eq!(vp.top, 0);
+        assert_eq!(cursor.row, 7);
+    }
+
+    #[test]
+    fn test_reposition_commands() {
+        let mut vp = viewport(11, 2);
+
+        vp.center_on(50);
+        assert_eq!(vp.top, 45);
+
+        vp.top_on(50);
+        assert_eq!(vp.top, 48);
+
+        vp.bottom_on(50);
+        assert_eq!(vp.top, 42);
+        assert_eq!(vp.bottom(), 52);
+    }
+
+    #[test]
+    fn test_screen_motions() {
+        let mut vp = viewport(10, 2);
+        vp.top = 20;
+
+        assert_eq!(vp.screen_top(100), 22);
+        assert_eq!(vp.screen_middle(100), 24);
+        assert_eq!(vp.screen_bottom(100), 27);
+
+        // バッファ先頭・末尾ではscrolloffを適用しない
+        vp.top = 0;
+        assert_eq!(vp.screen_top(5), 0);
+        assert_eq!(vp.screen_middle(5), 2);
+        assert_eq!(vp.screen_bottom(5), 4);
+    }
+
+    #[test]
+    fn test_to_screen() {
+        let mut vp = Viewport::new(10, 20);
+        vp.top = 5;
+        vp.left = 3;
+
+        assert_eq!(vp.to_screen(5, 3), Some((0, 0)));
+        assert_eq!(vp.to_screen(14, 22), Some((9, 19)));
+        assert_eq!(vp.to_screen(4, 3), None);
+        assert_eq!(vp.to_screen(15, 3), None);
+        assert_eq!(vp.to_screen(5, 23), None);
+    }
+
+    #[test]
+    fn test_visible_text() {
+        let mut vp = Viewport::new(10, 5);
+        assert_eq!(vp.visible_text("Hello, World!", 8), "Hello");
+        assert_eq!(vp.visible_text("a\tb", 4), "a   b");
+        assert_eq!(vp.visible_text("日本語", 8), "日本 ");
+
+        vp.left = 7;
+        assert_eq!(vp.visible_text("Hello, World!", 8), "World");
+        assert_eq!(vp.visible_text("short", 8), "");
+        assert_eq!(vp.visible_text("\t\tx", 4), " x");
+        assert_eq!(vp.visible_text("abcdef日本語", 8), " 本語");
+    }
+}
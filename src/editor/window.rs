@@ -0,0 +1,809 @@
+use crate::editor::cursor::{Curswant, Position};
+use crate::editor::viewport::Viewport;
+use crate::error::{EditorError, Result};
+
+/// 画面上の矩形（ウィンドウの場合はステータスラインを含む）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub row: usize,
+    pub col: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Rect {
+    pub fn new(row: usize, col: usize, height: usize, width: usize) -> Self {
+        Self {
+            row,
+            col,
+            height,
+            width,
+        }
+    }
+}
+
+/// ウィンドウを分割する向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// `:split`: 上下に並べる
+    Horizontal,
+    /// `:vsplit`: 左右に並べる
+    Vertical,
+}
+
+/// `Ctrl-W h/j/k/l`で移動する向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+/// バッファを表示するウィンドウ（カーソルと表示範囲はウィンドウごとに持つ）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub id: usize,
+    /// 表示しているバッファの番号
+    pub buffer: usize,
+    pub cursor: Position,
+    pub curswant: Curswant,
+    pub viewport: Viewport,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(usize),
+    Split {
+        direction: SplitDirection,
+        children: Vec<Child>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Child {
+    node: Node,
+    /// 分割の向きの大きさ（縦の区切り線は含まない。0なら均等に割り当てる）
+    size: usize,
+}
+
+/// ステータスラインを含むウィンドウの最小の高さ
+const MIN_HEIGHT: usize = 2;
+const MIN_WIDTH: usize = 1;
+
+impl Node {
+    /// `direction`の向きに必要な最小の大きさ
+    fn min_size(&self, direction: SplitDirection) -> usize {
+        match self {
+            Node::Leaf(_) => match direction {
+                SplitDirection::Horizontal => MIN_HEIGHT,
+                SplitDirection::Vertical => MIN_WIDTH,
+            },
+            Node::Split {
+                direction: split,
+                children,
+            } => {
+                let sizes = children.iter().map(|child| child.node.min_size(direction));
+                if *split == direction {
+                    sizes.sum::<usize>() + separators(direction, children.len())
+                } else {
+                    sizes.max().unwrap_or(0)
+                }
+            }
+        }
+    }
+
+    fn leaves(&self, ids: &mut Vec<usize>) {
+        match self {
+            Node::Leaf(id) => ids.push(*id),
+            Node::Split { children, .. } => {
+                for child in children {
+                    child.node.leaves(ids);
+                }
+            }
+        }
+    }
+
+    /// `id`のウィンドウまでの子の位置
+    fn path(&self, id: usize) -> Option<Vec<usize>> {
+        match self {
+            Node::Leaf(leaf) => (*leaf == id).then(Vec::new),
+            Node::Split { children, .. } => {
+                children.iter().enumerate().find_map(|(index, child)| {
+                    let mut path = child.node.path(id)?;
+                    path.insert(0, index);
+                    Some(path)
+                })
+            }
+        }
+    }
+
+    fn at_mut(&mut self, path: &[usize]) -> &mut Node {
+        let Some((&index, rest)) = path.split_first() else {
+            return self;
+        };
+        match self {
+            Node::Split { children, .. } => children[index].node.at_mut(rest),
+            Node::Leaf(_) => self,
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let Node::Split { children, .. } = self {
+            for child in children {
+                child.size = 0;
+                child.node.equalize();
+            }
+        }
+    }
+
+    /// 子が1つだけの分割を取り除き、同じ向きの分割の入れ子を平らにする
+    fn normalize(&mut self) {
+        let Node::Split {
+            direction,
+            children,
+        } = self
+        else {
+            return;
+        };
+        let direction = *direction;
+        for child in children.iter_mut() {
+            child.node.normalize();
+        }
+        if children.iter().any(
+            |child| matches!(&child.node, Node::Split { direction: inner, .. } if *inner == direction),
+        ) {
+            let mut flattened = Vec::new();
+            for child in children.drain(..) {
+                match child.node {
+                    Node::Split {
+                        direction: inner,
+                        children: inner_children,
+                    } if inner == direction => {
+                        flattened.extend(inner_children.into_iter().map(|child| Child {
+                            size: 0,
+                            ..child
+                        }));
+                    }
+                    node => flattened.push(Child { node, size: 0 }),
+                }
+            }
+            *children = flattened;
+        }
+        if children.len() == 1 {
+            let only = children.pop().expect("one child").node;
+            *self = only;
+        }
+    }
+
+    /// `area`を子に割り当て、ウィンドウごとの矩形を`out`に追加する
+    fn arrange(&mut self, area: Rect, out: &mut Vec<(usize, Rect)>) {
+        let Node::Split {
+            direction,
+            children,
+        } = self
+        else {
+            if let Node::Leaf(id) = self {
+                out.push((*id, area));
+            }
+            return;
+        };
+        let direction = *direction;
+        let total = match direction {
+            SplitDirection::Horizontal => area.height,
+            SplitDirection::Vertical => area.width,
+        };
+        let available = total.saturating_sub(separators(direction, children.len()));
+        let mins: Vec<usize> = children
+            .iter()
+            .map(|child| child.node.min_size(direction))
+            .collect();
+        let sizes = fit(
+            &children.iter().map(|child| child.size).collect::<Vec<_>>(),
+            &mins,
+            available,
+        );
+
+        let mut offset = 0;
+        for (child, size) in children.iter_mut().zip(sizes) {
+            child.size = size;
+            let rect = match direction {
+                SplitDirection::Horizontal => {
+                    Rect::new(area.row + offset, area.col, size, area.width)
+                }
+                SplitDirection::Vertical => {
+                    Rect::new(area.row, area.col + offset, area.height, size)
+                }
+            };
+            child.node.arrange(rect, out);
+            offset += size + separators(direction, 2);
+        }
+    }
+}
+
+/// 左右に並べたウィンドウの間の区切り線の数
+fn separators(direction: SplitDirection, count: usize) -> usize {
+    match direction {
+        SplitDirection::Horizontal => 0,
+        SplitDirection::Vertical => count.saturating_sub(1),
+    }
+}
+
+/// 大きさの比を保ったまま合計を`available`に合わせる（最小の大きさは下回らない）
+fn fit(sizes: &[usize], mins: &[usize], available: usize) -> Vec<usize> {
+    let count = sizes.len();
+    let total: usize = sizes.iter().sum();
+    let mut fitted: Vec<usize> = if sizes.contains(&0) {
+        (0..count)
+            .map(|index| available / count + usize::from(index < available % count))
+            .collect()
+    } else {
+        sizes.iter().map(|size| size * available / total).collect()
+    };
+    for (size, &min) in fitted.iter_mut().zip(mins) {
+        *size = (*size).max(min);
+    }
+
+    let mut sum: usize = fitted.iter().sum();
+    if sum < available {
+        fitted[count - 1] += available - sum;
+    }
+    while sum > available {
+        let Some(index) = (0..count)
+            .filter(|&index| fitted[index] > mins[index])
+            .max_by_key(|&index| fitted[index])
+        else {
+            break;
+        };
+        fitted[index] -= 1;
+        sum -= 1;
+    }
+    fitted
+}
+
+/// ウィンドウの配置（分割の木）とカレントウィンドウ
+#[derive(Debug, Clone)]
+pub struct Layout {
+    root: Node,
+    windows: Vec<Window>,
+    current: usize,
+    previous: Option<usize>,
+    next_id: usize,
+}
+
+impl Layout {
+    /// `buffer`を表示するウィンドウ1つだけの配置
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            root: Node::Leaf(1),
+            windows: vec![Window {
+                id: 1,
+                buffer,
+                cursor: Position::origin(),
+                curswant: Curswant::default(),
+                viewport: Viewport::default(),
+            }],
+            current: 1,
+            previous: None,
+            next_id: 2,
+        }
+    }
+
+    pub fn current(&self) -> &Window {
+        self.window(self.current).expect("current window exists")
+    }
+
+    pub fn current_mut(&mut self) -> &mut Window {
+        let current = self.current;
+        self.window_mut(current).expect("current window exists")
+    }
+
+    pub fn current_id(&self) -> usize {
+        self.current
+    }
+
+    pub fn previous_id(&self) -> Option<usize> {
+        self.previous
+    }
+
+    pub fn window(&self, id: usize) -> Option<&Window> {
+        self.windows.iter().find(|window| window.id == id)
+    }
+
+    pub fn window_mut(&mut self, id: usize) -> Option<&mut Window> {
+        self.windows.iter_mut().find(|window| window.id == id)
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.windows.iter()
+    }
+
+    pub fn count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// 画面上の順序（左上から右下）に並べたウィンドウの番号
+    pub fn ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        self.root.leaves(&mut ids);
+        ids
+    }
+
+    /// `area`に収まるよう大きさを調整し、ウィンドウごとの矩形を返す
+    pub fn arrange(&mut self, area: Rect) -> Vec<(usize, Rect)> {
+        let mut rects = Vec::new();
+        self.root.arrange(area, &mut rects);
+        rects
+    }
+
+    /// カレントウィンドウを移す（`Ctrl-W p`で戻れるよう元のウィンドウを覚える）
+    pub fn focus(&mut self, id: usize) {
+        if id != self.current && self.window(id).is_some() {
+            self.previous = Some(self.current);
+            self.current = id;
+        }
+    }
+
+    /// `:split`/`:vsplit`: カレントウィンドウを分割し、上（左）に同じ状態の新しいウィンドウを作る
+    pub fn split(&mut self, direction: SplitDirection, area: Rect) -> Result<usize> {
+        let rect = self.rect(area, self.current);
+        let enough = match direction {
+            SplitDirection::Horizontal => rect.height >= MIN_HEIGHT * 2,
+            SplitDirection::Vertical => rect.width > MIN_WIDTH * 2,
+        };
+        if !enough {
+            return Err(EditorError::invalid_command("Not enough room"));
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let window = Window {
+            id,
+            ..self.current().clone()
+        };
+        self.windows.push(window);
+
+        let path = self.root.path(self.current).expect("current window exists");
+        let leaf = Child {
+            node: Node::Leaf(id),
+            size: 0,
+        };
+        match path.split_last() {
+            Some((&index, parent_path)) => match self.root.at_mut(parent_path) {
+                // 同じ向きの分割にはそのまま加え、同じ向きに並ぶウィンドウの大きさを揃える
+                Node::Split {
+                    direction: parent,
+                    children,
+                } if *parent == direction => {
+                    children.insert(index, leaf);
+                    for child in children.iter_mut() {
+                        child.size = 0;
+                    }
+                }
+                Node::Split { children, .. } => {
+                    let node = &mut children[index].node;
+                    *node = new_split(direction, leaf, node.clone());
+                }
+                Node::Leaf(_) => {}
+            },
+            None => self.root = new_split(direction, leaf, self.root.clone()),
+        }
+
+        self.focus(id);
+        Ok(id)
+    }
+
+    /// `:close`: ウィンドウを閉じ、空いた領域を同じ分割の他のウィンドウに割り当てる
+    pub fn close(&mut self, id: usize) -> Result<()> {
+        if self.count() == 1 {
+            return Err(EditorError::invalid_command("Cannot close last window"));
+        }
+        let path = self
+            .root
+            .path(id)
+            .ok_or_else(|| EditorError::invalid_command("Invalid window"))?;
+        let (&index, parent_path) = path.split_last().expect("window is not the root");
+        if let Node::Split { children, .. } = self.root.at_mut(parent_path) {
+            children.remove(index);
+            for child in children.iter_mut() {
+                child.size = 0;
+            }
+        }
+        self.root.normalize();
+        self.windows.retain(|window| window.id != id);
+
+        if self.previous == Some(id) {
+            self.previous = None;
+        }
+        if self.current == id {
+            // 直前のウィンドウがあればそこに、なければ先頭のウィンドウに移る
+            self.current = self.previous.take().unwrap_or_else(|| self.ids()[0]);
+        }
+        Ok(())
+    }
+
+    /// `:only`: カレントウィンドウ以外を閉じる
+    pub fn only(&mut self) {
+        self.root = Node::Leaf(self.current);
+        let current = self.current;
+        self.windows.retain(|window| window.id == current);
+        self.previous = None;
+    }
+
+    /// `Ctrl-W w`/`Ctrl-W W`: `count`番目（なければ次・前）のウィンドウの番号
+    pub fn cycle(&self, count: Option<usize>, forward: bool) -> usize {
+        let ids = self.ids();
+        if let Some(count) = count {
+            return ids[count.clamp(1, ids.len()) - 1];
+        }
+        let index = ids.iter().position(|&id| id == self.current).unwrap_or(0);
+        let len = ids.len();
+        ids[if forward {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        }]
+    }
+
+    /// `Ctrl-W h/j/k/l`: `point`（画面上のカーソル位置）から`direction`に`count`個先のウィンドウ
+    pub fn neighbor(
+        &mut self,
+        area: Rect,
+        direction: Direction,
+        count: usize,
+        point: (usize, usize),
+    ) -> Option<usize> {
+        let rects = self.arrange(area);
+        let rect_of = |id: usize| rects.iter().find(|(other, _)| *other == id).map(|r| r.1);
+        let (row, col) = point;
+        let mut id = self.current;
+        for _ in 0..count {
+            let current = rect_of(id)?;
+            let contains_row = |rect: &Rect| rect.row <= row && row < rect.row + rect.height;
+            let contains_col = |rect: &Rect| rect.col <= col && col <= rect.col + rect.width;
+            let found = rects
+                .iter()
+                .filter(|(_, rect)| match direction {
+                    Direction::Left => rect.col + rect.width < current.col && contains_row(rect),
+                    Direction::Right => {
+                        rect.col > current.col + current.width && contains_row(rect)
+                    }
+                    Direction::Up => rect.row + rect.height <= current.row && contains_col(rect),
+                    Direction::Down => {
+                        rect.row >= current.row + current.height && contains_col(rect)
+                    }
+                })
+                // 最も近いウィンドウ
+                .min_by_key(|(_, rect)| match direction {
+                    Direction::Left => current.col - rect.col,
+                    Direction::Right => rect.col - current.col,
+                    Direction::Up => current.row - rect.row,
+                    Direction::Down => rect.row - current.row,
+                });
+            match found {
+                Some((next, _)) => id = *next,
+                None => break,
+            }
+        }
+        (id != self.current).then_some(id)
+    }
+
+    /// `Ctrl-W +`/`Ctrl-W -`/`Ctrl-W >`/`Ctrl-W <`: カレントウィンドウの高さ（幅）を`delta`だけ変える
+    ///
+    /// 増やす分は後ろ（足りなければ前）のウィンドウから取り、減らす分は隣のウィンドウに渡す。
+    pub fn resize(&mut self, area: Rect, direction: SplitDirection, delta: isize) {
+        self.adjust(area, direction, |_| delta);
+    }
+
+    /// `Ctrl-W _`/`Ctrl-W |`: カレントウィンドウの高さ（幅）を`size`にする（収まる範囲で）
+    pub fn set_size(&mut self, area: Rect, direction: SplitDirection, size: usize) {
+        self.adjust(area, direction, |current| {
+            size.min(isize::MAX as usize) as isize - current as isize
+        });
+    }
+
+    /// `Ctrl-W =`: すべてのウィンドウの大きさを揃える
+    pub fn equalize(&mut self) {
+        self.root.equalize();
+    }
+
+    /// `Ctrl-W x`: 同じ分割の次（`count`なら`count`番目）のウィンドウと入れ替える
+    ///
+    /// カーソルは元と同じ位置に入れ替わったウィンドウに移る。
+    pub fn exchange(&mut self, count: Option<usize>) {
+        let path = self.root.path(self.current).expect("current window exists");
+        let Some((&index, parent_path)) = path.split_last() else {
+            return;
+        };
+        let Node::Split { children, .. } = self.root.at_mut(parent_path) else {
+            return;
+        };
+        let other = match count {
+            Some(count) => count.saturating_sub(1),
+            None if index + 1 < children.len() => index + 1,
+            None => index.saturating_sub(1),
+        };
+        if other == index || other >= children.len() {
+            return;
+        }
+        let (Node::Leaf(a), Node::Leaf(b)) = (&children[index].node, &children[other].node) else {
+            return;
+        };
+        let (a, b) = (*a, *b);
+        children[index].node = Node::Leaf(b);
+        children[other].node = Node::Leaf(a);
+        self.focus(b);
+    }
+
+    /// `Ctrl-W r`/`Ctrl-W R`: 同じ分割のウィンドウを下（右）・上（左）に回転する
+    pub fn rotate(&mut self, forward: bool, count: usize) -> Result<()> {
+        let path = self.root.path(self.current).expect("current window exists");
+        let Some((_, parent_path)) = path.split_last() else {
+            return Ok(());
+        };
+        let Node::Split { children, .. } = self.root.at_mut(parent_path) else {
+            return Ok(());
+        };
+        let mut ids = children
+            .iter()
+            .map(|child| match child.node {
+                Node::Leaf(id) => Some(id),
+                Node::Split { .. } => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                EditorError::invalid_command("Cannot rotate when another window is split")
+            })?;
+        let count = count % ids.len();
+        if forward {
+            ids.rotate_right(count);
+        } else {
+            ids.rotate_left(count);
+        }
+        for (child, id) in children.iter_mut().zip(ids) {
+            child.node = Node::Leaf(id);
+        }
+        Ok(())
+    }
+
+    fn rect(&mut self, area: Rect, id: usize) -> Rect {
+        self.arrange(area)
+            .into_iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, rect)| rect)
+            .unwrap_or(area)
+    }
+
+    /// カレントウィンドウを含む最も内側の`direction`の分割で、大きさを調整する
+    fn adjust(
+        &mut self,
+        area: Rect,
+        direction: SplitDirection,
+        delta: impl FnOnce(usize) -> isize,
+    ) {
+        self.arrange(area);
+        let path = self.root.path(self.current).expect("current window exists");
+        let found = (0..path.len()).rev().find(|&depth| {
+            matches!(
+                self.root.at_mut(&path[..depth]),
+                Node::Split { direction: split, .. } if *split == direction
+            )
+        });
+        let Some(depth) = found else {
+            return;
+        };
+        let index = path[depth];
+        let Node::Split { children, .. } = self.root.at_mut(&path[..depth]) else {
+            return;
+        };
+
+        let mins: Vec<usize> = children
+            .iter()
+            .map(|child| child.node.min_size(direction))
+            .collect();
+        let mut sizes: Vec<usize> = children.iter().map(|child| child.size).collect();
+        let delta = delta(sizes[index]);
+        let others = (index + 1..sizes.len()).chain((0..index).rev());
+        if delta > 0 {
+            let mut need = delta as usize;
+            for other in others {
+                let take = need.min(sizes[other].saturating_sub(mins[other]));
+                sizes[other] -= take;
+                need -= take;
+            }
+            sizes[index] += delta as usize - need;
+        } else if let Some(other) = others.into_iter().next() {
+            let give = delta
+                .unsigned_abs()
+                .min(sizes[index].saturating_sub(mins[index]));
+            sizes[index] -= give;
+            sizes[other] += give;
+        }
+        for (child, size) in children.iter_mut().zip(sizes) {
+            child.size = size;
+        }
+    }
+}
+
+fn new_split(direction: SplitDirection, first: Child, second: Node) -> Node {
+    Node::Split {
+        direction,
+        children: vec![
+            first,
+            Child {
+                node: second,
+                size: 0,
+            },
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        row: 0,
+        col: 0,
+        height: 20,
+        width: 41,
+    };
+
+    fn rects(layout: &mut Layout) -> Vec<(usize, Rect)> {
+        layout.arrange(AREA)
+    }
+
+    #[test]
+    fn test_split_and_arrange() {
+        let mut layout = Layout::new(1);
+        assert_eq!(rects(&mut layout), [(1, AREA)]);
+
+        // 新しいウィンドウは上（左）に作り、カレントウィンドウにする
+        let top = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        assert_eq!(layout.current_id(), top);
+        assert_eq!(
+            rects(&mut layout),
+            [
+                (top, Rect::new(0, 0, 10, 41)),
+                (1, Rect::new(10, 0, 10, 41))
+            ]
+        );
+
+        // 縦に分割すると区切り線の1列を除いて分ける
+        let left = layout.split(SplitDirection::Vertical, AREA).unwrap();
+        assert_eq!(
+            rects(&mut layout),
+            [
+                (left, Rect::new(0, 0, 10, 20)),
+                (top, Rect::new(0, 21, 10, 20)),
+                (1, Rect::new(10, 0, 10, 41))
+            ]
+        );
+        assert_eq!(layout.ids(), [left, top, 1]);
+        assert_eq!(layout.window(left).unwrap().buffer, 1);
+
+        // 端末の大きさが変わると比を保って割り当て直す
+        let small = Rect::new(0, 0, 10, 21);
+        assert_eq!(
+            layout.arrange(small),
+            [
+                (left, Rect::new(0, 0, 5, 10)),
+                (top, Rect::new(0, 11, 5, 10)),
+                (1, Rect::new(5, 0, 5, 21))
+            ]
+        );
+
+        let mut layout = Layout::new(1);
+        let tiny = Rect::new(0, 0, 3, 2);
+        assert!(layout.split(SplitDirection::Horizontal, tiny).is_err());
+        assert!(layout.split(SplitDirection::Vertical, tiny).is_err());
+    }
+
+    #[test]
+    fn test_close_and_only() {
+        let mut layout = Layout::new(1);
+        assert!(layout.close(1).is_err());
+
+        let second = layout.split(SplitDirection::Vertical, AREA).unwrap();
+        let third = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        layout.close(third).unwrap();
+        // 直前のウィンドウに戻り、入れ子の分割はなくなる
+        assert_eq!(layout.current_id(), second);
+        assert_eq!(
+            rects(&mut layout),
+            [
+                (second, Rect::new(0, 0, 20, 20)),
+                (1, Rect::new(0, 21, 20, 20))
+            ]
+        );
+
+        layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        layout.only();
+        assert_eq!(layout.count(), 1);
+        assert_eq!(rects(&mut layout)[0].1, AREA);
+    }
+
+    #[test]
+    fn test_move_between_windows() {
+        let mut layout = Layout::new(1);
+        let right = layout.split(SplitDirection::Vertical, AREA).unwrap();
+        layout.focus(1);
+        let top_right = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        // 左: right、右上: top_right、右下: 1
+        assert_eq!(layout.ids(), [right, top_right, 1]);
+
+        assert_eq!(
+            layout.neighbor(AREA, Direction::Left, 1, (15, 25)),
+            Some(right)
+        );
+        assert_eq!(layout.neighbor(AREA, Direction::Down, 1, (0, 25)), Some(1));
+        assert_eq!(layout.neighbor(AREA, Direction::Up, 1, (0, 25)), None);
+        layout.focus(right);
+        assert_eq!(layout.neighbor(AREA, Direction::Right, 1, (15, 0)), Some(1));
+        assert_eq!(
+            layout.neighbor(AREA, Direction::Right, 1, (2, 0)),
+            Some(top_right)
+        );
+
+        assert_eq!(layout.cycle(None, true), top_right);
+        assert_eq!(layout.cycle(None, false), 1);
+        assert_eq!(layout.cycle(Some(3), true), 1);
+        assert_eq!(layout.cycle(Some(9), true), 1);
+        assert_eq!(layout.previous_id(), Some(top_right));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut layout = Layout::new(1);
+        let top = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        let middle = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        // 上から7、7、6行
+        assert_eq!(layout.ids(), [middle, top, 1]);
+
+        layout.resize(AREA, SplitDirection::Horizontal, 3);
+        let heights = |layout: &mut Layout| {
+            layout
+                .arrange(AREA)
+                .iter()
+                .map(|(_, rect)| rect.height)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(heights(&mut layout), [10, 4, 6]);
+
+        // 後ろのウィンドウが最小になったら前のウィンドウから取る
+        layout.focus(top);
+        layout.set_size(AREA, SplitDirection::Horizontal, usize::MAX);
+        assert_eq!(heights(&mut layout), [2, 16, 2]);
+        layout.resize(AREA, SplitDirection::Horizontal, -4);
+        assert_eq!(heights(&mut layout), [2, 12, 6]);
+
+        // 分割の向きが違えば何もしない
+        layout.resize(AREA, SplitDirection::Vertical, 4);
+        assert_eq!(heights(&mut layout), [2, 12, 6]);
+
+        layout.equalize();
+        assert_eq!(heights(&mut layout), [7, 7, 6]);
+    }
+
+    #[test]
+    fn test_exchange_and_rotate() {
+        let mut layout = Layout::new(1);
+        let second = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        let third = layout.split(SplitDirection::Horizontal, AREA).unwrap();
+        assert_eq!(layout.ids(), [third, second, 1]);
+
+        layout.exchange(None);
+        assert_eq!(layout.ids(), [second, third, 1]);
+        assert_eq!(layout.current_id(), second);
+
+        layout.rotate(true, 1).unwrap();
+        assert_eq!(layout.ids(), [1, second, third]);
+        assert_eq!(layout.current_id(), second);
+        layout.rotate(false, 2).unwrap();
+        assert_eq!(layout.ids(), [third, 1, second]);
+
+        layout.split(SplitDirection::Vertical, AREA).unwrap();
+        layout.focus(1);
+        assert!(layout.rotate(true, 1).is_err());
+    }
+}
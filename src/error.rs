@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::process::ExitStatus;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,6 +22,9 @@ pub enum EditorError {
     #[error("Permission denied: {path}")]
     PermissionDenied { path: PathBuf },
 
+    #[error("\"{path}\" is not a file")]
+    NotAFile { path: PathBuf },
+
     #[error("Undo stack is empty")]
     EmptyUndoStack,
 
@@ -35,6 +39,13 @@ pub enum EditorError {
 
     #[error("Configuration error: {0}")]
     Config(String),
+
+    #[error("Shell command `{command}` failed ({status}): {stderr}")]
+    ShellCommand {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, EditorError>;
@@ -63,6 +74,10 @@ impl EditorError {
         Self::PermissionDenied { path: path.into() }
     }
 
+    pub fn not_a_file<P: Into<PathBuf>>(path: P) -> Self {
+        Self::NotAFile { path: path.into() }
+    }
+
     pub fn terminal<S: Into<String>>(msg: S) -> Self {
         Self::Terminal(msg.into())
     }
@@ -74,6 +89,14 @@ impl EditorError {
     pub fn config<S: Into<String>>(msg: S) -> Self {
         Self::Config(msg.into())
     }
+
+    pub fn shell_command<S: Into<String>>(command: S, status: ExitStatus, stderr: S) -> Self {
+        Self::ShellCommand {
+            command: command.into(),
+            status,
+            stderr: stderr.into(),
+        }
+    }
 }
 
 // Crosstermのエラーは std::io::Error を通して処理される
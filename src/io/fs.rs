@@ -1,9 +1,131 @@
 use crate::error::{EditorError, Result};
 use std::fs;
-use std::path::Path;
+use std::hash::Hasher;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 pub struct FileSystem;
 
+/// 保存時に元のファイルをどう置き換えるか（`backupcopy`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackupCopy {
+    /// 通常は一時ファイルからの置き換え、シンボリックリンク・ハードリンクや所有者を保てない場合は上書き
+    #[default]
+    Auto,
+    /// 元のファイルにそのまま上書きする
+    Yes,
+    /// 常に一時ファイルに書いて置き換える（リンクは切れる）
+    No,
+}
+
+impl BackupCopy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(BackupCopy::Auto),
+            "yes" => Some(BackupCopy::Yes),
+            "no" => Some(BackupCopy::No),
+            _ => None,
+        }
+    }
+}
+
+/// `FileSystem::save`の動作を決めるオプション
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub backupcopy: BackupCopy,
+    /// 保存後もバックアップを残す
+    pub backup: bool,
+    /// 保存が終わるまでバックアップを作っておく
+    pub writebackup: bool,
+    /// バックアップを作るディレクトリのカンマ区切りの候補（`.`は保存するファイルと同じディレクトリ）
+    pub backupdir: String,
+    /// バックアップのファイル名に付ける文字列
+    pub backupext: String,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            backupcopy: BackupCopy::Auto,
+            backup: false,
+            writebackup: true,
+            backupdir: ".".to_string(),
+            backupext: "~".to_string(),
+        }
+    }
+}
+
+/// 読み込み・保存した時点のファイルの状態（外部での変更の検出に使う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    /// 内容のハッシュ（更新日時だけが変わった場合を区別する）
+    pub hash: u64,
+}
+
+/// `FileStamp::check`で分かったファイルの変化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Unchanged,
+    /// 更新日時は変わったが内容は同じ
+    Touched(FileStamp),
+    Modified(FileStamp),
+    Deleted,
+}
+
+impl FileStamp {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let metadata = fs::metadata(path)?;
+        let content = fs::read(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+            hash: hash(&content),
+        })
+    }
+
+    /// `path`のファイルがこの状態から変わったか（更新日時と大きさが同じなら内容は読まない）
+    pub fn check<P: AsRef<Path>>(&self, path: P) -> Result<FileChange> {
+        let path = path.as_ref();
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(FileChange::Deleted),
+            Err(e) => return Err(EditorError::Io(e)),
+        };
+        if metadata.modified().ok() == self.modified && metadata.len() == self.size {
+            return Ok(FileChange::Unchanged);
+        }
+        let current = Self::read(path)?;
+        if current.hash == self.hash && current.size == self.size {
+            Ok(FileChange::Touched(current))
+        } else {
+            Ok(FileChange::Modified(current))
+        }
+    }
+}
+
+fn hash(content: &[u8]) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    hasher.write(content);
+    hasher.finish()
+}
+
+/// `FileSystem::open`で開いたパスの内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenedFile {
+    /// 通常のファイル（書き込めなければ`writable`が偽）
+    Text { content: String, writable: bool },
+    /// まだ存在しないファイル（最初の保存で作る）
+    New,
+    /// 読み込む権限のないファイル
+    PermissionDenied,
+    /// ディレクトリ（名前順のエントリ）
+    Directory(Vec<PathBuf>),
+}
+
 impl FileSystem {
     pub fn new() -> Self {
         Self
@@ -25,49 +147,214 @@ impl FileSystem {
         }
     }
 
+    /// 編集するパスを開く（デバイスなど、通常のファイルでもディレクトリでもなければエラー）
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<OpenedFile> {
+        let path = path.as_ref();
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::NotFound => return Ok(OpenedFile::New),
+                std::io::ErrorKind::PermissionDenied => return Ok(OpenedFile::PermissionDenied),
+                _ => return Err(EditorError::Io(e)),
+            },
+        };
+        if metadata.is_dir() {
+            return Ok(OpenedFile::Directory(Self::list_dir(path)?));
+        }
+        if !metadata.is_file() {
+            return Err(EditorError::not_a_file(path));
+        }
+
+        match Self::read_file(path) {
+            Ok(content) => Ok(OpenedFile::Text {
+                content,
+                // 追記モードで開けるかで書き込めるかを判定する（内容は変わらない）
+                writable: fs::OpenOptions::new().append(true).open(path).is_ok(),
+            }),
+            Err(EditorError::PermissionDenied { .. }) => Ok(OpenedFile::PermissionDenied),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// バックアップを作らずに保存する（書き込みは`save`と同じく途中で失敗しても元の内容を残す）
     pub fn write_file<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
+        let options = WriteOptions {
+            writebackup: false,
+            ..WriteOptions::default()
+        };
+        Self::save(path, content, &options)
+    }
+
+    /// `content`を保存する
+    ///
+    /// 同じディレクトリの一時ファイルに書いてfsyncし、名前を変えて元のファイルと置き換える
+    /// （パーミッション・所有者・拡張属性は元のファイルから写す）。リンクを保つ必要がある場合や
+    /// 所有者を写せない場合、`backupcopy`に従って元のファイルに直接上書きする。
+    pub fn save<P: AsRef<Path>>(path: P, content: &str, options: &WriteOptions) -> Result<()> {
         let path = path.as_ref();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent)?;
+        }
 
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)?;
+        // 書き込みに失敗しても元の内容が残るよう、先にバックアップを作る
+        let backup = if options.backup || options.writebackup {
+            Self::create_write_backup(path, options)?
+        } else {
+            None
+        };
+
+        Self::replace(path, content, options.backupcopy).map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => EditorError::permission_denied(path),
+            _ => EditorError::Io(e),
+        })?;
+
+        // 保存できたら、`writebackup`のためだけに作ったバックアップは消し、
+        // `backup`なら前回のバックアップと置き換える（どちらもこの保存で作ったファイルだけを扱う）。
+        // 内容はもう書き込めているので、後片付けに失敗しても保存は失敗にしない
+        if let Some((backup, name)) = backup {
+            if !options.backup {
+                let _ = fs::remove_file(backup);
+            } else if backup != name {
+                let _ = fs::rename(backup, name);
             }
         }
+        Ok(())
+    }
 
-        match fs::write(path, content) {
-            Ok(()) => Ok(()),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::PermissionDenied => Err(EditorError::permission_denied(path)),
-                _ => Err(EditorError::Io(e)),
-            },
+    /// 保存前のバックアップを`backupdir`の候補のうち最初に存在するディレクトリに新しく作る
+    ///
+    /// 名前はファイル名に`backupext`を付けたもの。そのファイルが既にあれば番号を付けた名前で作り、
+    /// 既存のファイルは上書きしない。作ったファイルと本来の名前を返す（ファイルがなければ`None`）。
+    fn create_write_backup(
+        path: &Path,
+        options: &WriteOptions,
+    ) -> Result<Option<(PathBuf, PathBuf)>> {
+        let Ok(mut source) = fs::File::open(path) else {
+            return Ok(None);
+        };
+        let dir = backup_dir(path, &options.backupdir)?;
+        let name = path
+            .file_name()
+            .map_or("rvim".into(), |name| name.to_string_lossy());
+        let backup_name = dir.join(format!("{}{}", name, options.backupext));
+
+        let mut attempt = 0;
+        let (backup, mut file) = loop {
+            let backup = match attempt {
+                0 => backup_name.clone(),
+                n => dir.join(format!("{}.{}{}", name, n, options.backupext)),
+            };
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&backup)
+            {
+                Ok(file) => break (backup, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                }
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    return Err(EditorError::permission_denied(&backup));
+                }
+                Err(e) => return Err(EditorError::Io(e)),
+            }
+        };
+
+        // 書き込みに失敗したら作りかけのバックアップを消す
+        let mut copy = || -> io::Result<()> {
+            io::copy(&mut source, &mut file)?;
+            file.set_permissions(source.metadata()?.permissions())?;
+            file.sync_all()
+        };
+        if let Err(e) = copy() {
+            let _ = fs::remove_file(&backup);
+            return Err(EditorError::Io(e));
         }
+        Ok(Some((backup, backup_name)))
+    }
+
+    /// `path`の内容を`content`に置き換える（一時ファイルからの置き換えか上書きかは`backupcopy`で決める）
+    fn replace(path: &Path, content: &str, backupcopy: BackupCopy) -> io::Result<()> {
+        let existing = fs::metadata(path).ok();
+        let overwrite = match (&existing, backupcopy) {
+            (None, _) | (Some(_), BackupCopy::No) => false,
+            (Some(_), BackupCopy::Yes) => true,
+            (Some(metadata), BackupCopy::Auto) => is_linked(path, metadata),
+        };
+        if !overwrite {
+            match TempFile::create(path, existing.as_ref()) {
+                Ok(temp) => return temp.commit(path, content),
+                // ディレクトリに書き込めない・所有者を写せない場合は上書きに切り替える
+                Err(_) if existing.is_some() && backupcopy == BackupCopy::Auto => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    }
+
+    /// `:w >> {file}`: `content`をファイルの末尾に追加する（ファイルがなければ作る）
+    ///
+    /// 末尾が改行で終わっていないファイルには、改行を挟んで別の行として追加する。
+    pub fn append<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
+        let path = path.as_ref();
+        let needs_newline = fs::read(path)
+            .map(|existing| existing.last().is_some_and(|&byte| byte != b'\n'))
+            .unwrap_or(false);
+        let append = || -> io::Result<()> {
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)?;
+            if needs_newline {
+                file.write_all(b"\n")?;
+            }
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        };
+        append().map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => EditorError::permission_denied(path),
+            _ => EditorError::Io(e),
+        })
     }
 
     pub fn file_exists<P: AsRef<Path>>(path: P) -> bool {
         path.as_ref().exists()
     }
 
+    /// 同じディレクトリにバックアップ（`name.bak`、既にあれば`name.1.bak`…）を作る
+    ///
+    /// 既存のファイルは上書きしない。ファイルがなければ何もしない。
     pub fn create_backup<P: AsRef<Path>>(path: P) -> Result<()> {
+        let options = WriteOptions {
+            backupdir: ".".to_string(),
+            backupext: ".bak".to_string(),
+            ..WriteOptions::default()
+        };
+        Self::create_write_backup(path.as_ref(), &options).map(|_| ())
+    }
+
+    /// ディレクトリ内のエントリを名前順に返す
+    pub fn list_dir<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
         let path = path.as_ref();
 
         if !path.exists() {
-            return Ok(()); // バックアップ対象が存在しない場合はOK
+            return Err(EditorError::file_not_found(path));
         }
 
-        let backup_path = path.with_extension(format!(
-            "{}.bak",
-            path.extension().and_then(|s| s.to_str()).unwrap_or("")
-        ));
-
-        match fs::copy(path, &backup_path) {
-            Ok(_) => Ok(()),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::PermissionDenied => {
-                    Err(EditorError::permission_denied(&backup_path))
-                }
-                _ => Err(EditorError::Io(e)),
-            },
-        }
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        Ok(entries)
     }
 
     pub fn get_file_size<P: AsRef<Path>>(path: P) -> Result<u64> {
@@ -90,6 +377,140 @@ impl Default for FileSystem {
     }
 }
 
+/// `backupdir`の候補のうち最初に存在するディレクトリ（`.`は`path`と同じディレクトリ）
+fn backup_dir(path: &Path, backupdir: &str) -> Result<PathBuf> {
+    backupdir
+        .split(',')
+        .filter(|dir| !dir.is_empty())
+        .find_map(|dir| match dir {
+            "." => Some(match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            }),
+            dir => Path::new(dir).is_dir().then(|| PathBuf::from(dir)),
+        })
+        .ok_or_else(|| EditorError::config(format!("Cannot create backup file in {}", backupdir)))
+}
+
+/// シンボリックリンクかハードリンクがあり、置き換えるとリンクが切れるファイルか
+fn is_linked(path: &Path, metadata: &fs::Metadata) -> bool {
+    let symlink = fs::symlink_metadata(path).is_ok_and(|link| link.file_type().is_symlink());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        symlink || metadata.nlink() > 1
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        symlink
+    }
+}
+
+/// 保存先と同じディレクトリに作る一時ファイル（置き換える前に失敗したら消す）
+struct TempFile {
+    path: PathBuf,
+    file: fs::File,
+    committed: bool,
+}
+
+impl TempFile {
+    /// 一時ファイルを作り、`metadata`（元のファイル）の所有者・パーミッション・拡張属性を写す
+    fn create(target: &Path, metadata: Option<&fs::Metadata>) -> io::Result<Self> {
+        let dir = match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let name = target
+            .file_name()
+            .map_or("rvim".into(), |name| name.to_string_lossy());
+
+        let mut attempt = 0;
+        let temp = loop {
+            let path = dir.join(format!(".{}.{}-{}.tmp", name, std::process::id(), attempt));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => {
+                    break Self {
+                        path,
+                        file,
+                        committed: false,
+                    };
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        if let Some(metadata) = metadata {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::{MetadataExt, fchown};
+                let created = temp.file.metadata()?;
+                if (created.uid(), created.gid()) != (metadata.uid(), metadata.gid()) {
+                    fchown(&temp.file, Some(metadata.uid()), Some(metadata.gid()))?;
+                }
+            }
+            // 所有者を変えると setuid などが落ちるため、パーミッションは後から写す
+            temp.file.set_permissions(metadata.permissions())?;
+            copy_xattrs(target, &temp.path);
+        }
+        Ok(temp)
+    }
+
+    /// 内容を書いてfsyncし、`target`と置き換える
+    fn commit(mut self, target: &Path, content: &str) -> io::Result<()> {
+        self.file.write_all(content.as_bytes())?;
+        self.file.sync_all()?;
+        fs::rename(&self.path, target)?;
+        self.committed = true;
+
+        // 名前の変更もディスクに書き出す（対応していない環境では無視する）
+        if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty())
+            && let Ok(dir) = fs::File::open(dir)
+        {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 拡張属性を写す（対応していないファイルシステムや写せない属性は無視する）
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn copy_xattrs(from: &Path, to: &Path) {
+    use rustix::fs::{XattrFlags, getxattr, listxattr, setxattr};
+
+    let mut names = vec![0u8; 64 * 1024];
+    let Ok(len) = listxattr(from, &mut names[..]) else {
+        return;
+    };
+    let mut value = vec![0u8; 64 * 1024];
+    for name in names[..len].split(|&byte| byte == 0) {
+        if name.is_empty() {
+            continue;
+        }
+        if let Ok(len) = getxattr(from, name, &mut value[..]) {
+            let _ = setxattr(to, name, &value[..len], XattrFlags::empty());
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn copy_xattrs(_from: &Path, _to: &Path) {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,6 +525,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::default_constructed_unit_structs)]
     fn test_file_system_default() {
         let fs = FileSystem::default();
         assert_eq!(std::mem::size_of_val(&fs), 0);
@@ -158,6 +580,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_append() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("log.txt");
+
+        // ないファイルは作り、改行で終わらないファイルには行として追加する
+        FileSystem::append(&file_path, "one")?;
+        FileSystem::append(&file_path, "two\nthree")?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "one\ntwo\nthree");
+
+        fs::write(&file_path, "first\n")?;
+        FileSystem::append(&file_path, "second")?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "first\nsecond");
+        Ok(())
+    }
+
     #[test]
     fn test_file_exists() -> Result<()> {
         let temp_file = NamedTempFile::new().unwrap();
@@ -195,6 +633,15 @@ mod tests {
 
         let backup_path = temp_dir.path().join("test.rs.bak");
         assert!(FileSystem::file_exists(&backup_path));
+
+        // 既存のバックアップは上書きせず、番号を付けた名前で作る
+        FileSystem::write_file(&file_path, "fn main() { two() }")?;
+        FileSystem::create_backup(&file_path)?;
+        assert_eq!(FileSystem::read_file(&backup_path)?, content);
+        assert_eq!(
+            FileSystem::read_file(temp_dir.path().join("test.rs.1.bak"))?,
+            "fn main() { two() }"
+        );
         Ok(())
     }
 
@@ -208,6 +655,215 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_save_backup() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        let backup_path = temp_dir.path().join("test.txt~");
+        FileSystem::write_file(&file_path, "old")?;
+
+        // writebackupだけなら保存後にバックアップを消す
+        FileSystem::save(&file_path, "new", &WriteOptions::default())?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "new");
+        assert!(!backup_path.exists());
+
+        let options = WriteOptions {
+            backup: true,
+            ..WriteOptions::default()
+        };
+        FileSystem::save(&file_path, "newer", &options)?;
+        assert_eq!(FileSystem::read_file(&backup_path)?, "new");
+
+        // backupdirの候補のうち存在する最初のディレクトリに作る
+        let backup_dir = temp_dir.path().join("backup");
+        fs::create_dir(&backup_dir).unwrap();
+        let options = WriteOptions {
+            backup: true,
+            backupdir: format!("/nonexistent,{}", backup_dir.display()),
+            ..WriteOptions::default()
+        };
+        FileSystem::save(&file_path, "newest", &options)?;
+        assert_eq!(
+            FileSystem::read_file(backup_dir.join("test.txt~"))?,
+            "newer"
+        );
+
+        // 一時ファイルは残らない
+        let mut entries = FileSystem::list_dir(temp_dir.path())?;
+        entries.sort();
+        assert_eq!(entries, [backup_dir, file_path, backup_path]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_keeps_existing_files_with_backup_name() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("foo.rs");
+        let bak_path = temp_dir.path().join("foo.rs.bak");
+        FileSystem::write_file(&file_path, "old")?;
+        fs::write(&bak_path, "mine").unwrap();
+
+        // 同じ名前のファイルがあっても上書きも削除もしない
+        let options = WriteOptions {
+            backupext: ".bak".to_string(),
+            ..WriteOptions::default()
+        };
+        FileSystem::save(&file_path, "new", &options)?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "new");
+        assert_eq!(FileSystem::read_file(&bak_path)?, "mine");
+        assert_eq!(
+            FileSystem::list_dir(temp_dir.path())?,
+            [file_path.clone(), bak_path.clone()]
+        );
+
+        // `backup`では保存できてから前回のバックアップと置き換える
+        let options = WriteOptions {
+            backup: true,
+            ..options
+        };
+        FileSystem::save(&file_path, "newer", &options)?;
+        assert_eq!(FileSystem::read_file(&bak_path)?, "new");
+        assert_eq!(
+            FileSystem::list_dir(temp_dir.path())?,
+            [file_path, bak_path]
+        );
+
+        // 拡張子のないファイルにもファイル名の後ろに付ける
+        let makefile = temp_dir.path().join("Makefile");
+        FileSystem::write_file(&makefile, "all:")?;
+        let options = WriteOptions {
+            backup: true,
+            ..WriteOptions::default()
+        };
+        FileSystem::save(&makefile, "test:", &options)?;
+        assert_eq!(
+            FileSystem::read_file(temp_dir.path().join("Makefile~"))?,
+            "all:"
+        );
+
+        // 書き込めた後はバックアップを置き換えられなくても保存は成功にする
+        let blocked = temp_dir.path().join("blocked.txt");
+        FileSystem::write_file(&blocked, "old")?;
+        fs::create_dir(temp_dir.path().join("blocked.txt~")).unwrap();
+        fs::write(temp_dir.path().join("blocked.txt~/keep"), "").unwrap();
+        FileSystem::save(&blocked, "new", &options)?;
+        assert_eq!(FileSystem::read_file(&blocked)?, "new");
+        assert_eq!(
+            FileSystem::read_file(temp_dir.path().join("blocked.txt.1~"))?,
+            "old"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_save_preserves_metadata_and_links() -> Result<()> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("script.sh");
+        FileSystem::write_file(&file_path, "old")?;
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        FileSystem::save(&file_path, "new", &WriteOptions::default())?;
+        let metadata = fs::metadata(&file_path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+
+        // autoではハードリンクとシンボリックリンクを保つ
+        let hard_link = temp_dir.path().join("hard_link.sh");
+        let soft_link = temp_dir.path().join("soft_link.sh");
+        fs::hard_link(&file_path, &hard_link).unwrap();
+        symlink(&file_path, &soft_link).unwrap();
+        FileSystem::save(&hard_link, "hard", &WriteOptions::default())?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "hard");
+        FileSystem::save(&soft_link, "soft", &WriteOptions::default())?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "soft");
+        assert!(fs::symlink_metadata(&soft_link).unwrap().is_symlink());
+
+        // noでは置き換えるためハードリンクが切れる
+        let options = WriteOptions {
+            backupcopy: BackupCopy::No,
+            ..WriteOptions::default()
+        };
+        FileSystem::save(&hard_link, "broken", &options)?;
+        assert_eq!(FileSystem::read_file(&file_path)?, "soft");
+        assert_eq!(fs::metadata(&hard_link).unwrap().nlink(), 1);
+        assert_eq!(
+            fs::metadata(&hard_link).unwrap().permissions().mode() & 0o777,
+            0o750
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_stamp() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        FileSystem::write_file(&file_path, "old")?;
+        let stamp = FileStamp::read(&file_path)?;
+        assert_eq!(stamp.check(&file_path)?, FileChange::Unchanged);
+
+        // 内容が同じなら更新日時が変わっても変更とみなさない
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        let file = fs::File::options().write(true).open(&file_path).unwrap();
+        file.set_modified(later).unwrap();
+        assert!(matches!(stamp.check(&file_path)?, FileChange::Touched(_)));
+
+        FileSystem::write_file(&file_path, "new")?;
+        match stamp.check(&file_path)? {
+            FileChange::Modified(current) => assert_ne!(current.hash, stamp.hash),
+            change => panic!("unexpected change: {:?}", change),
+        }
+
+        fs::remove_file(&file_path).unwrap();
+        assert_eq!(stamp.check(&file_path)?, FileChange::Deleted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_dir() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        FileSystem::write_file(temp_dir.path().join("b.txt"), "")?;
+        FileSystem::write_file(temp_dir.path().join("a.txt"), "")?;
+
+        let entries = FileSystem::list_dir(temp_dir.path())?;
+        assert_eq!(
+            entries,
+            vec![temp_dir.path().join("a.txt"), temp_dir.path().join("b.txt")]
+        );
+        assert!(FileSystem::list_dir("/nonexistent/dir").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        FileSystem::write_file(&path, "text")?;
+
+        assert_eq!(
+            FileSystem::open(&path)?,
+            OpenedFile::Text {
+                content: "text".to_string(),
+                writable: true
+            }
+        );
+        assert_eq!(
+            FileSystem::open(temp_dir.path().join("new.txt"))?,
+            OpenedFile::New
+        );
+        assert_eq!(
+            FileSystem::open(temp_dir.path())?,
+            OpenedFile::Directory(vec![path])
+        );
+        #[cfg(unix)]
+        assert!(matches!(
+            FileSystem::open("/dev/null"),
+            Err(EditorError::NotAFile { .. })
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_get_file_size() -> Result<()> {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1,8 +1,14 @@
 pub mod fs;
+pub mod shell;
+pub mod swap;
 pub mod terminal;
+pub mod watch;
 
-pub use fs::FileSystem;
-pub use terminal::{Terminal, TerminalPosition, TerminalSize};
+pub use fs::{BackupCopy, FileChange, FileStamp, FileSystem, OpenedFile, WriteOptions};
+pub use shell::Shell;
+pub use swap::{SwapFile, SwapInfo};
+pub use terminal::{Terminal, TerminalEvent, TerminalPosition, TerminalSize};
+pub use watch::{WatchEvent, Watcher};
 
 #[cfg(test)]
 mod tests {
@@ -88,7 +94,7 @@ mod tests {
 
         // 異なる形式のパスでのテスト
         let file1 = temp_dir.path().join("file1.txt");
-        let file2: PathBuf = temp_dir.path().join("file2.txt").into();
+        let file2: PathBuf = temp_dir.path().join("file2.txt");
         let file3 = temp_dir.path().join("subdirectory").join("file3.txt");
 
         let content = "Test content";
@@ -0,0 +1,64 @@
+use crate::error::{EditorError, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub struct Shell;
+
+impl Shell {
+    /// `input`を標準入力に渡して`command`を`sh -c`で実行し、標準出力を返す
+    ///
+    /// 終了ステータスが0以外なら標準エラー出力を持つ`EditorError::ShellCommand`を返す。
+    pub fn filter(command: &str, input: &str) -> Result<String> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // 出力がパイプを埋めても止まらないよう、入力は別スレッドで書き込む
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        // 入力を読まずに終了するコマンドではパイプが閉じられるが、出力は有効
+        let _ = writer.join();
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(EditorError::shell_command(
+                command,
+                output.status,
+                stderr.trim_end(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let output = Shell::filter("sort -r", "a\nc\nb\n").unwrap();
+        assert_eq!(output, "c\nb\na\n");
+
+        // 入力を読まないコマンドでも出力を返す
+        assert_eq!(Shell::filter("echo ok", "ignored\n").unwrap(), "ok\n");
+    }
+
+    #[test]
+    fn test_filter_failure_carries_stderr() {
+        let error = Shell::filter("echo oops >&2; exit 3", "").unwrap_err();
+        match error {
+            EditorError::ShellCommand { status, stderr, .. } => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "oops");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use crate::error::{EditorError, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// スワップファイルの1行目
+const MAGIC: &str = "rvim swap file";
+
+/// 変更の記録がこの数を超えたら、内容全体を書き直して短くする
+const COMPACT_RECORDS: usize = 100;
+
+/// 編集中のバッファの内容を書き出すスワップファイル
+///
+/// ヘッダ（PID・ホスト名・ファイル名）の後に、前回書き出した内容からの変更を
+/// `@ 開始行 削除する行数 挿入する行数`、挿入する行、`.`の順に追記していく。
+/// 途中で落ちて最後の記録が欠けていても、そこまでの変更から復元できる。
+#[derive(Debug)]
+pub struct SwapFile {
+    path: PathBuf,
+    file: fs::File,
+    /// 最後に書き出した内容
+    lines: Vec<String>,
+    records: usize,
+}
+
+impl SwapFile {
+    /// `file`のスワップファイルを`directory`の候補のうち最初に作れる場所に作り、`lines`を書き出す
+    ///
+    /// 同じ名前のスワップファイルがあれば`.swo`、`.swn`…と名前を変える。
+    /// パーミッションは`file`と同じ（新しいファイルなら自分だけが読み書きできる）にする。
+    pub fn create(file: &Path, directory: &str, lines: &[String]) -> Result<Self> {
+        let header = Header::current(file);
+        let mut options = fs::OpenOptions::new();
+        options.append(true).create_new(true);
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            // 元のファイルを読めないユーザーに内容が漏れないよう、作るときから同じにする
+            let mode = fs::metadata(file).map_or(0o600, |metadata| {
+                metadata.permissions().mode() & 0o777 | 0o600
+            });
+            options.mode(mode);
+            mode
+        };
+        for path in candidates(file, directory) {
+            match options.open(&path) {
+                Ok(mut handle) => {
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        // umaskに関わらず元のファイルと同じにする
+                        handle.set_permissions(fs::Permissions::from_mode(mode))?;
+                    }
+                    handle.write_all(header.to_string().as_bytes())?;
+                    handle.write_all(record(0, 0, lines).as_bytes())?;
+                    handle.sync_data()?;
+                    return Ok(Self {
+                        path,
+                        file: handle,
+                        lines: lines.to_vec(),
+                        records: 1,
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => continue,
+                Err(e) => return Err(EditorError::Io(e)),
+            }
+        }
+        Err(EditorError::config(format!(
+            "Unable to open swap file for \"{}\"",
+            file.display()
+        )))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 前回から変わった行を追記する（変わっていなければ何もせず`false`を返す）
+    pub fn sync(&mut self, lines: &[String]) -> Result<bool> {
+        let prefix = self
+            .lines
+            .iter()
+            .zip(lines)
+            .take_while(|(old, new)| old == new)
+            .count();
+        if prefix == self.lines.len() && prefix == lines.len() {
+            return Ok(false);
+        }
+        let suffix = self.lines[prefix..]
+            .iter()
+            .rev()
+            .zip(lines[prefix..].iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        if self.records >= COMPACT_RECORDS {
+            self.compact(lines)?;
+        } else {
+            let removed = self.lines.len() - prefix - suffix;
+            let inserted = &lines[prefix..lines.len() - suffix];
+            self.file
+                .write_all(record(prefix, removed, inserted).as_bytes())?;
+            self.file.sync_data()?;
+            self.records += 1;
+        }
+        self.lines = lines.to_vec();
+        Ok(true)
+    }
+
+    /// バッファを閉じたときなど、不要になったスワップファイルを消す
+    pub fn remove(self) -> Result<()> {
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    /// 変更の記録を捨て、ヘッダと内容全体だけのファイルに置き換える
+    fn compact(&mut self, lines: &[String]) -> Result<()> {
+        let content = fs::read_to_string(&self.path)?;
+        let header_end = content.find("\n\n").map_or(content.len(), |end| end + 2);
+        let file_name = self
+            .path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let temp = self.path.with_file_name(format!("{}.new", file_name));
+
+        // 途中で終了して残った一時ファイルには書き込まず、作り直す
+        let _ = fs::remove_file(&temp);
+        let permissions = fs::metadata(&self.path)?.permissions();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            // 作った時点からスワップファイルと同じパーミッションにする
+            options.mode(permissions.mode() & 0o777);
+        }
+        let mut handle = options.open(&temp)?;
+        handle.set_permissions(permissions)?;
+        handle.write_all(&content.as_bytes()[..header_end])?;
+        handle.write_all(record(0, 0, lines).as_bytes())?;
+        handle.sync_data()?;
+        fs::rename(&temp, &self.path)?;
+
+        self.file = fs::OpenOptions::new().append(true).open(&self.path)?;
+        self.records = 1;
+        Ok(())
+    }
+}
+
+/// スワップファイルのヘッダ（誰がどのファイルを編集しているか）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapInfo {
+    pub pid: u32,
+    pub host: String,
+    /// 編集していたファイル（絶対パス）
+    pub file: PathBuf,
+    /// 最後に書き出した日時（スワップファイルの更新日時）
+    pub modified: Option<SystemTime>,
+}
+
+impl SwapInfo {
+    /// スワップファイルのヘッダを読む
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let header = Header::parse(path, &content)?;
+        Ok(Self {
+            pid: header.pid,
+            host: header.host,
+            file: header.file,
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+        })
+    }
+
+    /// スワップファイルを書いたプロセスがまだ動いているか（別のホストなら分からないため`false`）
+    pub fn is_running(&self) -> bool {
+        self.host == hostname() && process_exists(self.pid)
+    }
+
+    /// 既存のスワップファイルを見つけたときに表示するATTENTIONメッセージ
+    pub fn attention(&self, swap: &Path, file: &Path) -> Vec<String> {
+        let mut lines = vec![
+            "ATTENTION".to_string(),
+            format!("Found a swap file by the name \"{}\"", swap.display()),
+            format!(
+                "          owned by: pid {} on {}{}",
+                self.pid,
+                self.host,
+                if self.is_running() {
+                    " (STILL RUNNING)"
+                } else {
+                    ""
+                }
+            ),
+        ];
+        if let Some(modified) = self.modified {
+            lines.push(format!("             dated: {}", format_time(modified)));
+        }
+        lines.push(format!("         file name: {}", self.file.display()));
+        lines.push(format!("While opening file \"{}\"", file.display()));
+        let file_modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+        if let Some(file_modified) = file_modified {
+            lines.push(format!(
+                "             dated: {}",
+                format_time(file_modified)
+            ));
+            if self.modified.is_some_and(|swap| file_modified > swap) {
+                lines.push("      NEWER than swap file!".to_string());
+            }
+        }
+        lines.push(String::new());
+        lines.push(
+            "(1) Another program may be editing the same file. If this is the case,".to_string(),
+        );
+        lines.push(
+            "    be careful not to end up with two different instances of the same".to_string(),
+        );
+        lines.push("    file when making changes. Quit, or continue with caution.".to_string());
+        lines.push("(2) An edit session for this file crashed.".to_string());
+        lines.push(format!(
+            "    If this is the case, use \":recover\" or \"rvim -r {}\"",
+            file.display()
+        ));
+        lines.push("    to recover the changes.".to_string());
+        lines
+    }
+}
+
+/// スワップファイルに書き出した内容を復元する（欠けている最後の記録は無視する）
+pub fn recover(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    Header::parse(path, &content)?;
+    let body = content.find("\n\n").map_or("", |end| &content[end + 2..]);
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut input = body.split('\n');
+    while let Some(line) = input.next() {
+        let Some(numbers) = line.strip_prefix("@ ") else {
+            break;
+        };
+        let numbers: Vec<usize> = numbers.split(' ').filter_map(|n| n.parse().ok()).collect();
+        let [start, removed, count] = numbers[..] else {
+            break;
+        };
+        let inserted: Vec<String> = input.by_ref().take(count).map(str::to_string).collect();
+        if inserted.len() < count || input.next() != Some(".") || start + removed > lines.len() {
+            break;
+        }
+        lines.splice(start..start + removed, inserted);
+    }
+    Ok(lines)
+}
+
+/// `file`のスワップファイルのうち存在するもの
+pub fn find(file: &Path, directory: &str) -> Vec<PathBuf> {
+    candidates(file, directory)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// `dir`にあるスワップファイルの一覧（`rvim -r`で表示する）
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(is_swap_extension)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// スワップファイルの名前の候補（`directory`の各ディレクトリに`.swp`、`.swo`…`.swa`）
+///
+/// `.`は編集するファイルと同じディレクトリで`.name.swp`、それ以外のディレクトリでは
+/// 同じ名前のファイルと区別するため、絶対パスの`/`を`%`にした名前にする。
+fn candidates(file: &Path, directory: &str) -> Vec<PathBuf> {
+    let name = file
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    let absolute = std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf());
+
+    let mut candidates = Vec::new();
+    for dir in directory.split(',').filter(|dir| !dir.is_empty()) {
+        let (dir, base) = match dir {
+            "." => match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => {
+                    (parent.to_path_buf(), format!(".{}", name))
+                }
+                _ => (PathBuf::from("."), format!(".{}", name)),
+            },
+            dir => (
+                PathBuf::from(dir),
+                absolute
+                    .to_string_lossy()
+                    .replace(std::path::MAIN_SEPARATOR, "%"),
+            ),
+        };
+        if !dir.is_dir() {
+            continue;
+        }
+        for suffix in ('a'..='p').rev() {
+            candidates.push(dir.join(format!("{}.sw{}", base, suffix)));
+        }
+    }
+    candidates
+}
+
+fn is_swap_extension(ext: &str) -> bool {
+    ext.len() == 3 && ext.starts_with("sw") && ext[2..].chars().all(|c| ('a'..='p').contains(&c))
+}
+
+/// 変更の記録1つ（`start`行から`removed`行を`inserted`で置き換える）
+fn record(start: usize, removed: usize, inserted: &[String]) -> String {
+    let mut text = format!("@ {} {} {}\n", start, removed, inserted.len());
+    for line in inserted {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text.push_str(".\n");
+    text
+}
+
+struct Header {
+    pid: u32,
+    host: String,
+    file: PathBuf,
+}
+
+impl Header {
+    fn current(file: &Path) -> Self {
+        Self {
+            pid: std::process::id(),
+            host: hostname(),
+            file: std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf()),
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Self> {
+        let invalid = || EditorError::parse(format!("\"{}\" is not a swap file", path.display()));
+        let mut lines = content.lines();
+        if lines.next() != Some(MAGIC) {
+            return Err(invalid());
+        }
+        let mut header = Self {
+            pid: 0,
+            host: String::new(),
+            file: PathBuf::new(),
+        };
+        for line in lines.take_while(|line| !line.is_empty()) {
+            match line.split_once(": ") {
+                Some(("pid", pid)) => header.pid = pid.parse().map_err(|_| invalid())?,
+                Some(("host", host)) => header.host = host.to_string(),
+                Some(("file", file)) => header.file = PathBuf::from(file),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(header)
+    }
+}
+
+impl std::fmt::Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "pid: {}", self.pid)?;
+        writeln!(f, "host: {}", self.host)?;
+        writeln!(f, "file: {}", self.file.display())?;
+        writeln!(f)
+    }
+}
+
+fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        rustix::system::uname()
+            .nodename()
+            .to_string_lossy()
+            .into_owned()
+    }
+    #[cfg(not(unix))]
+    {
+        std::env::var("COMPUTERNAME").unwrap_or_else(|_| "localhost".to_string())
+    }
+}
+
+fn process_exists(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Some(pid) = i32::try_from(pid)
+            .ok()
+            .and_then(rustix::process::Pid::from_raw)
+        else {
+            return false;
+        };
+        // 権限がなくてシグナルを送れない場合もプロセスは存在する
+        match rustix::process::test_kill_process(pid) {
+            Ok(()) => true,
+            Err(e) => e == rustix::io::Errno::PERM,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        pid == std::process::id()
+    }
+}
+
+/// 日時を`YYYY-MM-DD HH:MM:SS UTC`の形式にする
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+
+    // 1970-01-01からの日数をグレゴリオ暦の日付にする
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sync_and_recover() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("test.txt");
+        let mut swap = SwapFile::create(&file, ".", &lines(&["a", "b", "c"]))?;
+        assert_eq!(swap.path(), temp_dir.path().join(".test.txt.swp"));
+
+        assert!(swap.sync(&lines(&["a", "x", "y", "c"]))?);
+        assert!(!swap.sync(&lines(&["a", "x", "y", "c"]))?);
+        assert!(swap.sync(&lines(&["x", "y"]))?);
+        assert_eq!(recover(swap.path())?, lines(&["x", "y"]));
+
+        // 書きかけの記録は無視する
+        let path = swap.path().to_path_buf();
+        let mut handle = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        handle.write_all(b"@ 0 1 2\nz\n").unwrap();
+        assert_eq!(recover(&path)?, lines(&["x", "y"]));
+
+        swap.remove()?;
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("test.txt");
+        let mut swap = SwapFile::create(&file, ".", &lines(&[""]))?;
+        for count in 1..=COMPACT_RECORDS + 5 {
+            swap.sync(&vec!["line".to_string(); count])?;
+        }
+        assert!(swap.records < COMPACT_RECORDS);
+        assert_eq!(
+            recover(swap.path())?,
+            vec!["line".to_string(); COMPACT_RECORDS + 5]
+        );
+        assert_eq!(SwapInfo::read(swap.path())?.pid, std::process::id());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_swap_file_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // 新しいファイルは自分だけが読み書きできる
+        let file = temp_dir.path().join("new.txt");
+        let swap = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        assert_eq!(mode(swap.path()), 0o600);
+
+        // 既存のファイルのパーミッションを写し、自分は常に読み書きできる
+        let file = temp_dir.path().join("secret.txt");
+        fs::write(&file, "a").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+        let mut swap = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        assert_eq!(mode(swap.path()), 0o640);
+        // 残っていた一時ファイルのパーミッションは引き継がない
+        let stale = swap.path().with_file_name(".secret.txt.swp.new");
+        fs::write(&stale, "stale").unwrap();
+        fs::set_permissions(&stale, fs::Permissions::from_mode(0o666)).unwrap();
+        swap.compact(&lines(&["b"]))?;
+        assert_eq!(mode(swap.path()), 0o640);
+        assert!(!stale.exists());
+
+        let file = temp_dir.path().join("readonly.txt");
+        fs::write(&file, "a").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o444)).unwrap();
+        let swap = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        assert_eq!(mode(swap.path()), 0o644);
+        Ok(())
+    }
+
+    #[test]
+    fn test_candidates() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("test.txt");
+        let first = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        let second = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        assert_eq!(second.path(), temp_dir.path().join(".test.txt.swo"));
+        assert_eq!(
+            find(&file, "."),
+            [first.path().to_path_buf(), second.path().to_path_buf()]
+        );
+        assert_eq!(
+            list(temp_dir.path())?,
+            [second.path().to_path_buf(), first.path().to_path_buf()]
+        );
+
+        // スワップディレクトリでは絶対パスから名前を作る
+        let swap_dir = temp_dir.path().join("swap");
+        fs::create_dir(&swap_dir).unwrap();
+        let directory = format!("/nonexistent,{}", swap_dir.display());
+        let swap = SwapFile::create(&file, &directory, &lines(&["a"]))?;
+        assert_eq!(swap.path().parent(), Some(swap_dir.as_path()));
+        assert!(swap.path().to_string_lossy().ends_with("%test.txt.swp"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_info() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("test.txt");
+        let swap = SwapFile::create(&file, ".", &lines(&["a"]))?;
+        let info = SwapInfo::read(swap.path())?;
+        assert_eq!(info.file, file);
+        assert!(info.is_running());
+
+        let attention = info.attention(swap.path(), &file);
+        assert_eq!(attention[0], "ATTENTION");
+        assert!(attention[2].ends_with("(STILL RUNNING)"));
+
+        fs::write(temp_dir.path().join("other.txt"), "text").unwrap();
+        assert!(SwapInfo::read(&temp_dir.path().join("other.txt")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_time(time), "2024-02-29 12:34:56 UTC");
+        assert_eq!(
+            format_time(SystemTime::UNIX_EPOCH),
+            "1970-01-01 00:00:00 UTC"
+        );
+    }
+}
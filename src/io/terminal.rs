@@ -1,4 +1,5 @@
 use crate::error::{EditorError, Result};
+use crate::io::watch::Watcher;
 use crossterm::{
     ExecutableCommand, cursor,
     event::{self, Event, KeyEvent},
@@ -7,7 +8,7 @@ use crossterm::{
     terminal::{self, Clear, ClearType},
 };
 use std::io::{self, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TerminalSize {
@@ -33,8 +34,22 @@ impl TerminalPosition {
     }
 }
 
+/// `Terminal::wait`で待った結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalEvent {
+    Key(KeyEvent),
+    FocusGained,
+    /// 監視しているファイルに変化があった
+    Watch,
+    Timeout,
+    /// 端末の大きさの変更など、描画し直すだけのイベント
+    Other,
+}
+
 pub struct Terminal {
-    stdout: io::Stdout,
+    stdout: Box<dyn Write>,
+    // 端末を使わない場合の画面の大きさ（`Terminal::headless`）
+    headless: Option<TerminalSize>,
 }
 
 impl Terminal {
@@ -46,11 +61,30 @@ impl Terminal {
         execute!(stdout, terminal::EnterAlternateScreen).map_err(|e| {
             EditorError::terminal(format!("Failed to enter alternate screen: {}", e))
         })?;
+        // フォーカスを得たときに外部でのファイルの変更を確かめられるよう、通知を受け取る
+        let _ = execute!(stdout, event::EnableFocusChange);
 
-        Ok(Self { stdout })
+        Ok(Self {
+            stdout: Box::new(stdout),
+            headless: None,
+        })
+    }
+
+    /// 端末を使わず、出力を捨てる`size`の大きさの画面（テストや端末のない環境で使う）
+    ///
+    /// キー入力は読めないため、`read_key`などはエラーを返す。
+    pub fn headless(size: TerminalSize) -> Self {
+        Self {
+            stdout: Box::new(io::sink()),
+            headless: Some(size),
+        }
     }
 
     pub fn cleanup(&mut self) -> Result<()> {
+        if self.headless.is_some() {
+            return Ok(());
+        }
+        let _ = execute!(self.stdout, event::DisableFocusChange);
         execute!(self.stdout, terminal::LeaveAlternateScreen).map_err(|e| {
             EditorError::terminal(format!("Failed to leave alternate screen: {}", e))
         })?;
@@ -62,6 +96,9 @@ impl Terminal {
     }
 
     pub fn size(&self) -> Result<TerminalSize> {
+        if let Some(size) = self.headless {
+            return Ok(size);
+        }
         let (width, height) = terminal::size()
             .map_err(|e| EditorError::terminal(format!("Failed to get terminal size: {}", e)))?;
 
@@ -155,6 +192,7 @@ impl Terminal {
     }
 
     pub fn read_key(&mut self) -> Result<KeyEvent> {
+        self.check_input()?;
         loop {
             if event::poll(Duration::from_millis(100))
                 .map_err(|e| EditorError::terminal(format!("Failed to poll events: {}", e)))?
@@ -170,6 +208,7 @@ impl Terminal {
     }
 
     pub fn read_key_timeout(&mut self, timeout: Duration) -> Result<Option<KeyEvent>> {
+        self.check_input()?;
         if event::poll(timeout)
             .map_err(|e| EditorError::terminal(format!("Failed to poll events: {}", e)))?
         {
@@ -185,6 +224,86 @@ impl Terminal {
     }
 }
 
+impl Terminal {
+    /// 端末を使わない画面ではキー入力を待たない
+    fn check_input(&self) -> Result<()> {
+        match self.headless {
+            Some(_) => Err(EditorError::terminal("No terminal to read input from")),
+            None => Ok(()),
+        }
+    }
+
+    /// キー入力・フォーカスの変化と、`watcher`が監視しているファイルの変化を`timeout`まで待つ
+    pub fn wait(&mut self, timeout: Duration, watcher: &Watcher) -> Result<TerminalEvent> {
+        self.check_input()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if Self::poll(Duration::ZERO)? {
+                return self.read_event();
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(TerminalEvent::Timeout);
+            }
+            // 端末の大きさの変更はシグナルで届くため、短い間隔で区切って待つ
+            let slice = remaining.min(Duration::from_millis(100));
+
+            #[cfg(unix)]
+            if let Some(fd) = watcher.fd() {
+                if Self::poll_with(fd, slice)? {
+                    return Ok(TerminalEvent::Watch);
+                }
+                continue;
+            }
+            #[cfg(not(unix))]
+            let _ = watcher;
+            if Self::poll(slice)? {
+                return self.read_event();
+            }
+        }
+    }
+
+    /// 端末の入力と`fd`を同時に待ち、`fd`が読めるようになったか返す
+    #[cfg(unix)]
+    fn poll_with(fd: std::os::fd::BorrowedFd<'_>, timeout: Duration) -> Result<bool> {
+        use rustix::event::{PollFd, PollFlags, Timespec, poll};
+        use std::io::IsTerminal;
+
+        let stdin = io::stdin();
+        let mut fds = vec![PollFd::new(&fd, PollFlags::IN)];
+        if stdin.is_terminal() {
+            fds.push(PollFd::new(&stdin, PollFlags::IN));
+        }
+        let timeout = Timespec::try_from(timeout)
+            .map_err(|e| EditorError::terminal(format!("Failed to poll events: {}", e)))?;
+        match poll(&mut fds, Some(&timeout)) {
+            Ok(_) | Err(rustix::io::Errno::INTR) => {}
+            Err(e) => {
+                return Err(EditorError::terminal(format!(
+                    "Failed to poll events: {}",
+                    e
+                )));
+            }
+        }
+        Ok(fds[0].revents().contains(PollFlags::IN))
+    }
+
+    fn poll(timeout: Duration) -> Result<bool> {
+        event::poll(timeout)
+            .map_err(|e| EditorError::terminal(format!("Failed to poll events: {}", e)))
+    }
+
+    fn read_event(&mut self) -> Result<TerminalEvent> {
+        match event::read()
+            .map_err(|e| EditorError::terminal(format!("Failed to read event: {}", e)))?
+        {
+            Event::Key(key_event) => Ok(TerminalEvent::Key(key_event)),
+            Event::FocusGained => Ok(TerminalEvent::FocusGained),
+            _ => Ok(TerminalEvent::Other),
+        }
+    }
+}
+
 impl Drop for Terminal {
     fn drop(&mut self) {
         // エラーを無視してクリーンアップを試行
@@ -219,10 +338,8 @@ mod tests {
     #[test]
     #[ignore] // CI環境では端末が利用できない場合があるため
     fn test_terminal_creation() {
-        let result = Terminal::new();
         // CI環境では失敗する可能性があるが、ローカルテストでは成功すべき
-        if result.is_ok() {
-            let mut terminal = result.unwrap();
+        if let Ok(mut terminal) = Terminal::new() {
             assert!(terminal.cleanup().is_ok());
         }
     }
@@ -251,6 +368,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_headless_terminal() -> Result<()> {
+        let mut terminal = Terminal::headless(TerminalSize::new(100, 30));
+        assert_eq!(terminal.size()?, TerminalSize::new(100, 30));
+
+        // 出力は捨て、キー入力は待たずにエラーを返す
+        terminal.clear_screen()?;
+        terminal.write_at(TerminalPosition::new(5, 3), "Hello")?;
+        terminal.flush()?;
+        assert!(terminal.read_key().is_err());
+        assert!(terminal.wait(Duration::ZERO, &Watcher::polling()).is_err());
+        terminal.cleanup()
+    }
+
     #[test]
     fn test_write_at() -> Result<()> {
         // この関数は実際のターミナル操作を含まないテスト
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 監視しているファイルの変化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    Changed(PathBuf),
+    Deleted(PathBuf),
+    /// 別の名前に変えられた（元の名前にはファイルがない）
+    Renamed(PathBuf),
+}
+
+impl WatchEvent {
+    pub fn path(&self) -> &Path {
+        match self {
+            WatchEvent::Changed(path) | WatchEvent::Deleted(path) | WatchEvent::Renamed(path) => {
+                path
+            }
+        }
+    }
+}
+
+/// 読み込んだファイルの変化を監視する（Linuxではinotify、使えなければ更新日時を確かめる）
+#[derive(Debug)]
+pub struct Watcher {
+    backend: Backend,
+    files: Vec<PathBuf>,
+    /// 次の`read_events`で届ける変化（監視し直したディレクトリのファイル）
+    pending: Vec<WatchEvent>,
+}
+
+#[derive(Debug)]
+enum Backend {
+    #[cfg(target_os = "linux")]
+    Inotify(Inotify),
+    /// ファイルごとに前回確かめたときの更新日時と大きさ（存在しなければ`None`）
+    Polling(HashMap<PathBuf, Option<(Option<SystemTime>, u64)>>),
+}
+
+/// ファイルを置き換える保存（rename）も検出できるよう、ファイルのあるディレクトリを監視する
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+struct Inotify {
+    fd: std::os::fd::OwnedFd,
+    /// ウォッチ記述子ごとのディレクトリ
+    dirs: HashMap<i32, PathBuf>,
+    /// 削除された・まだないため監視できていないディレクトリ（`set_files`のたびに監視し直す）
+    missing: Vec<PathBuf>,
+}
+
+impl Watcher {
+    /// 使えればinotify、使えなければ更新日時を確かめる方法で監視する
+    pub fn new() -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            use rustix::fs::inotify::{self, CreateFlags};
+            if let Ok(fd) = inotify::init(CreateFlags::NONBLOCK | CreateFlags::CLOEXEC) {
+                return Self {
+                    backend: Backend::Inotify(Inotify {
+                        fd,
+                        dirs: HashMap::new(),
+                        missing: Vec::new(),
+                    }),
+                    files: Vec::new(),
+                    pending: Vec::new(),
+                };
+            }
+        }
+        Self::polling()
+    }
+
+    /// 更新日時と大きさを`read_events`のたびに確かめる方法で監視する
+    pub fn polling() -> Self {
+        Self {
+            backend: Backend::Polling(HashMap::new()),
+            files: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// 変化を知るには`read_events`を定期的に呼ぶ必要があるか
+    pub fn is_polling(&self) -> bool {
+        matches!(self.backend, Backend::Polling(_))
+    }
+
+    /// 変化があると読めるようになるファイル記述子（端末の入力と一緒に待つ）
+    #[cfg(unix)]
+    pub fn fd(&self) -> Option<std::os::fd::BorrowedFd<'_>> {
+        match &self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Inotify(inotify) => {
+                use std::os::fd::AsFd;
+                Some(inotify.fd.as_fd())
+            }
+            Backend::Polling(_) => None,
+        }
+    }
+
+    /// `read_events`を待たずに届ける変化があるか
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// 監視するファイルを`files`にする（inotifyで監視できないディレクトリがあれば確かめる方法に切り替える）
+    pub fn set_files(&mut self, files: &[PathBuf]) {
+        if self.files == files && !self.has_missing() {
+            return;
+        }
+        self.files = files.to_vec();
+
+        match &mut self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Inotify(inotify) => {
+                use rustix::fs::inotify::{WatchFlags, add_watch, remove_watch};
+
+                let dirs: Vec<PathBuf> = files.iter().map(|file| parent_dir(file)).collect();
+                // 要らなくなったディレクトリの監視をやめる
+                inotify.dirs.retain(|&wd, dir| {
+                    let keep = dirs.contains(dir);
+                    if !keep {
+                        let _ = remove_watch(&inotify.fd, wd);
+                    }
+                    keep
+                });
+
+                let flags = WatchFlags::CLOSE_WRITE
+                    | WatchFlags::MOVED_TO
+                    | WatchFlags::MOVED_FROM
+                    | WatchFlags::DELETE;
+                inotify.missing.retain(|dir| dirs.contains(dir));
+                for dir in dirs {
+                    if inotify.dirs.values().any(|watched| *watched == dir) {
+                        continue;
+                    }
+                    match add_watch(&inotify.fd, &dir, flags) {
+                        Ok(wd) => {
+                            // 監視していなかった間に作られたファイルは変化として届ける
+                            if inotify.missing.contains(&dir) {
+                                inotify.missing.retain(|missing| *missing != dir);
+                                self.pending.extend(
+                                    files
+                                        .iter()
+                                        .filter(|file| parent_dir(file) == dir && file.exists())
+                                        .map(|file| WatchEvent::Changed(file.clone())),
+                                );
+                            }
+                            inotify.dirs.insert(wd, dir);
+                        }
+                        // まだないディレクトリ（新しいファイルの保存先）は作られてから監視する
+                        Err(rustix::io::Errno::NOENT) => {
+                            if !inotify.missing.contains(&dir) {
+                                inotify.missing.push(dir);
+                            }
+                        }
+                        Err(_) => {
+                            self.backend = Backend::Polling(HashMap::new());
+                            self.files.clear();
+                            self.set_files(files);
+                            return;
+                        }
+                    }
+                }
+            }
+            Backend::Polling(stamps) => {
+                stamps.retain(|path, _| files.contains(path));
+                for file in files {
+                    stamps
+                        .entry(file.clone())
+                        .or_insert_with(|| metadata_stamp(file));
+                }
+            }
+        }
+    }
+
+    /// 監視できていないディレクトリがあるか
+    fn has_missing(&self) -> bool {
+        match &self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Inotify(inotify) => !inotify.missing.is_empty(),
+            Backend::Polling(_) => false,
+        }
+    }
+
+    /// 前回から起きた変化（同じファイルの変化は最後の1つにまとめる）
+    pub fn read_events(&mut self) -> Vec<WatchEvent> {
+        let mut events: Vec<WatchEvent> = Vec::new();
+        let mut push = |event: WatchEvent| {
+            events.retain(|other| other.path() != event.path());
+            events.push(event);
+        };
+        for event in std::mem::take(&mut self.pending) {
+            push(event);
+        }
+
+        match &mut self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Inotify(inotify) => {
+                use rustix::fs::inotify::{ReadFlags, Reader};
+                use std::mem::MaybeUninit;
+                use std::os::unix::ffi::OsStrExt;
+
+                let mut buf = [MaybeUninit::uninit(); 4096];
+                let mut reader = Reader::new(&inotify.fd, &mut buf);
+                while let Ok(event) = reader.next() {
+                    let flags = event.events();
+                    if flags.contains(ReadFlags::QUEUE_OVERFLOW) {
+                        // 取りこぼした変化があるため、すべてのファイルを確かめてもらう
+                        for file in &self.files {
+                            push(WatchEvent::Changed(file.clone()));
+                        }
+                        continue;
+                    }
+                    // ディレクトリが削除されると監視も外れるため、作り直されたら監視し直す
+                    if flags.contains(ReadFlags::IGNORED) {
+                        if let Some(dir) = inotify.dirs.remove(&event.wd())
+                            && !inotify.missing.contains(&dir)
+                        {
+                            inotify.missing.push(dir);
+                        }
+                        continue;
+                    }
+                    let (Some(dir), Some(name)) =
+                        (inotify.dirs.get(&event.wd()), event.file_name())
+                    else {
+                        continue;
+                    };
+                    let name = std::ffi::OsStr::from_bytes(name.to_bytes());
+                    for file in &self.files {
+                        if parent_dir(file) != *dir || file.file_name() != Some(name) {
+                            continue;
+                        }
+                        push(if flags.contains(ReadFlags::DELETE) {
+                            WatchEvent::Deleted(file.clone())
+                        } else if flags.contains(ReadFlags::MOVED_FROM) {
+                            WatchEvent::Renamed(file.clone())
+                        } else {
+                            WatchEvent::Changed(file.clone())
+                        });
+                    }
+                }
+            }
+            Backend::Polling(stamps) => {
+                for (file, stamp) in stamps.iter_mut() {
+                    let current = metadata_stamp(file);
+                    if current == *stamp {
+                        continue;
+                    }
+                    *stamp = current;
+                    push(match current {
+                        Some(_) => WatchEvent::Changed(file.clone()),
+                        None => WatchEvent::Deleted(file.clone()),
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ファイルのあるディレクトリ（相対パスでディレクトリがなければカレントディレクトリ）
+fn parent_dir(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn metadata_stamp(file: &Path) -> Option<(Option<SystemTime>, u64)> {
+    fs::metadata(file)
+        .ok()
+        .map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn check_events(mut watcher: Watcher) {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.txt");
+        let other = temp_dir.path().join("b.txt");
+        fs::write(&file, "one").unwrap();
+        watcher.set_files(std::slice::from_ref(&file));
+        assert_eq!(watcher.read_events(), []);
+
+        // 監視していないファイルの変化は届けない
+        fs::write(&other, "other").unwrap();
+        fs::write(&file, "two lines").unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Changed(file.clone())]);
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Deleted(file.clone())]);
+
+        // 別のファイルで置き換える保存も変化として届ける
+        fs::rename(&other, &file).unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Changed(file.clone())]);
+
+        watcher.set_files(&[]);
+        fs::write(&file, "three").unwrap();
+        assert_eq!(watcher.read_events(), []);
+    }
+
+    #[test]
+    fn test_polling() {
+        let watcher = Watcher::polling();
+        assert!(watcher.is_polling());
+        check_events(watcher);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_inotify() {
+        let watcher = Watcher::new();
+        if watcher.is_polling() {
+            return; // inotifyを使えない環境
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.txt");
+        fs::write(&file, "one").unwrap();
+
+        let mut renamed = Watcher::new();
+        renamed.set_files(std::slice::from_ref(&file));
+        fs::rename(&file, temp_dir.path().join("c.txt")).unwrap();
+        assert_eq!(renamed.read_events(), [WatchEvent::Renamed(file)]);
+
+        check_events(watcher);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_inotify_recreated_directory() {
+        let mut watcher = Watcher::new();
+        if watcher.is_polling() {
+            return; // inotifyを使えない環境
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        let file = dir.join("a.txt");
+        let later = temp_dir.path().join("later").join("b.txt");
+        fs::create_dir(&dir).unwrap();
+        fs::write(&file, "one").unwrap();
+        let files = [file.clone(), later.clone()];
+        watcher.set_files(&files);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Deleted(file.clone())]);
+
+        // 作り直されたディレクトリは同じファイルの一覧でも監視し直し、その間の変化も届ける
+        fs::create_dir(&dir).unwrap();
+        fs::write(&file, "two").unwrap();
+        watcher.set_files(&files);
+        assert!(watcher.has_pending());
+        assert_eq!(watcher.read_events(), [WatchEvent::Changed(file.clone())]);
+        fs::write(&file, "three").unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Changed(file)]);
+
+        // 後から作られたディレクトリも監視する
+        fs::create_dir(later.parent().unwrap()).unwrap();
+        watcher.set_files(&files);
+        assert!(!watcher.has_pending());
+        fs::write(&later, "new").unwrap();
+        assert_eq!(watcher.read_events(), [WatchEvent::Changed(later)]);
+    }
+}
@@ -1,57 +1,159 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
+use crossterm::event::KeyCode;
+use rvim::editor::cursor::{byte_column, char_width, virtual_column};
+use rvim::editor::{
+    ArgList, BufferList, Direction, FileState, GlobalOptions, Layout, Rect, SplitDirection,
+    TabPages, Window,
+};
+use rvim::editor::{buffer_list, text_object};
+use rvim::io::{FileChange, FileStamp, Shell, SwapFile, SwapInfo, TerminalEvent, Watcher, swap};
+use rvim::vim::ex::{Address, AddressBase, ExCommand, ExRange, WriteTarget};
+use rvim::vim::replace;
+use rvim::vim::visual::{self, BlockInsert, VisualEffect};
+use rvim::vim::{
+    CommandResult, Completion, InsertSession, Mode, Register, Registers, Selection, VisualKind,
+};
 use rvim::{
-    Buffer, EditorError, FileSystem, Key, KeyMapper, ModeManager, Position, Result, Terminal,
-    VimCommand,
+    Buffer, Curswant, EditorError, FileSystem, Key, KeyMapper, ModeManager, Position, Result,
+    Terminal, TerminalPosition, Viewport, VimCommand,
 };
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// バッファごとのスワップファイルの状態
+enum Swap {
+    /// 既存のスワップファイルは確認済みで、最初に変更を書き出すときに作る
+    Unwritten,
+    Active(SwapFile),
+    /// 読み込み専用で開いたなど、スワップファイルを使わない
+    Disabled,
+}
 
 struct Editor {
-    buffer: Buffer,
+    buffers: BufferList,
+    // 起動時に指定したファイルの一覧（`:next`などで移る）
+    arg_list: ArgList,
+    // タブページごとのウィンドウの配置（カレントウィンドウのカーソル・表示範囲は`cursor`などが最新）
+    tabs: TabPages,
+    // `hidden`などバッファによらないオプション
+    options: GlobalOptions,
+    // バッファ番号ごとのスワップファイル（既存のスワップファイルを確認したバッファだけ持つ）
+    swap_files: HashMap<usize, Swap>,
+    // 前回スワップファイルに書き出してから入力したキーの数
+    keys_since_sync: usize,
+    // 読み込んでいるバッファのファイルの監視
+    watcher: Watcher,
     cursor: Position,
+    curswant: Curswant,
+    viewport: Viewport,
     mode_manager: ModeManager,
     key_mapper: KeyMapper,
+    registers: Registers,
+    block_insert: Option<BlockInsert>,
+    insert_session: Option<InsertSession>,
+    completion: Option<Completion>,
+    // `Ctrl-O`のコマンド実行後にInsertモードへ戻るか
+    restart_insert: bool,
+    // コマンドラインに表示するメッセージ（外部コマンドのエラーなど、次のキー入力で消える）
+    message: Option<String>,
     terminal: Terminal,
-    file_path: Option<PathBuf>,
     should_quit: bool,
 }
 
 impl Editor {
-    fn new() -> Result<Self> {
-        Ok(Self {
-            buffer: Buffer::new(),
-            cursor: Position::origin(),
-            mode_manager: ModeManager::new(),
-            key_mapper: KeyMapper::new(),
-            terminal: Terminal::new()?,
-            file_path: None,
-            should_quit: false,
-        })
+    fn new(terminal: Terminal) -> Self {
+        Self::with_buffer(Buffer::new(), terminal)
+    }
+
+    /// `path`を開いて起動する（存在しなければ新しいファイルとして編集する）
+    ///
+    /// デバイスなど開けないファイルは、空のバッファで起動して理由を表示する。
+    fn with_file(path: PathBuf, terminal: Terminal) -> Self {
+        match buffer_list::load(&path) {
+            Ok(buffer) => Self::with_buffer(buffer, terminal),
+            Err(error) => {
+                let mut editor = Self::new(terminal);
+                editor.message = Some(error.to_string());
+                editor
+            }
+        }
     }
 
-    fn with_file(path: PathBuf) -> Result<Self> {
-        let content = FileSystem::read_file(&path)?;
-        let buffer = Buffer::from_content(&content).with_file_path(path.clone());
+    /// `files`を引数リストにして、先頭のファイルを開く
+    fn with_files(files: Vec<PathBuf>, terminal: Terminal) -> Self {
+        let mut editor = match files.first() {
+            Some(path) => Self::with_file(path.clone(), terminal),
+            None => Self::new(terminal),
+        };
+        editor.arg_list = ArgList::new(files);
+        editor
+    }
 
-        Ok(Self {
-            buffer,
+    fn with_buffer(buffer: Buffer, terminal: Terminal) -> Self {
+        Self {
+            buffers: BufferList::new(buffer),
+            arg_list: ArgList::default(),
+            tabs: TabPages::new(Layout::new(1)),
+            options: GlobalOptions::default(),
+            swap_files: HashMap::new(),
+            keys_since_sync: 0,
+            watcher: Watcher::new(),
             cursor: Position::origin(),
+            curswant: Curswant::default(),
+            viewport: Viewport::default(),
             mode_manager: ModeManager::new(),
             key_mapper: KeyMapper::new(),
-            terminal: Terminal::new()?,
-            file_path: Some(path),
+            registers: Registers::new(),
+            block_insert: None,
+            insert_session: None,
+            completion: None,
+            restart_insert: false,
+            message: None,
+            terminal,
             should_quit: false,
-        })
+        }
     }
 
     fn run(&mut self) -> Result<()> {
         self.terminal.clear_screen()?;
         self.terminal.hide_cursor()?;
+        self.check_swap_files()?;
 
         while !self.should_quit {
             self.render()?;
-            self.handle_input()?;
+            self.update_watcher();
+            // 監視し直したディレクトリで起きていた変化は待たずに確かめる
+            if self.watcher.has_pending() {
+                self.handle_watch_events()?;
+            }
+            // `updatetime`の間キー入力がないか、`updatecount`回入力したら変更をスワップファイルに書き出す
+            let timeout = Duration::from_millis(self.options.updatetime.max(1) as u64);
+            match self.terminal.wait(timeout, &self.watcher)? {
+                TerminalEvent::Key(key_event) => {
+                    self.handle_input(Key::from(key_event))?;
+                    self.keys_since_sync += 1;
+                    if self.keys_since_sync >= self.options.updatecount {
+                        self.sync_swap_files();
+                    }
+                }
+                TerminalEvent::Watch => self.handle_watch_events()?,
+                TerminalEvent::FocusGained => self.check_timestamps()?,
+                TerminalEvent::Timeout => {
+                    if self.keys_since_sync > 0 {
+                        self.sync_swap_files();
+                    }
+                    // inotifyを使えなければ、キー入力がないときに更新日時を確かめる
+                    if self.watcher.is_polling() {
+                        self.handle_watch_events()?;
+                    }
+                }
+                TerminalEvent::Other => {}
+            }
+            self.check_swap_files()?;
         }
 
+        self.remove_swap_files();
         self.terminal.show_cursor()?;
         self.terminal.cleanup()?;
         Ok(())
@@ -60,287 +162,2715 @@ impl Editor {
     fn render(&mut self) -> Result<()> {
         self.terminal.clear_screen()?;
 
-        // バッファ内容を描画
-        for (row, line) in self.buffer.to_string().lines().enumerate() {
-            // カーソル行の場合、カーソル位置をハイライト
-            if row == self.cursor.row {
-                self.render_line_with_cursor_highlight(row, line)?;
+        // 最下行のコマンドラインと最上行のタブラインを除いた領域にウィンドウを並べる
+        let terminal_size = self.terminal.size()?;
+        let area = self.window_area()?;
+        self.store_window();
+        if self.tabline_visible() {
+            self.render_tabline(area.width)?;
+        }
+        let mut active = area;
+        for (id, rect) in self.tabs.layout_mut().arrange(area) {
+            let Some(mut window) = self.tabs.layout().window(id).cloned() else {
+                continue;
+            };
+            let is_current = id == self.tabs.layout().current_id();
+            self.render_window(&mut window, rect, is_current)?;
+
+            // 右端以外のウィンドウは右側に区切り線を引く
+            if rect.col + rect.width < area.width {
+                for row in rect.row..rect.row + rect.height {
+                    self.terminal.write_at(
+                        TerminalPosition::new((rect.col + rect.width) as u16, row as u16),
+                        "|",
+                    )?;
+                }
+            }
+
+            if is_current {
+                self.viewport = window.viewport;
+                active = rect;
+            }
+            if let Some(stored) = self.tabs.layout_mut().window_mut(id) {
+                *stored = window;
+            }
+        }
+
+        self.render_completion_menu(active, area.height)?;
+
+        // Commandモードの場合、入力コマンドを表示
+        let command_row = terminal_size.height.saturating_sub(1);
+        if let Mode::Command { input } = self.mode_manager.current() {
+            let command_line = format!(":{}", input);
+            self.terminal
+                .write_at(TerminalPosition::new(0, command_row), &command_line)?;
+        } else if let Some(message) = &self.message {
+            // 複数行のメッセージ（`:ls`の一覧など）はコマンドラインから上に向かって表示する
+            let lines: Vec<&str> = message.lines().collect();
+            let top = (command_row + 1).saturating_sub(lines.len() as u16);
+            for (offset, line) in lines.iter().enumerate() {
+                let row = top + offset as u16;
+                self.terminal.write_at(
+                    TerminalPosition::new(0, row),
+                    &format!("{:<width$}", line, width = terminal_size.width as usize),
+                )?;
+            }
+        }
+
+        // カーソル位置（カレントウィンドウ内の相対位置）に移動
+        if let Some((screen_row, screen_col)) = self
+            .viewport
+            .to_screen(self.cursor.row, self.cursor_display_col())
+        {
+            self.terminal.move_cursor(TerminalPosition::new(
+                (active.col + screen_col) as u16,
+                (active.row + screen_row) as u16,
+            ))?;
+        }
+
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// ウィンドウを並べる領域（最下行のコマンドラインとタブラインを除いた画面全体）
+    fn window_area(&self) -> Result<Rect> {
+        let size = self.terminal.size()?;
+        let top = usize::from(self.tabline_visible());
+        Ok(Rect::new(
+            top,
+            0,
+            (size.height as usize).saturating_sub(1 + top),
+            size.width as usize,
+        ))
+    }
+
+    /// `showtabline`に従ってタブラインを表示するか
+    fn tabline_visible(&self) -> bool {
+        match self.options.showtabline {
+            0 => false,
+            1 => self.tabs.count() > 1,
+            _ => true,
+        }
+    }
+
+    /// 画面最上行にタブページのラベルを並べる（カレントタブページは反転して表示する）
+    fn render_tabline(&mut self, width: usize) -> Result<()> {
+        use crossterm::style::Color;
+        let labels: Vec<String> = self
+            .tabs
+            .iter()
+            .map(|layout| format!(" {} ", TabPages::label(layout, &self.buffers)))
+            .collect();
+
+        self.terminal.move_cursor(TerminalPosition::new(0, 0))?;
+        let mut used = 0;
+        for (index, label) in labels.iter().enumerate() {
+            let label: String = label.chars().take(width - used).collect();
+            used += label.chars().count();
+            if index == self.tabs.index() {
+                self.terminal.set_background_color(Color::White)?;
+                self.terminal.set_foreground_color(Color::Black)?;
             } else {
-                self.terminal
-                    .write_at(rvim::TerminalPosition::new(0, row as u16), line)?;
+                self.terminal.set_background_color(Color::DarkGrey)?;
             }
+            self.terminal.write(&label)?;
+            self.terminal.reset_colors()?;
         }
+        self.terminal.set_background_color(Color::DarkGrey)?;
+        self.terminal.write(&" ".repeat(width - used))?;
+        self.terminal.reset_colors()?;
+        Ok(())
+    }
 
-        // ステータスライン描画
-        let terminal_size = self.terminal.size()?;
-        let status_row = terminal_size.height.saturating_sub(1);
+    /// `scrolloff`と`sidescrolloff`をウィンドウの表示範囲に反映する
+    fn apply_scroll_options(&self, viewport: &mut Viewport) {
+        viewport.scrolloff = self.options.scrolloff;
+        viewport.sidescrolloff = self.options.sidescrolloff;
+    }
+
+    /// ウィンドウの表示範囲のテキストと、最下行のステータスラインを描画する
+    fn render_window(&mut self, window: &mut Window, rect: Rect, is_current: bool) -> Result<()> {
+        let Some(entry) = self.buffers.get(window.buffer) else {
+            return Ok(());
+        };
+        let buffer = &entry.buffer;
+        let tabstop = buffer.options().tabstop;
+        let text_height = rect.height.saturating_sub(1);
+
+        // 他のウィンドウでの編集で行が減っている場合があるため、カーソルを収めてからスクロールする
+        let row = window.cursor.row.min(buffer.line_count() - 1);
+        let line = buffer.line(row)?;
+        window.cursor = Position::new(row, window.cursor.col.min(line.len()));
+        let cursor_col = virtual_column(line, window.cursor.col, tabstop);
+        window.viewport.resize(text_height, rect.width);
+        self.apply_scroll_options(&mut window.viewport);
+        window
+            .viewport
+            .scroll_to_cursor(row, cursor_col, buffer.line_count());
+        let viewport = window.viewport;
+
+        // 画面上の各行（バッファの範囲外は`None`）
+        let selection = if is_current {
+            self.visual_selection()
+        } else {
+            None
+        };
+        let rows: Vec<_> = (0..text_height)
+            .map(|screen_row| {
+                let row = viewport.top + screen_row;
+                let line = buffer.line(row).ok()?;
+                // タブは`tabstop`に合わせて空白に展開して描画する
+                let visible = viewport.visible_text(line, tabstop);
+
+                // 選択範囲（画面上の表示列）
+                let left = viewport.left;
+                let selected = selection.and_then(|selection| {
+                    let (from, to) = selection.span(buffer, row)?;
+                    let (from, mut to) = (
+                        virtual_column(line, from, tabstop),
+                        virtual_column(line, to, tabstop),
+                    );
+                    // 空行や改行も選択されていることが分かるよう1文字分表示する
+                    if from == to && selection.kind != VisualKind::Block {
+                        to += 1;
+                    }
+                    Some((from.saturating_sub(left), to.saturating_sub(left)))
+                });
+
+                // カレントウィンドウのカーソル行の場合、カーソル位置をハイライト
+                let cursor = (is_current && row == window.cursor.row)
+                    .then(|| cursor_col.saturating_sub(left));
+                Some((visible, cursor, selected))
+            })
+            .collect();
+        let status = self.status_line(window, buffer, is_current);
+
+        for (offset, row) in rows.into_iter().enumerate() {
+            let screen_row = (rect.row + offset) as u16;
+            match row {
+                Some((visible, cursor, selected)) => {
+                    self.render_line(screen_row, rect.col as u16, &visible, cursor, selected)?
+                }
+                None => self
+                    .terminal
+                    .write_at(TerminalPosition::new(rect.col as u16, screen_row), "~")?,
+            }
+        }
+
+        // ステータスライン（カレントウィンドウは反転して表示する）
+        use crossterm::style::Color;
+        let status: String = status.chars().take(rect.width).collect();
+        let status = format!("{:<width$}", status, width = rect.width);
+        self.terminal.move_cursor(TerminalPosition::new(
+            rect.col as u16,
+            (rect.row + text_height) as u16,
+        ))?;
+        if is_current {
+            self.terminal.set_background_color(Color::White)?;
+            self.terminal.set_foreground_color(Color::Black)?;
+        } else {
+            self.terminal.set_background_color(Color::DarkGrey)?;
+        }
+        self.terminal.write(&status)?;
+        self.terminal.reset_colors()?;
+        Ok(())
+    }
 
-        let mode_str = format!("-- {} --", self.mode_manager.current());
-        let position_str = format!("{}:{}", self.cursor.row + 1, self.cursor.col + 1);
-        let file_str = self
-            .file_path
-            .as_ref()
+    /// ステータスラインの内容（カレントウィンドウにはモードも表示する）
+    fn status_line(&self, window: &Window, buffer: &Buffer, is_current: bool) -> String {
+        let file_str = buffer
+            .file_path()
             .and_then(|p| p.file_name())
             .and_then(|n| n.to_str())
             .unwrap_or("[No Name]");
+        // 新しいファイル・読み込み専用などの印と変更ありの印
+        let mut modified_str = String::new();
+        if let Some(label) = buffer.file_state().label() {
+            modified_str.push(' ');
+            modified_str.push_str(label);
+        }
+        if buffer.options().readonly && buffer.file_state() == FileState::Loaded {
+            modified_str.push_str(" [RO]");
+        }
+        if buffer.is_modified() {
+            modified_str.push_str(" [+]");
+        }
+        let position_str = format!("{}:{}", window.cursor.row + 1, window.cursor.col + 1);
+        if !is_current {
+            return format!("{}{} | {}", file_str, modified_str, position_str);
+        }
 
-        let status = format!("{} | {} | {}", mode_str, file_str, position_str);
-        self.terminal
-            .write_at(rvim::TerminalPosition::new(0, status_row), &status)?;
+        let mode_str = if self.restart_insert {
+            "-- (insert) --".to_string()
+        } else {
+            format!("-- {} --", self.mode_manager.current())
+        };
+        format!(
+            "{} | {}{} | {}",
+            mode_str, file_str, modified_str, position_str
+        )
+    }
 
-        // Commandモードの場合、入力コマンドを表示
-        if let rvim::vim::Mode::Command { input } = self.mode_manager.current() {
-            let command_line = format!(":{}", input);
-            self.terminal.write_at(
-                rvim::TerminalPosition::new(0, status_row.saturating_sub(1)),
-                &command_line,
-            )?;
-        }
+    /// 補完候補のポップアップメニューをカーソル行の下（入りきらなければ上）に描画する
+    fn render_completion_menu(&mut self, window: Rect, screen_height: usize) -> Result<()> {
+        use crossterm::style::Color;
+        const MAX_ITEMS: usize = 10;
 
-        // カーソル位置に移動
-        self.terminal.move_cursor(rvim::TerminalPosition::new(
-            self.cursor.col as u16,
-            self.cursor.row as u16,
-        ))?;
+        let Some(completion) = &self.completion else {
+            return Ok(());
+        };
+        let start = completion.start();
+        let line = self.buffers.current().line(start.row)?;
+        let Some((screen_row, screen_col)) = self.viewport.to_screen(
+            start.row,
+            virtual_column(line, start.col, self.buffers.current().options().tabstop),
+        ) else {
+            return Ok(());
+        };
+        let (screen_row, screen_col) = (window.row + screen_row, window.col + screen_col);
 
-        self.terminal.flush()?;
+        // 選択中の候補が見えるよう表示範囲をずらす
+        let candidates = completion.candidates();
+        let height = candidates.len().min(MAX_ITEMS);
+        let first = completion
+            .selected()
+            .map_or(0, |index| (index + 1).saturating_sub(height));
+        let width = candidates
+            .iter()
+            .map(|candidate| candidate.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let below = screen_row + 1;
+        let top = if below + height <= screen_height {
+            below
+        } else {
+            screen_row.saturating_sub(height)
+        };
+
+        let items: Vec<(String, bool)> = candidates
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(index, candidate)| {
+                (
+                    format!(" {:<width$} ", candidate, width = width),
+                    completion.selected() == Some(index),
+                )
+            })
+            .collect();
+        for (offset, (item, selected)) in items.into_iter().enumerate() {
+            let row = (top + offset) as u16;
+            self.terminal
+                .move_cursor(TerminalPosition::new(screen_col as u16, row))?;
+            if selected {
+                self.terminal.set_background_color(Color::White)?;
+                self.terminal.set_foreground_color(Color::Black)?;
+            } else {
+                self.terminal.set_background_color(Color::DarkGrey)?;
+            }
+            self.terminal.write(&item)?;
+            self.terminal.reset_colors()?;
+        }
         Ok(())
     }
 
-    fn render_line_with_cursor_highlight(&mut self, row: usize, line: &str) -> Result<()> {
+    fn render_line(
+        &mut self,
+        screen_row: u16,
+        screen_col: u16,
+        line: &str,
+        cursor_col: Option<usize>,
+        selected: Option<(usize, usize)>,
+    ) -> Result<()> {
         use crossterm::style::Color;
-        
-        self.terminal.move_cursor(rvim::TerminalPosition::new(0, row as u16))?;
-        
-        let chars: Vec<char> = line.chars().collect();
-        
-        for (col, &ch) in chars.iter().enumerate() {
-            if col == self.cursor.col {
+
+        self.terminal
+            .move_cursor(TerminalPosition::new(screen_col, screen_row))?;
+
+        let is_selected = |col: usize| selected.is_some_and(|(from, to)| from <= col && col < to);
+
+        // 全角文字は2列を占めるため、画面上の列を数えながら描画する
+        let mut col = 0;
+        for ch in line.chars() {
+            let width = char_width(ch);
+            let at = col;
+            col += width;
+            if width == 0 {
+                self.terminal.write(&ch.to_string())?;
+            } else if cursor_col == Some(at) {
                 // カーソル位置の文字をハイライト
                 self.terminal.set_background_color(Color::White)?;
                 self.terminal.set_foreground_color(Color::Black)?;
                 self.terminal.write(&ch.to_string())?;
                 self.terminal.reset_colors()?;
+            } else if is_selected(at) {
+                self.terminal.set_background_color(Color::DarkGrey)?;
+                self.terminal.write(&ch.to_string())?;
+                self.terminal.reset_colors()?;
             } else {
                 self.terminal.write(&ch.to_string())?;
             }
         }
-        
+
         // カーソルが行末を超えている場合の処理
-        if self.cursor.col >= chars.len() {
-            // 行末にカーソルを表示
+        let line_width = col;
+        if let Some(cursor_col) = cursor_col.filter(|&col| col >= line_width) {
+            // 行末にカーソルを表示（手前の選択範囲も埋める）
+            for col in line_width..cursor_col {
+                if is_selected(col) {
+                    self.terminal.set_background_color(Color::DarkGrey)?;
+                }
+                self.terminal.write(" ")?;
+                self.terminal.reset_colors()?;
+            }
             self.terminal.set_background_color(Color::White)?;
             self.terminal.write(" ")?;
             self.terminal.reset_colors()?;
+        } else if is_selected(line_width) {
+            self.terminal.set_background_color(Color::DarkGrey)?;
+            self.terminal.write(" ")?;
+            self.terminal.reset_colors()?;
         }
-        
+
         Ok(())
     }
 
-    fn handle_input(&mut self) -> Result<()> {
-        let key_event = self.terminal.read_key()?;
-        let key = Key::from(key_event);
+    fn cursor_display_col(&self) -> usize {
+        self.buffers
+            .current()
+            .line(self.cursor.row)
+            .map(|line| {
+                virtual_column(
+                    line,
+                    self.cursor.col,
+                    self.buffers.current().options().tabstop,
+                )
+            })
+            .unwrap_or(0)
+    }
+
+    fn handle_input(&mut self, key: Key) -> Result<()> {
+        self.message = None;
 
         // キーをVIMコマンドにマップ
+        self.key_mapper
+            .set_tildeop(self.buffers.current().options().tildeop);
         let command = self.key_mapper.map_key(&key, self.mode_manager.current());
 
+        // gvや`'<,'>`で参照できるよう、ビジュアル選択をマークに記録する
+        if let Some(selection) = self.visual_selection() {
+            let (start, end) = selection.mark_positions(self.buffers.current());
+            self.buffers
+                .current_mut()
+                .marks_mut()
+                .set_visual(selection.kind.to_char(), start, end);
+        }
+
+        let was_typing = self.is_typing();
+
+        // 補完以外のキーを入力したら、選択中の候補を確定してメニューを閉じる
+        if command != VimCommand::Noop
+            && !matches!(
+                command,
+                VimCommand::Complete { .. } | VimCommand::CompleteCancel
+            )
+        {
+            self.completion = None;
+        }
+
         // コマンドを実行
-        match command.execute(&mut self.buffer, &mut self.cursor)? {
+        let result = match command.execute(self.buffers.current_mut(), &mut self.cursor) {
+            Ok(result) => result,
+            // Exコマンドの失敗（外部コマンドの終了ステータスなど）はメッセージとして表示する
+            Err(error) if matches!(command, VimCommand::ExecuteCommand(_)) => {
+                self.message = Some(error.to_string());
+                rvim::vim::CommandResult::None
+            }
+            Err(error) => return Err(error),
+        };
+        match result {
             rvim::vim::CommandResult::None => {}
-            rvim::vim::CommandResult::DeletedChar(_) => {}
-            rvim::vim::CommandResult::DeletedLine(_) => {}
+            rvim::vim::CommandResult::DeletedChar(ch) => {
+                self.registers.set(Register::charwise(ch));
+            }
+            rvim::vim::CommandResult::DeletedLine(line) => {
+                self.registers.set(Register::linewise(line));
+            }
+            rvim::vim::CommandResult::Yanked(register)
+            | rvim::vim::CommandResult::Deleted(register) => {
+                self.registers.set(register);
+            }
+            rvim::vim::CommandResult::Changed(register) => {
+                self.registers.set(register);
+                self.mode_manager.enter_insert();
+            }
             rvim::vim::CommandResult::ModeTransition => {
                 self.handle_mode_transition(&command)?;
             }
+            rvim::vim::CommandResult::ScrollRequested => {
+                self.handle_scroll(&command)?;
+            }
+            rvim::vim::CommandResult::VisualRequested => {
+                self.handle_visual(&command)?;
+            }
+            rvim::vim::CommandResult::ReplaceRequested => {
+                self.handle_replace(&command)?;
+            }
+            rvim::vim::CommandResult::CompletionRequested => {
+                self.handle_completion(&command)?;
+            }
+            rvim::vim::CommandResult::CommandLineRequested(input) => {
+                self.open_command_line(input)?;
+            }
             rvim::vim::CommandResult::SaveRequested => {
-                self.save_file()?;
+                // 読み込み専用のバッファなど保存できない理由はメッセージとして表示する
+                if let Err(error) = self.save_file(false) {
+                    self.message = Some(error.to_string());
+                }
                 // Commandモードから実行された場合はNormalモードに戻る
                 if self.mode_manager.current().is_command() {
                     self.mode_manager.enter_normal();
                 }
             }
+            rvim::vim::CommandResult::EditorCommand(ex) => {
+                // 失敗してもエディタは終了せず、理由をメッセージとして表示する
+                if let Err(error) = self.execute_editor_command(&ex) {
+                    self.message = Some(error.to_string());
+                }
+            }
             rvim::vim::CommandResult::QuitRequested => {
-                if self.buffer.is_modified() {
-                    // 変更がある場合は警告を表示（簡略化）
-                    // Commandモードから実行された場合はNormalモードに戻る
-                    if self.mode_manager.current().is_command() {
-                        self.mode_manager.enter_normal();
-                    }
-                    return Ok(());
+                if let Err(error) = self.quit_window(false) {
+                    self.message = Some(error.to_string());
                 }
-                self.should_quit = true;
             }
             rvim::vim::CommandResult::SaveAndQuitRequested => {
-                self.save_file()?;
-                self.should_quit = true;
+                // `ZZ`は`:x`と同じく変更があるときだけ保存する
+                let result = ExCommand::parse("x").and_then(|ex| self.write_command(&ex));
+                if let Err(error) = result {
+                    self.message = Some(error.to_string());
+                }
             }
             rvim::vim::CommandResult::ForceQuitRequested => {
-                self.should_quit = true;
+                if let Err(error) = self.quit_window(true) {
+                    self.message = Some(error.to_string());
+                }
+            }
+        }
+
+        // Exコマンドの実行後はNormalモードに戻る
+        if matches!(command, VimCommand::ExecuteCommand(_))
+            && self.mode_manager.current().is_command()
+        {
+            self.mode_manager.enter_normal();
+        }
+
+        // `Ctrl-O`で実行したNormalモードのコマンドが終わったらInsertモードに戻る
+        if self.restart_insert && command != VimCommand::Noop {
+            match self.mode_manager.current() {
+                Mode::Normal if command != VimCommand::InsertNormalCommand => {
+                    self.mode_manager.enter_insert();
+                    self.restart_insert = false;
+                }
+                Mode::Insert => self.restart_insert = false,
+                _ => {}
             }
         }
+        self.update_undo_group(was_typing, &command);
 
         // カーソル位置の境界チェック
         self.adjust_cursor_position()?;
+        self.update_curswant(&command);
 
         Ok(())
     }
 
+    /// テキストを打ち込むモード（InsertモードとReplaceモード）か
+    fn is_typing(&self) -> bool {
+        let mode = self.mode_manager.current();
+        mode.is_insert() || mode.is_replace()
+    }
+
+    /// InsertモードやReplaceモードで入力したテキストを1回のundoで戻せるようにまとめる
+    fn update_undo_group(&mut self, was_typing: bool, command: &VimCommand) {
+        match (was_typing, self.is_typing()) {
+            (false, true) => self.buffers.current_mut().begin_undo_group(),
+            (true, false) => self.buffers.current_mut().end_undo_group(),
+            // Vimと同様、入力中のカーソル移動でundoを区切る
+            (true, true)
+                if matches!(
+                    command,
+                    VimCommand::MoveLeft
+                        | VimCommand::MoveDown
+                        | VimCommand::MoveUp
+                        | VimCommand::MoveRight
+                        | VimCommand::MoveLineStart
+                        | VimCommand::MoveLineEnd
+                ) =>
+            {
+                self.buffers.current_mut().end_undo_group();
+                self.buffers.current_mut().begin_undo_group();
+            }
+            _ => {}
+        }
+    }
+
+    fn update_curswant(&mut self, command: &VimCommand) {
+        // 複数キーコマンドの入力途中などでは希望列を変更しない
+        if *command == VimCommand::Noop {
+            return;
+        }
+        let buffer = self.buffers.current();
+        let tabstop = buffer.options().tabstop;
+        let Ok(line) = buffer.line(self.cursor.row) else {
+            return;
+        };
+
+        if command.is_vertical_motion() {
+            // 短い行を通過しても元の列に戻れるよう、希望列から列を復元する
+            self.curswant.apply(line, &mut self.cursor, tabstop);
+        } else if *command == VimCommand::MoveLineEnd {
+            self.curswant = Curswant::EndOfLine;
+        } else {
+            self.curswant = Curswant::from_position(line, self.cursor, tabstop);
+        }
+    }
+
     fn handle_mode_transition(&mut self, command: &VimCommand) -> Result<()> {
         match command {
-            VimCommand::EnterInsert => {
-                self.mode_manager.enter_insert();
-            }
-            VimCommand::EnterInsertAfter => {
+            VimCommand::EnterInsert { entry, count } => {
+                // カーソルは挿入位置に移動済み
+                self.insert_session = Some(InsertSession::new(*entry, self.cursor, *count));
                 self.mode_manager.enter_insert();
-                // カーソルを一つ右に移動
-                if let Ok(line_length) = self.buffer.line_length(self.cursor.row) {
-                    self.cursor.move_right(line_length).ok();
-                }
             }
-            VimCommand::EnterInsertNewLine => {
-                self.buffer.insert_line(self.cursor.row + 1)?;
-                self.cursor.row += 1;
-                self.cursor.col = 0;
-                self.mode_manager.enter_insert();
+            VimCommand::InsertNormalCommand => {
+                // 入力の繰り返しは行わず、Normalモードのコマンドを1つ受け付ける
+                self.insert_session = None;
+                self.restart_insert = true;
+                self.mode_manager.enter_normal();
             }
-            VimCommand::EnterVisual => {
-                self.mode_manager.enter_visual(self.cursor);
+            VimCommand::EnterReplace => {
+                self.mode_manager.enter_replace();
             }
+            VimCommand::EnterVisual => self.enter_visual(VisualKind::Char)?,
+            VimCommand::EnterVisualLine => self.enter_visual(VisualKind::Line)?,
+            VimCommand::EnterVisualBlock => self.enter_visual(VisualKind::Block)?,
             VimCommand::EnterCommand => {
+                let from_visual = self.mode_manager.current().is_visual();
                 self.mode_manager.enter_command();
+                // ビジュアルモードからは選択範囲を対象にする
+                if from_visual {
+                    self.mode_manager
+                        .update_command_input("'<,'>".to_string())?;
+                }
+            }
+            VimCommand::ReselectVisual => {
+                if self.mode_manager.current().is_visual() {
+                    return Ok(());
+                }
+                let Some((mode, start, end)) = self.buffers.current().marks().visual() else {
+                    return Ok(());
+                };
+                let Some(kind) = VisualKind::from_char(mode) else {
+                    return Ok(());
+                };
+                // 編集で行が短くなっている場合は行末に収める
+                let clamp = |pos: Position| {
+                    let len = self.buffers.current().line_length(pos.row).unwrap_or(0);
+                    Position::new(pos.row, pos.col.min(len))
+                };
+                self.mode_manager.enter_visual_kind(clamp(start), kind);
+                self.cursor = clamp(end);
             }
             VimCommand::ExitToNormal => {
+                if self.mode_manager.current().is_insert() {
+                    // 矩形挿入で入力したテキストを残りの行にも反映する
+                    if let Some(block) = self.block_insert.take() {
+                        visual::finish_block_insert(
+                            &block,
+                            self.buffers.current_mut(),
+                            self.cursor,
+                        )?;
+                    }
+                    // カウント付きの挿入では入力したテキストを繰り返す
+                    if let Some(session) = self.insert_session.take() {
+                        session.finish(self.buffers.current_mut(), &mut self.cursor)?;
+                    }
+                    // `gi`で再開できるよう終了位置を記録する
+                    self.buffers.current_mut().marks_mut().set('^', self.cursor);
+                }
                 self.mode_manager.enter_normal();
             }
+            VimCommand::SelectTextObject { object, count } => {
+                let Mode::Visual { start, .. } = *self.mode_manager.current() else {
+                    return Ok(());
+                };
+                if let Some((start, end)) = text_object::select_visual(
+                    self.buffers.current(),
+                    start,
+                    self.cursor,
+                    *object,
+                    *count,
+                ) {
+                    self.mode_manager.set_visual_start(start)?;
+                    self.cursor = end;
+                }
+            }
             VimCommand::CommandInput(ch) => {
-                if let rvim::vim::Mode::Command { input } = self.mode_manager.current() {
+                if let Mode::Command { input } = self.mode_manager.current() {
                     let mut new_input = input.clone();
                     new_input.push(*ch);
                     self.mode_manager.update_command_input(new_input)?;
                 }
             }
             VimCommand::CommandBackspace => {
-                if let rvim::vim::Mode::Command { input } = self.mode_manager.current() {
+                if let Mode::Command { input } = self.mode_manager.current() {
                     let mut new_input = input.clone();
                     new_input.pop();
                     self.mode_manager.update_command_input(new_input)?;
                 }
             }
-            VimCommand::ExecuteCommand(_) => {
-                // ExecuteCommand処理後はNormalモードに戻る
-                self.mode_manager.enter_normal();
-            }
             _ => {}
         }
         Ok(())
     }
 
-    fn save_file(&mut self) -> Result<()> {
-        if let Some(path) = &self.file_path {
-            FileSystem::write_file(path, &self.buffer.to_string())?;
-            self.buffer.mark_saved();
-        } else {
-            // ファイル名を指定していない場合の処理（簡略化）
-            return Err(EditorError::config("No file name specified".to_string()));
+    fn handle_completion(&mut self, command: &VimCommand) -> Result<()> {
+        match command {
+            VimCommand::Complete { kind, forward } => {
+                if let Some(completion) = &mut self.completion {
+                    completion.select(*forward);
+                } else {
+                    // 現在のバッファの次に、一覧にある他のバッファから探す
+                    let others = self.buffers.others();
+                    self.completion = Completion::new(
+                        *kind,
+                        *forward,
+                        self.buffers.current(),
+                        &others,
+                        self.cursor,
+                    );
+                }
+                if let Some(completion) = &self.completion {
+                    completion.apply(self.buffers.current_mut(), &mut self.cursor)?;
+                }
+            }
+            VimCommand::CompleteCancel => {
+                if let Some(mut completion) = self.completion.take() {
+                    completion.cancel();
+                    completion.apply(self.buffers.current_mut(), &mut self.cursor)?;
+                }
+            }
+            _ => {}
         }
         Ok(())
     }
 
-    fn adjust_cursor_position(&mut self) -> Result<()> {
-        // 行数の境界チェック
-        let total_lines = self.buffer.line_count();
-        if self.cursor.row >= total_lines {
-            self.cursor.row = total_lines.saturating_sub(1);
+    fn handle_replace(&mut self, command: &VimCommand) -> Result<()> {
+        match command {
+            VimCommand::ReplaceChar(ch) => {
+                let replaced =
+                    replace::overwrite(self.buffers.current_mut(), &mut self.cursor, *ch)?;
+                self.mode_manager.push_replaced(replaced)?;
+            }
+            VimCommand::ReplaceNewLine => {
+                let replaced = replace::line_break(self.buffers.current_mut(), &mut self.cursor)?;
+                self.mode_manager.push_replaced(replaced)?;
+            }
+            VimCommand::ReplaceBackspace => {
+                // 記録がなければ（Replaceモードに入る前の位置では）カーソルを戻すだけ
+                match self.mode_manager.pop_replaced() {
+                    Some(replaced) => {
+                        replace::restore(self.buffers.current_mut(), &mut self.cursor, replaced)?
+                    }
+                    None => {
+                        self.cursor.move_left().ok();
+                    }
+                }
+            }
+            _ => {}
         }
+        Ok(())
+    }
 
-        // 列数の境界チェック
-        if let Ok(line_length) = self.buffer.line_length(self.cursor.row) {
-            self.cursor.clamp_to_line(line_length);
+    /// ビジュアルモードに入る（ビジュアルモード中なら種類を切り替える）
+    fn enter_visual(&mut self, kind: VisualKind) -> Result<()> {
+        if self.mode_manager.current().is_visual() {
+            self.mode_manager.set_visual_kind(kind)
+        } else {
+            self.mode_manager.enter_visual_kind(self.cursor, kind);
+            Ok(())
         }
-
-        Ok(())
     }
-}
 
-fn main() -> Result<()> {
-    let matches = Command::new("rvim")
-        .version("0.1.0")
-        .about("A VIM-like text editor written in Rust")
-        .arg(
-            Arg::new("file")
-                .help("File to edit")
-                .index(1)
-                .required(false),
-        )
-        .get_matches();
+    /// 現在のビジュアルモードの選択範囲
+    fn visual_selection(&self) -> Option<Selection> {
+        let Mode::Visual { start, kind } = *self.mode_manager.current() else {
+            return None;
+        };
+        let mut selection = Selection::new(start, self.cursor, kind);
+        selection.to_eol = kind == VisualKind::Block && self.curswant == Curswant::EndOfLine;
+        Some(selection)
+    }
 
-    let mut editor = if let Some(file_path) = matches.get_one::<String>("file") {
-        let path = PathBuf::from(file_path);
-        Editor::with_file(path)?
-    } else {
-        Editor::new()?
-    };
+    fn handle_visual(&mut self, command: &VimCommand) -> Result<()> {
+        let Some(selection) = self.visual_selection() else {
+            return Ok(());
+        };
 
-    match editor.run() {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("Editor error: {}", e);
-            std::process::exit(1);
+        match command {
+            VimCommand::VisualSwapEnds => {
+                self.mode_manager.set_visual_start(self.cursor)?;
+                self.cursor = selection.anchor;
+            }
+            VimCommand::VisualSwapCorner => {
+                if selection.kind != VisualKind::Block {
+                    self.mode_manager.set_visual_start(self.cursor)?;
+                    self.cursor = selection.anchor;
+                    return Ok(());
+                }
+                // 矩形では同じ行のまま左右の端を入れ替える
+                let buffer = self.buffers.current();
+                let tabstop = buffer.options().tabstop;
+                let column = |pos: Position| {
+                    buffer
+                        .line(pos.row)
+                        .map(|line| virtual_column(line, pos.col, tabstop))
+                        .unwrap_or(0)
+                };
+                let at_column = |row: usize, col: usize| {
+                    let line = buffer.line(row).unwrap_or("");
+                    Position::new(row, byte_column(line, col, tabstop))
+                };
+                let (anchor, cursor) = (selection.anchor, selection.cursor);
+                let new_anchor = at_column(anchor.row, column(cursor));
+                let new_cursor = at_column(cursor.row, column(anchor));
+                self.mode_manager.set_visual_start(new_anchor)?;
+                self.cursor = new_cursor;
+            }
+            VimCommand::VisualOperate { action, count } => {
+                let effect = visual::apply(
+                    *action,
+                    *count,
+                    &selection,
+                    self.buffers.current_mut(),
+                    &mut self.cursor,
+                    &mut self.registers,
+                )?;
+                self.mode_manager.enter_normal();
+                match effect {
+                    VisualEffect::Normal => {}
+                    VisualEffect::Insert => self.mode_manager.enter_insert(),
+                    VisualEffect::BlockInsert(block) => {
+                        self.block_insert = Some(block);
+                        self.mode_manager.enter_insert();
+                    }
+                    VisualEffect::CommandLine(input) => self.open_command_line(input)?,
+                }
+            }
+            _ => {}
         }
+        Ok(())
     }
 
-    Ok(())
-}
+    /// `input`を入力済みにしてCommandモードに入る
+    fn open_command_line(&mut self, input: String) -> Result<()> {
+        self.mode_manager.enter_command();
+        self.mode_manager.update_command_input(input)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn handle_scroll(&mut self, command: &VimCommand) -> Result<()> {
+        let line_count = self.buffers.current().line_count();
+        let cursor = &mut self.cursor;
 
-    #[test]
-    fn test_editor_creation() {
-        // ターミナルが利用できない環境では失敗する可能性がある
-        let result = Editor::new();
-        if result.is_ok() {
-            let editor = result.unwrap();
-            assert_eq!(editor.cursor.row, 0);
-            assert_eq!(editor.cursor.col, 0);
-            assert!(editor.mode_manager.current().is_normal());
-            assert!(!editor.should_quit);
+        match command {
+            VimCommand::ScrollPageDown => self.viewport.page_down(cursor, line_count),
+            VimCommand::ScrollPageUp => self.viewport.page_up(cursor, line_count),
+            VimCommand::ScrollHalfPageDown => self.viewport.half_page_down(cursor, line_count),
+            VimCommand::ScrollHalfPageUp => self.viewport.half_page_up(cursor),
+            VimCommand::ScrollLineDown => self.viewport.scroll_lines(1, cursor, line_count),
+            VimCommand::ScrollLineUp => self.viewport.scroll_lines(-1, cursor, line_count),
+            VimCommand::ScrollCursorCenter => self.viewport.center_on(cursor.row),
+            VimCommand::ScrollCursorTop => self.viewport.top_on(cursor.row),
+            VimCommand::ScrollCursorBottom => self.viewport.bottom_on(cursor.row),
+            VimCommand::MoveScreenTop
+            | VimCommand::MoveScreenMiddle
+            | VimCommand::MoveScreenBottom => {
+                cursor.row = match command {
+                    VimCommand::MoveScreenTop => self.viewport.screen_top(line_count),
+                    VimCommand::MoveScreenMiddle => self.viewport.screen_middle(line_count),
+                    _ => self.viewport.screen_bottom(line_count),
+                };
+                cursor.col = self.buffers.current().first_non_blank(cursor.row)?;
+            }
+            _ => {}
         }
+        Ok(())
     }
 
-    #[test]
-    fn test_editor_with_content() {
-        use std::io::Write;
-        use tempfile::NamedTempFile;
-
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "Hello, World!").unwrap();
+    /// カレントバッファを保存する（`force`でなければ読み込み専用のバッファは保存しない）
+    fn save_file(&mut self, force: bool) -> Result<()> {
+        self.save_buffer(self.buffers.current_number(), force)
+    }
 
-        let result = Editor::with_file(temp_file.path().to_path_buf());
-        if result.is_ok() {
-            let editor = result.unwrap();
-            assert_eq!(editor.buffer.line(0).unwrap(), "Hello, World!");
-            assert_eq!(editor.file_path, Some(temp_file.path().to_path_buf()));
+    /// バッファをそのファイルに保存する
+    fn save_buffer(&mut self, number: usize, force: bool) -> Result<()> {
+        let options = self.options.write_options();
+        let Some(entry) = self.buffers.get_mut(number) else {
+            return Ok(());
+        };
+        let buffer = &mut entry.buffer;
+        if buffer.options().readonly && !force {
+            return Err(EditorError::invalid_command(
+                "'readonly' option is set (add ! to override)",
+            ));
+        }
+        let Some(path) = buffer.file_path().cloned() else {
+            return Err(EditorError::invalid_command("No file name"));
+        };
+        // 読み込んだ後に外部で変更されたファイルは`!`がなければ上書きしない
+        if !force
+            && let Some(stamp) = buffer.stamp()
+            && matches!(stamp.check(&path)?, FileChange::Modified(_))
+        {
+            return Err(EditorError::invalid_command(
+                "File has changed since reading it (add ! to override)",
+            ));
         }
+        FileSystem::save(&path, &buffer.to_string(), &options)?;
+        buffer.mark_saved();
+        buffer.set_stamp(FileStamp::read(&path).ok());
+        Ok(())
     }
 
-    #[test]
-    fn test_cursor_adjustment() {
-        if let Ok(mut editor) = Editor::new() {
-            // バッファに内容を設定
-            editor.buffer = Buffer::from_content("Short\nLonger line");
+    /// `:w`/`:wq`/`:x`/`:update`（`:x`と`:update`は変更があるときだけ書き込む）
+    fn write_command(&mut self, command: &ExCommand) -> Result<()> {
+        let always = matches!(command.name.as_str(), "w" | "write" | "wq");
+        if always || self.buffers.current().is_modified() {
+            self.write_lines(command)?;
+        }
+        if matches!(command.name.as_str(), "wq" | "x" | "xit" | "exi" | "exit") {
+            self.quit_window(false)?;
+        }
+        Ok(())
+    }
+
+    /// `:[range]w [>>] [file]`/`:[range]w !cmd`: 範囲の行（省略時はバッファ全体）を書き出す
+    fn write_lines(&mut self, command: &ExCommand) -> Result<()> {
+        let buffer = self.buffers.current();
+        let last = buffer.line_count() - 1;
+        let (start, end) = command
+            .line_range(buffer, self.cursor.row)?
+            .unwrap_or((0, last));
+        let lines = &buffer.lines()[start..=end];
+        let own_path = buffer.file_path().cloned();
+
+        let (path, own) = match command.write_target() {
+            WriteTarget::Command(cmd) => {
+                let input: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+                let output = Shell::filter(&cmd, &input)?;
+                if !output.trim_end().is_empty() {
+                    self.message = Some(output.trim_end().to_string());
+                }
+                return Ok(());
+            }
+            WriteTarget::Append(file) => {
+                let path = file
+                    .or(own_path.clone())
+                    .ok_or_else(|| EditorError::invalid_command("No file name"))?;
+                FileSystem::append(&path, &lines.join("\n"))?;
+                // 自分で追加した分は外部の変更として扱わない
+                if own_path.as_ref() == Some(&path) {
+                    self.buffers
+                        .current_mut()
+                        .set_stamp(FileStamp::read(&path).ok());
+                }
+                return Ok(());
+            }
+            // 名前のないバッファは書き込んだファイルの名前にする
+            WriteTarget::File(path) if own_path.is_none() => {
+                if !command.bang && path.exists() {
+                    return Err(EditorError::invalid_command(
+                        "File exists (add ! to override)",
+                    ));
+                }
+                self.rename_buffer(path.clone());
+                (path, true)
+            }
+            WriteTarget::File(path) => {
+                let own = own_path.as_ref() == Some(&path);
+                (path, own)
+            }
+            WriteTarget::Buffer => (
+                own_path.ok_or_else(|| EditorError::invalid_command("No file name"))?,
+                true,
+            ),
+        };
+
+        if own && (start, end) == (0, last) {
+            return self.save_file(command.bang);
+        }
+        if own && !command.bang {
+            return Err(EditorError::invalid_command(
+                "Use ! to write partial buffer",
+            ));
+        }
+        if !own && !command.bang && path.exists() {
+            return Err(EditorError::invalid_command(
+                "File exists (add ! to override)",
+            ));
+        }
+        let text = self.buffers.current().lines()[start..=end].join("\n");
+        FileSystem::save(&path, &text, &self.options.write_options())?;
+        if own {
+            self.buffers
+                .current_mut()
+                .set_stamp(FileStamp::read(&path).ok());
+        }
+        Ok(())
+    }
+
+    /// `:sav[eas][!] {file}`: バッファの名前を変えて保存する
+    fn save_as(&mut self, command: &ExCommand) -> Result<()> {
+        let WriteTarget::File(path) = command.write_target() else {
+            return Err(EditorError::invalid_command("Argument required"));
+        };
+        if !command.bang && path.exists() && self.buffers.current().file_path() != Some(&path) {
+            return Err(EditorError::invalid_command(
+                "File exists (add ! to override)",
+            ));
+        }
+        self.rename_buffer(path);
+        self.save_file(command.bang)
+    }
+
+    /// `:wa`/`:wqa`/`:xa`: 変更のあるバッファをすべて保存する（保存できないバッファがあっても残りは保存する）
+    fn write_all(&mut self, force: bool) -> Result<()> {
+        let modified: Vec<usize> = self
+            .buffers
+            .entries()
+            .filter(|entry| entry.buffer.is_modified())
+            .map(|entry| entry.number)
+            .collect();
+        let mut first_error = None;
+        for number in modified {
+            let result = match self.buffers.get(number) {
+                Some(entry) if entry.buffer.file_path().is_none() => Err(
+                    EditorError::invalid_command(format!("No file name for buffer {}", number)),
+                ),
+                _ => self.save_buffer(number, force),
+            };
+            if let Err(error) = result {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// カレントバッファのファイル名を付け替える（スワップファイルは新しい名前で作り直す）
+    fn rename_buffer(&mut self, path: PathBuf) {
+        let number = self.buffers.current_number();
+        if let Some(Swap::Active(_)) = self.swap_files.get(&number)
+            && let Some(Swap::Active(swap)) = self.swap_files.insert(number, Swap::Unwritten)
+        {
+            let _ = swap.remove();
+        }
+        let buffer = self.buffers.current_mut();
+        buffer.set_file_path(path);
+        buffer.set_stamp(None);
+    }
+
+    /// バッファの切り替えなど、エディタ全体に関わるExコマンドを実行する
+    fn execute_editor_command(&mut self, command: &ExCommand) -> Result<()> {
+        // 他のウィンドウにも表示しているバッファは離れても変更が残る
+        let hidden = self.options.hidden || self.shown_elsewhere();
+        let args = command.args.as_str();
+        match command.name.as_str() {
+            "se" | "set" => {
+                for arg in args.split_whitespace() {
+                    self.options.set(arg)?;
+                }
+                // 他のウィンドウには描画するときに反映する
+                let mut viewport = self.viewport;
+                self.apply_scroll_options(&mut viewport);
+                self.viewport = viewport;
+            }
+            // `:w!`は読み込み専用のバッファや既存のファイルにも書き込む
+            "w" | "write" | "wq" | "x" | "xit" | "exi" | "exit" | "up" | "update" => {
+                self.write_command(command)?
+            }
+            "sav" | "saveas" => self.save_as(command)?,
+            "wa" | "wall" => self.write_all(command.bang)?,
+            "wqa" | "wqall" | "xa" | "xall" => {
+                self.write_all(command.bang)?;
+                self.should_quit = true;
+            }
+            // `:e!`は読み直して変更を破棄する
+            "e" | "edit" if args.is_empty() => {
+                self.buffers.check_abandon(false, command.bang)?;
+                self.buffers.reload(self.buffers.current_number())?;
+            }
+            "e" | "edit" => {
+                self.buffers.check_abandon(hidden, command.bang)?;
+                let number = match args {
+                    "#" => self.buffers.resolve(args)?,
+                    path => self.buffers.open(Path::new(path))?,
+                };
+                self.switch_buffer(number)?;
+            }
+            // `:recover [file]`: スワップファイルから内容を復元する（自分のスワップファイルは除く）
+            "rec" | "recover" => {
+                if !args.is_empty() {
+                    self.buffers.check_abandon(hidden, command.bang)?;
+                    let number = self.buffers.open(Path::new(args))?;
+                    self.switch_buffer(number)?;
+                }
+                self.buffers.check_abandon(false, command.bang)?;
+                let number = self.buffers.current_number();
+                let path = self
+                    .buffers
+                    .current()
+                    .file_path()
+                    .cloned()
+                    .ok_or_else(|| EditorError::invalid_command("No file name"))?;
+                let own = match self.swap_files.get(&number) {
+                    Some(Swap::Active(swap)) => Some(swap.path().to_path_buf()),
+                    _ => None,
+                };
+                let swap_path = swap::find(&path, &self.options.directory)
+                    .into_iter()
+                    .find(|swap_path| Some(swap_path) != own.as_ref())
+                    .ok_or_else(|| {
+                        EditorError::invalid_command(format!(
+                            "No swap file found for {}",
+                            path.display()
+                        ))
+                    })?;
+                self.recover(number, &swap_path)?;
+                self.swap_files.entry(number).or_insert(Swap::Unwritten);
+            }
+            "checkt" | "checktime" => self.check_timestamps()?,
+            "ene" | "enew" => {
+                self.buffers.check_abandon(hidden, command.bang)?;
+                let number = self.buffers.add(Buffer::new());
+                self.switch_buffer(number)?;
+            }
+            "ls" | "buffers" | "files" => {
+                self.message = Some(self.buffers.list(command.bang, self.cursor).join("\n"));
+            }
+            "b" | "buffer" => {
+                let number = match command.count() {
+                    Some(number) => self.buffers.resolve(&number.to_string())?,
+                    None => self.buffers.resolve(args)?,
+                };
+                self.buffers.check_abandon(hidden, command.bang)?;
+                self.switch_buffer(number)?;
+            }
+            "bn" | "bnext" | "bp" | "bprevious" | "bN" | "bNext" => {
+                let forward = matches!(command.name.as_str(), "bn" | "bnext");
+                let number = self.buffers.cycle(command.count().unwrap_or(1), forward);
+                self.buffers.check_abandon(hidden, command.bang)?;
+                self.switch_buffer(number)?;
+            }
+            "bd" | "bdelete" | "bw" | "bwipeout" => {
+                let number = match command.count() {
+                    Some(number) => self.buffers.resolve(&number.to_string())?,
+                    None => self.buffers.resolve(args)?,
+                };
+                let wipe = matches!(command.name.as_str(), "bw" | "bwipeout");
+                self.buffers
+                    .delete(number, wipe, command.bang, &mut self.cursor)?;
+                self.viewport.top = 0;
+                // 削除したバッファを表示している他のウィンドウ（とそれだけのタブページ）は閉じる
+                self.store_window();
+                self.tabs.close_buffer(number)?;
+                self.load_window()?;
+            }
+            "sp" | "split" | "vs" | "vsplit" | "new" | "vne" | "vnew" => {
+                let direction = if matches!(command.name.as_str(), "vs" | "vsplit" | "vne" | "vnew")
+                {
+                    SplitDirection::Vertical
+                } else {
+                    SplitDirection::Horizontal
+                };
+                self.split_window(direction, command.count())?;
+                // 分割元のウィンドウにも表示しているため、バッファを切り替えても変更は残る
+                if matches!(command.name.as_str(), "new" | "vne" | "vnew") {
+                    let number = self.buffers.add(Buffer::new());
+                    self.switch_buffer(number)?;
+                } else if !args.is_empty() && command.count().is_none() {
+                    let number = self.buffers.open(Path::new(args))?;
+                    self.switch_buffer(number)?;
+                }
+            }
+            "clo" | "close" => {
+                let id = match command.count() {
+                    Some(count) => self.tabs.layout().cycle(Some(count), true),
+                    None => self.tabs.layout().current_id(),
+                };
+                self.close_window(id)?;
+            }
+            "on" | "only" => self.only_window(command.bang)?,
+            "winc" | "wincmd" => self.window_command(args, command.count())?,
+            "res" | "resize" => {
+                let area = self.window_area()?;
+                let direction = SplitDirection::Horizontal;
+                match (
+                    args.chars().next(),
+                    args.get(1..).and_then(|n| n.parse().ok()),
+                ) {
+                    (Some('+'), Some(amount)) => {
+                        self.tabs.layout_mut().resize(area, direction, amount)
+                    }
+                    (Some('-'), Some(amount)) => {
+                        self.tabs.layout_mut().resize(area, direction, -amount)
+                    }
+                    _ => {
+                        let height = args.parse().unwrap_or(usize::MAX - 1);
+                        self.tabs.layout_mut().set_size(area, direction, height + 1);
+                    }
+                }
+            }
+            "tabnew" | "tabe" | "tabedit" => {
+                // `:0tabnew`は先頭、`:$tabnew`は末尾、`:N tabnew`はN番目の後ろに作る
+                let index = match command.range {
+                    Some(ExRange::Single(Address {
+                        base: AddressBase::Line(number),
+                        offset: 0,
+                    })) => number,
+                    Some(ExRange::Single(Address {
+                        base: AddressBase::Last,
+                        ..
+                    })) => self.tabs.count(),
+                    _ => self.tabs.index() + 1,
+                };
+                let number = if args.is_empty() {
+                    self.buffers.add(Buffer::new())
+                } else {
+                    self.buffers.open(Path::new(args))?
+                };
+                self.open_tab(index, number)?;
+            }
+            "tabc" | "tabclose" => {
+                let index = match command.count() {
+                    Some(number) => self.tab_index(number)?,
+                    None => self.tabs.index(),
+                };
+                if !command.bang && self.tab_has_changes(index) {
+                    return Err(EditorError::invalid_command(
+                        "No write since last change (add ! to override)",
+                    ));
+                }
+                self.store_window();
+                self.tabs.close(index)?;
+                self.load_window()?;
+            }
+            "tabo" | "tabonly" => {
+                self.store_window();
+                let mut kept = false;
+                for index in (0..self.tabs.count()).rev() {
+                    if index == self.tabs.index() {
+                        continue;
+                    }
+                    if !command.bang && self.tab_has_changes(index) {
+                        kept = true;
+                        continue;
+                    }
+                    self.tabs.close(index)?;
+                }
+                self.load_window()?;
+                if kept {
+                    return Err(EditorError::invalid_command(
+                        "Other tab page contains changes",
+                    ));
+                }
+            }
+            "tabn" | "tabnext" => {
+                let index = match command.count() {
+                    Some(number) => self.tab_index(number)?,
+                    None => self.tabs.cycle(1, true),
+                };
+                self.focus_tab(index)?;
+            }
+            "tabp" | "tabprevious" | "tabN" | "tabNext" => {
+                let index = self.tabs.cycle(command.count().unwrap_or(1), false);
+                self.focus_tab(index)?;
+            }
+            "tabfir" | "tabfirst" | "tabr" | "tabrewind" => self.focus_tab(0)?,
+            "tabl" | "tablast" => self.focus_tab(self.tabs.count() - 1)?,
+            "tabm" | "tabmove" => {
+                // `N`はN番目のタブページの後ろ（0なら先頭）、`+N`/`-N`は今の位置からの移動
+                let current = self.tabs.index();
+                let relative = args.get(1..).and_then(|n| {
+                    n.parse::<usize>()
+                        .ok()
+                        .or_else(|| n.is_empty().then_some(1))
+                });
+                let index = match (args.chars().next(), relative) {
+                    (Some('+'), Some(amount)) => current + amount,
+                    (Some('-'), Some(amount)) => current.checked_sub(amount).ok_or_else(|| {
+                        EditorError::invalid_command(format!("Invalid argument: {}", args))
+                    })?,
+                    _ if (args.is_empty() && command.count().is_none()) || args == "$" => {
+                        usize::MAX
+                    }
+                    _ => match command.count() {
+                        Some(number) if number > current => number - 1,
+                        Some(number) => number,
+                        None => {
+                            return Err(EditorError::invalid_command(format!(
+                                "Invalid argument: {}",
+                                args
+                            )));
+                        }
+                    },
+                };
+                if index != usize::MAX && index >= self.tabs.count() {
+                    return Err(EditorError::invalid_command(format!(
+                        "Invalid argument: {}",
+                        args
+                    )));
+                }
+                self.tabs.move_current(index);
+            }
+            "ar" | "args" if args.is_empty() => self.message = Some(self.arg_list.display()),
+            "ar" | "args" => {
+                // 引数リストを置き換えて先頭のファイルを編集する
+                self.buffers.check_abandon(hidden, command.bang)?;
+                self.arg_list = ArgList::new(args.split_whitespace().map(PathBuf::from).collect());
+                self.edit_arg(0, true)?;
+            }
+            "n" | "next" => {
+                let index = self.arg_list.next(command.count().unwrap_or(1))?;
+                self.edit_arg(index, command.bang)?;
+            }
+            "N" | "Next" | "prev" | "previous" => {
+                let index = self.arg_list.previous(command.count().unwrap_or(1))?;
+                self.edit_arg(index, command.bang)?;
+            }
+            "fir" | "first" | "rew" | "rewind" => self.edit_arg(0, command.bang)?,
+            "la" | "last" => self.edit_arg(self.arg_list.len().saturating_sub(1), command.bang)?,
+            "arga" | "argadd" => {
+                // 引数がなければカレントバッファのファイルを追加する
+                let files: Vec<PathBuf> = if args.is_empty() {
+                    let path = self
+                        .buffers
+                        .current()
+                        .file_path()
+                        .ok_or_else(|| EditorError::invalid_command("No file name"))?;
+                    vec![path.to_path_buf()]
+                } else {
+                    args.split_whitespace().map(PathBuf::from).collect()
+                };
+                // `:0argadd`は先頭、`:$argadd`は末尾、`:N argadd`はN番目の後ろに追加する
+                let position = match command.range {
+                    Some(ExRange::Single(Address {
+                        base: AddressBase::Line(number),
+                        offset: 0,
+                    })) => number,
+                    Some(ExRange::Single(Address {
+                        base: AddressBase::Last,
+                        ..
+                    })) => self.arg_list.len(),
+                    _ if self.arg_list.is_empty() => 0,
+                    _ => self.arg_list.index() + 1,
+                };
+                self.arg_list.add(position, files);
+            }
+            "argd" | "argdelete" if args.is_empty() => {
+                let index = match command.count() {
+                    Some(number) => number
+                        .checked_sub(1)
+                        .ok_or_else(|| EditorError::invalid_command("Invalid range"))?,
+                    None => self.arg_list.index(),
+                };
+                self.arg_list.remove(index)?;
+            }
+            "argd" | "argdelete" => {
+                for pattern in args.split_whitespace() {
+                    self.arg_list.delete(pattern)?;
+                }
+            }
+            "argdo" | "bufdo" => self.run_each(command)?,
+            _ => {}
+        }
+        self.adjust_cursor_position()
+    }
+
+    /// 引数リストの`index`番目のファイルを編集する
+    fn edit_arg(&mut self, index: usize, force: bool) -> Result<()> {
+        let path = self
+            .arg_list
+            .get(index)
+            .ok_or_else(|| EditorError::invalid_command("No argument list"))?
+            .to_path_buf();
+        let hidden = self.options.hidden || self.shown_elsewhere();
+        self.buffers.check_abandon(hidden, force)?;
+        let number = self.buffers.open(&path)?;
+        self.switch_buffer(number)?;
+        self.arg_list.set_index(index);
+        Ok(())
+    }
+
+    /// `:argdo`/`:bufdo`: 引数リストのファイル（一覧のバッファ）ごとにExコマンドを実行する
+    ///
+    /// コマンドで変更したバッファは次のファイルに移る前に保存する。
+    fn run_each(&mut self, command: &ExCommand) -> Result<()> {
+        let hidden = self.options.hidden || self.shown_elsewhere();
+        self.buffers.check_abandon(hidden, command.bang)?;
+        // `:argdo`は引数リストの位置、`:bufdo`はバッファ番号を順に移る
+        let argdo = command.name == "argdo";
+        let targets: Vec<usize> = if argdo {
+            (0..self.arg_list.len()).collect()
+        } else {
+            self.buffers
+                .entries()
+                .filter(|entry| entry.listed)
+                .map(|entry| entry.number)
+                .collect()
+        };
+
+        for target in targets {
+            if argdo {
+                self.edit_arg(target, command.bang)?;
+            } else {
+                let hidden = self.options.hidden || self.shown_elsewhere();
+                self.buffers.check_abandon(hidden, command.bang)?;
+                self.switch_buffer(target)?;
+            }
+            self.run_ex(&command.args)?;
+
+            let buffer = self.buffers.current();
+            if buffer.is_modified() && buffer.file_path().is_some() {
+                self.save_file(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// メッセージを表示し、`choices`のいずれかのキーを入力するまで待つ（`Esc`は最後の選択肢）
+    fn prompt(&mut self, message: String, choices: &str) -> Result<char> {
+        self.message = Some(message);
+        self.render()?;
+        self.message = None;
+        loop {
+            match Key::from(self.terminal.read_key()?).code {
+                KeyCode::Char(ch) if choices.contains(ch.to_ascii_lowercase()) => {
+                    return Ok(ch.to_ascii_lowercase());
+                }
+                KeyCode::Esc => return Ok(choices.chars().last().unwrap_or_default()),
+                _ => {}
+            }
+        }
+    }
+
+    /// 閉じたバッファのスワップファイルを消し、新しく読み込んだバッファに既存のスワップファイルがないか確かめる
+    fn check_swap_files(&mut self) -> Result<()> {
+        let numbers: Vec<usize> = self.buffers.entries().map(|entry| entry.number).collect();
+        let closed: Vec<usize> = self
+            .swap_files
+            .keys()
+            .copied()
+            .filter(|number| !numbers.contains(number))
+            .collect();
+        for number in closed {
+            if let Some(Swap::Active(swap)) = self.swap_files.remove(&number) {
+                let _ = swap.remove();
+            }
+        }
+
+        for number in numbers {
+            if self.swap_files.contains_key(&number) {
+                continue;
+            }
+            let Some(entry) = self.buffers.get(number) else {
+                continue;
+            };
+            if entry.buffer.file_state() == FileState::Directory {
+                self.swap_files.insert(number, Swap::Disabled);
+                continue;
+            }
+            let existing = entry
+                .buffer
+                .file_path()
+                .filter(|_| self.options.swapfile)
+                .and_then(|path| swap::find(path, &self.options.directory).into_iter().next());
+            let state = match existing {
+                Some(swap_path) => self.attention(number, &swap_path)?,
+                None => Swap::Unwritten,
+            };
+            // `(Q)uit`を選ぶとバッファを閉じている
+            if self.buffers.get(number).is_some() {
+                self.swap_files.insert(number, state);
+            }
+        }
+        Ok(())
+    }
+
+    /// 既存のスワップファイルを見つけたときにATTENTIONメッセージを表示し、どうするか選んでもらう
+    fn attention(&mut self, number: usize, swap_path: &Path) -> Result<Swap> {
+        let Some(path) = self
+            .buffers
+            .get(number)
+            .and_then(|entry| entry.buffer.file_path())
+            .cloned()
+        else {
+            return Ok(Swap::Unwritten);
+        };
+        let info = match SwapInfo::read(swap_path) {
+            Ok(info) => info,
+            Err(error) => {
+                self.message = Some(error.to_string());
+                return Ok(Swap::Unwritten);
+            }
+        };
+
+        // 書いたプロセスが動いている間は消させない
+        let (choices, question) = if info.is_running() {
+            (
+                "oerq",
+                "[O]pen Read-Only, (E)dit anyway, (R)ecover, (Q)uit: ",
+            )
+        } else {
+            (
+                "oerdq",
+                "[O]pen Read-Only, (E)dit anyway, (R)ecover, (D)elete it, (Q)uit: ",
+            )
+        };
+        let mut lines = info.attention(swap_path, &path);
+        lines.push(format!(
+            "Swap file \"{}\" already exists!",
+            swap_path.display()
+        ));
+        lines.push(question.to_string());
+
+        match self.prompt(lines.join("\n"), choices)? {
+            'o' => {
+                if let Some(entry) = self.buffers.get_mut(number) {
+                    entry.buffer.options_mut().readonly = true;
+                }
+                Ok(Swap::Disabled)
+            }
+            'e' => Ok(Swap::Unwritten),
+            'r' => {
+                self.recover(number, swap_path)?;
+                Ok(Swap::Unwritten)
+            }
+            'd' => {
+                std::fs::remove_file(swap_path)?;
+                Ok(Swap::Unwritten)
+            }
+            _ => {
+                if self.buffers.entries().count() == 1 {
+                    self.should_quit = true;
+                } else {
+                    self.run_ex(&format!("bwipeout! {}", number))?;
+                }
+                Ok(Swap::Disabled)
+            }
+        }
+    }
+
+    /// スワップファイルから`number`のバッファの内容を復元する（保存するまで変更ありとして扱う）
+    fn recover(&mut self, number: usize, swap_path: &Path) -> Result<()> {
+        let mut lines = swap::recover(swap_path)?;
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let entry = self.buffers.get_mut(number).ok_or_else(|| {
+            EditorError::invalid_command(format!("Buffer {} does not exist", number))
+        })?;
+        let last = entry.buffer.line_count() - 1;
+        entry.buffer.replace_lines(0, last, lines)?;
+        if number == self.buffers.current_number() {
+            self.adjust_cursor_position()?;
+        }
+        self.message = Some(format!(
+            "Recovery completed. You should check if everything is OK.\nYou may want to delete the swap file \"{}\" now.",
+            swap_path.display()
+        ));
+        Ok(())
+    }
+
+    /// 読み込んだファイルが外部で変更されていないか確かめる（`:checktime`、フォーカスを得たとき）
+    fn check_timestamps(&mut self) -> Result<()> {
+        let numbers: Vec<usize> = self.buffers.entries().map(|entry| entry.number).collect();
+        for number in numbers {
+            self.check_timestamp(number)?;
+        }
+        Ok(())
+    }
+
+    /// `number`のバッファのファイルが外部で変更されていないか確かめる
+    ///
+    /// `autoread`でバッファに変更がなければ確認せずに読み直し、そうでなければ読み直すか選んでもらう。
+    /// 削除されたファイルはステータスラインに`[Deleted]`と表示する。
+    fn check_timestamp(&mut self, number: usize) -> Result<()> {
+        let Some(entry) = self.buffers.get(number) else {
+            return Ok(());
+        };
+        let Some(path) = entry.buffer.file_path().cloned() else {
+            return Ok(());
+        };
+        let modified = entry.buffer.is_modified();
+        let change = match (entry.buffer.stamp(), entry.buffer.file_state()) {
+            (Some(stamp), _) => stamp.check(&path),
+            // 削除されたファイルが作り直されたら変更として扱う
+            (None, FileState::Deleted) if path.exists() => {
+                FileStamp::read(&path).map(FileChange::Modified)
+            }
+            _ => return Ok(()),
+        };
+        // 読めなくなったファイルなどは次の機会に確かめる
+        let Ok(change) = change else {
+            return Ok(());
+        };
+
+        let stamp = match change {
+            FileChange::Unchanged => return Ok(()),
+            FileChange::Touched(current) => Some(current),
+            FileChange::Deleted => {
+                self.message = Some(format!(
+                    "E211: File \"{}\" no longer available",
+                    path.display()
+                ));
+                None
+            }
+            FileChange::Modified(_) if self.options.autoread && !modified => {
+                return self.reload_buffer(number);
+            }
+            FileChange::Modified(current) => {
+                let warning = if modified {
+                    format!(
+                        "W12: Warning: File \"{}\" has changed and the buffer was changed in rvim as well",
+                        path.display()
+                    )
+                } else {
+                    format!(
+                        "W11: Warning: File \"{}\" has changed since editing started",
+                        path.display()
+                    )
+                };
+                let message = format!("{}\n[O]K, (L)oad File: ", warning);
+                if self.prompt(message, "lo")? == 'l' {
+                    return self.reload_buffer(number);
+                }
+                // 変更を残す場合は、もう一度変わるまで確認しない
+                Some(current)
+            }
+        };
+        if let Some(entry) = self.buffers.get_mut(number) {
+            let state = match (stamp, entry.buffer.file_state()) {
+                (None, _) => FileState::Deleted,
+                (Some(_), FileState::Deleted) => FileState::Loaded,
+                (Some(_), state) => state,
+            };
+            entry.buffer.set_stamp(stamp);
+            entry.buffer.set_file_state(state);
+        }
+        Ok(())
+    }
+
+    /// 監視しているファイルの変化を受け取り、そのファイルのバッファを確かめる
+    fn handle_watch_events(&mut self) -> Result<()> {
+        for event in self.watcher.read_events() {
+            let numbers: Vec<usize> = self
+                .buffers
+                .entries()
+                .filter(|entry| {
+                    entry.buffer.file_path().map(PathBuf::as_path) == Some(event.path())
+                })
+                .map(|entry| entry.number)
+                .collect();
+            for number in numbers {
+                self.check_timestamp(number)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 読み込んでいるバッファのファイルを監視する
+    fn update_watcher(&mut self) {
+        let files: Vec<PathBuf> = self
+            .buffers
+            .entries()
+            .filter(|entry| entry.buffer.file_state() != FileState::Directory)
+            .filter_map(|entry| entry.buffer.file_path().cloned())
+            .collect();
+        self.watcher.set_files(&files);
+    }
+
+    /// ファイルを読み直してバッファの変更を破棄する
+    fn reload_buffer(&mut self, number: usize) -> Result<()> {
+        self.buffers.reload(number)?;
+        if number == self.buffers.current_number() {
+            self.adjust_cursor_position()?;
+        }
+        Ok(())
+    }
+
+    /// 変更のあるバッファの内容をスワップファイルに書き出す（最初の変更でスワップファイルを作る）
+    fn sync_swap_files(&mut self) {
+        self.keys_since_sync = 0;
+        if !self.options.swapfile || self.options.updatecount == 0 {
+            return;
+        }
+        for entry in self.buffers.entries() {
+            let Some(path) = entry.buffer.file_path() else {
+                continue;
+            };
+            let result = match self.swap_files.get_mut(&entry.number) {
+                Some(Swap::Active(swap)) => swap.sync(entry.buffer.lines()).map(|_| ()),
+                Some(Swap::Unwritten) if entry.buffer.is_modified() => {
+                    SwapFile::create(path, &self.options.directory, entry.buffer.lines()).map(
+                        |swap| {
+                            self.swap_files.insert(entry.number, Swap::Active(swap));
+                        },
+                    )
+                }
+                _ => Ok(()),
+            };
+            // 書き出せないスワップファイルは使うのをやめる
+            if let Err(error) = result {
+                self.swap_files.insert(entry.number, Swap::Disabled);
+                self.message = Some(format!("Unable to write swap file: {}", error));
+            }
+        }
+    }
+
+    /// 終了時にスワップファイルを消す
+    fn remove_swap_files(&mut self) {
+        for (_, swap) in self.swap_files.drain() {
+            if let Swap::Active(swap) = swap {
+                let _ = swap.remove();
+            }
+        }
+    }
+
+    /// `:argdo`などから受け取ったExコマンドを1つ実行する
+    fn run_ex(&mut self, input: &str) -> Result<()> {
+        let command = VimCommand::ExecuteCommand(input.to_string());
+        match command.execute(self.buffers.current_mut(), &mut self.cursor)? {
+            CommandResult::EditorCommand(ex) => self.execute_editor_command(&ex)?,
+            CommandResult::SaveRequested => self.save_file(false)?,
+            CommandResult::DeletedLine(line) => self.registers.set(Register::linewise(line)),
+            CommandResult::Yanked(register) | CommandResult::Deleted(register) => {
+                self.registers.set(register)
+            }
+            _ => {}
+        }
+        self.adjust_cursor_position()
+    }
+
+    /// `-o`/`-O`/`-p`: 引数リストのファイルをそれぞれ別のウィンドウ・タブページで開く
+    ///
+    /// `direction`が`None`ならタブページで開く。
+    fn open_all_args(&mut self, direction: Option<SplitDirection>) -> Result<()> {
+        // 開けないファイル（デバイスなど）は飛ばし、理由を表示する
+        let mut numbers = Vec::new();
+        for file in self.arg_list.files().to_vec() {
+            match self.buffers.open(&file) {
+                Ok(number) => numbers.push(number),
+                Err(error) => self.message = Some(error.to_string()),
+            }
+        }
+        let Some((&last, rest)) = numbers.split_last() else {
+            return Ok(());
+        };
+        let Some(direction) = direction else {
+            self.switch_buffer(numbers[0])?;
+            for &number in &numbers[1..] {
+                self.open_tab(self.tabs.count(), number)?;
+            }
+            return self.focus_tab(0);
+        };
+
+        // 新しいウィンドウは上（左）にできるため、最後のファイルから順に開く
+        self.switch_buffer(last)?;
+        for &number in rest.iter().rev() {
+            // 画面に入りきらなければ、残りのウィンドウに先頭のファイルを表示して終える
+            if self.split_window(direction, None).is_err() {
+                return self.switch_buffer(numbers[0]);
+            }
+            self.switch_buffer(number)?;
+        }
+        Ok(())
+    }
+
+    /// `index`の位置に`number`のバッファを表示するタブページを作る
+    fn open_tab(&mut self, index: usize, number: usize) -> Result<()> {
+        let mut layout = Layout::new(number);
+        if let Some(entry) = self.buffers.get(number) {
+            layout.current_mut().cursor = entry.cursor;
+        }
+        self.store_window();
+        self.tabs.insert(index, layout);
+        self.load_window()
+    }
+
+    /// `:tabnext N`などのタブページ番号（1始まり）を位置に変換する
+    fn tab_index(&self, number: usize) -> Result<usize> {
+        if (1..=self.tabs.count()).contains(&number) {
+            Ok(number - 1)
+        } else {
+            Err(EditorError::invalid_command("Invalid range"))
+        }
+    }
+
+    /// タブページを閉じると失われる変更があるか（他のタブページに表示しているバッファは残る）
+    fn tab_has_changes(&self, index: usize) -> bool {
+        if self.options.hidden {
+            return false;
+        }
+        let Some(layout) = self.tabs.iter().nth(index) else {
+            return false;
+        };
+        layout.windows().any(|window| {
+            let modified = self
+                .buffers
+                .get(window.buffer)
+                .is_some_and(|entry| entry.buffer.is_modified());
+            let shown = self
+                .tabs
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .any(|(_, layout)| layout.windows().any(|other| other.buffer == window.buffer));
+            modified && !shown
+        })
+    }
+
+    /// `index`のタブページに移る
+    fn focus_tab(&mut self, index: usize) -> Result<()> {
+        self.store_window();
+        self.tabs.focus(index);
+        self.load_window()
+    }
+
+    /// `:q`: ウィンドウかタブページが複数あればカレントウィンドウを閉じ、最後のウィンドウならエディタを終了する
+    ///
+    /// `force`なら、他に表示していないバッファの変更を破棄する（`hidden`なら隠す）。
+    fn quit_window(&mut self, force: bool) -> Result<()> {
+        let hidden = self.options.hidden || self.shown_elsewhere();
+        if self.tabs.layout().count() > 1 || self.tabs.count() > 1 {
+            if !force {
+                self.buffers.check_abandon(hidden, false)?;
+            } else if !hidden && self.buffers.current().is_modified() {
+                self.buffers.reload(self.buffers.current_number())?;
+            }
+            return self.close_window(self.tabs.layout().current_id());
+        }
+
+        // 隠れているバッファも含め、保存していない変更があれば終了しない
+        if !force && let Some(entry) = self.buffers.first_modified() {
+            return Err(EditorError::invalid_command(format!(
+                "No write since last change for buffer \"{}\" (add ! to override)",
+                entry.name()
+            )));
+        }
+        self.should_quit = true;
+        Ok(())
+    }
+
+    /// カレントバッファを他のウィンドウ（他のタブページを含む）でも表示しているか
+    fn shown_elsewhere(&self) -> bool {
+        let (tab, current) = (self.tabs.index(), self.tabs.layout().current_id());
+        let number = self.buffers.current_number();
+        self.tabs.iter().enumerate().any(|(index, layout)| {
+            layout
+                .windows()
+                .any(|window| (index != tab || window.id != current) && window.buffer == number)
+        })
+    }
+
+    /// カレントウィンドウのカーソルなどをレイアウトに書き戻す
+    fn store_window(&mut self) {
+        let window = self.tabs.layout_mut().current_mut();
+        window.buffer = self.buffers.current_number();
+        window.cursor = self.cursor;
+        window.curswant = self.curswant;
+        window.viewport = self.viewport;
+    }
+
+    /// レイアウトのカレントウィンドウの状態を読み込む
+    fn load_window(&mut self) -> Result<()> {
+        let window = self.tabs.layout().current().clone();
+        self.buffers.focus(window.buffer, self.cursor);
+        self.cursor = window.cursor;
+        self.curswant = window.curswant;
+        self.viewport = window.viewport;
+        self.adjust_cursor_position()
+    }
+
+    /// ウィンドウ`id`に移る
+    fn focus_window(&mut self, id: usize) -> Result<()> {
+        self.store_window();
+        self.tabs.layout_mut().focus(id);
+        self.load_window()
+    }
+
+    /// ウィンドウ`id`を閉じる（タブページの最後のウィンドウならタブページを閉じる）
+    fn close_window(&mut self, id: usize) -> Result<()> {
+        self.store_window();
+        if self.tabs.layout().count() == 1 && self.tabs.count() > 1 {
+            self.tabs.close(self.tabs.index())?;
+        } else {
+            self.tabs.layout_mut().close(id)?;
+        }
+        self.load_window()
+    }
+
+    /// カレントウィンドウを分割する（新しいウィンドウが同じバッファを表示してカレントになる）
+    fn split_window(&mut self, direction: SplitDirection, size: Option<usize>) -> Result<()> {
+        let area = self.window_area()?;
+        self.store_window();
+        self.tabs.layout_mut().split(direction, area)?;
+        if let Some(size) = size {
+            // 高さはステータスラインを除いた行数
+            let size = match direction {
+                SplitDirection::Horizontal => size + 1,
+                SplitDirection::Vertical => size,
+            };
+            self.tabs.layout_mut().set_size(area, direction, size);
+        }
+        self.load_window()
+    }
+
+    /// `:only`: カレントウィンドウ以外を閉じる
+    ///
+    /// `hidden`でも`force`でもなければ、他に表示していない変更のあるバッファのウィンドウは残す。
+    fn only_window(&mut self, force: bool) -> Result<()> {
+        self.store_window();
+        let current = self.tabs.layout().current_id();
+        let shown = self.buffers.current_number();
+        let mut kept = false;
+        for id in self.tabs.layout().ids() {
+            let Some(window) = self.tabs.layout().window(id) else {
+                continue;
+            };
+            let modified = self
+                .buffers
+                .get(window.buffer)
+                .is_some_and(|entry| entry.buffer.is_modified());
+            if id == current {
+                continue;
+            }
+            if modified && window.buffer != shown && !self.options.hidden && !force {
+                kept = true;
+                continue;
+            }
+            self.tabs.layout_mut().close(id)?;
+        }
+        self.load_window()?;
+        if kept {
+            return Err(EditorError::invalid_command(
+                "Other window contains changes",
+            ));
+        }
+        Ok(())
+    }
+
+    /// `Ctrl-W {key}`/`:[count]wincmd {key}`
+    fn window_command(&mut self, key: &str, count: Option<usize>) -> Result<()> {
+        let area = self.window_area()?;
+        let amount = count.unwrap_or(1) as isize;
+        match key {
+            "s" | "S" => self.split_window(SplitDirection::Horizontal, count)?,
+            "v" => self.split_window(SplitDirection::Vertical, count)?,
+            "n" => {
+                self.split_window(SplitDirection::Horizontal, count)?;
+                let number = self.buffers.add(Buffer::new());
+                self.switch_buffer(number)?;
+            }
+            "c" => self.close_window(self.tabs.layout().current_id())?,
+            "q" => self.quit_window(false)?,
+            "o" => self.only_window(false)?,
+            "h" | "j" | "k" | "l" => {
+                let direction = match key {
+                    "h" => Direction::Left,
+                    "j" => Direction::Down,
+                    "k" => Direction::Up,
+                    _ => Direction::Right,
+                };
+                // 画面上のカーソル位置から見た隣のウィンドウ
+                self.store_window();
+                let rect = self
+                    .tabs
+                    .layout_mut()
+                    .arrange(area)
+                    .into_iter()
+                    .find(|(id, _)| *id == self.tabs.layout().current_id())
+                    .map_or(area, |(_, rect)| rect);
+                let point = (
+                    rect.row + self.cursor.row.saturating_sub(self.viewport.top),
+                    rect.col + self.cursor_display_col().saturating_sub(self.viewport.left),
+                );
+                if let Some(id) =
+                    self.tabs
+                        .layout_mut()
+                        .neighbor(area, direction, count.unwrap_or(1), point)
+                {
+                    self.focus_window(id)?;
+                }
+            }
+            "w" | "W" => {
+                let id = self.tabs.layout().cycle(count, key == "w");
+                self.focus_window(id)?;
+            }
+            "p" => {
+                let id = self
+                    .tabs
+                    .layout()
+                    .previous_id()
+                    .ok_or_else(|| EditorError::invalid_command("No previous window"))?;
+                self.focus_window(id)?;
+            }
+            "=" => self.tabs.layout_mut().equalize(),
+            "+" => self
+                .tabs
+                .layout_mut()
+                .resize(area, SplitDirection::Horizontal, amount),
+            "-" => self
+                .tabs
+                .layout_mut()
+                .resize(area, SplitDirection::Horizontal, -amount),
+            ">" => self
+                .tabs
+                .layout_mut()
+                .resize(area, SplitDirection::Vertical, amount),
+            "<" => self
+                .tabs
+                .layout_mut()
+                .resize(area, SplitDirection::Vertical, -amount),
+            "_" => self.tabs.layout_mut().set_size(
+                area,
+                SplitDirection::Horizontal,
+                count.map_or(usize::MAX, |count| count + 1),
+            ),
+            "|" => self.tabs.layout_mut().set_size(
+                area,
+                SplitDirection::Vertical,
+                count.unwrap_or(usize::MAX),
+            ),
+            "x" => {
+                self.store_window();
+                self.tabs.layout_mut().exchange(count);
+                self.load_window()?;
+            }
+            "r" | "R" => self
+                .tabs
+                .layout_mut()
+                .rotate(key == "r", count.unwrap_or(1))?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `number`のバッファを表示する（カーソルはそのバッファで最後にいた位置に戻す）
+    fn switch_buffer(&mut self, number: usize) -> Result<()> {
+        if number == self.buffers.current_number() {
+            return Ok(());
+        }
+        let hidden = self.options.hidden || self.shown_elsewhere();
+        self.buffers.switch(number, &mut self.cursor, hidden)?;
+        self.viewport.top = 0;
+        self.curswant = Curswant::default();
+        Ok(())
+    }
+
+    fn adjust_cursor_position(&mut self) -> Result<()> {
+        // 行数の境界チェック
+        let total_lines = self.buffers.current().line_count();
+        if self.cursor.row >= total_lines {
+            self.cursor.row = total_lines.saturating_sub(1);
+        }
+
+        // 列数の境界チェック
+        if let Ok(line_length) = self.buffers.current().line_length(self.cursor.row) {
+            self.cursor.clamp_to_line(line_length);
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let matches = Command::new("rvim")
+        .version("0.1.0")
+        .about("A VIM-like text editor written in Rust")
+        .arg(
+            Arg::new("files")
+                .help("Files to edit")
+                .num_args(0..)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("o")
+                .short('o')
+                .help("Open one window per file, stacked")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("O")
+                .short('O')
+                .help("Open one window per file, side by side")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("p")
+                .short('p')
+                .help("Open one tab page per file")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("r")
+                .short('r')
+                .help("List swap files, or recover the files from them")
+                .action(ArgAction::SetTrue),
+        )
+        .get_matches();
 
-            // カーソルを無効な位置に設定
-            editor.cursor = Position::new(0, 10);
+    let files: Vec<PathBuf> = matches
+        .get_many::<String>("files")
+        .map(|files| files.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    // ファイルを指定せずに`-r`ならスワップファイルの一覧を表示して終わる
+    if matches.get_flag("r") && files.is_empty() {
+        print_swap_files()?;
+        return Ok(());
+    }
+    let mut editor = Editor::with_files(files, Terminal::new()?);
+    if matches.get_flag("r")
+        && let Err(error) = editor.run_ex("recover")
+    {
+        editor.message = Some(error.to_string());
+    }
+    if matches.get_flag("p") {
+        editor.open_all_args(None)?;
+    } else if matches.get_flag("O") {
+        editor.open_all_args(Some(SplitDirection::Vertical))?;
+    } else if matches.get_flag("o") {
+        editor.open_all_args(Some(SplitDirection::Horizontal))?;
+    }
+
+    match editor.run() {
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Editor error: {}", e);
+            std::process::exit(1);
+        }
+    }
 
-            // 境界調整
-            editor.adjust_cursor_position().unwrap();
+    Ok(())
+}
 
-            // 正しい位置に調整されているはず
-            assert_eq!(editor.cursor.col, 5); // "Short"の長さ
+/// `rvim -r`: カレントディレクトリのスワップファイルとその情報を表示する
+fn print_swap_files() -> Result<()> {
+    println!("Swap files found:");
+    println!("   In current directory:");
+    let files = swap::list(Path::new("."))?;
+    if files.is_empty() {
+        println!("      -- none --");
+    }
+    for (index, path) in files.iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!("{}.    {}", index + 1, name);
+        match SwapInfo::read(path) {
+            Ok(info) => {
+                println!("          owned by: pid {} on {}", info.pid, info.host);
+                if let Some(modified) = info.modified {
+                    println!("             dated: {}", swap::format_time(modified));
+                }
+                println!("         file name: {}", info.file.display());
+                if info.is_running() {
+                    println!("        process ID: {} (STILL RUNNING)", info.pid);
+                }
+            }
+            Err(error) => println!("         {}", error),
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rvim::TerminalSize;
+
+    /// 端末を使わずに120x40の画面で動かす
+    fn headless() -> Terminal {
+        Terminal::headless(TerminalSize::new(120, 40))
+    }
+
+    #[test]
+    fn test_editor_creation() {
+        let editor = Editor::new(headless());
+        assert_eq!(editor.cursor.row, 0);
+        assert_eq!(editor.cursor.col, 0);
+        assert!(editor.mode_manager.current().is_normal());
+        assert!(!editor.should_quit);
+    }
+
+    #[test]
+    fn test_editor_with_content() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "Hello, World!").unwrap();
+
+        let editor = Editor::with_file(temp_file.path().to_path_buf(), headless());
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "Hello, World!");
+        assert_eq!(
+            editor.buffers.current().file_path(),
+            Some(&temp_file.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_open_special_files() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("new.rs");
+
+        // 存在しないファイルは新しいファイルとして開き、最初の保存で作る
+        let mut editor = Editor::with_file(path.clone(), headless());
+        assert_eq!(editor.buffers.current().file_path(), Some(&path));
+        let status = editor.status_line(
+            editor.tabs.layout().current(),
+            editor.buffers.current(),
+            false,
+        );
+        assert!(status.contains("new.rs [New]"));
+        editor
+            .execute_editor_command(&ExCommand::parse("w!").unwrap())
+            .unwrap();
+        assert!(path.exists());
+        assert_eq!(editor.buffers.current().file_state(), FileState::Loaded);
+
+        // 読み込み専用のバッファは`!`がなければ保存しない
+        editor.buffers.current_mut().options_mut().readonly = true;
+        assert!(editor.save_file(false).is_err());
+        editor.save_file(true).unwrap();
+
+        // デバイスは開かずに理由を表示する
+        #[cfg(unix)]
+        {
+            let editor = Editor::with_file(PathBuf::from("/dev/null"), headless());
+            assert_eq!(editor.buffers.current().file_path(), None);
+            assert!(editor.message.unwrap().contains("is not a file"));
+        }
+    }
+
+    #[test]
+    fn test_swap_files() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        let swap_path = dir.path().join(".a.txt.swp");
+        std::fs::write(&path, "one\ntwo").unwrap();
+
+        let mut editor = Editor::with_file(path.clone(), headless());
+        // スワップファイルは最初に変更を書き出すときに作る
+        editor.check_swap_files().unwrap();
+        editor.sync_swap_files();
+        assert!(!swap_path.exists());
+        editor.run_ex("1d").unwrap();
+        editor.sync_swap_files();
+        assert!(swap_path.exists());
+
+        // 別のセッションで復元する（自分のスワップファイルは使わない）
+        let mut other = Editor::with_file(path.clone(), headless());
+        other.run_ex("recover").unwrap();
+        assert_eq!(other.buffers.current().to_string(), "two");
+        assert!(other.buffers.current().is_modified());
+        assert!(other.message.take().unwrap().contains("Recovery completed"));
+        // 変更があれば`!`がなければ復元し直さない
+        assert!(other.run_ex("recover").is_err());
+        other.run_ex("recover!").unwrap();
+        assert!(editor.run_ex("recover").is_err());
+
+        editor.remove_swap_files();
+        assert!(!swap_path.exists());
+    }
+
+    #[test]
+    fn test_external_changes() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "one").unwrap();
+
+        let mut editor = Editor::with_file(path.clone(), headless());
+        // `autoread`なら変更のないバッファは確認せずに読み直す
+        std::fs::write(&path, "two\nlines").unwrap();
+        editor.run_ex("set ar").unwrap();
+        editor.run_ex("checktime").unwrap();
+        assert_eq!(editor.buffers.current().to_string(), "two\nlines");
+
+        // 外部で変更されたファイルは`!`がなければ上書きしない
+        editor.run_ex("1d").unwrap();
+        std::fs::write(&path, "three").unwrap();
+        let error = editor.save_file(false).unwrap_err();
+        assert!(error.to_string().contains("changed since reading"));
+        editor.save_file(true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "lines");
+        editor.save_file(false).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        editor.run_ex("checktime").unwrap();
+        assert!(
+            editor
+                .message
+                .take()
+                .unwrap()
+                .contains("no longer available")
+        );
+        assert!(editor.buffers.current().stamp().is_none());
+    }
+
+    #[test]
+    fn test_write_commands() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+
+        let mut editor = Editor::new(headless());
+        editor
+            .buffers
+            .current_mut()
+            .replace_lines(0, 0, vec!["one".into(), "two".into(), "three".into()])
+            .unwrap();
+        // 名前のないバッファは書き込んだファイルの名前になる
+        let error = editor.run_ex("w").unwrap_err();
+        assert!(error.to_string().contains("No file name"));
+        // 既存のファイルには`!`なしでは書き込まず、名前も変えない
+        std::fs::write(path("exists.txt"), "existing").unwrap();
+        let error = editor
+            .run_ex(&format!("w {}", path("exists.txt").display()))
+            .unwrap_err();
+        assert!(error.to_string().contains("File exists"));
+        assert_eq!(read("exists.txt"), "existing");
+        assert!(editor.buffers.current().file_path().is_none());
+        editor
+            .run_ex(&format!("w {}", path("a.txt").display()))
+            .unwrap();
+        assert_eq!(editor.buffers.current().file_path(), Some(&path("a.txt")));
+        assert!(!editor.buffers.current().is_modified());
+
+        // 範囲の書き出しと追加では名前を変えない
+        editor
+            .run_ex(&format!("2,3w {}", path("b.txt").display()))
+            .unwrap();
+        assert_eq!(read("b.txt"), "two\nthree");
+        assert!(
+            editor
+                .run_ex(&format!("1w {}", path("b.txt").display()))
+                .is_err()
+        );
+        editor
+            .run_ex(&format!("1w >> {}", path("b.txt").display()))
+            .unwrap();
+        assert_eq!(read("b.txt"), "two\nthree\none");
+        assert!(editor.run_ex("1w").is_err());
+        editor
+            .run_ex(&format!("w !cat > {}", path("c.txt").display()))
+            .unwrap();
+        assert_eq!(read("c.txt"), "one\ntwo\nthree\n");
+
+        // `:update`と`:x`は変更があるときだけ書き込む
+        std::fs::write(path("a.txt"), "external").unwrap();
+        editor.run_ex("update").unwrap();
+        assert_eq!(read("a.txt"), "external");
+        editor.run_ex("1d").unwrap();
+        assert!(editor.run_ex("update").is_err());
+        editor.run_ex("w!").unwrap();
+        assert_eq!(read("a.txt"), "two\nthree");
+
+        editor
+            .run_ex(&format!("saveas {}", path("d.txt").display()))
+            .unwrap();
+        assert_eq!(editor.buffers.current().file_path(), Some(&path("d.txt")));
+        assert_eq!(read("d.txt"), "two\nthree");
+
+        // `:wa`は保存できないバッファがあっても残りを保存する
+        editor.run_ex("1d").unwrap();
+        editor.options.hidden = true;
+        editor.run_ex("enew").unwrap();
+        editor
+            .buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+        let error = editor.run_ex("wa").unwrap_err();
+        assert!(error.to_string().contains("No file name for buffer"));
+        assert_eq!(read("d.txt"), "three");
+
+        editor
+            .run_ex(&format!("w {}", path("e.txt").display()))
+            .unwrap();
+        editor.run_ex("xa").unwrap();
+        assert!(editor.should_quit);
+    }
+
+    #[test]
+    fn test_file_watching() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "one").unwrap();
+
+        for watcher in [Watcher::new(), Watcher::polling()] {
+            let mut editor = Editor::with_file(path.clone(), headless());
+            editor.watcher = watcher;
+            editor.options.autoread = true;
+            editor.update_watcher();
+
+            std::fs::write(&path, "two\nlines").unwrap();
+            editor.handle_watch_events().unwrap();
+            assert_eq!(editor.buffers.current().to_string(), "two\nlines");
+
+            // 削除されたファイルはステータスラインに表示し、作り直されたら読み直す
+            std::fs::remove_file(&path).unwrap();
+            editor.handle_watch_events().unwrap();
+            let status = editor.status_line(
+                editor.tabs.layout().current(),
+                editor.buffers.current(),
+                true,
+            );
+            assert!(status.contains("[Deleted]"));
+
+            std::fs::write(&path, "one").unwrap();
+            editor.handle_watch_events().unwrap();
+            assert_eq!(editor.buffers.current().to_string(), "one");
+            assert_eq!(editor.buffers.current().file_state(), FileState::Loaded);
+        }
+    }
+
+    #[test]
+    fn test_scroll_options() {
+        let mut editor = Editor::new(headless());
+        let content: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        *editor.buffers.current_mut() = Buffer::from_content(&content.join("\n"));
+        editor.cursor = Position::new(50, 0);
+        editor.render().unwrap();
+        assert_eq!(editor.viewport.bottom(), 55);
+
+        editor.run_ex("set so=0 siso=3").unwrap();
+        assert_eq!(
+            (editor.viewport.scrolloff, editor.viewport.sidescrolloff),
+            (0, 3)
+        );
+        editor.cursor = Position::new(70, 0);
+        editor.render().unwrap();
+        assert_eq!(editor.viewport.bottom(), 70);
+
+        // 分割した他のウィンドウにも適用する
+        editor.run_ex("sp").unwrap();
+        editor.render().unwrap();
+        assert!(
+            editor
+                .tabs
+                .layout()
+                .windows()
+                .all(|window| window.viewport.scrolloff == 0)
+        );
+    }
+
+    #[test]
+    fn test_curswant_through_blank_line() {
+        let mut editor = Editor::new(headless());
+        *editor.buffers.current_mut() = Buffer::from_content("Hello, World!\n\nAnother line");
+        editor.cursor = Position::new(0, 7);
+        editor.update_curswant(&VimCommand::MoveRight);
+
+        for _ in 0..2 {
+            VimCommand::MoveDown
+                .execute(editor.buffers.current_mut(), &mut editor.cursor)
+                .unwrap();
+            editor.update_curswant(&VimCommand::MoveDown);
+        }
+        assert_eq!(editor.cursor, Position::new(2, 7));
+    }
+
+    #[test]
+    fn test_replace_mode_undo() {
+        let mut editor = Editor::new(headless());
+        *editor.buffers.current_mut() = Buffer::from_content("abcd");
+        let keys = |editor: &mut Editor, keys: &[Key]| {
+            for key in keys {
+                editor.handle_input(key.clone()).unwrap();
+            }
+        };
+
+        // 1回の`R…<Esc>`は1回の`u`で戻す
+        keys(
+            &mut editor,
+            &[
+                Key::char('R'),
+                Key::char('x'),
+                Key::char('y'),
+                Key::escape(),
+            ],
+        );
+        assert_eq!(editor.buffers.current().to_string(), "xycd");
+        keys(&mut editor, &[Key::char('u')]);
+        assert_eq!(editor.buffers.current().to_string(), "abcd");
+        assert!(!editor.buffers.current().is_modified());
+
+        // Ctrlキーは置き換えない
+        keys(
+            &mut editor,
+            &[Key::char('R'), Key::ctrl('w'), Key::escape()],
+        );
+        assert_eq!(editor.buffers.current().to_string(), "abcd");
+    }
+
+    #[test]
+    fn test_ex_error_message() {
+        let mut editor = Editor::new(headless());
+        *editor.buffers.current_mut() = Buffer::from_content("a\nb");
+
+        // 失敗したExコマンドの理由はメッセージとして表示し、Normalモードに戻る
+        for key in ":'<,'>d".chars().map(Key::char).chain([Key::enter()]) {
+            editor.handle_input(key).unwrap();
+        }
+        assert!(editor.message.take().is_some());
+        assert!(editor.mode_manager.current().is_normal());
+        assert_eq!(editor.buffers.current().line_count(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_force_quit_error_message() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "one").unwrap();
+        let mut editor = Editor::new(headless());
+        editor.run_ex("sp").unwrap();
+        editor.run_ex(&format!("e {}", path.display())).unwrap();
+        editor
+            .buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+
+        // 変更を捨てるための読み直しに失敗しても、エディタは終了せずメッセージを表示する
+        std::fs::remove_file(&path).unwrap();
+        let _socket = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        for key in [Key::char('Z'), Key::char('Q')] {
+            editor.handle_input(key).unwrap();
+        }
+        assert!(editor.message.take().is_some());
+        assert!(!editor.should_quit);
+        assert_eq!(editor.tabs.layout().count(), 2);
+    }
+
+    #[test]
+    fn test_buffer_commands() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("other.txt");
+        std::fs::write(&path, "other\nfile").unwrap();
+        let run = |editor: &mut Editor, input: &str| {
+            editor.execute_editor_command(&ExCommand::parse(input).unwrap())
+        };
+
+        let mut editor = Editor::new(headless());
+        editor
+            .buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+
+        // 変更があるバッファは`hidden`か`!`がなければ離れられない
+        let edit = format!("e {}", path.display());
+        assert!(run(&mut editor, &edit).is_err());
+        run(&mut editor, "set hidden").unwrap();
+        run(&mut editor, &edit).unwrap();
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "other");
+        editor.cursor = Position::new(1, 2);
+
+        run(&mut editor, "b #").unwrap();
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "x");
+        run(&mut editor, "bn").unwrap();
+        assert_eq!(editor.cursor, Position::new(1, 2));
+
+        run(&mut editor, "ls").unwrap();
+        assert_eq!(editor.message.as_ref().unwrap().lines().count(), 2);
+
+        run(&mut editor, "bw").unwrap();
+        assert_eq!(editor.buffers.current_number(), 1);
+        assert!(run(&mut editor, "b 2").is_err());
+    }
+
+    #[test]
+    fn test_arg_list() {
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let files: Vec<PathBuf> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, format!("{}\nkeep", name)).unwrap();
+                path
+            })
+            .collect();
+        let run = |editor: &mut Editor, input: &str| {
+            editor.execute_editor_command(&ExCommand::parse(input).unwrap())
+        };
+
+        let mut editor = Editor::with_files(files.clone(), headless());
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "a.txt");
+        run(&mut editor, "next").unwrap();
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "b.txt");
+        run(&mut editor, "args").unwrap();
+        let message = editor.message.clone().unwrap();
+        assert!(message.contains(&format!("[{}]", files[1].display())));
+        assert!(run(&mut editor, "2next").is_err());
+        run(&mut editor, "last").unwrap();
+        run(&mut editor, "prev 2").unwrap();
+        assert_eq!(editor.arg_list.index(), 0);
+
+        run(&mut editor, "argdelete *b.txt").unwrap();
+        run(&mut editor, &format!("$argadd {}", files[1].display())).unwrap();
+        assert_eq!(
+            editor.arg_list.files(),
+            [files[0].clone(), files[2].clone(), files[1].clone()]
+        );
+
+        // 各ファイルでコマンドを実行し、変更を保存する
+        run(&mut editor, "argdo 1d").unwrap();
+        assert_eq!(editor.arg_list.index(), 2);
+        for path in &files {
+            assert_eq!(std::fs::read_to_string(path).unwrap(), "keep");
+        }
+        run(&mut editor, "bufdo 1d").unwrap();
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), "");
+    }
+
+    #[test]
+    fn test_window_commands() {
+        let run = |editor: &mut Editor, input: &str| {
+            editor.execute_editor_command(&ExCommand::parse(input).unwrap())
+        };
+
+        let mut editor = Editor::new(headless());
+        *editor.buffers.current_mut() = Buffer::from_content("one\ntwo\nthree");
+        editor.cursor = Position::new(2, 0);
+
+        // 分割したウィンドウは同じバッファを表示し、カーソルはウィンドウごとに持つ
+        run(&mut editor, "sp").unwrap();
+        assert_eq!(editor.tabs.layout().count(), 2);
+        editor.cursor = Position::new(0, 1);
+        run(&mut editor, "wincmd j").unwrap();
+        assert_eq!(editor.cursor, Position::new(2, 0));
+        run(&mut editor, "wincmd p").unwrap();
+        assert_eq!(editor.cursor, Position::new(0, 1));
+
+        // 変更があっても他のウィンドウに表示していれば切り替えられる
+        editor
+            .buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+        run(&mut editor, "enew").unwrap();
+        assert_eq!(editor.buffers.current().to_string(), "");
+        run(&mut editor, "vnew").unwrap();
+        assert_eq!(editor.tabs.layout().count(), 3);
+        editor.render().unwrap();
+
+        // 変更のあるバッファのウィンドウは`!`がなければ閉じない（バッファは隠れる）
+        run(&mut editor, "close").unwrap();
+        assert!(run(&mut editor, "only").is_err());
+        assert_eq!(editor.tabs.layout().count(), 2);
+        run(&mut editor, "only!").unwrap();
+        assert_eq!(editor.tabs.layout().count(), 1);
+        assert!(run(&mut editor, "close").is_err());
+        run(&mut editor, "b 1").unwrap();
+        assert_eq!(editor.buffers.current().line(0).unwrap(), "xone");
+    }
+
+    #[test]
+    fn test_tab_pages() {
+        let run = |editor: &mut Editor, input: &str| {
+            editor.execute_editor_command(&ExCommand::parse(input).unwrap())
+        };
+
+        let mut editor = Editor::new(headless());
+        *editor.buffers.current_mut() = Buffer::from_content("one\ntwo");
+        editor.cursor = Position::new(1, 0);
+        let area = editor.window_area().unwrap();
+
+        // タブページが2つになるとタブラインの分だけウィンドウの領域が狭くなる
+        run(&mut editor, "tabnew").unwrap();
+        assert_eq!(editor.tabs.count(), 2);
+        assert_eq!(editor.tabs.index(), 1);
+        assert_eq!(editor.buffers.current().to_string(), "");
+        assert_eq!(editor.window_area().unwrap().row, area.row + 1);
+        run(&mut editor, "set stal=0").unwrap();
+        assert_eq!(editor.window_area().unwrap(), area);
+        run(&mut editor, "set stal=2").unwrap();
+        editor.render().unwrap();
+
+        // タブページごとにウィンドウの配置とカーソルを持つ
+        run(&mut editor, "sp").unwrap();
+        run(&mut editor, "tabnext 1").unwrap();
+        assert_eq!(editor.tabs.layout().count(), 1);
+        assert_eq!(editor.cursor, Position::new(1, 0));
+        run(&mut editor, "tabnext").unwrap();
+        assert_eq!(editor.tabs.layout().count(), 2);
+
+        // 変更のあるバッファだけを表示しているタブページは`!`がなければ閉じない
+        editor
+            .buffers
+            .current_mut()
+            .insert_char(Position::origin(), 'x')
+            .unwrap();
+        assert!(run(&mut editor, "tabclose").is_err());
+        run(&mut editor, "0tabnew").unwrap();
+        assert_eq!(editor.tabs.index(), 0);
+        run(&mut editor, "tabmove").unwrap();
+        assert_eq!(editor.tabs.index(), 2);
+        run(&mut editor, "tabmove -1").unwrap();
+        assert_eq!(editor.tabs.index(), 1);
+        assert!(run(&mut editor, "tabonly").is_err());
+        assert_eq!(editor.tabs.count(), 2);
+
+        // 最後のウィンドウを閉じるとタブページも閉じる
+        run(&mut editor, "tabfirst").unwrap();
+        run(&mut editor, "close").unwrap();
+        assert_eq!(editor.tabs.count(), 1);
+        assert_eq!(editor.buffers.current().to_string(), "x");
+        assert!(run(&mut editor, "tabonly").is_ok());
+    }
+
+    #[test]
+    fn test_cursor_adjustment() {
+        let mut editor = Editor::new(headless());
+        // バッファに内容を設定
+        *editor.buffers.current_mut() = Buffer::from_content("Short\nLonger line");
+
+        // カーソルを無効な位置に設定
+        editor.cursor = Position::new(0, 10);
+
+        // 境界調整
+        editor.adjust_cursor_position().unwrap();
+
+        // 正しい位置に調整されているはず
+        assert_eq!(editor.cursor.col, 5); // "Short"の長さ
+    }
 }
 
 #[cfg(test)]
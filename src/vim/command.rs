@@ -1,5 +1,36 @@
-use crate::editor::{Buffer, Position};
-use crate::error::Result;
+use crate::editor::format;
+use crate::editor::motion;
+use crate::editor::text_object::{self, TextObject, TextRange};
+use crate::editor::{Buffer, GlobalOptions, Position};
+use crate::error::{EditorError, Result};
+use crate::io::Shell;
+use crate::vim::completion::CompletionKind;
+use crate::vim::ex::ExCommand;
+use crate::vim::increment;
+use crate::vim::insert::{self, InsertEntry};
+use crate::vim::register::Register;
+use crate::vim::replace;
+use crate::vim::sort::{self, SortOptions};
+use crate::vim::visual::VisualAction;
+
+/// `d`/`c`/`y`などテキスト範囲に作用するオペレータ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    ShiftRight,
+    ShiftLeft,
+    ToggleCase,
+    Lowercase,
+    Uppercase,
+    /// `gq`: 行を整形してカーソルを最後の行に移す
+    Format,
+    /// `gw`: 行を整形してカーソルを元の位置に残す
+    FormatKeepCursor,
+    /// `!`: 範囲を入力済みのコマンドラインを開き、外部コマンドで行を置き換える
+    Filter,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum VimCommand {
@@ -8,27 +39,132 @@ pub enum VimCommand {
     MoveDown,
     MoveUp,
     MoveRight,
+    MoveDisplayDown,
+    MoveDisplayUp,
     MoveLineStart,
     MoveLineEnd,
     MoveBufferStart,
     MoveBufferEnd,
     MoveWordForward,
     MoveWordBackward,
+    /// `e`: 単語の末尾へ移動する
+    MoveWordEnd,
+    /// `}`/`{`: 段落の後ろ・前の空行へ移動する
+    MoveParagraphForward,
+    MoveParagraphBackward,
+    MoveScreenTop,
+    MoveScreenMiddle,
+    MoveScreenBottom,
+
+    // Scrolling
+    ScrollPageDown,
+    ScrollPageUp,
+    ScrollHalfPageDown,
+    ScrollHalfPageUp,
+    ScrollLineDown,
+    ScrollLineUp,
+    ScrollCursorCenter,
+    ScrollCursorTop,
+    ScrollCursorBottom,
 
     // Editing commands
     InsertChar(char),
+    InsertTab,
     DeleteChar,
     DeleteCharBackward, // Backspace用
+    DeleteCharForward,  // Insertモードの Delete用
+    DeleteWordBackward,
+    DeleteToLineStart,
+    ShiftIndentRight,
+    ShiftIndentLeft,
     DeleteLine,
+    YankLine,
     NewLine,
+    ReplaceChars {
+        ch: char,
+        count: usize,
+    },
+    /// `s`: カウント分の文字を削除してInsertモードに入る
+    SubstituteChars {
+        count: usize,
+    },
+    /// `S`/`cc`: カウント分の行を空にしてInsertモードに入る
+    ChangeLines {
+        count: usize,
+    },
+    /// `C`: 行末（カウント分先の行の行末）まで削除してInsertモードに入る
+    ChangeToLineEnd {
+        count: usize,
+    },
+    OperateTextObject {
+        operator: Operator,
+        object: TextObject,
+        count: usize,
+    },
+    /// `~`（`notildeop`）: カーソルからカウント分の文字の大文字・小文字を切り替える
+    ToggleCaseChars {
+        count: usize,
+    },
+    /// `Ctrl-A`/`Ctrl-X`: カーソル以降の数値に`amount`を加える
+    AddToNumber {
+        amount: i64,
+    },
+    /// `J`/`gJ`: カウント分の行（最低2行）を連結する
+    JoinLines {
+        count: usize,
+        spaces: bool,
+    },
+    /// `>>`/`<<`: カウント分の行にオペレータを適用する
+    OperateLines {
+        operator: Operator,
+        count: usize,
+    },
+    /// `>j`/`<G`など: カウント分の移動コマンドで動く範囲にオペレータを適用する
+    OperateMotion {
+        operator: Operator,
+        motion: Box<VimCommand>,
+        count: usize,
+    },
 
     // Mode transitions
-    EnterInsert,
-    EnterInsertAfter,
-    EnterInsertNewLine,
+    EnterInsert {
+        entry: InsertEntry,
+        count: usize,
+    },
+    EnterReplace,
+    /// `Ctrl-O`: Normalモードのコマンドを1つ実行してInsertモードに戻る
+    InsertNormalCommand,
     EnterVisual,
+    EnterVisualLine,
+    EnterVisualBlock,
     EnterCommand,
     ExitToNormal,
+    ReselectVisual,
+    SelectTextObject {
+        object: TextObject,
+        count: usize,
+    },
+
+    // Insert mode completion
+    Complete {
+        kind: CompletionKind,
+        forward: bool,
+    },
+    CompleteCancel,
+    CompleteAccept,
+
+    // Replace mode operations
+    ReplaceChar(char),
+    ReplaceNewLine,
+    ReplaceBackspace,
+
+    // Visual mode operations
+    VisualSwapEnds,
+    VisualSwapCorner,
+    VisualOperate {
+        action: VisualAction,
+        count: usize,
+    },
 
     // File operations
     Save,
@@ -56,7 +192,8 @@ impl VimCommand {
                 cursor.move_left().ok(); // エラーを無視して境界で停止
                 Ok(CommandResult::None)
             }
-            VimCommand::MoveDown => {
+            VimCommand::MoveDown | VimCommand::MoveDisplayDown => {
+                // 折り返し表示がないため、表示行の移動は実際の行移動と同じ
                 let total_lines = buffer.line_count();
                 cursor.move_down(total_lines).ok(); // エラーを無視して境界で停止
 
@@ -66,7 +203,7 @@ impl VimCommand {
                 }
                 Ok(CommandResult::None)
             }
-            VimCommand::MoveUp => {
+            VimCommand::MoveUp | VimCommand::MoveDisplayUp => {
                 cursor.move_up().ok(); // エラーを無視して境界で停止
 
                 // 新しい行の長さに合わせてカーソル位置を調整
@@ -105,9 +242,28 @@ impl VimCommand {
                 cursor.move_to_buffer_end(total_lines, last_line_length);
                 Ok(CommandResult::None)
             }
+            VimCommand::MoveWordForward => {
+                *cursor = motion::word_forward(buffer, *cursor);
+                Ok(CommandResult::None)
+            }
+            VimCommand::MoveWordEnd => {
+                *cursor = motion::word_end(buffer, *cursor);
+                Ok(CommandResult::None)
+            }
+            VimCommand::MoveWordBackward => {
+                *cursor = motion::word_backward(buffer, *cursor);
+                Ok(CommandResult::None)
+            }
+            VimCommand::MoveParagraphForward => {
+                *cursor = motion::paragraph_forward(buffer, cursor.row);
+                Ok(CommandResult::None)
+            }
+            VimCommand::MoveParagraphBackward => {
+                *cursor = motion::paragraph_backward(buffer, cursor.row);
+                Ok(CommandResult::None)
+            }
             VimCommand::InsertChar(ch) => {
-                buffer.insert_char(*cursor, *ch)?;
-                cursor.move_right(buffer.line_length(cursor.row)?).ok();
+                insert::insert_char(buffer, cursor, *ch)?;
                 Ok(CommandResult::None)
             }
             VimCommand::DeleteChar => {
@@ -117,9 +273,15 @@ impl VimCommand {
                     Ok(CommandResult::None)
                 }
             }
+            VimCommand::InsertTab => {
+                insert::insert_tab(buffer, cursor)?;
+                Ok(CommandResult::None)
+            }
             VimCommand::DeleteCharBackward => {
                 // Backspace: カーソルの左の文字を削除してカーソルを左に移動
-                if cursor.col > 0 {
+                if insert::delete_soft_tab(buffer, cursor)? {
+                    Ok(CommandResult::None)
+                } else if cursor.col > 0 {
                     cursor.move_left().ok();
                     if let Some(deleted) = buffer.delete_char(*cursor)? {
                         Ok(CommandResult::DeletedChar(deleted))
@@ -128,20 +290,8 @@ impl VimCommand {
                     }
                 } else if cursor.row > 0 {
                     // 行の先頭で前の行と結合
-                    let current_line = buffer.line(cursor.row)?.to_string();
-                    let prev_line_length = buffer.line_length(cursor.row - 1)?;
-                    
-                    buffer.delete_line(cursor.row)?;
                     cursor.row -= 1;
-                    cursor.col = prev_line_length;
-                    
-                    // 削除した行の内容を前の行に追加
-                    for ch in current_line.chars() {
-                        buffer.insert_char(*cursor, ch)?;
-                        cursor.move_right(buffer.line_length(cursor.row)?).ok();
-                    }
-                    cursor.col = prev_line_length; // カーソルを結合位置に戻す
-                    
+                    cursor.col = buffer.join_lines(cursor.row, cursor.row + 1, false)?;
                     Ok(CommandResult::None)
                 } else {
                     Ok(CommandResult::None)
@@ -162,10 +312,45 @@ impl VimCommand {
                     Ok(CommandResult::None)
                 }
             }
+            VimCommand::YankLine => {
+                let line = buffer.line(cursor.row)?;
+                Ok(CommandResult::Yanked(Register::linewise(line)))
+            }
+            VimCommand::OperateTextObject {
+                operator,
+                object,
+                count,
+            } => match text_object::select(buffer, *cursor, *object, *count) {
+                Some(range) => apply_operator(*operator, range, buffer, cursor),
+                None => Ok(CommandResult::None),
+            },
             VimCommand::NewLine => {
-                buffer.insert_line(cursor.row + 1)?;
-                cursor.row += 1;
-                cursor.col = 0;
+                insert::break_line(buffer, cursor)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::DeleteCharForward => {
+                insert::delete_char_at(buffer, *cursor)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::DeleteWordBackward => {
+                insert::delete_word_before(buffer, cursor)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::DeleteToLineStart => {
+                insert::delete_line_before(buffer, cursor)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::ShiftIndentRight | VimCommand::ShiftIndentLeft => {
+                let amount = if *self == VimCommand::ShiftIndentRight {
+                    1
+                } else {
+                    -1
+                };
+                insert::shift_indent(buffer, cursor, amount)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::ReplaceChars { ch, count } => {
+                replace::replace_chars(buffer, cursor, *ch, *count)?;
                 Ok(CommandResult::None)
             }
             VimCommand::Undo => {
@@ -176,15 +361,159 @@ impl VimCommand {
                 buffer.redo()?;
                 Ok(CommandResult::None)
             }
-            VimCommand::EnterInsert
-            | VimCommand::EnterInsertAfter
-            | VimCommand::EnterInsertNewLine
+            VimCommand::EnterInsert { entry, .. } => {
+                entry.prepare(buffer, cursor)?;
+                // 入力したテキストの繰り返しはエディタ側で記録する
+                Ok(CommandResult::ModeTransition)
+            }
+            VimCommand::SubstituteChars { count } => {
+                let line = buffer.line(cursor.row)?;
+                let len: usize = line
+                    .get(cursor.col..)
+                    .unwrap_or("")
+                    .chars()
+                    .take(*count)
+                    .map(char::len_utf8)
+                    .sum();
+                let end = Position::new(cursor.row, cursor.col + len);
+                apply_operator(
+                    Operator::Change,
+                    TextRange::charwise(*cursor, end),
+                    buffer,
+                    cursor,
+                )
+            }
+            VimCommand::ChangeLines { count } => {
+                let end = (cursor.row + count.saturating_sub(1)).min(buffer.line_count() - 1);
+                apply_operator(
+                    Operator::Change,
+                    TextRange::lines(cursor.row, end),
+                    buffer,
+                    cursor,
+                )
+            }
+            VimCommand::ToggleCaseChars { count } => {
+                let line = buffer.line(cursor.row)?;
+                let rest = line.get(cursor.col..).unwrap_or("");
+                let Some((last, ch)) = rest.char_indices().take(*count).last() else {
+                    return Ok(CommandResult::None);
+                };
+                let end = cursor.col + last + ch.len_utf8();
+                let len = line.len();
+                transform_span(buffer, cursor.row, cursor.col, end, |text| {
+                    convert_case(Operator::ToggleCase, text)
+                })?;
+                // 変換した文字の次へ進む（行末では最後の文字に留まる）
+                let end = end + buffer.line_length(cursor.row)? - len;
+                cursor.col = if end < buffer.line_length(cursor.row)? {
+                    end
+                } else {
+                    let line = buffer.line(cursor.row)?;
+                    line[..end]
+                        .char_indices()
+                        .last()
+                        .map_or(0, |(index, _)| index)
+                };
+                Ok(CommandResult::None)
+            }
+            VimCommand::AddToNumber { amount } => {
+                let line = buffer.line(cursor.row)?;
+                let nrformats = &buffer.options().nrformats;
+                if let Some((start, end, text)) =
+                    increment::add(line, cursor.col, *amount, nrformats)
+                {
+                    buffer.replace_str(Position::new(cursor.row, start), end - start, &text)?;
+                    // カーソルは数値の最後の文字の上
+                    cursor.col = start + text.len() - 1;
+                }
+                Ok(CommandResult::None)
+            }
+            VimCommand::JoinLines { count, spaces } => {
+                let end = cursor.row + (*count).max(2) - 1;
+                cursor.col = buffer.join_lines(cursor.row, end, *spaces)?;
+                Ok(CommandResult::None)
+            }
+            VimCommand::OperateLines { operator, count } => {
+                let end = (cursor.row + count.saturating_sub(1)).min(buffer.line_count() - 1);
+                apply_operator(*operator, TextRange::lines(cursor.row, end), buffer, cursor)
+            }
+            VimCommand::OperateMotion {
+                operator,
+                motion,
+                count,
+            } => {
+                // `cw`は単語の後ろの空白を残す（`ce`と同じ）
+                let on_word = buffer
+                    .line(cursor.row)?
+                    .get(cursor.col..)
+                    .and_then(|rest| rest.chars().next())
+                    .is_some_and(|ch| !ch.is_whitespace());
+                let motion = match **motion {
+                    VimCommand::MoveWordForward if *operator == Operator::Change && on_word => {
+                        &VimCommand::MoveWordEnd
+                    }
+                    ref motion => motion,
+                };
+                match motion_range(motion, *count, buffer, *cursor)? {
+                    Some(range) => apply_operator(*operator, range, buffer, cursor),
+                    None => Ok(CommandResult::None),
+                }
+            }
+            VimCommand::ChangeToLineEnd { count } => {
+                let row = (cursor.row + count.saturating_sub(1)).min(buffer.line_count() - 1);
+                let end = Position::new(row, buffer.line_length(row)?);
+                apply_operator(
+                    Operator::Change,
+                    TextRange::charwise(*cursor, end),
+                    buffer,
+                    cursor,
+                )
+            }
+            VimCommand::EnterReplace
+            | VimCommand::InsertNormalCommand
             | VimCommand::EnterVisual
+            | VimCommand::EnterVisualLine
+            | VimCommand::EnterVisualBlock
             | VimCommand::EnterCommand
-            | VimCommand::ExitToNormal => {
+            | VimCommand::ExitToNormal
+            | VimCommand::ReselectVisual
+            | VimCommand::SelectTextObject { .. } => {
                 // Mode transitions are handled by the mode manager
                 Ok(CommandResult::ModeTransition)
             }
+            VimCommand::MoveScreenTop
+            | VimCommand::MoveScreenMiddle
+            | VimCommand::MoveScreenBottom
+            | VimCommand::ScrollPageDown
+            | VimCommand::ScrollPageUp
+            | VimCommand::ScrollHalfPageDown
+            | VimCommand::ScrollHalfPageUp
+            | VimCommand::ScrollLineDown
+            | VimCommand::ScrollLineUp
+            | VimCommand::ScrollCursorCenter
+            | VimCommand::ScrollCursorTop
+            | VimCommand::ScrollCursorBottom => {
+                // 画面位置に依存するため、ビューポートを持つエディタ側で処理する
+                Ok(CommandResult::ScrollRequested)
+            }
+            VimCommand::Complete { .. }
+            | VimCommand::CompleteCancel
+            | VimCommand::CompleteAccept => {
+                // 候補の一覧は表示中のポップアップと共にエディタ側で管理する
+                Ok(CommandResult::CompletionRequested)
+            }
+            VimCommand::ReplaceChar(_)
+            | VimCommand::ReplaceNewLine
+            | VimCommand::ReplaceBackspace => {
+                // 上書きした内容はモードが記録しているため、エディタ側で処理する
+                Ok(CommandResult::ReplaceRequested)
+            }
+            VimCommand::VisualSwapEnds
+            | VimCommand::VisualSwapCorner
+            | VimCommand::VisualOperate { .. } => {
+                // 選択範囲はモードが持っているため、エディタ側で処理する
+                Ok(CommandResult::VisualRequested)
+            }
             VimCommand::Save => Ok(CommandResult::SaveRequested),
             VimCommand::Quit => Ok(CommandResult::QuitRequested),
             VimCommand::SaveAndQuit => Ok(CommandResult::SaveAndQuitRequested),
@@ -194,21 +523,400 @@ impl VimCommand {
                 Ok(CommandResult::ModeTransition)
             }
             VimCommand::ExecuteCommand(cmd) => {
-                // Parse and execute command
-                match cmd.as_str() {
-                    "q" => Ok(CommandResult::QuitRequested),
-                    "w" => Ok(CommandResult::SaveRequested),
-                    "wq" => Ok(CommandResult::SaveAndQuitRequested),
-                    "q!" => Ok(CommandResult::ForceQuitRequested),
-                    _ => Ok(CommandResult::None), // Unknown command
+                // 解釈できない範囲や実行の失敗はエディタ側でメッセージとして表示する
+                execute_ex(&ExCommand::parse(cmd)?, buffer, cursor)
+            }
+            VimCommand::Noop => Ok(CommandResult::None),
+        }
+    }
+
+    /// オペレータの範囲に使える移動コマンドか
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            VimCommand::MoveLeft
+                | VimCommand::MoveDown
+                | VimCommand::MoveUp
+                | VimCommand::MoveRight
+                | VimCommand::MoveLineStart
+                | VimCommand::MoveLineEnd
+                | VimCommand::MoveBufferStart
+                | VimCommand::MoveBufferEnd
+                | VimCommand::MoveWordForward
+                | VimCommand::MoveWordBackward
+                | VimCommand::MoveWordEnd
+                | VimCommand::MoveParagraphForward
+                | VimCommand::MoveParagraphBackward
+        )
+    }
+
+    /// 希望列（curswant）を維持したまま行を移動するコマンドか
+    pub fn is_vertical_motion(&self) -> bool {
+        matches!(
+            self,
+            VimCommand::MoveDown
+                | VimCommand::MoveUp
+                | VimCommand::MoveDisplayDown
+                | VimCommand::MoveDisplayUp
+                | VimCommand::MoveBufferStart
+                | VimCommand::MoveBufferEnd
+                | VimCommand::ScrollPageDown
+                | VimCommand::ScrollPageUp
+                | VimCommand::ScrollHalfPageDown
+                | VimCommand::ScrollHalfPageUp
+                | VimCommand::ScrollLineDown
+                | VimCommand::ScrollLineUp
+        )
+    }
+}
+
+/// Exコマンドを実行する
+fn execute_ex(
+    command: &ExCommand,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+) -> Result<CommandResult> {
+    // バッファの切り替えなどエディタ全体に関わるコマンドはエディタ側で実行する
+    if command.is_editor_command() {
+        return Ok(CommandResult::EditorCommand(command.clone()));
+    }
+
+    let range = command.line_range(buffer, cursor.row)?;
+    let (start, end) = range.unwrap_or((cursor.row, cursor.row));
+
+    match command.name.as_str() {
+        // `:12`や`:'>`のように範囲のみの場合はその最終行へ移動する
+        "" => {
+            if range.is_some() {
+                cursor.row = end;
+                cursor.col = buffer.first_non_blank(end)?;
+            }
+            Ok(CommandResult::None)
+        }
+        "q" | "quit" if command.bang => Ok(CommandResult::ForceQuitRequested),
+        "q" | "quit" => Ok(CommandResult::QuitRequested),
+        // `:w!`・`:w {file}`・`:[range]w`などはエディタ側で実行する
+        "w" | "write" if command.bang || range.is_some() || !command.args.trim().is_empty() => {
+            Ok(CommandResult::EditorCommand(command.clone()))
+        }
+        "w" | "write" => Ok(CommandResult::SaveRequested),
+        "d" | "delete" => apply_operator(
+            Operator::Delete,
+            TextRange::lines(start, end),
+            buffer,
+            cursor,
+        ),
+        "y" | "yank" => {
+            // ヤンクではカーソルを動かさない
+            let mut position = *cursor;
+            apply_operator(
+                Operator::Yank,
+                TextRange::lines(start, end),
+                buffer,
+                &mut position,
+            )
+        }
+        "ret" | "retab" => {
+            let tabstop = match command.args.trim() {
+                "" => None,
+                arg => Some(
+                    arg.parse()
+                        .map_err(|_| EditorError::config(format!("Invalid argument: {}", arg)))?,
+                ),
+            };
+            let (start, end) = range.unwrap_or((0, buffer.line_count() - 1));
+            buffer.retab(start, end, tabstop, command.bang)?;
+            Ok(CommandResult::None)
+        }
+        "j" | "join" => {
+            // `:[range]j [count]`は範囲の最終行から`count`行を連結する
+            let (start, end) = match command.args.trim() {
+                "" if start == end => (start, end + 1),
+                "" => (start, end),
+                arg => {
+                    let count: usize = arg
+                        .parse()
+                        .map_err(|_| EditorError::config(format!("Invalid argument: {}", arg)))?;
+                    (end, end + count.max(2) - 1)
                 }
+            };
+            buffer.join_lines(start, end, !command.bang)?;
+            cursor.row = start;
+            cursor.col = buffer.first_non_blank(start)?;
+            Ok(CommandResult::None)
+        }
+        "sor" | "sort" => {
+            let options = SortOptions::parse(&command.args, command.bang)?;
+            let (start, end) = range.unwrap_or((0, buffer.line_count() - 1));
+            sort::sort_lines(buffer, start, end, &options)?;
+            Ok(CommandResult::None)
+        }
+        // `:{range}!cmd`: 範囲の行を外部コマンドの出力で置き換える
+        "!" if range.is_some() && !command.args.is_empty() => {
+            let input = (start..=end)
+                .map(|row| buffer.line(row).map(|line| format!("{}\n", line)))
+                .collect::<Result<String>>()?;
+            let output = Shell::filter(&command.args, &input)?;
+            let lines = output.lines().map(str::to_string).collect();
+            buffer.replace_lines(start, end, lines)?;
+            cursor.row = start.min(buffer.line_count() - 1);
+            cursor.col = buffer.first_non_blank(cursor.row)?;
+            Ok(CommandResult::None)
+        }
+        "se" | "set" => {
+            // `hidden`などエディタ全体のオプションはエディタ側で設定する
+            let (global, local): (Vec<&str>, Vec<&str>) = command
+                .args
+                .split_whitespace()
+                .partition(|arg| GlobalOptions::is_global(arg));
+            for arg in local {
+                buffer.options_mut().set(arg)?;
             }
-            VimCommand::MoveWordForward | VimCommand::MoveWordBackward => {
-                // TODO: Implement word movement
-                Ok(CommandResult::None)
+            if global.is_empty() {
+                return Ok(CommandResult::None);
             }
-            VimCommand::Noop => Ok(CommandResult::None),
+            Ok(CommandResult::EditorCommand(ExCommand {
+                args: global.join(" "),
+                ..command.clone()
+            }))
+        }
+        name if name.starts_with('>') || name.starts_with('<') => {
+            let amount = name.len() as isize;
+            let amount = if name.starts_with('>') {
+                amount
+            } else {
+                -amount
+            };
+            buffer.shift_lines(start, end, amount)?;
+            cursor.row = end;
+            cursor.col = buffer.first_non_blank(end)?;
+            Ok(CommandResult::None)
+        }
+        _ => Ok(CommandResult::None), // Unknown command
+    }
+}
+
+/// オペレータを範囲に適用する（範囲全体で1回のundo単位）
+pub(crate) fn apply_operator(
+    operator: Operator,
+    range: TextRange,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+) -> Result<CommandResult> {
+    match operator {
+        Operator::ShiftRight | Operator::ShiftLeft => {
+            let amount = if operator == Operator::ShiftRight {
+                1
+            } else {
+                -1
+            };
+            buffer.shift_lines(range.start.row, range.last_row(), amount)?;
+            cursor.row = range.start.row;
+            cursor.col = buffer.first_non_blank(cursor.row)?;
+            return Ok(CommandResult::None);
+        }
+        Operator::ToggleCase | Operator::Lowercase | Operator::Uppercase => {
+            transform_range(buffer, range, |text| convert_case(operator, text))?;
+            *cursor = range.start;
+            return Ok(CommandResult::None);
+        }
+        Operator::Format | Operator::FormatKeepCursor => {
+            let last = format::format_lines(buffer, range.start.row, range.last_row())?;
+            if operator == Operator::Format {
+                *cursor = Position::new(last, buffer.first_non_blank(last)?);
+            } else {
+                cursor.row = cursor.row.min(buffer.line_count() - 1);
+                let line = buffer.line(cursor.row)?;
+                cursor.col = (0..=cursor.col.min(line.len()))
+                    .rev()
+                    .find(|&col| line.is_char_boundary(col))
+                    .unwrap_or(0);
+            }
+            return Ok(CommandResult::None);
         }
+        Operator::Filter => {
+            let (start, end) = (range.start.row, range.last_row());
+            return Ok(CommandResult::CommandLineRequested(filter_range(
+                start, end, cursor.row,
+            )));
+        }
+        Operator::Delete | Operator::Change | Operator::Yank => {}
+    }
+
+    if range.linewise {
+        let (start, end) = (range.start.row, range.end.row);
+        let text = (start..=end)
+            .map(|row| buffer.line(row).map(str::to_string))
+            .collect::<Result<Vec<_>>>()?
+            .join("\n");
+        let register = Register::linewise(text);
+
+        match operator {
+            Operator::Yank => {
+                *cursor = Position::new(start, 0);
+                Ok(CommandResult::Yanked(register))
+            }
+            Operator::Delete => {
+                buffer.delete_lines(start, end)?;
+                cursor.row = start.min(buffer.line_count() - 1);
+                cursor.col = buffer.first_non_blank(cursor.row)?;
+                Ok(CommandResult::Deleted(register))
+            }
+            _ => {
+                // 最初の行を空行として残し、そこで入力を始める
+                buffer.begin_undo_group();
+                let result = (|| {
+                    if end > start {
+                        buffer.delete_lines(start + 1, end)?;
+                    }
+                    let len = buffer.line_length(start)?;
+                    buffer.delete_str(Position::new(start, 0), len)
+                })();
+                buffer.end_undo_group();
+                result?;
+
+                *cursor = Position::new(start, 0);
+                Ok(CommandResult::Changed(register))
+            }
+        }
+    } else {
+        let register = Register::charwise(buffer.text_range(range.start, range.end)?);
+        if operator != Operator::Yank {
+            buffer.delete_range(range.start, range.end)?;
+        }
+        *cursor = range.start;
+
+        Ok(match operator {
+            Operator::Yank => CommandResult::Yanked(register),
+            Operator::Delete => CommandResult::Deleted(register),
+            _ => CommandResult::Changed(register),
+        })
+    }
+}
+
+/// カーソルから`motion`をカウント分繰り返した位置までの範囲（動かなければ`None`）
+///
+/// 上下の移動は行単位、`e`と`$`は移動先の文字を含む文字単位になる。文字単位の範囲が
+/// 行頭で終わり、最初の行の先頭の空白より前から始まる場合（`}`など）は、
+/// その前の行までの行単位にする。
+fn motion_range(
+    motion: &VimCommand,
+    count: usize,
+    buffer: &mut Buffer,
+    cursor: Position,
+) -> Result<Option<TextRange>> {
+    let mut target = cursor;
+    for step in 1..=count {
+        let from = target;
+        motion.execute(buffer, &mut target)?;
+        // 最後の`w`が行を越えたら、越える前の行末までにする
+        if step == count && *motion == VimCommand::MoveWordForward && target.row > from.row {
+            target = Position::new(from.row, buffer.line_length(from.row)?);
+        }
+    }
+    if matches!(motion, VimCommand::MoveWordEnd | VimCommand::MoveLineEnd) {
+        let line = buffer.line(target.row)?;
+        target.col = text_object::next_boundary(line, target.col.min(line.len()));
+    }
+    let (start, end) = (cursor.min(target), cursor.max(target));
+
+    if matches!(
+        motion,
+        VimCommand::MoveDown
+            | VimCommand::MoveUp
+            | VimCommand::MoveBufferStart
+            | VimCommand::MoveBufferEnd
+    ) {
+        return Ok(Some(TextRange::lines(start.row, end.row)));
+    }
+    if start == end {
+        return Ok(None);
+    }
+    if end.col == 0 && end.row > start.row && start.col <= buffer.first_non_blank(start.row)? {
+        return Ok(Some(TextRange::lines(start.row, end.row - 1)));
+    }
+    Ok(Some(TextRange::charwise(start, end)))
+}
+
+/// `!{motion}`で入力済みにするコマンドライン（`.,.+2!`のようにカーソル行からの相対で表す）
+fn filter_range(start: usize, end: usize, current: usize) -> String {
+    let address = |row: usize| {
+        if row == current {
+            ".".to_string()
+        } else if start == current {
+            format!(".+{}", row - current)
+        } else {
+            (row + 1).to_string()
+        }
+    };
+    if start == end {
+        format!("{}!", address(start))
+    } else {
+        format!("{},{}!", address(start), address(end))
+    }
+}
+
+/// 範囲内の各行のテキストを`f`で変換する
+pub(crate) fn transform_range(
+    buffer: &mut Buffer,
+    range: TextRange,
+    f: impl Fn(&str) -> String,
+) -> Result<()> {
+    buffer.begin_undo_group();
+    let result = (|| {
+        for row in range.start.row..=range.last_row() {
+            let len = buffer.line_length(row)?;
+            let from = if !range.linewise && row == range.start.row {
+                range.start.col
+            } else {
+                0
+            };
+            let to = if !range.linewise && row == range.end.row {
+                range.end.col.min(len)
+            } else {
+                len
+            };
+            transform_span(buffer, row, from, to, &f)?;
+        }
+        Ok(())
+    })();
+    buffer.end_undo_group();
+    result
+}
+
+/// 行内の`from`から`to`の直前までのテキストを`f`で変換する
+pub(crate) fn transform_span(
+    buffer: &mut Buffer,
+    row: usize,
+    from: usize,
+    to: usize,
+    f: impl Fn(&str) -> String,
+) -> Result<()> {
+    if from >= to {
+        return Ok(());
+    }
+    let old = buffer.text_range(Position::new(row, from), Position::new(row, to))?;
+    let new = f(&old);
+    if new != old {
+        buffer.replace_str(Position::new(row, from), to - from, &new)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn convert_case(operator: Operator, text: &str) -> String {
+    match operator {
+        Operator::Lowercase => text.to_lowercase(),
+        Operator::Uppercase => text.to_uppercase(),
+        _ => text
+            .chars()
+            .flat_map(|ch| {
+                if ch.is_uppercase() {
+                    ch.to_lowercase().collect::<Vec<_>>()
+                } else {
+                    ch.to_uppercase().collect::<Vec<_>>()
+                }
+            })
+            .collect(),
     }
 }
 
@@ -217,9 +925,22 @@ pub enum CommandResult {
     None,
     DeletedChar(char),
     DeletedLine(String),
+    Yanked(Register),
+    Deleted(Register),
+    /// 削除後にInsertモードへ移る
+    Changed(Register),
     ModeTransition,
+    ScrollRequested,
+    VisualRequested,
+    ReplaceRequested,
+    CompletionRequested,
+    /// 入力済みのテキストでコマンドラインを開く（`!{motion}`）
+    CommandLineRequested(String),
+    /// バッファの切り替えなど、エディタ側で実行するExコマンド
+    EditorCommand(ExCommand),
     SaveRequested,
     QuitRequested,
+    /// 変更があれば保存して閉じる（`ZZ`）
     SaveAndQuitRequested,
     ForceQuitRequested,
 }
@@ -333,6 +1054,8 @@ mod tests {
         let result = VimCommand::NewLine.execute(&mut buffer, &mut cursor);
         assert!(result.is_ok());
         assert_eq!(buffer.line_count(), 2);
+        assert_eq!(buffer.line(0).unwrap(), "He");
+        assert_eq!(buffer.line(1).unwrap(), "llo");
         assert_eq!(cursor.row, 1);
         assert_eq!(cursor.col, 0);
     }
@@ -412,7 +1135,10 @@ mod tests {
         let mut cursor = Position::new(0, 0);
 
         let commands = [
-            VimCommand::EnterInsert,
+            VimCommand::EnterInsert {
+                entry: InsertEntry::Before,
+                count: 1,
+            },
             VimCommand::EnterVisual,
             VimCommand::EnterCommand,
             VimCommand::ExitToNormal,
@@ -444,6 +1170,655 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_move_display_lines() {
+        let mut buffer = BufferBuilder::with_content("Line 1\nLine 2").build();
+        let mut cursor = Position::new(0, 3);
+
+        VimCommand::MoveDisplayDown
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(cursor, Position::new(1, 3));
+
+        VimCommand::MoveDisplayUp
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(cursor, Position::new(0, 3));
+    }
+
+    #[test]
+    fn test_scroll_commands() {
+        let mut buffer = BufferBuilder::with_content("Line 1\nLine 2").build();
+        let mut cursor = Position::new(0, 0);
+
+        let commands = [
+            VimCommand::MoveScreenTop,
+            VimCommand::MoveScreenMiddle,
+            VimCommand::MoveScreenBottom,
+            VimCommand::ScrollPageDown,
+            VimCommand::ScrollPageUp,
+            VimCommand::ScrollHalfPageDown,
+            VimCommand::ScrollHalfPageUp,
+            VimCommand::ScrollLineDown,
+            VimCommand::ScrollLineUp,
+            VimCommand::ScrollCursorCenter,
+            VimCommand::ScrollCursorTop,
+            VimCommand::ScrollCursorBottom,
+        ];
+
+        for cmd in &commands {
+            let result = cmd.execute(&mut buffer, &mut cursor);
+            assert_eq!(result.unwrap(), CommandResult::ScrollRequested);
+            assert_eq!(cursor, Position::new(0, 0)); // カーソルはエディタ側で移動する
+        }
+    }
+
+    #[test]
+    fn test_is_vertical_motion() {
+        assert!(VimCommand::MoveDown.is_vertical_motion());
+        assert!(VimCommand::MoveUp.is_vertical_motion());
+        assert!(VimCommand::MoveDisplayDown.is_vertical_motion());
+        assert!(VimCommand::MoveDisplayUp.is_vertical_motion());
+        assert!(VimCommand::MoveBufferEnd.is_vertical_motion());
+        assert!(VimCommand::ScrollHalfPageDown.is_vertical_motion());
+
+        assert!(!VimCommand::MoveLeft.is_vertical_motion());
+        assert!(!VimCommand::MoveLineEnd.is_vertical_motion());
+        assert!(!VimCommand::MoveScreenTop.is_vertical_motion());
+        assert!(!VimCommand::InsertChar('a').is_vertical_motion());
+    }
+
+    fn operate(operator: Operator, inner: bool, key: char, count: usize) -> VimCommand {
+        VimCommand::OperateTextObject {
+            operator,
+            object: TextObject::from_key(inner, key).unwrap(),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_change_inner_quote() {
+        let mut buffer = BufferBuilder::with_content("say(\"hello\")").build();
+        let mut cursor = Position::new(0, 6);
+
+        let result = operate(Operator::Change, true, '"', 1).execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Changed(Register::charwise("hello"))
+        );
+        assert_eq!(buffer.line(0).unwrap(), "say(\"\")");
+        assert_eq!(cursor, Position::new(0, 5));
+    }
+
+    #[test]
+    fn test_delete_around_paragraph() {
+        let mut buffer = BufferBuilder::with_content("a\nb\n\nc").build();
+        let mut cursor = Position::new(1, 0);
+
+        let result = operate(Operator::Delete, false, 'p', 1).execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Deleted(Register::linewise("a\nb\n"))
+        );
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "c");
+        assert_eq!(cursor, Position::new(0, 0));
+
+        // 1回のundoで元に戻る
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(buffer.line(1).unwrap(), "b");
+    }
+
+    #[test]
+    fn test_yank_inner_tag() {
+        let mut buffer = BufferBuilder::with_content("<p>text</p>").build();
+        let mut cursor = Position::new(0, 5);
+
+        let result = operate(Operator::Yank, true, 't', 1).execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Yanked(Register::charwise("text"))
+        );
+        assert_eq!(buffer.line(0).unwrap(), "<p>text</p>");
+        assert_eq!(cursor, Position::new(0, 3));
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn test_change_linewise_block_keeps_line() {
+        let mut buffer = BufferBuilder::with_content("{\n  a\n  b\n}").build();
+        let mut cursor = Position::new(1, 2);
+
+        let result = operate(Operator::Change, true, '{', 1).execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Changed(Register::linewise("  a\n  b"))
+        );
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.line(1).unwrap(), "");
+        assert_eq!(cursor, Position::new(1, 0));
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(buffer.line(2).unwrap(), "  b");
+    }
+
+    #[test]
+    fn test_operate_without_text_object() {
+        let mut buffer = BufferBuilder::with_content("no parens").build();
+        let mut cursor = Position::new(0, 2);
+
+        let result = operate(Operator::Delete, true, '(', 1).execute(&mut buffer, &mut cursor);
+        assert_eq!(result.unwrap(), CommandResult::None);
+        assert_eq!(buffer.line(0).unwrap(), "no parens");
+    }
+
+    #[test]
+    fn test_yank_line() {
+        let mut buffer = BufferBuilder::with_content("one\ntwo").build();
+        let mut cursor = Position::new(1, 1);
+
+        let result = VimCommand::YankLine.execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Yanked(Register::linewise("two"))
+        );
+    }
+
+    #[test]
+    fn test_change_commands() {
+        let mut buffer = BufferBuilder::with_content("abcdef\n  two\nthree").build();
+        let mut cursor = Position::new(0, 1);
+
+        let result = VimCommand::SubstituteChars { count: 2 }.execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Changed(Register::charwise("bc"))
+        );
+        assert_eq!(buffer.line(0).unwrap(), "adef");
+        assert_eq!(cursor, Position::new(0, 1));
+
+        // 行末を越えるカウントは行末までに制限する
+        let result = VimCommand::ChangeToLineEnd { count: 2 }.execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Changed(Register::charwise("def\n  two"))
+        );
+        assert_eq!(buffer.line(0).unwrap(), "a");
+        assert_eq!(buffer.line(1).unwrap(), "three");
+
+        let result = VimCommand::ChangeLines { count: 5 }.execute(&mut buffer, &mut cursor);
+        assert_eq!(
+            result.unwrap(),
+            CommandResult::Changed(Register::linewise("a\nthree"))
+        );
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "");
+        assert_eq!(cursor, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_operate_lines_shift() {
+        let mut buffer = BufferBuilder::with_content("a\n  b\nc").build();
+        let mut cursor = Position::new(0, 0);
+
+        let command = VimCommand::OperateLines {
+            operator: Operator::ShiftRight,
+            count: 2,
+        };
+        command.execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    a");
+        assert_eq!(buffer.line(1).unwrap(), "      b");
+        assert_eq!(buffer.line(2).unwrap(), "c");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        let command = VimCommand::OperateLines {
+            operator: Operator::ShiftLeft,
+            count: 9,
+        };
+        command.execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "  b");
+    }
+
+    #[test]
+    fn test_operate_motion_linewise() {
+        let mut buffer = BufferBuilder::with_content("a\nb\n\nc\nd").build();
+        let mut cursor = Position::new(0, 0);
+        let operate = |operator, motion, count| VimCommand::OperateMotion {
+            operator,
+            motion: Box::new(motion),
+            count,
+        };
+
+        // `>j`: カーソル行と次の行
+        operate(Operator::ShiftRight, VimCommand::MoveDown, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "    a\n    b\n\nc\nd");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        // `>}`: 段落の後ろの空行は含めない
+        cursor = Position::new(0, 0);
+        operate(Operator::ShiftRight, VimCommand::MoveParagraphForward, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "        a\n        b\n\nc\nd");
+
+        // `<G`: 最後の行まで（途中の行から上へ戻る`<gg`も同じ）
+        cursor = Position::new(1, 8);
+        operate(Operator::ShiftLeft, VimCommand::MoveBufferEnd, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "        a\n    b\n\nc\nd");
+        assert_eq!(cursor, Position::new(1, 4));
+        operate(Operator::ShiftLeft, VimCommand::MoveBufferStart, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "    a\nb\n\nc\nd");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        // `d2j`: 行単位で削除してレジスタに入れる
+        cursor = Position::new(2, 0);
+        let result = operate(Operator::Delete, VimCommand::MoveDown, 2)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(result, CommandResult::Deleted(Register::linewise("\nc\nd")));
+        assert_eq!(buffer.to_string(), "    a\nb");
+    }
+
+    #[test]
+    fn test_operate_motion_charwise() {
+        let mut buffer = BufferBuilder::with_content("foo bar baz\nqux").build();
+        let mut cursor = Position::new(0, 0);
+        let operate = |operator, motion, count| VimCommand::OperateMotion {
+            operator,
+            motion: Box::new(motion),
+            count,
+        };
+
+        // `gUw`は次の単語の先頭の直前まで、`gUe`は単語の末尾の文字まで
+        operate(Operator::Uppercase, VimCommand::MoveWordForward, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "FOO bar baz");
+        cursor = Position::new(0, 4);
+        operate(Operator::Uppercase, VimCommand::MoveWordEnd, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "FOO BAR baz");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        // 行の最後の単語の`w`は次の行へ進まない
+        cursor = Position::new(0, 8);
+        operate(Operator::Uppercase, VimCommand::MoveWordForward, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "FOO BAR BAZ\nqux");
+
+        // `gu$`は行末の文字まで
+        cursor = Position::new(0, 1);
+        operate(Operator::Lowercase, VimCommand::MoveLineEnd, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "Foo bar baz\nqux");
+        assert_eq!(cursor, Position::new(0, 1));
+
+        // `cw`は単語の後ろの空白を残す
+        cursor = Position::new(0, 4);
+        let result = operate(Operator::Change, VimCommand::MoveWordForward, 1)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(result, CommandResult::Changed(Register::charwise("bar")));
+        assert_eq!(buffer.line(0).unwrap(), "Foo  baz");
+    }
+
+    #[test]
+    fn test_toggle_case_chars() {
+        let mut buffer = BufferBuilder::with_content("aßcD").build();
+        let mut cursor = Position::new(0, 0);
+
+        VimCommand::ToggleCaseChars { count: 2 }
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "ASScD");
+        assert_eq!(cursor, Position::new(0, 3));
+
+        VimCommand::ToggleCaseChars { count: 5 }
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "ASSCd");
+        assert_eq!(cursor, Position::new(0, 4));
+    }
+
+    #[test]
+    fn test_format_operators() {
+        let mut buffer = BufferBuilder::with_content("aa bb\ncc dd ee\n\nff").build();
+        buffer.options_mut().textwidth = 8;
+        let mut cursor = Position::new(0, 4);
+
+        // `gw`はカーソルを動かさない
+        VimCommand::OperateLines {
+            operator: Operator::FormatKeepCursor,
+            count: 2,
+        }
+        .execute(&mut buffer, &mut cursor)
+        .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "aa bb cc");
+        assert_eq!(buffer.line(1).unwrap(), "dd ee");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        buffer.options_mut().textwidth = 0;
+        VimCommand::OperateTextObject {
+            operator: Operator::Format,
+            object: TextObject::from_key(false, 'p').unwrap(),
+            count: 1,
+        }
+        .execute(&mut buffer, &mut cursor)
+        .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "aa bb cc dd ee");
+        assert_eq!(buffer.line(1).unwrap(), "");
+        // `gq`は整形した最後の行に移る
+        assert_eq!(cursor, Position::new(1, 0));
+    }
+
+    #[test]
+    fn test_format_motion() {
+        let mut buffer = BufferBuilder::with_content("aa bb\ncc dd ee\nff\n\ngg hh\nii").build();
+        buffer.options_mut().textwidth = 8;
+        let mut cursor = Position::new(0, 3);
+        let operate = |operator, motion| VimCommand::OperateMotion {
+            operator,
+            motion: Box::new(motion),
+            count: 1,
+        };
+
+        // `gq}`: 段落の途中からでも段落の終わりまでの行を整形し、後ろの空行は残す
+        operate(Operator::Format, VimCommand::MoveParagraphForward)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "aa bb cc\ndd ee ff\n\ngg hh\nii");
+        assert_eq!(cursor, Position::new(1, 0));
+
+        // `gwj`: カーソルを動かさない
+        cursor = Position::new(3, 1);
+        operate(Operator::FormatKeepCursor, VimCommand::MoveDown)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.to_string(), "aa bb cc\ndd ee ff\n\ngg hh ii");
+        assert_eq!(cursor, Position::new(3, 1));
+    }
+
+    #[test]
+    fn test_add_to_number() {
+        let mut buffer = BufferBuilder::with_content("version = \"0.9.9\"").build();
+        let mut cursor = Position::new(0, 14);
+
+        VimCommand::AddToNumber { amount: 1 }
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "version = \"0.9.10\"");
+        assert_eq!(cursor, Position::new(0, 16));
+
+        VimCommand::AddToNumber { amount: -11 }
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "version = \"0.9.-1\"");
+
+        // 数値がなければ何もしない
+        let mut cursor = Position::new(0, 17);
+        VimCommand::AddToNumber { amount: 1 }
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(cursor, Position::new(0, 17));
+    }
+
+    #[test]
+    fn test_join_lines_command() {
+        let mut buffer = BufferBuilder::with_content("a\n b\nc\nd").build();
+        let mut cursor = Position::new(0, 0);
+
+        let command = VimCommand::JoinLines {
+            count: 1,
+            spaces: true,
+        };
+        command.execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a b");
+        assert_eq!(cursor, Position::new(0, 1));
+
+        let command = VimCommand::JoinLines {
+            count: 3,
+            spaces: false,
+        };
+        command.execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a bcd");
+        assert_eq!(cursor, Position::new(0, 4));
+        assert_eq!(buffer.line_count(), 1);
+    }
+
+    #[test]
+    fn test_ex_join() {
+        let mut buffer = BufferBuilder::with_content("a\nb\nc\nd\n  e\nf").build();
+        let mut cursor = Position::new(0, 0);
+
+        ex("j").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a b");
+        ex("2,3j!").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "cd");
+        assert_eq!(cursor, Position::new(1, 0));
+        ex("2j 3").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "cd e f");
+        assert_eq!(buffer.line_count(), 2);
+    }
+
+    fn ex(input: &str) -> VimCommand {
+        VimCommand::ExecuteCommand(input.to_string())
+    }
+
+    #[test]
+    fn test_ex_commands_with_range() {
+        let mut buffer = BufferBuilder::with_content("a\nb\nc\nd\ne").build();
+        let mut cursor = Position::new(0, 0);
+
+        assert_eq!(
+            ex("q!").execute(&mut buffer, &mut cursor).unwrap(),
+            CommandResult::ForceQuitRequested
+        );
+
+        ex("3").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(cursor, Position::new(2, 0));
+
+        let result = ex("2,3y").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(result, CommandResult::Yanked(Register::linewise("b\nc")));
+        assert_eq!(cursor, Position::new(2, 0));
+
+        ex(".,$>>").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(2).unwrap(), "        c");
+        assert_eq!(buffer.line(4).unwrap(), "        e");
+        assert_eq!(cursor, Position::new(4, 8));
+
+        let result = ex("2,3d").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(
+            result,
+            CommandResult::Deleted(Register::linewise("b\n        c"))
+        );
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.line(1).unwrap(), "        d");
+    }
+
+    #[test]
+    fn test_ex_set_options() {
+        let mut buffer = BufferBuilder::with_content("    a").build();
+        let mut cursor = Position::new(0, 5);
+
+        ex("set noai si").execute(&mut buffer, &mut cursor).unwrap();
+        assert!(!buffer.options().autoindent);
+        assert!(buffer.options().smartindent);
+
+        ex("set ts=4 noet")
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.options().tabstop, 4);
+        assert!(!buffer.options().expandtab);
+
+        // 不明なオプションはエラーとしてエディタ側で表示する
+        assert!(ex("set nosuch").execute(&mut buffer, &mut cursor).is_err());
+
+        // エディタ全体のオプションはエディタ側に渡す
+        let result = ex("set hidden sw=2")
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(
+            result,
+            CommandResult::EditorCommand(ExCommand::parse("set hidden").unwrap())
+        );
+        assert_eq!(buffer.options().shiftwidth, 2);
+    }
+
+    #[test]
+    fn test_ex_editor_commands() {
+        let mut buffer = BufferBuilder::with_content("a").build();
+        let mut cursor = Position::new(0, 0);
+
+        // 範囲の位置に書いたバッファ番号は行番号として解釈しない
+        for input in [
+            "e other.txt",
+            "ls",
+            "5b",
+            "bn",
+            "bd!",
+            "w!",
+            "w a.txt",
+            "1w >> log.txt",
+            "wq",
+            "x",
+            "wa",
+        ] {
+            let result = ex(input).execute(&mut buffer, &mut cursor).unwrap();
+            assert_eq!(
+                result,
+                CommandResult::EditorCommand(ExCommand::parse(input).unwrap())
+            );
+        }
+        // 引数も範囲もない`:w`は通常の保存
+        let result = ex("w").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(result, CommandResult::SaveRequested);
+    }
+
+    #[test]
+    fn test_ex_retab() {
+        let mut buffer = BufferBuilder::with_content("\ta\n\tb").build();
+        let mut cursor = Position::new(0, 0);
+
+        ex("2retab 4").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\ta");
+        assert_eq!(buffer.line(1).unwrap(), "        b");
+        assert_eq!(buffer.options().tabstop, 4);
+
+        ex("set noet").execute(&mut buffer, &mut cursor).unwrap();
+        ex("retab!").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\ta");
+        assert_eq!(buffer.line(1).unwrap(), "\t\tb");
+    }
+
+    #[test]
+    fn test_ex_sort() {
+        let mut buffer = BufferBuilder::with_content("c\nb\na\nb").build();
+        let mut cursor = Position::new(0, 0);
+
+        ex("2,$sort").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.to_string(), "c\na\nb\nb");
+        ex("sort! u").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.to_string(), "c\nb\na");
+
+        // 1回のundoで戻る
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "c\na\nb\nb");
+    }
+
+    #[test]
+    fn test_ex_filter() {
+        let mut buffer = BufferBuilder::with_content("x\n  b\n  a\ny").build();
+        let mut cursor = Position::new(0, 0);
+
+        ex("2,3!sort").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.to_string(), "x\n  a\n  b\ny");
+        assert_eq!(cursor, Position::new(1, 2));
+
+        // 出力の行数が変わってもよい
+        ex("%!head -n 1").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.to_string(), "x");
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "x\n  a\n  b\ny");
+
+        // 失敗したら標準エラー出力を持つエラーを返し、バッファは変更しない
+        let error = ex("1!echo bad >&2; exit 1")
+            .execute(&mut buffer, &mut cursor)
+            .unwrap_err();
+        assert!(matches!(error, EditorError::ShellCommand { ref stderr, .. } if stderr == "bad"));
+        assert_eq!(buffer.line(0).unwrap(), "x");
+    }
+
+    #[test]
+    fn test_filter_operator_opens_command_line() {
+        let mut buffer = BufferBuilder::with_content("a\nb\nc\n\nd").build();
+        let mut cursor = Position::new(1, 0);
+
+        let result = VimCommand::OperateLines {
+            operator: Operator::Filter,
+            count: 2,
+        }
+        .execute(&mut buffer, &mut cursor)
+        .unwrap();
+        assert_eq!(result, CommandResult::CommandLineRequested(".,.+1!".into()));
+
+        let result = VimCommand::OperateTextObject {
+            operator: Operator::Filter,
+            object: TextObject::from_key(false, 'p').unwrap(),
+            count: 1,
+        }
+        .execute(&mut buffer, &mut cursor)
+        .unwrap();
+        assert_eq!(result, CommandResult::CommandLineRequested("1,4!".into()));
+        assert_eq!(filter_range(1, 1, 1), ".!");
+
+        // `!}`は段落の後ろの空行を含めず、`!G`は最後の行まで
+        let filter = |motion| VimCommand::OperateMotion {
+            operator: Operator::Filter,
+            motion: Box::new(motion),
+            count: 1,
+        };
+        let result = filter(VimCommand::MoveParagraphForward)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(result, CommandResult::CommandLineRequested(".,.+1!".into()));
+        let result = filter(VimCommand::MoveBufferEnd)
+            .execute(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(result, CommandResult::CommandLineRequested(".,.+3!".into()));
+        assert_eq!(cursor, Position::new(1, 0));
+    }
+
+    #[test]
+    fn test_ex_visual_marks_range() {
+        let mut buffer = BufferBuilder::with_content("a\nb\nc\nd").build();
+        let mut cursor = Position::new(0, 0);
+
+        // マークが未設定の範囲はエラーにして何もしない
+        assert!(ex("'<,'>d").execute(&mut buffer, &mut cursor).is_err());
+        assert_eq!(buffer.line_count(), 4);
+
+        buffer
+            .marks_mut()
+            .set_visual('V', Position::new(2, 0), Position::new(3, 0));
+        // マークより前の行を削除しても同じ行を指し続ける
+        ex("1d").execute(&mut buffer, &mut cursor).unwrap();
+        let result = ex("'<,'>d").execute(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(result, CommandResult::Deleted(Register::linewise("c\nd")));
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "b");
+    }
+
     #[test]
     fn test_noop_command() {
         let mut buffer = BufferBuilder::new().build();
@@ -0,0 +1,379 @@
+use crate::editor::{Buffer, Position};
+use crate::error::Result;
+use crate::io::FileSystem;
+
+/// Insertモードの補完の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// `Ctrl-N`/`Ctrl-P`: バッファ内のキーワード
+    Keyword,
+    /// `Ctrl-X Ctrl-L`: 行全体
+    Line,
+    /// `Ctrl-X Ctrl-F`: ファイル名
+    FileName,
+}
+
+/// 補完候補の一覧と選択状態（ポップアップメニューに表示する）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    kind: CompletionKind,
+    // 補完で置き換えるテキストの開始位置と、補完前のテキスト
+    start: Position,
+    original: String,
+    candidates: Vec<String>,
+    // 候補を探した方向（`Ctrl-P`なら後方）
+    forward: bool,
+    // 選択中の候補（`None`なら補完前のテキスト）
+    selected: Option<usize>,
+}
+
+impl Completion {
+    /// カーソル前のテキストに対する補完を開始する（候補がなければ`None`）
+    ///
+    /// キーワードは現在のバッファのカーソルに近い順、続いて`others`のバッファから探す。
+    pub fn new(
+        kind: CompletionKind,
+        forward: bool,
+        buffer: &Buffer,
+        others: &[&Buffer],
+        cursor: Position,
+    ) -> Option<Self> {
+        let line = buffer.line(cursor.row).ok()?;
+        let before = line.get(..cursor.col)?;
+
+        let (start_col, candidates) = match kind {
+            CompletionKind::Keyword => {
+                let start_col = before
+                    .char_indices()
+                    .rev()
+                    .take_while(|&(_, ch)| is_keyword(ch))
+                    .last()
+                    .map_or(cursor.col, |(index, _)| index);
+                let prefix = &before[start_col..];
+                let start = Position::new(cursor.row, start_col);
+                (
+                    start_col,
+                    keyword_candidates(prefix, start, forward, buffer, others),
+                )
+            }
+            CompletionKind::Line => {
+                let start_col = before.len() - before.trim_start().len();
+                let prefix = &before[start_col..];
+                (
+                    start_col,
+                    line_candidates(prefix, cursor.row, forward, buffer),
+                )
+            }
+            CompletionKind::FileName => {
+                let start_col = before
+                    .char_indices()
+                    .rev()
+                    .take_while(|&(_, ch)| !ch.is_whitespace())
+                    .last()
+                    .map_or(cursor.col, |(index, _)| index);
+                (start_col, file_candidates(&before[start_col..]))
+            }
+        };
+
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            kind,
+            start: Position::new(cursor.row, start_col),
+            original: before[start_col..].to_string(),
+            candidates,
+            forward,
+            selected: Some(0),
+        })
+    }
+
+    pub fn kind(&self) -> CompletionKind {
+        self.kind
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// 選択中の候補（未選択なら補完前のテキスト）
+    pub fn text(&self) -> &str {
+        self.selected
+            .map_or(&self.original, |index| &self.candidates[index])
+    }
+
+    /// 選択を移動する（`forward`が探した方向と同じなら次の候補）
+    ///
+    /// 両端では補完前のテキストを挟んで循環する。
+    pub fn select(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.selected = if forward == self.forward {
+            match self.selected {
+                None => Some(0),
+                Some(index) if index + 1 < len => Some(index + 1),
+                Some(_) => None,
+            }
+        } else {
+            match self.selected {
+                None => Some(len - 1),
+                Some(0) => None,
+                Some(index) => Some(index - 1),
+            }
+        };
+    }
+
+    /// 選択を取り消して補完前のテキストに戻す（`Ctrl-E`）
+    pub fn cancel(&mut self) {
+        self.selected = None;
+    }
+
+    /// 選択中のテキストを補完位置に反映する（カーソルは補完したテキストの直後）
+    pub fn apply(&self, buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+        let len = cursor.col.saturating_sub(self.start.col);
+        let text = self.text();
+        buffer.replace_str(self.start, len, text)?;
+        *cursor = Position::new(self.start.row, self.start.col + text.len());
+        Ok(())
+    }
+}
+
+fn is_keyword(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// 行内のキーワードとその開始位置
+fn keywords(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut rest = line.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while rest.next_if(|&(_, ch)| !is_keyword(ch)).is_some() {}
+        let (start, _) = rest.next()?;
+        let mut end = line.len();
+        while let Some(&(index, ch)) = rest.peek() {
+            if !is_keyword(ch) {
+                end = index;
+                break;
+            }
+            rest.next();
+        }
+        Some((start, &line[start..end]))
+    })
+}
+
+fn keyword_candidates(
+    prefix: &str,
+    start: Position,
+    forward: bool,
+    buffer: &Buffer,
+    others: &[&Buffer],
+) -> Vec<String> {
+    let matches = |buffer: &Buffer| -> Vec<(Position, String)> {
+        (0..buffer.line_count())
+            .flat_map(|row| {
+                let line = buffer.line(row).unwrap_or("");
+                keywords(line)
+                    .filter(|(_, word)| word.starts_with(prefix) && *word != prefix)
+                    .map(move |(col, word)| (Position::new(row, col), word.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    };
+
+    // カーソルから近い順（`Ctrl-P`なら後方へ）に並べ、入力中の単語自体は除く
+    let current = matches(buffer);
+    let (before, after): (Vec<_>, Vec<_>) = current
+        .into_iter()
+        .filter(|(pos, _)| *pos != start)
+        .partition(|(pos, _)| *pos < start);
+    let ordered: Vec<String> = if forward {
+        after
+            .into_iter()
+            .chain(before)
+            .map(|(_, word)| word)
+            .collect()
+    } else {
+        before
+            .into_iter()
+            .rev()
+            .chain(after.into_iter().rev())
+            .map(|(_, word)| word)
+            .collect()
+    };
+
+    let others = others
+        .iter()
+        .flat_map(|other| matches(other).into_iter().map(|(_, word)| word));
+    unique(ordered.into_iter().chain(others))
+}
+
+fn line_candidates(prefix: &str, row: usize, forward: bool, buffer: &Buffer) -> Vec<String> {
+    let count = buffer.line_count();
+    // 現在行の次（`Ctrl-P`なら前）の行から順に1周する
+    let rows = (1..count).map(|offset| {
+        if forward {
+            (row + offset) % count
+        } else {
+            (row + count - offset) % count
+        }
+    });
+    unique(rows.filter_map(|row| {
+        let line = buffer.line(row).ok()?.trim_start();
+        (line.starts_with(prefix) && line != prefix).then(|| line.to_string())
+    }))
+}
+
+fn file_candidates(prefix: &str) -> Vec<String> {
+    // `dir/na`なら`dir/`内の`na`で始まるエントリ
+    let (dir, name) = match prefix.rfind('/') {
+        Some(index) => (&prefix[..=index], &prefix[index + 1..]),
+        None => ("", prefix),
+    };
+    let entries = FileSystem::list_dir(if dir.is_empty() { "." } else { dir }).unwrap_or_default();
+
+    entries
+        .iter()
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_str()?;
+            // 隠しファイルは`.`を入力したときだけ候補にする
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+            let suffix = if path.is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, suffix))
+        })
+        .collect()
+}
+
+/// 最初に現れた順序を保って重複を除く
+fn unique(words: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    words.filter(|word| seen.insert(word.clone())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fs::test_helpers::TestFileHelper;
+
+    #[test]
+    fn test_keyword_completion_order() {
+        let buffer = Buffer::from_content("foobar fooqux\nfo\nfoobaz food fooqux");
+        let cursor = Position::new(1, 2);
+
+        let completion =
+            Completion::new(CompletionKind::Keyword, true, &buffer, &[], cursor).unwrap();
+        assert_eq!(completion.start(), Position::new(1, 0));
+        assert_eq!(
+            completion.candidates(),
+            ["foobaz", "food", "fooqux", "foobar"]
+        );
+
+        let completion =
+            Completion::new(CompletionKind::Keyword, false, &buffer, &[], cursor).unwrap();
+        assert_eq!(
+            completion.candidates(),
+            ["fooqux", "foobar", "food", "foobaz"]
+        );
+
+        assert!(
+            Completion::new(
+                CompletionKind::Keyword,
+                true,
+                &buffer,
+                &[],
+                Position::new(0, 0)
+            )
+            .is_some(),
+            "空のプレフィックスでは全てのキーワードが候補になる"
+        );
+    }
+
+    #[test]
+    fn test_keyword_completion_from_other_buffers() {
+        let buffer = Buffer::from_content("ab");
+        let other = Buffer::from_content("abc abd");
+
+        let completion = Completion::new(
+            CompletionKind::Keyword,
+            true,
+            &buffer,
+            &[&other],
+            Position::new(0, 2),
+        )
+        .unwrap();
+        assert_eq!(completion.candidates(), ["abc", "abd"]);
+    }
+
+    #[test]
+    fn test_cycle_and_cancel() {
+        let mut buffer = Buffer::from_content("alpha alpine\nal");
+        let mut cursor = Position::new(1, 2);
+
+        let mut completion =
+            Completion::new(CompletionKind::Keyword, true, &buffer, &[], cursor).unwrap();
+        completion.apply(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "alpha");
+        assert_eq!(cursor, Position::new(1, 5));
+
+        completion.select(true);
+        completion.apply(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "alpine");
+
+        // 最後の候補の次は元のテキスト
+        completion.select(true);
+        assert_eq!(completion.selected(), None);
+        completion.select(false);
+        assert_eq!(completion.selected(), Some(1));
+
+        completion.cancel();
+        completion.apply(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "al");
+        assert_eq!(cursor, Position::new(1, 2));
+    }
+
+    #[test]
+    fn test_line_completion() {
+        let buffer = Buffer::from_content("let x = 1;\n    let y = 2;\n  le\nlet x = 1;");
+        let completion = Completion::new(
+            CompletionKind::Line,
+            false,
+            &buffer,
+            &[],
+            Position::new(2, 4),
+        )
+        .unwrap();
+        assert_eq!(completion.start(), Position::new(2, 2));
+        assert_eq!(completion.candidates(), ["let y = 2;", "let x = 1;"]);
+    }
+
+    #[test]
+    fn test_file_name_completion() {
+        let helper = TestFileHelper::new();
+        helper.create_test_file("notes.txt", "");
+        helper.create_test_file("sub/inner.txt", "");
+        let dir = helper.temp_dir.path().display().to_string();
+
+        let buffer = Buffer::from_content(&format!("see {}/", dir));
+        let cursor = Position::new(0, buffer.line_length(0).unwrap());
+        let completion =
+            Completion::new(CompletionKind::FileName, true, &buffer, &[], cursor).unwrap();
+        assert_eq!(completion.start(), Position::new(0, 4));
+        assert_eq!(
+            completion.candidates(),
+            [format!("{}/notes.txt", dir), format!("{}/sub/", dir)]
+        );
+
+        let buffer = Buffer::from_content(&format!("{}/zz", dir));
+        let cursor = Position::new(0, buffer.line_length(0).unwrap());
+        assert!(Completion::new(CompletionKind::FileName, true, &buffer, &[], cursor).is_none());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::editor::Buffer;
+use crate::error::{EditorError, Result};
+use std::path::PathBuf;
+
+/// 行アドレスの基準（`12`、`.`、`$`、`'<`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressBase {
+    Line(usize),
+    Current,
+    Last,
+    Mark(char),
+}
+
+/// 行アドレス（基準と`+N`/`-N`のオフセット）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub base: AddressBase,
+    pub offset: isize,
+}
+
+impl Address {
+    pub fn new(base: AddressBase) -> Self {
+        Self { base, offset: 0 }
+    }
+
+    /// 0始まりの行番号に解決する
+    pub fn resolve(&self, buffer: &Buffer, current: usize) -> Result<usize> {
+        let row = match self.base {
+            AddressBase::Line(line) => line.saturating_sub(1),
+            AddressBase::Current => current,
+            AddressBase::Last => buffer.line_count() - 1,
+            AddressBase::Mark(name) => {
+                buffer
+                    .marks()
+                    .get(name)
+                    .ok_or_else(|| {
+                        EditorError::invalid_command(format!("Mark not set: '{}", name))
+                    })?
+                    .row
+            }
+        };
+
+        row.checked_add_signed(self.offset)
+            .filter(|&row| row < buffer.line_count())
+            .ok_or_else(|| EditorError::invalid_command("Invalid range"))
+    }
+}
+
+/// Exコマンドの行範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExRange {
+    /// `%`
+    Whole,
+    Single(Address),
+    Pair(Address, Address),
+}
+
+/// `:w`系のコマンドの書き込み先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteTarget {
+    /// バッファのファイル
+    Buffer,
+    /// `:w {file}`
+    File(PathBuf),
+    /// `:w >> [file]`（ファイル名がなければバッファのファイルに追加する）
+    Append(Option<PathBuf>),
+    /// `:w !{cmd}`
+    Command(String),
+}
+
+/// `:[range]name[!] [args]`形式のExコマンド
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExCommand {
+    pub range: Option<ExRange>,
+    pub name: String,
+    pub bang: bool,
+    pub args: String,
+}
+
+impl ExCommand {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: input.trim_start_matches([' ', ':']).chars().collect(),
+            index: 0,
+        };
+
+        let range = parser.range()?;
+        parser.skip_spaces();
+        let name = parser.name();
+        let bang = parser.eat('!');
+        let args = parser.rest().trim().to_string();
+
+        Ok(Self {
+            range,
+            name,
+            bang,
+            args,
+        })
+    }
+
+    /// バッファの一覧など、エディタ側で実行するコマンドか
+    pub fn is_editor_command(&self) -> bool {
+        matches!(
+            self.name.as_str(),
+            "e" | "edit"
+                | "ene"
+                | "enew"
+                | "ls"
+                | "buffers"
+                | "files"
+                | "b"
+                | "buffer"
+                | "bn"
+                | "bnext"
+                | "bp"
+                | "bprevious"
+                | "bN"
+                | "bNext"
+                | "bd"
+                | "bdelete"
+                | "bw"
+                | "bwipeout"
+                | "sp"
+                | "split"
+                | "vs"
+                | "vsplit"
+                | "new"
+                | "vne"
+                | "vnew"
+                | "clo"
+                | "close"
+                | "on"
+                | "only"
+                | "winc"
+                | "wincmd"
+                | "res"
+                | "resize"
+                | "tabnew"
+                | "tabe"
+                | "tabedit"
+                | "tabc"
+                | "tabclose"
+                | "tabo"
+                | "tabonly"
+                | "tabm"
+                | "tabmove"
+                | "tabn"
+                | "tabnext"
+                | "tabp"
+                | "tabprevious"
+                | "tabN"
+                | "tabNext"
+                | "tabfir"
+                | "tabfirst"
+                | "tabr"
+                | "tabrewind"
+                | "tabl"
+                | "tablast"
+                | "ar"
+                | "args"
+                | "n"
+                | "next"
+                | "N"
+                | "Next"
+                | "prev"
+                | "previous"
+                | "fir"
+                | "first"
+                | "rew"
+                | "rewind"
+                | "la"
+                | "last"
+                | "arga"
+                | "argadd"
+                | "argd"
+                | "argdelete"
+                | "argdo"
+                | "bufdo"
+                | "rec"
+                | "recover"
+                | "checkt"
+                | "checktime"
+                | "wq"
+                | "x"
+                | "xit"
+                | "exi"
+                | "exit"
+                | "up"
+                | "update"
+                | "sav"
+                | "saveas"
+                | "wa"
+                | "wall"
+                | "wqa"
+                | "wqall"
+                | "xa"
+                | "xall"
+        )
+    }
+
+    /// `:w`系のコマンドの引数が表す書き込み先
+    pub fn write_target(&self) -> WriteTarget {
+        let args = self.args.trim();
+        if let Some(file) = args.strip_prefix(">>") {
+            let file = file.trim();
+            WriteTarget::Append((!file.is_empty()).then(|| PathBuf::from(file)))
+        } else if let Some(command) = args.strip_prefix('!') {
+            WriteTarget::Command(command.trim().to_string())
+        } else if args.is_empty() {
+            WriteTarget::Buffer
+        } else {
+            WriteTarget::File(PathBuf::from(args))
+        }
+    }
+
+    /// `:3b`や`:bn 2`のように、範囲の位置か引数に書いた数
+    pub fn count(&self) -> Option<usize> {
+        match self.range {
+            Some(ExRange::Single(Address {
+                base: AddressBase::Line(count),
+                offset: 0,
+            })) => Some(count),
+            _ => self.args.trim().parse().ok(),
+        }
+    }
+
+    /// 範囲を0始まりの行番号（両端を含む）に解決する（範囲の指定がなければ`None`）
+    pub fn line_range(&self, buffer: &Buffer, current: usize) -> Result<Option<(usize, usize)>> {
+        let range = match self.range {
+            None => return Ok(None),
+            Some(ExRange::Whole) => (0, buffer.line_count() - 1),
+            Some(ExRange::Single(address)) => {
+                let row = address.resolve(buffer, current)?;
+                (row, row)
+            }
+            Some(ExRange::Pair(start, end)) => {
+                let start = start.resolve(buffer, current)?;
+                let end = end.resolve(buffer, current)?;
+                // 逆順の範囲は入れ替える
+                (start.min(end), start.max(end))
+            }
+        };
+        Ok(Some(range))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') {
+            self.index += 1;
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.index;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.index += 1;
+        }
+        let digits: String = self.chars[start..self.index].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn range(&mut self) -> Result<Option<ExRange>> {
+        if self.eat('%') {
+            return Ok(Some(ExRange::Whole));
+        }
+        let Some(start) = self.address()? else {
+            return Ok(None);
+        };
+        if !self.eat(',') {
+            return Ok(Some(ExRange::Single(start)));
+        }
+        // `N,`のように終端を省略した場合は現在行
+        let end = self
+            .address()?
+            .unwrap_or(Address::new(AddressBase::Current));
+        Ok(Some(ExRange::Pair(start, end)))
+    }
+
+    fn address(&mut self) -> Result<Option<Address>> {
+        let base = match self.peek() {
+            Some(ch) if ch.is_ascii_digit() => self.number().map(AddressBase::Line),
+            Some('.') => {
+                self.index += 1;
+                Some(AddressBase::Current)
+            }
+            Some('$') => {
+                self.index += 1;
+                Some(AddressBase::Last)
+            }
+            Some('\'') => {
+                self.index += 1;
+                let name = self
+                    .peek()
+                    .ok_or_else(|| EditorError::invalid_command("Missing mark name"))?;
+                self.index += 1;
+                Some(AddressBase::Mark(name))
+            }
+            _ => None,
+        };
+
+        // `+N`/`-N`のオフセット（`+`のみなら1）
+        let mut offset = 0isize;
+        let mut has_offset = false;
+        while let Some(sign @ ('+' | '-')) = self.peek() {
+            self.index += 1;
+            let amount = self.number().unwrap_or(1) as isize;
+            offset += if sign == '+' { amount } else { -amount };
+            has_offset = true;
+        }
+
+        Ok(match base {
+            Some(base) => Some(Address { base, offset }),
+            None if has_offset => Some(Address {
+                base: AddressBase::Current,
+                offset,
+            }),
+            None => None,
+        })
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.index;
+        match self.peek() {
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+                    self.index += 1;
+                }
+            }
+            // `>>`のように同じ記号を繰り返すコマンド
+            Some(ch @ ('>' | '<')) => {
+                while self.peek() == Some(ch) {
+                    self.index += 1;
+                }
+            }
+            Some(ch) if ch != ' ' => self.index += 1,
+            _ => {}
+        }
+        self.chars[start..self.index].iter().collect()
+    }
+
+    fn rest(&mut self) -> String {
+        let rest = self.chars[self.index..].iter().collect();
+        self.index = self.chars.len();
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Position;
+
+    fn address(base: AddressBase, offset: isize) -> Address {
+        Address { base, offset }
+    }
+
+    #[test]
+    fn test_parse_without_range() {
+        let command = ExCommand::parse("q!").unwrap();
+        assert_eq!(command.range, None);
+        assert_eq!(command.name, "q");
+        assert!(command.bang);
+
+        let command = ExCommand::parse("w file.txt").unwrap();
+        assert_eq!(command.name, "w");
+        assert!(!command.bang);
+        assert_eq!(command.args, "file.txt");
+    }
+
+    #[test]
+    fn test_write_target() {
+        let target = |input: &str| ExCommand::parse(input).unwrap().write_target();
+        assert_eq!(target("w"), WriteTarget::Buffer);
+        assert_eq!(
+            target("w! a.txt"),
+            WriteTarget::File(PathBuf::from("a.txt"))
+        );
+        assert_eq!(target("w >>"), WriteTarget::Append(None));
+        assert_eq!(
+            target("1,2w >> log.txt"),
+            WriteTarget::Append(Some(PathBuf::from("log.txt")))
+        );
+        assert_eq!(
+            target("w !wc -l"),
+            WriteTarget::Command("wc -l".to_string())
+        );
+
+        // `:w!cmd`は`!`付きの書き込みで、コマンドには渡さない
+        let command = ExCommand::parse("w!cmd").unwrap();
+        assert!(command.bang);
+        assert_eq!(
+            command.write_target(),
+            WriteTarget::File(PathBuf::from("cmd"))
+        );
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let command = ExCommand::parse("'<,'>d").unwrap();
+        assert_eq!(
+            command.range,
+            Some(ExRange::Pair(
+                address(AddressBase::Mark('<'), 0),
+                address(AddressBase::Mark('>'), 0)
+            ))
+        );
+        assert_eq!(command.name, "d");
+
+        let command = ExCommand::parse("%y").unwrap();
+        assert_eq!(command.range, Some(ExRange::Whole));
+
+        let command = ExCommand::parse(".,$-1>>").unwrap();
+        assert_eq!(
+            command.range,
+            Some(ExRange::Pair(
+                address(AddressBase::Current, 0),
+                address(AddressBase::Last, -1)
+            ))
+        );
+        assert_eq!(command.name, ">>");
+
+        let command = ExCommand::parse("12").unwrap();
+        assert_eq!(
+            command.range,
+            Some(ExRange::Single(address(AddressBase::Line(12), 0)))
+        );
+        assert_eq!(command.name, "");
+
+        let command = ExCommand::parse("+2,+").unwrap();
+        assert_eq!(
+            command.range,
+            Some(ExRange::Pair(
+                address(AddressBase::Current, 2),
+                address(AddressBase::Current, 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_line_range() {
+        let mut buffer = Buffer::from_content("1\n2\n3\n4\n5");
+        buffer
+            .marks_mut()
+            .set_visual('v', Position::new(3, 0), Position::new(1, 0));
+
+        let range = |input: &str| ExCommand::parse(input).unwrap().line_range(&buffer, 2);
+        assert_eq!(range("d").unwrap(), None);
+        assert_eq!(range("%").unwrap(), Some((0, 4)));
+        assert_eq!(range("2,4").unwrap(), Some((1, 3)));
+        assert_eq!(range(".-1,$").unwrap(), Some((1, 4)));
+        // 逆順のマークは入れ替える
+        assert_eq!(range("'<,'>").unwrap(), Some((1, 3)));
+
+        assert!(range("1,9").is_err());
+        assert!(range("'a").is_err());
+    }
+
+    #[test]
+    fn test_editor_commands() {
+        let command = ExCommand::parse("b#").unwrap();
+        assert!(command.is_editor_command());
+        assert_eq!(command.args, "#");
+        assert!(!ExCommand::parse("d").unwrap().is_editor_command());
+        assert!(ExCommand::parse("2tabnew").unwrap().is_editor_command());
+        let command = ExCommand::parse("argdo %s/a/b/g").unwrap();
+        assert!(command.is_editor_command());
+        assert_eq!(command.args, "%s/a/b/g");
+
+        assert_eq!(ExCommand::parse("3b").unwrap().count(), Some(3));
+        assert_eq!(ExCommand::parse("bn 2").unwrap().count(), Some(2));
+        assert_eq!(ExCommand::parse("bn").unwrap().count(), None);
+        assert_eq!(ExCommand::parse(".b").unwrap().count(), None);
+    }
+}
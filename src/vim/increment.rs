@@ -0,0 +1,217 @@
+/// `Ctrl-A`/`Ctrl-X`で扱う数値の種類（`nrformats`で有効にする）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberKind {
+    Decimal,
+    Hex,
+    Octal,
+    Binary,
+    Alpha,
+}
+
+/// 行内で見つかった数値（`start..end`は符号やプレフィックスを含む）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Number {
+    kind: NumberKind,
+    start: usize,
+    end: usize,
+}
+
+/// `line`の`col`以降（`col`が数値の途中ならその数値）に`amount`を加える
+///
+/// 置き換える範囲の開始位置、終了位置と新しいテキストを返す。数値がなければ`None`。
+pub fn add(line: &str, col: usize, amount: i64, nrformats: &str) -> Option<(usize, usize, String)> {
+    let formats: Vec<&str> = nrformats.split(',').collect();
+    let number = numbers(line, &formats)
+        .into_iter()
+        .find(|number| number.end > col)?;
+    let text = &line[number.start..number.end];
+
+    let replacement = match number.kind {
+        NumberKind::Decimal => add_decimal(text, amount),
+        NumberKind::Alpha => {
+            let ch = text.chars().next()?;
+            let (first, last) = if ch.is_ascii_lowercase() {
+                (b'a', b'z')
+            } else {
+                (b'A', b'Z')
+            };
+            let code = (ch as i64 + amount).clamp(first as i64, last as i64);
+            char::from(code as u8).to_string()
+        }
+        kind => {
+            let (prefix, digits) = text.split_at(if kind == NumberKind::Octal { 0 } else { 2 });
+            let radix = match kind {
+                NumberKind::Hex => 16,
+                NumberKind::Octal => 8,
+                _ => 2,
+            };
+            let value = u64::from_str_radix(digits, radix).ok()?;
+            let value = value.wrapping_add_signed(amount);
+            let formatted = match kind {
+                NumberKind::Hex => {
+                    // 最後の英字の大文字・小文字に合わせる
+                    let upper = digits
+                        .chars()
+                        .rev()
+                        .find(|ch| ch.is_ascii_alphabetic())
+                        .is_some_and(|ch| ch.is_ascii_uppercase());
+                    if upper {
+                        format!("{:X}", value)
+                    } else {
+                        format!("{:x}", value)
+                    }
+                }
+                NumberKind::Octal => format!("0{:o}", value),
+                _ => format!("{:b}", value),
+            };
+            // 先頭の0を含めて元の桁数を保つ
+            format!("{}{:0>width$}", prefix, formatted, width = digits.len())
+        }
+    };
+    Some((number.start, number.end, replacement))
+}
+
+/// 符号付きの10進数に加算する（先頭の0による桁数は保つ）
+fn add_decimal(text: &str, amount: i64) -> String {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value: i128 = digits.parse().unwrap_or(i128::MAX);
+    let value = if negative { -value } else { value };
+    let value = (value + amount as i128).clamp(-(u64::MAX as i128), u64::MAX as i128);
+
+    let width = if digits.len() > 1 && digits.starts_with('0') {
+        digits.len()
+    } else {
+        0
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    format!("{}{:0>width$}", sign, value.unsigned_abs(), width = width)
+}
+
+/// 行内の数値を左から順に切り出す
+fn numbers(line: &str, formats: &[&str]) -> Vec<Number> {
+    let bytes = line.as_bytes();
+    let enabled = |format: &str| formats.contains(&format);
+    let mut numbers = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let ch = bytes[i];
+        if ch.is_ascii_alphabetic() && enabled("alpha") {
+            numbers.push(Number {
+                kind: NumberKind::Alpha,
+                start: i,
+                end: i + 1,
+            });
+            i += 1;
+            continue;
+        }
+        if !ch.is_ascii_digit() {
+            // 英字以外の複数バイト文字は1文字分進める
+            i += line[i..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+
+        let digits_end = |from: usize, is_digit: fn(&u8) -> bool| {
+            from + bytes[from..].iter().take_while(|b| is_digit(b)).count()
+        };
+        let prefix = bytes.get(i + 1).map(u8::to_ascii_lowercase);
+        let number = if ch == b'0'
+            && prefix == Some(b'x')
+            && enabled("hex")
+            && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit)
+        {
+            Number {
+                kind: NumberKind::Hex,
+                start: i,
+                end: digits_end(i + 2, u8::is_ascii_hexdigit),
+            }
+        } else if ch == b'0'
+            && prefix == Some(b'b')
+            && enabled("bin")
+            && bytes.get(i + 2).is_some_and(|b| matches!(b, b'0' | b'1'))
+        {
+            Number {
+                kind: NumberKind::Binary,
+                start: i,
+                end: digits_end(i + 2, |b| matches!(b, b'0' | b'1')),
+            }
+        } else {
+            let end = digits_end(i, u8::is_ascii_digit);
+            let octal = ch == b'0'
+                && end - i > 1
+                && enabled("octal")
+                && bytes[i..end].iter().all(|b| (b'0'..=b'7').contains(b));
+            // 直前の`-`は負の数の符号とみなす
+            let start = if !octal && i > 0 && bytes[i - 1] == b'-' && !enabled("unsigned") {
+                i - 1
+            } else {
+                i
+            };
+            Number {
+                kind: if octal {
+                    NumberKind::Octal
+                } else {
+                    NumberKind::Decimal
+                },
+                start,
+                end,
+            }
+        };
+        i = number.end;
+        numbers.push(number);
+    }
+    numbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT: &str = "bin,hex";
+
+    fn apply(line: &str, col: usize, amount: i64, nrformats: &str) -> Option<String> {
+        let (start, end, text) = add(line, col, amount, nrformats)?;
+        Some(format!("{}{}{}", &line[..start], text, &line[end..]))
+    }
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!(
+            apply("version = 1.9.0", 0, 1, DEFAULT).unwrap(),
+            "version = 2.9.0"
+        );
+        assert_eq!(
+            apply("version = 1.9.0", 11, 1, DEFAULT).unwrap(),
+            "version = 1.10.0"
+        );
+        // 数値の途中からでも数値全体に加算する
+        assert_eq!(apply("x 129 y", 3, 5, DEFAULT).unwrap(), "x 134 y");
+        assert_eq!(apply("x 1", 0, -3, DEFAULT).unwrap(), "x -2");
+        assert_eq!(apply("x -1", 0, 3, DEFAULT).unwrap(), "x 2");
+        assert_eq!(apply("a-1", 0, 1, "unsigned").unwrap(), "a-2");
+        assert_eq!(apply("007", 0, 1, DEFAULT).unwrap(), "008");
+        assert_eq!(apply("no digits", 0, 1, DEFAULT), None);
+        assert_eq!(apply("1 x", 2, 1, DEFAULT), None);
+    }
+
+    #[test]
+    fn test_radix_formats() {
+        assert_eq!(apply("0x0f", 0, 1, DEFAULT).unwrap(), "0x10");
+        assert_eq!(apply("0xFF", 2, 1, DEFAULT).unwrap(), "0x100");
+        assert_eq!(apply("0x00", 0, -1, DEFAULT).unwrap(), "0xffffffffffffffff");
+        assert_eq!(apply("0b0111", 0, 1, DEFAULT).unwrap(), "0b1000");
+        assert_eq!(apply("007", 0, 1, "octal").unwrap(), "010");
+        // 無効な形式は10進数として扱う
+        assert_eq!(apply("0x10", 0, 1, "").unwrap(), "1x10");
+    }
+
+    #[test]
+    fn test_alpha() {
+        assert_eq!(apply("item b", 5, 1, "alpha").unwrap(), "item c");
+        assert_eq!(apply("Z", 0, 3, "alpha").unwrap(), "Z");
+        assert_eq!(apply("a1", 0, 1, "alpha").unwrap(), "b1");
+    }
+}
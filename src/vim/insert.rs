@@ -0,0 +1,687 @@
+use crate::editor::cursor::virtual_column;
+use crate::editor::{Buffer, Position};
+use crate::editor::{comment, format, indent};
+use crate::error::Result;
+
+/// Insertモードに入る位置（`i` `a` `I` `A` `gI` `o` `O` `gi`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertEntry {
+    /// `i`: カーソルの前
+    Before,
+    /// `a`: カーソルの後
+    After,
+    /// `I`: 最初の非空白文字の前
+    FirstNonBlank,
+    /// `A`: 行末
+    LineEnd,
+    /// `gI`: 行の先頭（インデントも含む）
+    LineStart,
+    /// `o`: 下に新しい行
+    NewLineBelow,
+    /// `O`: 上に新しい行
+    NewLineAbove,
+    /// `gi`: 最後にInsertモードを抜けた位置（`'^`マーク）
+    LastInsert,
+}
+
+impl InsertEntry {
+    /// カーソルを挿入位置に移動する（`o`/`O`では新しい行を作ってインデントする）
+    pub fn prepare(self, buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+        let line = buffer.line(cursor.row)?;
+        match self {
+            InsertEntry::Before => {}
+            InsertEntry::After => {
+                if let Some(ch) = line.get(cursor.col..).and_then(|rest| rest.chars().next()) {
+                    cursor.col += ch.len_utf8();
+                }
+            }
+            InsertEntry::FirstNonBlank => cursor.col = buffer.first_non_blank(cursor.row)?,
+            InsertEntry::LineEnd => cursor.col = line.len(),
+            InsertEntry::LineStart => cursor.col = 0,
+            InsertEntry::NewLineBelow => {
+                let row = cursor.row + 1;
+                buffer.insert_line(row)?;
+                *cursor = open_line(buffer, row, cursor.row)?;
+            }
+            InsertEntry::NewLineAbove => {
+                // autoindentでは下に押し出した元の行のインデントに合わせる
+                buffer.insert_line(cursor.row)?;
+                *cursor = open_line(buffer, cursor.row, cursor.row + 1)?;
+            }
+            InsertEntry::LastInsert => {
+                // 未設定ならカーソル位置から挿入する
+                if let Some(mark) = buffer.marks().get('^') {
+                    let len = buffer.line_length(mark.row)?;
+                    *cursor = Position::new(mark.row, mark.col.min(len));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn opens_line(self) -> bool {
+        matches!(self, InsertEntry::NewLineBelow | InsertEntry::NewLineAbove)
+    }
+}
+
+/// カウント付きの挿入（`3ix<Esc>`）で入力したテキストを、Insertモード終了時に繰り返すための記録
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsertSession {
+    start: Position,
+    count: usize,
+    opens_line: bool,
+}
+
+impl InsertSession {
+    pub fn new(entry: InsertEntry, start: Position, count: usize) -> Self {
+        Self {
+            start,
+            count,
+            opens_line: entry.opens_line(),
+        }
+    }
+
+    /// 入力したテキストを残りのカウント分繰り返す（`o`/`O`では行ごと繰り返す）
+    pub fn finish(&self, buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+        // Backspaceで挿入開始位置より前に戻った場合は繰り返さない
+        if self.count <= 1 || *cursor <= self.start {
+            return Ok(());
+        }
+        let text = buffer.text_range(self.start, *cursor)?;
+        let repeat = self.count - 1;
+
+        *cursor = if self.opens_line {
+            // 繰り返す行にも最初の行と同じインデントを付ける
+            let indent = buffer.line(self.start.row)?[..self.start.col].to_string();
+            let end = Position::new(cursor.row, buffer.line_length(cursor.row)?);
+            buffer.insert_text(end, &format!("\n{}{}", indent, text).repeat(repeat))?
+        } else {
+            buffer.insert_text(*cursor, &text.repeat(repeat))?
+        };
+        Ok(())
+    }
+}
+
+/// 新しく開いた行にインデントを付け、カーソルをその末尾に置いた位置を返す
+fn open_line(buffer: &mut Buffer, row: usize, reference: usize) -> Result<Position> {
+    let indent = indent::open_line_indent(buffer, row, reference);
+    buffer.insert_str(Position::new(row, 0), &indent)?;
+    Ok(Position::new(row, indent.len()))
+}
+
+/// `Enter`: カーソル位置で行を分割し、新しい行をインデントする
+///
+/// 分割した後半の先頭の空白はインデントに置き換え、空白だけが残った元の行は空行にする。
+pub fn break_line(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    let line = buffer.line(cursor.row)?;
+    let (before, after) = line.split_at(cursor.col);
+    let indenting =
+        buffer.options().autoindent || indent::active_indenter(buffer.options()).is_some();
+    let after = if indenting {
+        after.trim_start_matches([' ', '\t'])
+    } else {
+        after
+    };
+    let after = after.to_string();
+    let clear_before = indenting && !before.is_empty() && before.trim().is_empty();
+
+    buffer.begin_undo_group();
+    let result = (|| -> Result<Position> {
+        let rest = buffer.line_length(cursor.row)? - cursor.col;
+        buffer.delete_str(*cursor, rest)?;
+        buffer.insert_line_with(cursor.row + 1, after.clone())?;
+        let position = open_line(buffer, cursor.row + 1, cursor.row)?;
+        if clear_before {
+            buffer.delete_str(Position::new(cursor.row, 0), cursor.col)?;
+        }
+        Ok(position)
+    })();
+    buffer.end_undo_group();
+    *cursor = result?;
+    Ok(())
+}
+
+/// 文字を挿入する（インデント規則の`indentkeys`を行頭に入力したら行を再インデントする）
+///
+/// 空白以外の文字で`textwidth`を超えたら、直前の空白で行を折り返す。
+pub fn insert_char(buffer: &mut Buffer, cursor: &mut Position, ch: char) -> Result<()> {
+    let line = buffer.line(cursor.row)?;
+    let at_line_start = line[..cursor.col].trim().is_empty();
+    buffer.insert_char(*cursor, ch)?;
+    cursor.col += ch.len_utf8();
+
+    let reindents = indent::active_indenter(buffer.options())
+        .is_some_and(|rule| rule.reindent_keys().contains(&ch));
+    if at_line_start && reindents {
+        let before = buffer.first_non_blank(cursor.row)?;
+        indent::reindent(buffer, cursor.row)?;
+        cursor.col = cursor.col - before + buffer.first_non_blank(cursor.row)?;
+    }
+    if !ch.is_whitespace() {
+        auto_wrap(buffer, cursor)?;
+    }
+    Ok(())
+}
+
+/// カーソルより前が`textwidth`を超えていれば折り返す（`formatoptions`の`t`、コメントでは`c`）
+///
+/// コメント内ではリーダーを付けて続け、それ以外は元の行のインデントに揃える。
+fn auto_wrap(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    let options = buffer.options();
+    if options.textwidth == 0 {
+        return Ok(());
+    }
+    let line = buffer.line(cursor.row)?;
+    let leader = comment::leader(line, &options.comments);
+    let enabled = if leader.is_some() {
+        options.has_format_option('c')
+    } else {
+        options.has_format_option('t')
+    };
+    if !enabled {
+        return Ok(());
+    }
+    let body_start = leader.map_or_else(
+        || line.len() - line.trim_start_matches([' ', '\t']).len(),
+        |leader| leader.len,
+    );
+    let Some((end, next)) =
+        format::break_point(&line[..cursor.col], body_start, options.textwidth, options)
+    else {
+        return Ok(());
+    };
+    let wrapped = format!(
+        "{}{}",
+        comment::continuation(line, &options.comments),
+        &line[next..]
+    );
+    let col = wrapped.len() - (line.len() - cursor.col);
+
+    buffer.begin_undo_group();
+    let result = (|| {
+        let len = buffer.line_length(cursor.row)?;
+        buffer.delete_str(Position::new(cursor.row, end), len - end)?;
+        buffer.insert_line_with(cursor.row + 1, wrapped)
+    })();
+    buffer.end_undo_group();
+    result?;
+    *cursor = Position::new(cursor.row + 1, col);
+    Ok(())
+}
+
+// Insertモードの削除キーは`backspace=indent,eol,start`相当で動作する:
+// インデントや挿入開始位置より前も削除でき、行頭では前の行と結合する。
+
+/// `Delete`: カーソル位置の文字を削除する（行末では次の行と結合する）
+pub fn delete_char_at(buffer: &mut Buffer, cursor: Position) -> Result<()> {
+    let len = buffer.line_length(cursor.row)?;
+    if cursor.col < len {
+        buffer.delete_char(cursor)?;
+    } else if cursor.row + 1 < buffer.line_count() {
+        buffer.delete_range(cursor, Position::new(cursor.row + 1, 0))?;
+    }
+    Ok(())
+}
+
+/// `Ctrl-W`: カーソルの前の単語（と直後の空白）を削除する
+pub fn delete_word_before(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    if cursor.col == 0 {
+        return join_previous_line(buffer, cursor);
+    }
+
+    let line = buffer.line(cursor.row)?;
+    let before = &line[..cursor.col];
+    let trimmed = before.trim_end_matches([' ', '\t']);
+    // 単語文字の連続、またはそれ以外の記号の連続を1単語とみなす
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = match trimmed.chars().next_back() {
+        Some(last) => {
+            let word = is_word(last);
+            trimmed
+                .char_indices()
+                .rev()
+                .take_while(|&(_, ch)| ch != ' ' && ch != '\t' && is_word(ch) == word)
+                .last()
+                .map_or(trimmed.len(), |(index, _)| index)
+        }
+        None => 0,
+    };
+
+    buffer.delete_str(Position::new(cursor.row, start), cursor.col - start)?;
+    cursor.col = start;
+    Ok(())
+}
+
+/// `Ctrl-U`: カーソルより前の行内のテキストを削除する
+pub fn delete_line_before(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    if cursor.col == 0 {
+        return join_previous_line(buffer, cursor);
+    }
+    buffer.delete_str(Position::new(cursor.row, 0), cursor.col)?;
+    cursor.col = 0;
+    Ok(())
+}
+
+/// 行頭で前の行と結合する
+fn join_previous_line(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    if cursor.row == 0 {
+        return Ok(());
+    }
+    let end = Position::new(cursor.row - 1, buffer.line_length(cursor.row - 1)?);
+    buffer.delete_range(end, *cursor)?;
+    *cursor = end;
+    Ok(())
+}
+
+/// `Ctrl-T`/`Ctrl-D`: インデントを`shiftwidth`の倍数に揃えながら1段増減する
+///
+/// カーソルは同じ文字の上に残す（インデント内にあった場合は新しいインデントの末尾）。
+pub fn shift_indent(buffer: &mut Buffer, cursor: &mut Position, amount: isize) -> Result<()> {
+    let options = buffer.options();
+    let step = options.shift_width();
+    let line = buffer.line(cursor.row)?;
+    let indent_len = buffer.first_non_blank(cursor.row)?;
+    let width = virtual_column(line, indent_len, options.tabstop);
+
+    let new_width = if amount > 0 {
+        (width / step + 1) * step
+    } else {
+        width.saturating_sub(1) / step * step
+    };
+    let indent = options.indent_string(new_width);
+    if line[..indent_len] == indent {
+        return Ok(());
+    }
+
+    buffer.replace_str(Position::new(cursor.row, 0), indent_len, &indent)?;
+    cursor.col = if cursor.col >= indent_len {
+        cursor.col - indent_len + indent.len()
+    } else {
+        indent.len()
+    };
+    Ok(())
+}
+
+/// `Tab`: タブ文字を入力する（`expandtab`や`softtabstop`では次の位置まで空白で埋める）
+///
+/// `noexpandtab`で`softtabstop`が有効な場合は、直前の空白と合わせてタブと空白を付け直す。
+pub fn insert_tab(buffer: &mut Buffer, cursor: &mut Position) -> Result<()> {
+    let options = buffer.options();
+    if !options.expandtab && options.softtabstop == 0 {
+        buffer.insert_char(*cursor, '\t')?;
+        cursor.col += 1;
+        return Ok(());
+    }
+
+    let line = buffer.line(cursor.row)?;
+    let step = if options.softtabstop > 0 {
+        options.softtabstop
+    } else {
+        options.tabstop
+    };
+    let vcol = virtual_column(line, cursor.col, options.tabstop);
+    let target = (vcol / step + 1) * step;
+    let start = if options.expandtab {
+        cursor.col
+    } else {
+        whitespace_start(line, cursor.col)
+    };
+    let text = options.whitespace(virtual_column(line, start, options.tabstop), target);
+
+    buffer.replace_str(Position::new(cursor.row, start), cursor.col - start, &text)?;
+    cursor.col = start + text.len();
+    Ok(())
+}
+
+/// `Backspace`: `softtabstop`が有効で直前が空白なら、前の`softtabstop`の倍数の位置まで削除する
+///
+/// 対象外の場合は何もせず`false`を返す（通常どおり1文字削除する）。
+pub fn delete_soft_tab(buffer: &mut Buffer, cursor: &mut Position) -> Result<bool> {
+    let options = buffer.options();
+    let line = buffer.line(cursor.row)?;
+    if options.softtabstop == 0 || !line[..cursor.col].ends_with([' ', '\t']) {
+        return Ok(false);
+    }
+
+    let vcol = virtual_column(line, cursor.col, options.tabstop);
+    let start = whitespace_start(line, cursor.col);
+    let from = virtual_column(line, start, options.tabstop);
+    // 空白の並びより前の文字は削除しない
+    let target = ((vcol - 1) / options.softtabstop * options.softtabstop).max(from);
+    let text = options.whitespace(from, target);
+
+    buffer.replace_str(Position::new(cursor.row, start), cursor.col - start, &text)?;
+    cursor.col = start + text.len();
+    Ok(true)
+}
+
+/// `col`の直前に続く空白の並びの開始位置
+fn whitespace_start(line: &str, col: usize) -> usize {
+    line[..col].trim_end_matches([' ', '\t']).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(content: &str, entry: InsertEntry, cursor: Position) -> (Buffer, Position) {
+        let mut buffer = Buffer::from_content(content);
+        let mut cursor = cursor;
+        entry.prepare(&mut buffer, &mut cursor).unwrap();
+        (buffer, cursor)
+    }
+
+    #[test]
+    fn test_prepare_positions() {
+        let at = Position::new(0, 4);
+        let content = "  héllo";
+
+        assert_eq!(prepare(content, InsertEntry::Before, at).1, at);
+        assert_eq!(
+            prepare(content, InsertEntry::After, Position::new(0, 3)).1,
+            Position::new(0, 5)
+        );
+        assert_eq!(
+            prepare(content, InsertEntry::FirstNonBlank, at).1,
+            Position::new(0, 2)
+        );
+        assert_eq!(
+            prepare(content, InsertEntry::LineEnd, at).1,
+            Position::new(0, 8)
+        );
+        assert_eq!(
+            prepare(content, InsertEntry::LineStart, at).1,
+            Position::new(0, 0)
+        );
+        // 空行では`a`でも移動しない
+        assert_eq!(
+            prepare("", InsertEntry::After, Position::new(0, 0)).1,
+            Position::new(0, 0)
+        );
+    }
+
+    #[test]
+    fn test_prepare_opens_lines() {
+        let (buffer, cursor) = prepare("a\nb", InsertEntry::NewLineBelow, Position::new(0, 1));
+        assert_eq!(buffer.line(1).unwrap(), "");
+        assert_eq!(buffer.line(2).unwrap(), "b");
+        assert_eq!(cursor, Position::new(1, 0));
+
+        let (buffer, cursor) = prepare("a\nb", InsertEntry::NewLineAbove, Position::new(1, 0));
+        assert_eq!(buffer.line(1).unwrap(), "");
+        assert_eq!(buffer.line(2).unwrap(), "b");
+        assert_eq!(cursor, Position::new(1, 0));
+    }
+
+    #[test]
+    fn test_prepare_opens_indented_lines() {
+        let (buffer, cursor) = prepare("  a\nb", InsertEntry::NewLineBelow, Position::new(0, 0));
+        assert_eq!(buffer.line(1).unwrap(), "  ");
+        assert_eq!(cursor, Position::new(1, 2));
+
+        // `O`は元の行のインデントに合わせる
+        let (buffer, cursor) = prepare("a\n\tb", InsertEntry::NewLineAbove, Position::new(1, 0));
+        assert_eq!(buffer.line(1).unwrap(), "\t");
+        assert_eq!(cursor, Position::new(1, 1));
+
+        let mut buffer = Buffer::from_content("if x {\n}");
+        buffer.options_mut().smartindent = true;
+        let mut cursor = Position::new(0, 0);
+        InsertEntry::NewLineBelow
+            .prepare(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "    ");
+        assert_eq!(cursor, Position::new(1, 4));
+    }
+
+    #[test]
+    fn test_break_line() {
+        let mut buffer = Buffer::from_content("    foo(bar)   baz");
+        let mut cursor = Position::new(0, 12);
+
+        break_line(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    foo(bar)");
+        assert_eq!(buffer.line(1).unwrap(), "    baz");
+        assert_eq!(cursor, Position::new(1, 4));
+
+        // インデントだけの行は空行にする
+        break_line(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "");
+        assert_eq!(buffer.line(2).unwrap(), "    baz");
+        assert_eq!(cursor, Position::new(2, 4));
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "    baz");
+
+        buffer.options_mut().autoindent = false;
+        let mut cursor = Position::new(1, 2);
+        break_line(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "  ");
+        assert_eq!(buffer.line(2).unwrap(), "  baz");
+        assert_eq!(cursor, Position::new(2, 0));
+    }
+
+    #[test]
+    fn test_auto_wrap() {
+        let mut buffer = Buffer::from_content("  one two thre");
+        buffer.options_mut().textwidth = 12;
+        let mut cursor = Position::new(0, 14);
+
+        insert_char(&mut buffer, &mut cursor, 'e').unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "  one two");
+        assert_eq!(buffer.line(1).unwrap(), "  three");
+        assert_eq!(cursor, Position::new(1, 7));
+
+        // 空白の入力では折り返さない
+        let mut buffer = Buffer::from_content("// aaaa bbbb");
+        buffer.options_mut().textwidth = 10;
+        let mut cursor = Position::new(0, 12);
+        insert_char(&mut buffer, &mut cursor, ' ').unwrap();
+        assert_eq!(buffer.line_count(), 1);
+
+        // コメントではリーダーを付けて続ける（カーソルより後ろのテキストも送る）
+        buffer.options_mut().textwidth = 8;
+        let mut cursor = Position::new(0, 8);
+        insert_char(&mut buffer, &mut cursor, 'x').unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "// aaaa");
+        assert_eq!(buffer.line(1).unwrap(), "// xbbbb ");
+        assert_eq!(cursor, Position::new(1, 4));
+
+        buffer.options_mut().set("fo-=c").unwrap();
+        let mut cursor = Position::new(1, 9);
+        insert_char(&mut buffer, &mut cursor, 'y').unwrap();
+        insert_char(&mut buffer, &mut cursor, 'z').unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "// xbbbb yz");
+    }
+
+    #[test]
+    fn test_closing_bracket_reindents() {
+        let mut buffer = Buffer::from_content("fn f() {\n    x\n    ");
+        buffer.options_mut().filetype = Some("rust".to_string());
+        let mut cursor = Position::new(2, 4);
+
+        insert_char(&mut buffer, &mut cursor, '}').unwrap();
+        assert_eq!(buffer.line(2).unwrap(), "}");
+        assert_eq!(cursor, Position::new(2, 1));
+
+        // 行の途中では再インデントしない
+        let mut cursor = Position::new(1, 5);
+        insert_char(&mut buffer, &mut cursor, ')').unwrap();
+        assert_eq!(buffer.line(1).unwrap(), "    x)");
+    }
+
+    #[test]
+    fn test_prepare_last_insert() {
+        let mut buffer = Buffer::from_content("one\ntwo");
+        let mut cursor = Position::new(0, 0);
+        buffer.marks_mut().set('^', Position::new(1, 2));
+
+        InsertEntry::LastInsert
+            .prepare(&mut buffer, &mut cursor)
+            .unwrap();
+        assert_eq!(cursor, Position::new(1, 2));
+    }
+
+    #[test]
+    fn test_delete_word_before() {
+        let mut buffer = Buffer::from_content("foo.bar(baz  \nnext");
+        let mut cursor = Position::new(0, 13);
+
+        delete_word_before(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "foo.bar(");
+        delete_word_before(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "foo.bar");
+        delete_word_before(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "foo.");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        // 行頭では前の行と結合する
+        let mut cursor = Position::new(1, 0);
+        delete_word_before(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "foo.next");
+        assert_eq!(cursor, Position::new(0, 4));
+    }
+
+    #[test]
+    fn test_delete_line_before_and_delete_char() {
+        let mut buffer = Buffer::from_content("  abc def\nxyz");
+        let mut cursor = Position::new(0, 6);
+
+        delete_line_before(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "def");
+        assert_eq!(cursor, Position::new(0, 0));
+
+        let cursor = Position::new(0, 3);
+        delete_char_at(&mut buffer, cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "defxyz");
+        delete_char_at(&mut buffer, cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "defyz");
+    }
+
+    #[test]
+    fn test_shift_indent() {
+        let mut buffer = Buffer::from_content("  foo");
+        let mut cursor = Position::new(0, 3);
+
+        shift_indent(&mut buffer, &mut cursor, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    foo");
+        assert_eq!(cursor, Position::new(0, 5));
+
+        shift_indent(&mut buffer, &mut cursor, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "        foo");
+
+        shift_indent(&mut buffer, &mut cursor, -1).unwrap();
+        shift_indent(&mut buffer, &mut cursor, -1).unwrap();
+        shift_indent(&mut buffer, &mut cursor, -1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "foo");
+        assert_eq!(cursor, Position::new(0, 1));
+
+        // 空行もインデントできる
+        let mut buffer = Buffer::from_content("");
+        let mut cursor = Position::new(0, 0);
+        shift_indent(&mut buffer, &mut cursor, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    ");
+        assert_eq!(cursor, Position::new(0, 4));
+    }
+
+    #[test]
+    fn test_shift_indent_with_tabs() {
+        let mut buffer = Buffer::from_content("\tfoo");
+        buffer.options_mut().expandtab = false;
+        let mut cursor = Position::new(0, 1);
+
+        shift_indent(&mut buffer, &mut cursor, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t    foo");
+        assert_eq!(cursor, Position::new(0, 5));
+
+        shift_indent(&mut buffer, &mut cursor, 1).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t\tfoo");
+        assert_eq!(cursor, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_insert_tab() {
+        let mut buffer = Buffer::from_content("ab");
+        buffer.options_mut().expandtab = false;
+        let mut cursor = Position::new(0, 1);
+        insert_tab(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a\tb");
+        assert_eq!(cursor, Position::new(0, 2));
+
+        // expandtabでは次のtabstopまで空白で埋める
+        let mut buffer = Buffer::from_content("ab");
+        let mut cursor = Position::new(0, 1);
+        insert_tab(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "a       b");
+        assert_eq!(cursor, Position::new(0, 8));
+
+        // softtabstopの位置で止まり、tabstopに届いたらタブにまとめる
+        let mut buffer = Buffer::from_content("");
+        buffer.options_mut().expandtab = false;
+        buffer.options_mut().softtabstop = 4;
+        let mut cursor = Position::new(0, 0);
+        insert_tab(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "    ");
+        insert_tab(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t");
+        insert_tab(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "\t    ");
+        assert_eq!(cursor, Position::new(0, 5));
+    }
+
+    #[test]
+    fn test_delete_soft_tab() {
+        let mut buffer = Buffer::from_content("\t    x");
+        buffer.options_mut().expandtab = false;
+        buffer.options_mut().softtabstop = 4;
+        let mut cursor = Position::new(0, 5);
+
+        assert!(delete_soft_tab(&mut buffer, &mut cursor).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "\tx");
+        assert_eq!(cursor, Position::new(0, 1));
+        assert!(delete_soft_tab(&mut buffer, &mut cursor).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "    x");
+        assert_eq!(cursor, Position::new(0, 4));
+
+        // 空白以外の直後では通常の削除
+        let mut cursor = Position::new(0, 5);
+        assert!(!delete_soft_tab(&mut buffer, &mut cursor).unwrap());
+
+        // 空白の並びより前は削除しない
+        let mut buffer = Buffer::from_content("abcde  x");
+        buffer.options_mut().softtabstop = 4;
+        let mut cursor = Position::new(0, 7);
+        assert!(delete_soft_tab(&mut buffer, &mut cursor).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "abcdex");
+        assert_eq!(cursor, Position::new(0, 5));
+    }
+
+    #[test]
+    fn test_session_repeats_text() {
+        let mut buffer = Buffer::from_content("ab");
+        let start = Position::new(0, 1);
+        let session = InsertSession::new(InsertEntry::Before, start, 3);
+
+        buffer.insert_str(start, "xy").unwrap();
+        let mut cursor = Position::new(0, 3);
+        session.finish(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "axyxyxyb");
+        assert_eq!(cursor, Position::new(0, 7));
+    }
+
+    #[test]
+    fn test_session_repeats_opened_lines() {
+        let (mut buffer, start) = prepare("a\nb", InsertEntry::NewLineBelow, Position::new(0, 0));
+        let session = InsertSession::new(InsertEntry::NewLineBelow, start, 3);
+
+        buffer.insert_str(start, "x").unwrap();
+        let mut cursor = Position::new(1, 1);
+        session.finish(&mut buffer, &mut cursor).unwrap();
+
+        let lines: Vec<_> = (0..buffer.line_count())
+            .map(|row| buffer.line(row).unwrap())
+            .collect();
+        assert_eq!(lines, vec!["a", "x", "x", "x", "b"]);
+        assert_eq!(cursor, Position::new(3, 1));
+    }
+}
@@ -1,5 +1,9 @@
-use crate::vim::command::VimCommand;
-use crate::vim::mode::Mode;
+use crate::editor::text_object::TextObject;
+use crate::vim::command::{Operator, VimCommand};
+use crate::vim::completion::CompletionKind;
+use crate::vim::insert::InsertEntry;
+use crate::vim::mode::{Mode, VisualKind};
+use crate::vim::visual::VisualAction;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -58,30 +62,172 @@ impl From<KeyEvent> for Key {
     }
 }
 
+/// Insertモードの補完のプレフィックス（`Ctrl-X`）
+const CTRL_X: char = '\x18';
+/// ウィンドウ操作のプレフィックス（`Ctrl-W`）
+const CTRL_W: char = '\x17';
+
 pub struct KeyMapper {
     // 現在は静的マッピングだが、将来的にはカスタマイズ可能にできる
+    // `g`などの複数キーコマンドの入力途中のプレフィックス
+    pending: Option<char>,
+    // 入力途中のカウント
+    count: Option<usize>,
+    // 対象の範囲を待っているオペレータとそのカウント
+    operator: Option<(Operator, usize)>,
+    // `~`をオペレータとして扱う（`tildeop`）
+    tildeop: bool,
 }
 
 impl KeyMapper {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            pending: None,
+            count: None,
+            operator: None,
+            tildeop: false,
+        }
+    }
+
+    pub fn set_tildeop(&mut self, tildeop: bool) {
+        self.tildeop = tildeop;
+    }
+
+    pub fn pending(&self) -> Option<char> {
+        self.pending
+    }
+
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    pub fn map_key(&mut self, key: &Key, mode: &Mode) -> VimCommand {
+        if self.accumulate_count(key, mode) {
+            return VimCommand::Noop;
+        }
+
+        let command = if let Some(prefix) = self.pending.take() {
+            self.map_pending(prefix, key, mode)
+        } else if let Some((operator, count)) = self.operator.take() {
+            self.map_operator_pending(operator, count, key)
+        } else {
+            match mode {
+                Mode::Normal => self.map_normal_mode(key),
+                Mode::Insert => self.map_insert_mode(key),
+                Mode::Replace { .. } => self.map_replace_mode(key),
+                Mode::Visual { kind, .. } => self.map_visual_mode(key, *kind),
+                Mode::Command { .. } => self.map_command_mode(key, mode),
+            }
+        };
+
+        // コマンドが確定したらカウントを破棄する
+        if self.pending.is_none() && self.operator.is_none() {
+            self.count = None;
+        }
+        command
+    }
+
+    /// 数字キーをカウントとして蓄積する（カウントのない`0`は行頭移動）
+    fn accumulate_count(&mut self, key: &Key, mode: &Mode) -> bool {
+        if self.pending.is_some() || !(mode.is_normal() || mode.is_visual()) {
+            return false;
+        }
+        let KeyCode::Char(ch) = key.code else {
+            return false;
+        };
+        let Some(digit) = ch.to_digit(10) else {
+            return false;
+        };
+        if key.modifiers.contains(KeyModifiers::CONTROL) || (digit == 0 && self.count.is_none()) {
+            return false;
+        }
+
+        let count = self.count.unwrap_or(0);
+        self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+        true
     }
 
-    pub fn map_key(&self, key: &Key, mode: &Mode) -> VimCommand {
-        match mode {
-            Mode::Normal => self.map_normal_mode(key),
-            Mode::Insert => self.map_insert_mode(key),
-            Mode::Visual { .. } => self.map_visual_mode(key),
-            Mode::Command { .. } => self.map_command_mode(key, mode),
+    fn take_count(&mut self) -> usize {
+        self.count.take().unwrap_or(1)
+    }
+
+    fn start_operator(&mut self, operator: Operator) -> VimCommand {
+        let count = self.take_count();
+        self.operator = Some((operator, count));
+        VimCommand::Noop
+    }
+
+    fn map_operator_pending(&mut self, operator: Operator, count: usize, key: &Key) -> VimCommand {
+        match (operator, &key.code) {
+            (Operator::Delete, KeyCode::Char('d')) => VimCommand::DeleteLine,
+            (Operator::Yank, KeyCode::Char('y')) => VimCommand::YankLine,
+            (Operator::Change, KeyCode::Char('c')) => VimCommand::ChangeLines { count },
+            (Operator::ShiftRight, KeyCode::Char('>'))
+            | (Operator::ShiftLeft, KeyCode::Char('<'))
+            | (Operator::ToggleCase, KeyCode::Char('~'))
+            | (Operator::Lowercase, KeyCode::Char('u'))
+            | (Operator::Uppercase, KeyCode::Char('U'))
+            | (Operator::Format, KeyCode::Char('q'))
+            | (Operator::Filter, KeyCode::Char('!'))
+            | (Operator::FormatKeepCursor, KeyCode::Char('w')) => {
+                VimCommand::OperateLines { operator, count }
+            }
+            // `g~g~`などの行単位の指定・`gg`とテキストオブジェクト
+            (_, KeyCode::Char(prefix @ ('g' | 'i' | 'a'))) => {
+                self.operator = Some((operator, count));
+                self.pending = Some(*prefix);
+                VimCommand::Noop
+            }
+            _ => self.operate_motion(operator, count, key),
         }
     }
 
-    fn map_normal_mode(&self, key: &Key) -> VimCommand {
+    /// `{operator}{motion}`: Normalモードと同じキーの移動コマンドを範囲にする
+    fn operate_motion(&mut self, operator: Operator, count: usize, key: &Key) -> VimCommand {
+        let motion = self.map_normal_mode(key);
+        if !motion.is_motion() {
+            // 移動以外のキーでオペレータは取り消される
+            self.operator = None;
+            self.pending = None;
+            return VimCommand::Noop;
+        }
+        // `2>3j`のようにオペレータ前後のカウントは掛け合わせる
+        VimCommand::OperateMotion {
+            operator,
+            motion: Box::new(motion),
+            count: count * self.take_count(),
+        }
+    }
+
+    fn map_normal_mode(&mut self, key: &Key) -> VimCommand {
         if key.modifiers.contains(KeyModifiers::CONTROL) {
-            return self.map_ctrl_keys(key);
+            return match key.code {
+                KeyCode::Char('a') => VimCommand::AddToNumber {
+                    amount: self.take_count() as i64,
+                },
+                KeyCode::Char('x') => VimCommand::AddToNumber {
+                    amount: -(self.take_count() as i64),
+                },
+                KeyCode::Char('w') => {
+                    self.pending = Some(CTRL_W);
+                    VimCommand::Noop
+                }
+                // `Ctrl-^`: オルタネートバッファ（カウントがあればその番号のバッファ）に切り替える
+                KeyCode::Char('^' | '6') => VimCommand::ExecuteCommand(match self.count.take() {
+                    Some(count) => format!("buffer {}", count),
+                    None => "buffer #".to_string(),
+                }),
+                _ => self.map_ctrl_keys(key),
+            };
         }
 
         match key.code {
+            // Multi-key prefixes
+            KeyCode::Char(prefix @ ('g' | 'z' | 'r' | 'Z')) => {
+                self.pending = Some(prefix);
+                VimCommand::Noop
+            }
+
             // Movement
             KeyCode::Char('h') => VimCommand::MoveLeft,
             KeyCode::Char('j') => VimCommand::MoveDown,
@@ -91,17 +237,51 @@ impl KeyMapper {
             KeyCode::Char('$') => VimCommand::MoveLineEnd,
             KeyCode::Char('w') => VimCommand::MoveWordForward,
             KeyCode::Char('b') => VimCommand::MoveWordBackward,
+            KeyCode::Char('e') => VimCommand::MoveWordEnd,
+            KeyCode::Char('}') => VimCommand::MoveParagraphForward,
+            KeyCode::Char('{') => VimCommand::MoveParagraphBackward,
+            KeyCode::Char('G') => VimCommand::MoveBufferEnd,
+            KeyCode::Char('H') => VimCommand::MoveScreenTop,
+            KeyCode::Char('M') => VimCommand::MoveScreenMiddle,
+            KeyCode::Char('L') => VimCommand::MoveScreenBottom,
 
             // Mode transitions
-            KeyCode::Char('i') => VimCommand::EnterInsert,
-            KeyCode::Char('a') => VimCommand::EnterInsertAfter,
-            KeyCode::Char('o') => VimCommand::EnterInsertNewLine,
+            KeyCode::Char('i') => self.enter_insert(InsertEntry::Before),
+            KeyCode::Char('a') => self.enter_insert(InsertEntry::After),
+            KeyCode::Char('I') => self.enter_insert(InsertEntry::FirstNonBlank),
+            KeyCode::Char('A') => self.enter_insert(InsertEntry::LineEnd),
+            KeyCode::Char('o') => self.enter_insert(InsertEntry::NewLineBelow),
+            KeyCode::Char('O') => self.enter_insert(InsertEntry::NewLineAbove),
+            KeyCode::Char('R') => VimCommand::EnterReplace,
             KeyCode::Char('v') => VimCommand::EnterVisual,
+            KeyCode::Char('V') => VimCommand::EnterVisualLine,
             KeyCode::Char(':') => VimCommand::EnterCommand,
 
             // Editing
             KeyCode::Char('x') => VimCommand::DeleteChar,
-            KeyCode::Char('d') => VimCommand::DeleteLine, // 簡略化: ddの代わり
+            KeyCode::Char('d') => self.start_operator(Operator::Delete),
+            KeyCode::Char('c') => self.start_operator(Operator::Change),
+            KeyCode::Char('s') => VimCommand::SubstituteChars {
+                count: self.take_count(),
+            },
+            KeyCode::Char('S') => VimCommand::ChangeLines {
+                count: self.take_count(),
+            },
+            KeyCode::Char('C') => VimCommand::ChangeToLineEnd {
+                count: self.take_count(),
+            },
+            KeyCode::Char('y') => self.start_operator(Operator::Yank),
+            KeyCode::Char('>') => self.start_operator(Operator::ShiftRight),
+            KeyCode::Char('<') => self.start_operator(Operator::ShiftLeft),
+            KeyCode::Char('!') => self.start_operator(Operator::Filter),
+            KeyCode::Char('~') if self.tildeop => self.start_operator(Operator::ToggleCase),
+            KeyCode::Char('~') => VimCommand::ToggleCaseChars {
+                count: self.take_count(),
+            },
+            KeyCode::Char('J') => VimCommand::JoinLines {
+                count: self.take_count(),
+                spaces: true,
+            },
             KeyCode::Char('u') => VimCommand::Undo,
 
             // Special keys
@@ -109,36 +289,289 @@ impl KeyMapper {
             KeyCode::Down => VimCommand::MoveDown,
             KeyCode::Up => VimCommand::MoveUp,
             KeyCode::Right => VimCommand::MoveRight,
+            KeyCode::PageDown => VimCommand::ScrollPageDown,
+            KeyCode::PageUp => VimCommand::ScrollPageUp,
 
             _ => VimCommand::Noop,
         }
     }
 
-    fn map_insert_mode(&self, key: &Key) -> VimCommand {
+    fn map_pending(&mut self, prefix: char, key: &Key, mode: &Mode) -> VimCommand {
+        match (prefix, &key.code) {
+            // `Ctrl-W {key}`（`Ctrl-W Ctrl-{key}`も同じ）は`:wincmd`として実行する
+            (CTRL_W, KeyCode::Char(ch)) => VimCommand::ExecuteCommand(match self.count.take() {
+                Some(count) => format!("{}wincmd {}", count, ch),
+                None => format!("wincmd {}", ch),
+            }),
+            // オペレータ入力中の`g`は`g~g~`などの行単位の指定か`gg`
+            ('g', KeyCode::Char(ch)) if self.operator.is_some() => match (self.operator.take(), ch)
+            {
+                (Some((operator @ Operator::ToggleCase, count)), '~')
+                | (Some((operator @ Operator::Lowercase, count)), 'u')
+                | (Some((operator @ Operator::Uppercase, count)), 'U')
+                | (Some((operator @ Operator::Format, count)), 'q')
+                | (Some((operator @ Operator::FormatKeepCursor, count)), 'w') => {
+                    VimCommand::OperateLines { operator, count }
+                }
+                (Some((operator, count)), 'g') => VimCommand::OperateMotion {
+                    operator,
+                    motion: Box::new(VimCommand::MoveBufferStart),
+                    count: count * self.take_count(),
+                },
+                _ => VimCommand::Noop,
+            },
+            // `g Ctrl-A`/`g Ctrl-X`: 行ごとに増分を増やして連番にする
+            ('g', KeyCode::Char(ch @ ('a' | 'x')))
+                if mode.is_visual() && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.visual_operate(VisualAction::AddToNumber {
+                    decrement: *ch == 'x',
+                    progressive: true,
+                })
+            }
+            ('g', KeyCode::Char('~')) if !mode.is_visual() => {
+                self.start_operator(Operator::ToggleCase)
+            }
+            ('g', KeyCode::Char('u')) if !mode.is_visual() => {
+                self.start_operator(Operator::Lowercase)
+            }
+            ('g', KeyCode::Char('U')) if !mode.is_visual() => {
+                self.start_operator(Operator::Uppercase)
+            }
+            ('g', KeyCode::Char('q')) if mode.is_visual() => {
+                self.visual_operate(VisualAction::Operator(Operator::Format))
+            }
+            ('g', KeyCode::Char('w')) if mode.is_visual() => {
+                self.visual_operate(VisualAction::Operator(Operator::FormatKeepCursor))
+            }
+            ('g', KeyCode::Char('q')) => self.start_operator(Operator::Format),
+            ('g', KeyCode::Char('w')) => self.start_operator(Operator::FormatKeepCursor),
+            ('g', KeyCode::Char('g')) => VimCommand::MoveBufferStart,
+            // `{N}gt`はN番目のタブページ、`{N}gT`はN個前のタブページに移る
+            ('g', KeyCode::Char(ch @ ('t' | 'T'))) if !mode.is_visual() => {
+                let name = if *ch == 't' { "tabnext" } else { "tabprevious" };
+                VimCommand::ExecuteCommand(match self.count.take() {
+                    Some(count) => format!("{} {}", name, count),
+                    None => name.to_string(),
+                })
+            }
+            ('g', KeyCode::Char('j')) | ('g', KeyCode::Down) => VimCommand::MoveDisplayDown,
+            ('g', KeyCode::Char('k')) | ('g', KeyCode::Up) => VimCommand::MoveDisplayUp,
+            ('g', KeyCode::Char('v')) => VimCommand::ReselectVisual,
+            ('g', KeyCode::Char('i')) => self.enter_insert(InsertEntry::LastInsert),
+            ('g', KeyCode::Char('I')) => self.enter_insert(InsertEntry::LineStart),
+            ('g', KeyCode::Char('J')) if mode.is_visual() => {
+                self.visual_operate(VisualAction::Join { spaces: false })
+            }
+            ('g', KeyCode::Char('J')) => VimCommand::JoinLines {
+                count: self.take_count(),
+                spaces: false,
+            },
+            // `ZZ`は変更があれば保存して閉じ（`:x`）、`ZQ`は保存せずに閉じる（`:q!`）
+            ('Z', KeyCode::Char('Z')) => VimCommand::SaveAndQuit,
+            ('Z', KeyCode::Char('Q')) => VimCommand::ForceQuit,
+            ('z', KeyCode::Char('z')) => VimCommand::ScrollCursorCenter,
+            ('z', KeyCode::Char('t')) => VimCommand::ScrollCursorTop,
+            ('z', KeyCode::Char('b')) => VimCommand::ScrollCursorBottom,
+            // `Ctrl-X Ctrl-L`/`Ctrl-X Ctrl-F`
+            (CTRL_X, KeyCode::Char('l')) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                VimCommand::Complete {
+                    kind: CompletionKind::Line,
+                    forward: false,
+                }
+            }
+            (CTRL_X, KeyCode::Char('f')) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                VimCommand::Complete {
+                    kind: CompletionKind::FileName,
+                    forward: true,
+                }
+            }
+            ('r', KeyCode::Char(ch)) if mode.is_visual() => {
+                self.visual_operate(VisualAction::Replace(*ch))
+            }
+            ('r', KeyCode::Char(ch)) => VimCommand::ReplaceChars {
+                ch: *ch,
+                count: self.take_count(),
+            },
+            // `r<CR>`は行を分割する
+            ('r', KeyCode::Enter) if !mode.is_visual() => VimCommand::ReplaceChars {
+                ch: '\n',
+                count: self.take_count(),
+            },
+            (prefix @ ('i' | 'a'), KeyCode::Char(ch)) => {
+                let operator = self.operator.take();
+                let Some(object) = TextObject::from_key(prefix == 'i', *ch) else {
+                    return VimCommand::Noop;
+                };
+                let count = self.take_count();
+                match operator {
+                    // `2d3aw`のようにオペレータ前後のカウントは掛け合わせる
+                    Some((operator, operator_count)) => VimCommand::OperateTextObject {
+                        operator,
+                        object,
+                        count: operator_count * count,
+                    },
+                    None => VimCommand::SelectTextObject { object, count },
+                }
+            }
+            _ => {
+                self.operator = None;
+                VimCommand::Noop
+            }
+        }
+    }
+
+    fn map_insert_mode(&mut self, key: &Key) -> VimCommand {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('w') => VimCommand::DeleteWordBackward,
+                KeyCode::Char('u') => VimCommand::DeleteToLineStart,
+                KeyCode::Char('t') => VimCommand::ShiftIndentRight,
+                KeyCode::Char('d') => VimCommand::ShiftIndentLeft,
+                KeyCode::Char('o') => VimCommand::InsertNormalCommand,
+                KeyCode::Char('n') => VimCommand::Complete {
+                    kind: CompletionKind::Keyword,
+                    forward: true,
+                },
+                KeyCode::Char('p') => VimCommand::Complete {
+                    kind: CompletionKind::Keyword,
+                    forward: false,
+                },
+                KeyCode::Char('e') => VimCommand::CompleteCancel,
+                KeyCode::Char('y') => VimCommand::CompleteAccept,
+                KeyCode::Char('x') => {
+                    self.pending = Some(CTRL_X);
+                    VimCommand::Noop
+                }
+                _ => VimCommand::Noop,
+            };
+        }
+
         match key.code {
             KeyCode::Esc => VimCommand::ExitToNormal,
             KeyCode::Char(c) => VimCommand::InsertChar(c),
             KeyCode::Enter => VimCommand::NewLine,
+            KeyCode::Tab => VimCommand::InsertTab,
             KeyCode::Backspace => VimCommand::DeleteCharBackward,
+            KeyCode::Delete => VimCommand::DeleteCharForward,
+            KeyCode::Left => VimCommand::MoveLeft,
+            KeyCode::Down => VimCommand::MoveDown,
+            KeyCode::Up => VimCommand::MoveUp,
+            KeyCode::Right => VimCommand::MoveRight,
+            KeyCode::Home => VimCommand::MoveLineStart,
+            KeyCode::End => VimCommand::MoveLineEnd,
             _ => VimCommand::Noop,
         }
     }
 
-    fn map_visual_mode(&self, key: &Key) -> VimCommand {
+    fn map_replace_mode(&self, key: &Key) -> VimCommand {
+        // `Ctrl-`キーで文字を置き換えない
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return VimCommand::Noop;
+        }
+
         match key.code {
             KeyCode::Esc => VimCommand::ExitToNormal,
-            KeyCode::Char('i') => VimCommand::EnterInsert,
+            KeyCode::Char(c) => VimCommand::ReplaceChar(c),
+            KeyCode::Enter => VimCommand::ReplaceNewLine,
+            KeyCode::Backspace => VimCommand::ReplaceBackspace,
+            _ => VimCommand::Noop,
+        }
+    }
 
-            // Movement (visual mode specific behavior could be added later)
-            KeyCode::Char('h') => VimCommand::MoveLeft,
-            KeyCode::Char('j') => VimCommand::MoveDown,
-            KeyCode::Char('k') => VimCommand::MoveUp,
-            KeyCode::Char('l') => VimCommand::MoveRight,
+    fn map_visual_mode(&mut self, key: &Key, kind: VisualKind) -> VimCommand {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('v') => Self::switch_visual(kind, VisualKind::Block),
+                KeyCode::Char(ch @ ('a' | 'x')) => self.visual_operate(VisualAction::AddToNumber {
+                    decrement: ch == 'x',
+                    progressive: false,
+                }),
+                _ => self.map_ctrl_keys(key),
+            };
+        }
+
+        match key.code {
+            KeyCode::Esc => VimCommand::ExitToNormal,
+            KeyCode::Char('v') => Self::switch_visual(kind, VisualKind::Char),
+            KeyCode::Char('V') => Self::switch_visual(kind, VisualKind::Line),
+            // 選択範囲を`'<,'>`として渡す
+            KeyCode::Char(':') => VimCommand::EnterCommand,
+
+            // Multi-key prefixes (text objects, `r{char}`, `gg`)
+            KeyCode::Char(prefix @ ('i' | 'a' | 'r' | 'g')) => {
+                self.pending = Some(prefix);
+                VimCommand::Noop
+            }
+
+            // Selection ends
+            KeyCode::Char('o') => VimCommand::VisualSwapEnds,
+            KeyCode::Char('O') => VimCommand::VisualSwapCorner,
+
+            // Operators
+            KeyCode::Char('d' | 'x') | KeyCode::Delete => {
+                self.visual_operate(VisualAction::Operator(Operator::Delete))
+            }
+            KeyCode::Char('c' | 's') => {
+                self.visual_operate(VisualAction::Operator(Operator::Change))
+            }
+            KeyCode::Char('y') => self.visual_operate(VisualAction::Operator(Operator::Yank)),
+            KeyCode::Char('>') => self.visual_operate(VisualAction::Operator(Operator::ShiftRight)),
+            KeyCode::Char('<') => self.visual_operate(VisualAction::Operator(Operator::ShiftLeft)),
+            KeyCode::Char('~') => self.visual_operate(VisualAction::Operator(Operator::ToggleCase)),
+            KeyCode::Char('u') => self.visual_operate(VisualAction::Operator(Operator::Lowercase)),
+            KeyCode::Char('U') => self.visual_operate(VisualAction::Operator(Operator::Uppercase)),
+            KeyCode::Char('J') => self.visual_operate(VisualAction::Join { spaces: true }),
+            KeyCode::Char('!') => self.visual_operate(VisualAction::Operator(Operator::Filter)),
+            KeyCode::Char('p' | 'P') => self.visual_operate(VisualAction::Put),
+            KeyCode::Char('I') => self.visual_operate(VisualAction::Insert),
+            KeyCode::Char('A') => self.visual_operate(VisualAction::Append),
+
+            // Movement
+            KeyCode::Char('h') | KeyCode::Left => VimCommand::MoveLeft,
+            KeyCode::Char('j') | KeyCode::Down => VimCommand::MoveDown,
+            KeyCode::Char('k') | KeyCode::Up => VimCommand::MoveUp,
+            KeyCode::Char('l') | KeyCode::Right => VimCommand::MoveRight,
+            KeyCode::Char('0') => VimCommand::MoveLineStart,
+            KeyCode::Char('$') => VimCommand::MoveLineEnd,
+            KeyCode::Char('w') => VimCommand::MoveWordForward,
+            KeyCode::Char('b') => VimCommand::MoveWordBackward,
+            KeyCode::Char('e') => VimCommand::MoveWordEnd,
+            KeyCode::Char('}') => VimCommand::MoveParagraphForward,
+            KeyCode::Char('{') => VimCommand::MoveParagraphBackward,
+            KeyCode::Char('G') => VimCommand::MoveBufferEnd,
+            KeyCode::Char('H') => VimCommand::MoveScreenTop,
+            KeyCode::Char('M') => VimCommand::MoveScreenMiddle,
+            KeyCode::Char('L') => VimCommand::MoveScreenBottom,
+            KeyCode::PageDown => VimCommand::ScrollPageDown,
+            KeyCode::PageUp => VimCommand::ScrollPageUp,
 
             _ => VimCommand::Noop,
         }
     }
 
+    /// 同じ種類なら終了、異なる種類ならビジュアルモードを切り替える
+    fn switch_visual(current: VisualKind, target: VisualKind) -> VimCommand {
+        if current == target {
+            return VimCommand::ExitToNormal;
+        }
+        match target {
+            VisualKind::Char => VimCommand::EnterVisual,
+            VisualKind::Line => VimCommand::EnterVisualLine,
+            VisualKind::Block => VimCommand::EnterVisualBlock,
+        }
+    }
+
+    fn enter_insert(&mut self, entry: InsertEntry) -> VimCommand {
+        let count = self.take_count();
+        VimCommand::EnterInsert { entry, count }
+    }
+
+    fn visual_operate(&mut self, action: VisualAction) -> VimCommand {
+        let count = self.take_count();
+        VimCommand::VisualOperate { action, count }
+    }
+
     fn map_command_mode(&self, key: &Key, mode: &Mode) -> VimCommand {
         match key.code {
             KeyCode::Esc => VimCommand::ExitToNormal,
@@ -159,8 +592,13 @@ impl KeyMapper {
     fn map_ctrl_keys(&self, key: &Key) -> VimCommand {
         match key.code {
             KeyCode::Char('r') => VimCommand::Redo,
-            KeyCode::Char('f') => VimCommand::MoveBufferEnd, // Ctrl+F: page down
-            KeyCode::Char('b') => VimCommand::MoveBufferStart, // Ctrl+B: page up
+            KeyCode::Char('v') => VimCommand::EnterVisualBlock,
+            KeyCode::Char('f') => VimCommand::ScrollPageDown,
+            KeyCode::Char('b') => VimCommand::ScrollPageUp,
+            KeyCode::Char('d') => VimCommand::ScrollHalfPageDown,
+            KeyCode::Char('u') => VimCommand::ScrollHalfPageUp,
+            KeyCode::Char('e') => VimCommand::ScrollLineDown,
+            KeyCode::Char('y') => VimCommand::ScrollLineUp,
             _ => VimCommand::Noop,
         }
     }
@@ -208,7 +646,7 @@ mod tests {
 
     #[test]
     fn test_normal_mode_movement_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Normal;
 
         let test_cases = [
@@ -230,17 +668,34 @@ mod tests {
 
     #[test]
     fn test_normal_mode_editing_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Normal;
 
         let test_cases = [
-            (Key::char('i'), VimCommand::EnterInsert),
-            (Key::char('a'), VimCommand::EnterInsertAfter),
-            (Key::char('o'), VimCommand::EnterInsertNewLine),
+            (
+                Key::char('i'),
+                VimCommand::EnterInsert {
+                    entry: InsertEntry::Before,
+                    count: 1,
+                },
+            ),
+            (
+                Key::char('a'),
+                VimCommand::EnterInsert {
+                    entry: InsertEntry::After,
+                    count: 1,
+                },
+            ),
+            (
+                Key::char('o'),
+                VimCommand::EnterInsert {
+                    entry: InsertEntry::NewLineBelow,
+                    count: 1,
+                },
+            ),
             (Key::char('v'), VimCommand::EnterVisual),
             (Key::char(':'), VimCommand::EnterCommand),
             (Key::char('x'), VimCommand::DeleteChar),
-            (Key::char('d'), VimCommand::DeleteLine),
             (Key::char('u'), VimCommand::Undo),
         ];
 
@@ -250,9 +705,382 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_insert_entry_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        let test_cases = [
+            ("I", InsertEntry::FirstNonBlank),
+            ("A", InsertEntry::LineEnd),
+            ("O", InsertEntry::NewLineAbove),
+            ("gi", InsertEntry::LastInsert),
+            ("gI", InsertEntry::LineStart),
+        ];
+        for (keys, entry) in test_cases {
+            let mut cmd = VimCommand::Noop;
+            for ch in format!("3{}", keys).chars() {
+                cmd = mapper.map_key(&Key::char(ch), &mode);
+            }
+            assert_eq!(cmd, VimCommand::EnterInsert { entry, count: 3 });
+        }
+    }
+
+    #[test]
+    fn test_format_operator_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+        let lines = |operator, count| VimCommand::OperateLines { operator, count };
+
+        let test_cases = [
+            ("gqq", lines(Operator::Format, 1)),
+            ("3gqgq", lines(Operator::Format, 3)),
+            ("gww", lines(Operator::FormatKeepCursor, 1)),
+            ("2!!", lines(Operator::Filter, 2)),
+            (
+                "gq}",
+                VimCommand::OperateMotion {
+                    operator: Operator::Format,
+                    motion: Box::new(VimCommand::MoveParagraphForward),
+                    count: 1,
+                },
+            ),
+            (
+                "!G",
+                VimCommand::OperateMotion {
+                    operator: Operator::Filter,
+                    motion: Box::new(VimCommand::MoveBufferEnd),
+                    count: 1,
+                },
+            ),
+            (
+                "gw2j",
+                VimCommand::OperateMotion {
+                    operator: Operator::FormatKeepCursor,
+                    motion: Box::new(VimCommand::MoveDown),
+                    count: 2,
+                },
+            ),
+            (
+                "gwap",
+                VimCommand::OperateTextObject {
+                    operator: Operator::FormatKeepCursor,
+                    object: TextObject::from_key(false, 'p').unwrap(),
+                    count: 1,
+                },
+            ),
+        ];
+        for (keys, expected_cmd) in test_cases {
+            let mut cmd = VimCommand::Noop;
+            for ch in keys.chars() {
+                cmd = mapper.map_key(&Key::char(ch), &mode);
+            }
+            assert_eq!(cmd, expected_cmd, "{}", keys);
+        }
+
+        let visual = Mode::Visual {
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
+        };
+        mapper.map_key(&Key::char('g'), &visual);
+        assert_eq!(
+            mapper.map_key(&Key::char('q'), &visual),
+            VimCommand::VisualOperate {
+                action: VisualAction::Operator(Operator::Format),
+                count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_case_and_number_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+        let lines = |operator, count| VimCommand::OperateLines { operator, count };
+
+        let test_cases = [
+            ("3~", VimCommand::ToggleCaseChars { count: 3 }),
+            ("g~~", lines(Operator::ToggleCase, 1)),
+            ("2gugu", lines(Operator::Lowercase, 2)),
+            ("gUU", lines(Operator::Uppercase, 1)),
+            ("gUu", VimCommand::Noop),
+            (
+                "guw",
+                VimCommand::OperateMotion {
+                    operator: Operator::Lowercase,
+                    motion: Box::new(VimCommand::MoveWordForward),
+                    count: 1,
+                },
+            ),
+            (
+                "gU$",
+                VimCommand::OperateMotion {
+                    operator: Operator::Uppercase,
+                    motion: Box::new(VimCommand::MoveLineEnd),
+                    count: 1,
+                },
+            ),
+            (
+                "g~2e",
+                VimCommand::OperateMotion {
+                    operator: Operator::ToggleCase,
+                    motion: Box::new(VimCommand::MoveWordEnd),
+                    count: 2,
+                },
+            ),
+            (
+                "gUiw",
+                VimCommand::OperateTextObject {
+                    operator: Operator::Uppercase,
+                    object: TextObject::from_key(true, 'w').unwrap(),
+                    count: 1,
+                },
+            ),
+        ];
+        for (keys, expected_cmd) in test_cases {
+            let mut cmd = VimCommand::Noop;
+            for ch in keys.chars() {
+                cmd = mapper.map_key(&Key::char(ch), &mode);
+            }
+            assert_eq!(cmd, expected_cmd, "{}", keys);
+        }
+
+        mapper.set_tildeop(true);
+        assert_eq!(mapper.map_key(&Key::char('~'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('~'), &mode),
+            lines(Operator::ToggleCase, 1)
+        );
+
+        mapper.map_key(&Key::char('5'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('x'), &mode),
+            VimCommand::AddToNumber { amount: -5 }
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('a'), &mode),
+            VimCommand::AddToNumber { amount: 1 }
+        );
+
+        let visual = Mode::Visual {
+            start: Position::new(0, 0),
+            kind: VisualKind::Line,
+        };
+        assert_eq!(mapper.map_key(&Key::char('g'), &visual), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('a'), &visual),
+            VimCommand::VisualOperate {
+                action: VisualAction::AddToNumber {
+                    decrement: false,
+                    progressive: true,
+                },
+                count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_alternate_buffer_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('^'), &mode),
+            VimCommand::ExecuteCommand("buffer #".to_string())
+        );
+        mapper.map_key(&Key::char('3'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('6'), &mode),
+            VimCommand::ExecuteCommand("buffer 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_window_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+        let wincmd = |input: &str| VimCommand::ExecuteCommand(input.to_string());
+
+        assert_eq!(mapper.map_key(&Key::ctrl('w'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::char('v'), &mode), wincmd("wincmd v"));
+
+        // `Ctrl-W Ctrl-W`は`Ctrl-W w`と同じ
+        mapper.map_key(&Key::ctrl('w'), &mode);
+        assert_eq!(mapper.map_key(&Key::ctrl('w'), &mode), wincmd("wincmd w"));
+
+        mapper.map_key(&Key::char('5'), &mode);
+        mapper.map_key(&Key::ctrl('w'), &mode);
+        assert_eq!(mapper.map_key(&Key::char('+'), &mode), wincmd("5wincmd +"));
+        assert_eq!(mapper.count(), None);
+    }
+
+    #[test]
+    fn test_change_commands_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        let test_cases = [
+            ("s", VimCommand::SubstituteChars { count: 1 }),
+            ("4s", VimCommand::SubstituteChars { count: 4 }),
+            ("2S", VimCommand::ChangeLines { count: 2 }),
+            ("3cc", VimCommand::ChangeLines { count: 3 }),
+            ("2C", VimCommand::ChangeToLineEnd { count: 2 }),
+            (
+                "3>>",
+                VimCommand::OperateLines {
+                    operator: Operator::ShiftRight,
+                    count: 3,
+                },
+            ),
+            (
+                "<<",
+                VimCommand::OperateLines {
+                    operator: Operator::ShiftLeft,
+                    count: 1,
+                },
+            ),
+            ("<>", VimCommand::Noop),
+            (
+                "3J",
+                VimCommand::JoinLines {
+                    count: 3,
+                    spaces: true,
+                },
+            ),
+            (
+                "gJ",
+                VimCommand::JoinLines {
+                    count: 1,
+                    spaces: false,
+                },
+            ),
+        ];
+        for (keys, expected_cmd) in test_cases {
+            let mut cmd = VimCommand::Noop;
+            for ch in keys.chars() {
+                cmd = mapper.map_key(&Key::char(ch), &mode);
+            }
+            assert_eq!(cmd, expected_cmd);
+        }
+    }
+
+    #[test]
+    fn test_operator_doubled_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        assert_eq!(mapper.map_key(&Key::char('d'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('d'), &mode),
+            VimCommand::DeleteLine
+        );
+
+        assert_eq!(mapper.map_key(&Key::char('y'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::char('y'), &mode), VimCommand::YankLine);
+
+        // 範囲以外のキーでオペレータは取り消される
+        assert_eq!(mapper.map_key(&Key::char('d'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::escape(), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('x'), &mode),
+            VimCommand::DeleteChar
+        );
+    }
+
+    #[test]
+    fn test_operator_motion_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+        let motion = |operator, motion, count| VimCommand::OperateMotion {
+            operator,
+            motion: Box::new(motion),
+            count,
+        };
+
+        let test_cases = [
+            (">j", motion(Operator::ShiftRight, VimCommand::MoveDown, 1)),
+            (
+                ">}",
+                motion(Operator::ShiftRight, VimCommand::MoveParagraphForward, 1),
+            ),
+            (
+                "<G",
+                motion(Operator::ShiftLeft, VimCommand::MoveBufferEnd, 1),
+            ),
+            (
+                "<gg",
+                motion(Operator::ShiftLeft, VimCommand::MoveBufferStart, 1),
+            ),
+            // オペレータの前後のカウントは掛け合わされる
+            ("2>3k", motion(Operator::ShiftRight, VimCommand::MoveUp, 6)),
+            ("d0", motion(Operator::Delete, VimCommand::MoveLineStart, 1)),
+            // 移動以外のキーでは取り消される
+            (">x", VimCommand::Noop),
+            ("x", VimCommand::DeleteChar),
+        ];
+        for (keys, expected_cmd) in test_cases {
+            let mut cmd = VimCommand::Noop;
+            for ch in keys.chars() {
+                cmd = mapper.map_key(&Key::char(ch), &mode);
+            }
+            assert_eq!(cmd, expected_cmd, "{}", keys);
+        }
+        assert_eq!(mapper.count(), None);
+    }
+
+    #[test]
+    fn test_operator_text_object_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        for key in ['c', 'i'] {
+            assert_eq!(mapper.map_key(&Key::char(key), &mode), VimCommand::Noop);
+        }
+        assert_eq!(
+            mapper.map_key(&Key::char('"'), &mode),
+            VimCommand::OperateTextObject {
+                operator: Operator::Change,
+                object: TextObject::from_key(true, '"').unwrap(),
+                count: 1,
+            }
+        );
+
+        // オペレータの前後のカウントは掛け合わされる
+        for key in ['2', 'd', '3', 'a'] {
+            assert_eq!(mapper.map_key(&Key::char(key), &mode), VimCommand::Noop);
+        }
+        assert_eq!(
+            mapper.map_key(&Key::char('p'), &mode),
+            VimCommand::OperateTextObject {
+                operator: Operator::Delete,
+                object: TextObject::from_key(false, 'p').unwrap(),
+                count: 6,
+            }
+        );
+        assert_eq!(mapper.count(), None);
+    }
+
+    #[test]
+    fn test_count_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        assert_eq!(mapper.map_key(&Key::char('1'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::char('0'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.count(), Some(10));
+
+        // コマンドが確定するとカウントは破棄される
+        assert_eq!(mapper.map_key(&Key::char('j'), &mode), VimCommand::MoveDown);
+        assert_eq!(mapper.count(), None);
+        assert_eq!(
+            mapper.map_key(&Key::char('0'), &mode),
+            VimCommand::MoveLineStart
+        );
+    }
+
     #[test]
     fn test_normal_mode_arrow_keys() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Normal;
 
         let test_cases = [
@@ -282,7 +1110,7 @@ mod tests {
 
     #[test]
     fn test_insert_mode_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Insert;
 
         // Character insertion
@@ -293,7 +1121,32 @@ mod tests {
         let test_cases = [
             (Key::escape(), VimCommand::ExitToNormal),
             (Key::enter(), VimCommand::NewLine),
+            (
+                Key::new(KeyCode::Tab, KeyModifiers::NONE),
+                VimCommand::InsertTab,
+            ),
             (Key::backspace(), VimCommand::DeleteCharBackward),
+            (
+                Key::new(KeyCode::Delete, KeyModifiers::NONE),
+                VimCommand::DeleteCharForward,
+            ),
+            (
+                Key::new(KeyCode::Left, KeyModifiers::NONE),
+                VimCommand::MoveLeft,
+            ),
+            (
+                Key::new(KeyCode::Home, KeyModifiers::NONE),
+                VimCommand::MoveLineStart,
+            ),
+            (
+                Key::new(KeyCode::End, KeyModifiers::NONE),
+                VimCommand::MoveLineEnd,
+            ),
+            (Key::ctrl('w'), VimCommand::DeleteWordBackward),
+            (Key::ctrl('u'), VimCommand::DeleteToLineStart),
+            (Key::ctrl('t'), VimCommand::ShiftIndentRight),
+            (Key::ctrl('d'), VimCommand::ShiftIndentLeft),
+            (Key::ctrl('o'), VimCommand::InsertNormalCommand),
         ];
 
         for (key, expected_cmd) in &test_cases {
@@ -302,20 +1155,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_completion_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Insert;
+
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('n'), &mode),
+            VimCommand::Complete {
+                kind: CompletionKind::Keyword,
+                forward: true
+            }
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('e'), &mode),
+            VimCommand::CompleteCancel
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('y'), &mode),
+            VimCommand::CompleteAccept
+        );
+
+        assert_eq!(mapper.map_key(&Key::ctrl('x'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('l'), &mode),
+            VimCommand::Complete {
+                kind: CompletionKind::Line,
+                forward: false
+            }
+        );
+        mapper.map_key(&Key::ctrl('x'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('f'), &mode),
+            VimCommand::Complete {
+                kind: CompletionKind::FileName,
+                forward: true
+            }
+        );
+        // `Ctrl-X`の後の通常の文字は破棄される
+        mapper.map_key(&Key::ctrl('x'), &mode);
+        assert_eq!(mapper.map_key(&Key::char('l'), &mode), VimCommand::Noop);
+    }
+
     #[test]
     fn test_visual_mode_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Visual {
             start: Position::new(0, 0),
+            kind: VisualKind::Char,
         };
 
         let test_cases = [
             (Key::escape(), VimCommand::ExitToNormal),
-            (Key::char('i'), VimCommand::EnterInsert),
             (Key::char('h'), VimCommand::MoveLeft),
             (Key::char('j'), VimCommand::MoveDown),
             (Key::char('k'), VimCommand::MoveUp),
             (Key::char('l'), VimCommand::MoveRight),
+            (Key::char(':'), VimCommand::EnterCommand),
         ];
 
         for (key, expected_cmd) in &test_cases {
@@ -324,9 +1220,122 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_visual_mode_switching() {
+        let mut mapper = KeyMapper::new();
+        let visual = |kind| Mode::Visual {
+            start: Position::new(0, 0),
+            kind,
+        };
+
+        assert_eq!(
+            mapper.map_key(&Key::char('V'), &Mode::Normal),
+            VimCommand::EnterVisualLine
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('v'), &Mode::Normal),
+            VimCommand::EnterVisualBlock
+        );
+
+        // 同じ種類のキーで終了、別の種類のキーで切り替え
+        let mode = visual(VisualKind::Line);
+        assert_eq!(
+            mapper.map_key(&Key::char('V'), &mode),
+            VimCommand::ExitToNormal
+        );
+        assert_eq!(
+            mapper.map_key(&Key::char('v'), &mode),
+            VimCommand::EnterVisual
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('v'), &mode),
+            VimCommand::EnterVisualBlock
+        );
+        assert_eq!(
+            mapper.map_key(&Key::ctrl('v'), &visual(VisualKind::Block)),
+            VimCommand::ExitToNormal
+        );
+    }
+
+    #[test]
+    fn test_visual_mode_operator_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Visual {
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
+        };
+        let operate = |action| VimCommand::VisualOperate { action, count: 1 };
+
+        let test_cases = [
+            ('d', operate(VisualAction::Operator(Operator::Delete))),
+            ('x', operate(VisualAction::Operator(Operator::Delete))),
+            ('c', operate(VisualAction::Operator(Operator::Change))),
+            ('y', operate(VisualAction::Operator(Operator::Yank))),
+            ('<', operate(VisualAction::Operator(Operator::ShiftLeft))),
+            ('~', operate(VisualAction::Operator(Operator::ToggleCase))),
+            ('u', operate(VisualAction::Operator(Operator::Lowercase))),
+            ('U', operate(VisualAction::Operator(Operator::Uppercase))),
+            ('J', operate(VisualAction::Join { spaces: true })),
+            ('p', operate(VisualAction::Put)),
+            ('I', operate(VisualAction::Insert)),
+            ('A', operate(VisualAction::Append)),
+            ('o', VimCommand::VisualSwapEnds),
+            ('O', VimCommand::VisualSwapCorner),
+            ('$', VimCommand::MoveLineEnd),
+        ];
+        for (key, expected_cmd) in &test_cases {
+            assert_eq!(mapper.map_key(&Key::char(*key), &mode), *expected_cmd);
+        }
+
+        // カウント付きのシフトと`r{char}`
+        assert_eq!(mapper.map_key(&Key::char('3'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('>'), &mode),
+            VimCommand::VisualOperate {
+                action: VisualAction::Operator(Operator::ShiftRight),
+                count: 3,
+            }
+        );
+        assert_eq!(mapper.map_key(&Key::char('r'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('-'), &mode),
+            operate(VisualAction::Replace('-'))
+        );
+        assert_eq!(mapper.map_key(&Key::char('g'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('J'), &mode),
+            operate(VisualAction::Join { spaces: false })
+        );
+    }
+
+    #[test]
+    fn test_visual_mode_text_object_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Visual {
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
+        };
+
+        assert_eq!(mapper.map_key(&Key::char('2'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::char('i'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.pending(), Some('i'));
+        assert_eq!(
+            mapper.map_key(&Key::char('('), &mode),
+            VimCommand::SelectTextObject {
+                object: TextObject::from_key(true, '(').unwrap(),
+                count: 2,
+            }
+        );
+
+        // 不明なオブジェクトは無視する
+        assert_eq!(mapper.map_key(&Key::char('a'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.map_key(&Key::char('x'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.pending(), None);
+    }
+
     #[test]
     fn test_command_mode_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Command {
             input: String::new(),
         };
@@ -357,13 +1366,17 @@ mod tests {
 
     #[test]
     fn test_ctrl_key_mapping() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Normal;
 
         let test_cases = [
             (Key::ctrl('r'), VimCommand::Redo),
-            (Key::ctrl('f'), VimCommand::MoveBufferEnd),
-            (Key::ctrl('b'), VimCommand::MoveBufferStart),
+            (Key::ctrl('f'), VimCommand::ScrollPageDown),
+            (Key::ctrl('b'), VimCommand::ScrollPageUp),
+            (Key::ctrl('d'), VimCommand::ScrollHalfPageDown),
+            (Key::ctrl('u'), VimCommand::ScrollHalfPageUp),
+            (Key::ctrl('e'), VimCommand::ScrollLineDown),
+            (Key::ctrl('y'), VimCommand::ScrollLineUp),
         ];
 
         for (key, expected_cmd) in &test_cases {
@@ -372,9 +1385,152 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_g_prefix_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        let test_cases = [
+            ('g', VimCommand::MoveBufferStart),
+            ('j', VimCommand::MoveDisplayDown),
+            ('k', VimCommand::MoveDisplayUp),
+            ('v', VimCommand::ReselectVisual),
+        ];
+
+        for (second, expected_cmd) in &test_cases {
+            assert_eq!(mapper.map_key(&Key::char('g'), &mode), VimCommand::Noop);
+            assert_eq!(mapper.pending(), Some('g'));
+            assert_eq!(mapper.map_key(&Key::char(*second), &mode), *expected_cmd);
+            assert_eq!(mapper.pending(), None);
+        }
+
+        assert_eq!(
+            mapper.map_key(&Key::char('G'), &mode),
+            VimCommand::MoveBufferEnd
+        );
+    }
+
+    #[test]
+    fn test_z_quit_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        assert_eq!(mapper.map_key(&Key::char('Z'), &mode), VimCommand::Noop);
+        assert_eq!(mapper.pending(), Some('Z'));
+        assert_eq!(
+            mapper.map_key(&Key::char('Z'), &mode),
+            VimCommand::SaveAndQuit
+        );
+        mapper.map_key(&Key::char('Z'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::char('Q'), &mode),
+            VimCommand::ForceQuit
+        );
+    }
+
+    #[test]
+    fn test_tab_page_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        mapper.map_key(&Key::char('g'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::char('t'), &mode),
+            VimCommand::ExecuteCommand("tabnext".to_string())
+        );
+        mapper.map_key(&Key::char('3'), &mode);
+        mapper.map_key(&Key::char('g'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::char('T'), &mode),
+            VimCommand::ExecuteCommand("tabprevious 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_scroll_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        let test_cases = [
+            ('z', VimCommand::ScrollCursorCenter),
+            ('t', VimCommand::ScrollCursorTop),
+            ('b', VimCommand::ScrollCursorBottom),
+        ];
+        for (second, expected_cmd) in &test_cases {
+            assert_eq!(mapper.map_key(&Key::char('z'), &mode), VimCommand::Noop);
+            assert_eq!(mapper.map_key(&Key::char(*second), &mode), *expected_cmd);
+        }
+
+        let test_cases = [
+            (Key::char('H'), VimCommand::MoveScreenTop),
+            (Key::char('M'), VimCommand::MoveScreenMiddle),
+            (Key::char('L'), VimCommand::MoveScreenBottom),
+            (
+                Key::new(KeyCode::PageDown, KeyModifiers::NONE),
+                VimCommand::ScrollPageDown,
+            ),
+            (
+                Key::new(KeyCode::PageUp, KeyModifiers::NONE),
+                VimCommand::ScrollPageUp,
+            ),
+        ];
+        for (key, expected_cmd) in &test_cases {
+            assert_eq!(mapper.map_key(key, &mode), *expected_cmd);
+        }
+    }
+
+    #[test]
+    fn test_replace_mapping() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        assert_eq!(
+            mapper.map_key(&Key::char('R'), &mode),
+            VimCommand::EnterReplace
+        );
+
+        // `3rx`: カウント付きの1文字置換（数字も置換文字になる）
+        mapper.map_key(&Key::char('3'), &mode);
+        assert_eq!(mapper.map_key(&Key::char('r'), &mode), VimCommand::Noop);
+        assert_eq!(
+            mapper.map_key(&Key::char('5'), &mode),
+            VimCommand::ReplaceChars { ch: '5', count: 3 }
+        );
+        mapper.map_key(&Key::char('r'), &mode);
+        assert_eq!(
+            mapper.map_key(&Key::enter(), &mode),
+            VimCommand::ReplaceChars { ch: '\n', count: 1 }
+        );
+
+        let replace = Mode::Replace {
+            replaced: Vec::new(),
+        };
+        let test_cases = [
+            (Key::char('a'), VimCommand::ReplaceChar('a')),
+            (Key::enter(), VimCommand::ReplaceNewLine),
+            (Key::backspace(), VimCommand::ReplaceBackspace),
+            (Key::escape(), VimCommand::ExitToNormal),
+            (Key::ctrl('w'), VimCommand::Noop),
+        ];
+        for (key, expected_cmd) in &test_cases {
+            assert_eq!(mapper.map_key(key, &replace), *expected_cmd);
+        }
+    }
+
+    #[test]
+    fn test_unknown_g_sequence_is_discarded() {
+        let mut mapper = KeyMapper::new();
+        let mode = Mode::Normal;
+
+        mapper.map_key(&Key::char('g'), &mode);
+        assert_eq!(mapper.map_key(&Key::char('z'), &mode), VimCommand::Noop);
+        // プレフィックスは破棄され、次のキーは通常通り解釈される
+        assert_eq!(mapper.map_key(&Key::char('j'), &mode), VimCommand::MoveDown);
+    }
+
     #[test]
     fn test_unmapped_key() {
-        let mapper = KeyMapper::new();
+        let mut mapper = KeyMapper::new();
         let mode = Mode::Normal;
 
         let cmd = mapper.map_key(&Key::char('z'), &mode);
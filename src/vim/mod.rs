@@ -1,10 +1,24 @@
 pub mod command;
+pub mod completion;
+pub mod ex;
+pub mod increment;
+pub mod insert;
 pub mod keymap;
 pub mod mode;
-
-pub use command::{CommandResult, VimCommand};
+pub mod pattern;
+pub mod register;
+pub mod replace;
+pub mod sort;
+pub mod visual;
+
+pub use command::{CommandResult, Operator, VimCommand};
+pub use completion::{Completion, CompletionKind};
+pub use ex::{ExCommand, ExRange, WriteTarget};
+pub use insert::{InsertEntry, InsertSession};
 pub use keymap::{Key, KeyMapper};
-pub use mode::{Mode, ModeManager};
+pub use mode::{Mode, ModeManager, VisualKind};
+pub use register::{Register, Registers};
+pub use visual::{Selection, VisualAction};
 
 #[cfg(test)]
 mod tests {
@@ -15,7 +29,7 @@ mod tests {
     fn test_vim_module_integration() {
         // VIMモジュール全体の統合テスト
         let mut mode_manager = ModeManager::new();
-        let key_mapper = KeyMapper::new();
+        let mut key_mapper = KeyMapper::new();
         let mut buffer = Buffer::new();
         let mut cursor = Position::new(0, 0);
 
@@ -25,7 +39,13 @@ mod tests {
         // 'i'キーでInsert modeに遷移
         let key = Key::char('i');
         let cmd = key_mapper.map_key(&key, mode_manager.current());
-        assert_eq!(cmd, VimCommand::EnterInsert);
+        assert_eq!(
+            cmd,
+            VimCommand::EnterInsert {
+                entry: InsertEntry::Before,
+                count: 1,
+            }
+        );
 
         mode_manager.enter_insert();
         assert!(mode_manager.current().is_insert());
@@ -51,7 +71,7 @@ mod tests {
 
     #[test]
     fn test_vim_movement_integration() {
-        let key_mapper = KeyMapper::new();
+        let mut key_mapper = KeyMapper::new();
         let mut buffer = Buffer::from_content("Hello\nWorld");
         let mut cursor = Position::new(0, 0);
 
@@ -77,7 +97,7 @@ mod tests {
 
     #[test]
     fn test_vim_editing_integration() {
-        let key_mapper = KeyMapper::new();
+        let mut key_mapper = KeyMapper::new();
         let mut buffer = Buffer::from_content("Hello");
         let mut cursor = Position::new(0, 5);
 
@@ -109,12 +129,18 @@ mod tests {
     #[test]
     fn test_mode_transitions_integration() {
         let mut mode_manager = ModeManager::new();
-        let key_mapper = KeyMapper::new();
+        let mut key_mapper = KeyMapper::new();
 
         // Normal -> Insert
         let key = Key::char('i');
         let cmd = key_mapper.map_key(&key, mode_manager.current());
-        assert_eq!(cmd, VimCommand::EnterInsert);
+        assert_eq!(
+            cmd,
+            VimCommand::EnterInsert {
+                entry: InsertEntry::Before,
+                count: 1,
+            }
+        );
         mode_manager.enter_insert();
         assert!(mode_manager.current().is_insert());
 
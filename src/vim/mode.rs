@@ -1,13 +1,48 @@
 use crate::editor::Position;
 use crate::error::{EditorError, Result};
+use crate::vim::replace::Replaced;
+
+/// ビジュアルモードの種類（`v`/`V`/`Ctrl-V`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisualKind {
+    #[default]
+    Char,
+    Line,
+    Block,
+}
+
+impl VisualKind {
+    /// モードを表す文字（`v`、`V`、`Ctrl-V`）
+    pub fn to_char(self) -> char {
+        match self {
+            VisualKind::Char => 'v',
+            VisualKind::Line => 'V',
+            VisualKind::Block => '\x16',
+        }
+    }
+
+    pub fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            'v' => Some(VisualKind::Char),
+            'V' => Some(VisualKind::Line),
+            '\x16' => Some(VisualKind::Block),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub enum Mode {
     #[default]
     Normal,
     Insert,
+    /// 上書き入力（Backspaceで戻せるよう上書きした内容を記録する）
+    Replace {
+        replaced: Vec<Replaced>,
+    },
     Visual {
         start: Position,
+        kind: VisualKind,
     },
     Command {
         input: String,
@@ -23,6 +58,10 @@ impl Mode {
         matches!(self, Mode::Insert)
     }
 
+    pub fn is_replace(&self) -> bool {
+        matches!(self, Mode::Replace { .. })
+    }
+
     pub fn is_visual(&self) -> bool {
         matches!(self, Mode::Visual { .. })
     }
@@ -37,7 +76,12 @@ impl std::fmt::Display for Mode {
         match self {
             Mode::Normal => write!(f, "NORMAL"),
             Mode::Insert => write!(f, "INSERT"),
-            Mode::Visual { .. } => write!(f, "VISUAL"),
+            Mode::Replace { .. } => write!(f, "REPLACE"),
+            Mode::Visual { kind, .. } => match kind {
+                VisualKind::Char => write!(f, "VISUAL"),
+                VisualKind::Line => write!(f, "VISUAL LINE"),
+                VisualKind::Block => write!(f, "VISUAL BLOCK"),
+            },
             Mode::Command { .. } => write!(f, "COMMAND"),
         }
     }
@@ -69,8 +113,18 @@ impl ModeManager {
         self.transition_to(Mode::Insert);
     }
 
+    pub fn enter_replace(&mut self) {
+        self.transition_to(Mode::Replace {
+            replaced: Vec::new(),
+        });
+    }
+
     pub fn enter_visual(&mut self, start: Position) {
-        self.transition_to(Mode::Visual { start });
+        self.enter_visual_kind(start, VisualKind::Char);
+    }
+
+    pub fn enter_visual_kind(&mut self, start: Position, kind: VisualKind) {
+        self.transition_to(Mode::Visual { start, kind });
     }
 
     pub fn enter_command(&mut self) {
@@ -96,14 +150,64 @@ impl ModeManager {
         }
     }
 
+    /// Replaceモードで上書きした内容を記録する
+    pub fn push_replaced(&mut self, entry: Replaced) -> Result<()> {
+        match &mut self.current {
+            Mode::Replace { replaced } => {
+                replaced.push(entry);
+                Ok(())
+            }
+            _ => Err(EditorError::invalid_mode_transition(
+                self.current.to_string(),
+                "Replace record".to_string(),
+            )),
+        }
+    }
+
+    /// Replaceモードで最後に上書きした内容を取り出す
+    pub fn pop_replaced(&mut self) -> Option<Replaced> {
+        match &mut self.current {
+            Mode::Replace { replaced } => replaced.pop(),
+            _ => None,
+        }
+    }
+
+    /// ビジュアルモードの選択開始位置を変更する（テキストオブジェクトによる拡張用）
+    pub fn set_visual_start(&mut self, position: Position) -> Result<()> {
+        match &mut self.current {
+            Mode::Visual { start, .. } => {
+                *start = position;
+                Ok(())
+            }
+            _ => Err(EditorError::invalid_mode_transition(
+                self.current.to_string(),
+                "Visual start update".to_string(),
+            )),
+        }
+    }
+
+    /// ビジュアルモードのまま種類（文字・行・矩形）を切り替える
+    pub fn set_visual_kind(&mut self, kind: VisualKind) -> Result<()> {
+        match &mut self.current {
+            Mode::Visual { kind: current, .. } => {
+                *current = kind;
+                Ok(())
+            }
+            _ => Err(EditorError::invalid_mode_transition(
+                self.current.to_string(),
+                "Visual kind update".to_string(),
+            )),
+        }
+    }
+
     pub fn can_transition_to(&self, target: &Mode) -> bool {
         match (&self.current, target) {
             // Normal can transition to any mode
             (Mode::Normal, _) => true,
-            // Insert can only transition to Normal
-            (Mode::Insert, Mode::Normal) => true,
-            // Visual can transition to Normal or Insert
-            (Mode::Visual { .. }, Mode::Normal | Mode::Insert) => true,
+            // Insert and Replace can only transition to Normal
+            (Mode::Insert | Mode::Replace { .. }, Mode::Normal) => true,
+            // Visual can transition to Normal, Insert or Command (`:'<,'>`)
+            (Mode::Visual { .. }, Mode::Normal | Mode::Insert | Mode::Command { .. }) => true,
             // Command can only transition to Normal
             (Mode::Command { .. }, Mode::Normal) => true,
             // All other transitions are invalid
@@ -145,13 +249,29 @@ mod tests {
     fn test_mode_display() {
         assert_eq!(Mode::Normal.to_string(), "NORMAL");
         assert_eq!(Mode::Insert.to_string(), "INSERT");
+        assert_eq!(
+            Mode::Replace {
+                replaced: Vec::new()
+            }
+            .to_string(),
+            "REPLACE"
+        );
         assert_eq!(
             Mode::Visual {
-                start: Position::new(0, 0)
+                start: Position::new(0, 0),
+                kind: VisualKind::Char,
             }
             .to_string(),
             "VISUAL"
         );
+        assert_eq!(
+            Mode::Visual {
+                start: Position::new(0, 0),
+                kind: VisualKind::Line,
+            }
+            .to_string(),
+            "VISUAL LINE"
+        );
         assert_eq!(
             Mode::Command {
                 input: "test".to_string()
@@ -177,6 +297,7 @@ mod tests {
 
         let visual = Mode::Visual {
             start: Position::new(0, 0),
+            kind: VisualKind::Char,
         };
         assert!(!visual.is_normal());
         assert!(!visual.is_insert());
@@ -222,7 +343,7 @@ mod tests {
 
         manager.enter_visual(start_pos);
         assert!(manager.current().is_visual());
-        if let Mode::Visual { start } = manager.current() {
+        if let Mode::Visual { start, .. } = manager.current() {
             assert_eq!(*start, start_pos);
         } else {
             panic!("Expected Visual mode");
@@ -252,13 +373,37 @@ mod tests {
         assert!(manager.previous().unwrap().is_insert());
     }
 
+    #[test]
+    fn test_replace_mode_records() {
+        let mut manager = ModeManager::new();
+        assert!(manager.push_replaced(Replaced::Appended).is_err());
+
+        manager.enter_replace();
+        assert!(manager.current().is_replace());
+        manager.push_replaced(Replaced::Char('a')).unwrap();
+        manager.push_replaced(Replaced::Appended).unwrap();
+        assert_eq!(manager.pop_replaced(), Some(Replaced::Appended));
+        assert_eq!(manager.pop_replaced(), Some(Replaced::Char('a')));
+        assert_eq!(manager.pop_replaced(), None);
+
+        // Replaceモードからは Normal にのみ遷移できる
+        assert!(!manager.can_transition_to(&Mode::Insert));
+        manager.enter_normal();
+        assert!(manager.current().is_normal());
+
+        // 再びReplaceモードに入ると記録はリセットされる
+        manager.enter_replace();
+        assert_eq!(manager.pop_replaced(), None);
+    }
+
     #[test]
     fn test_can_transition_from_normal() {
         let manager = ModeManager::new();
 
         assert!(manager.can_transition_to(&Mode::Insert));
         assert!(manager.can_transition_to(&Mode::Visual {
-            start: Position::new(0, 0)
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
         }));
         assert!(manager.can_transition_to(&Mode::Command {
             input: String::new()
@@ -274,7 +419,8 @@ mod tests {
         assert!(manager.can_transition_to(&Mode::Normal));
         assert!(!manager.can_transition_to(&Mode::Insert));
         assert!(!manager.can_transition_to(&Mode::Visual {
-            start: Position::new(0, 0)
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
         }));
         assert!(!manager.can_transition_to(&Mode::Command {
             input: String::new()
@@ -289,13 +435,23 @@ mod tests {
         assert!(manager.can_transition_to(&Mode::Normal));
         assert!(manager.can_transition_to(&Mode::Insert));
         assert!(!manager.can_transition_to(&Mode::Visual {
-            start: Position::new(1, 1)
+            start: Position::new(1, 1),
+            kind: VisualKind::Char,
         }));
-        assert!(!manager.can_transition_to(&Mode::Command {
+        // `:'<,'>`のためにCommandへ遷移できる
+        assert!(manager.can_transition_to(&Mode::Command {
             input: String::new()
         }));
     }
 
+    #[test]
+    fn test_visual_kind_chars() {
+        for kind in [VisualKind::Char, VisualKind::Line, VisualKind::Block] {
+            assert_eq!(VisualKind::from_char(kind.to_char()), Some(kind));
+        }
+        assert_eq!(VisualKind::from_char('x'), None);
+    }
+
     #[test]
     fn test_can_transition_from_command() {
         let mut manager = ModeManager::new();
@@ -304,7 +460,8 @@ mod tests {
         assert!(manager.can_transition_to(&Mode::Normal));
         assert!(!manager.can_transition_to(&Mode::Insert));
         assert!(!manager.can_transition_to(&Mode::Visual {
-            start: Position::new(0, 0)
+            start: Position::new(0, 0),
+            kind: VisualKind::Char,
         }));
         assert!(!manager.can_transition_to(&Mode::Command {
             input: String::new()
@@ -327,6 +484,7 @@ mod tests {
 
         let result = manager.try_transition_to(Mode::Visual {
             start: Position::new(0, 0),
+            kind: VisualKind::Char,
         });
         assert!(result.is_err());
         assert!(manager.current().is_insert()); // 状態は変更されない
@@ -354,4 +512,38 @@ mod tests {
         let result = manager.update_command_input("w".to_string());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_set_visual_start() {
+        let mut manager = ModeManager::new();
+        assert!(manager.set_visual_start(Position::new(1, 2)).is_err());
+
+        manager.enter_visual(Position::new(0, 0));
+        manager.set_visual_start(Position::new(1, 2)).unwrap();
+        assert_eq!(
+            manager.current(),
+            &Mode::Visual {
+                start: Position::new(1, 2),
+                kind: VisualKind::Char,
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_visual_kind() {
+        let mut manager = ModeManager::new();
+        assert!(manager.set_visual_kind(VisualKind::Line).is_err());
+
+        manager.enter_visual_kind(Position::new(2, 0), VisualKind::Block);
+        assert_eq!(manager.current().to_string(), "VISUAL BLOCK");
+
+        manager.set_visual_kind(VisualKind::Line).unwrap();
+        assert_eq!(
+            manager.current(),
+            &Mode::Visual {
+                start: Position::new(2, 0),
+                kind: VisualKind::Line,
+            }
+        );
+    }
 }
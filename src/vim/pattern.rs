@@ -0,0 +1,210 @@
+use crate::error::{EditorError, Result};
+
+/// Vimの`magic`な正規表現の簡易版
+///
+/// `.` `*` `[...]` `^` `$`と、`\+` `\=` `\?` `\d` `\s` `\w` `\a` `\l` `\u` `\x`（大文字は否定）、`\t`に対応する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    items: Vec<Item>,
+    anchored_start: bool,
+    anchored_end: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Item {
+    atom: Atom,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Atom {
+    Char(char),
+    Any,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `\d`などの文字クラス（大文字なら否定）
+    Named(char),
+}
+
+impl Atom {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            Atom::Char(expected) => ch == *expected,
+            Atom::Any => true,
+            Atom::Class { negated, ranges } => {
+                ranges.iter().any(|&(from, to)| from <= ch && ch <= to) != *negated
+            }
+            Atom::Named(name) => {
+                let matched = match name.to_ascii_lowercase() {
+                    'd' => ch.is_ascii_digit(),
+                    's' => ch == ' ' || ch == '\t',
+                    'w' => ch.is_ascii_alphanumeric() || ch == '_',
+                    'a' => ch.is_ascii_alphabetic(),
+                    'x' => ch.is_ascii_hexdigit(),
+                    'l' => ch.is_ascii_lowercase(),
+                    _ => ch.is_ascii_uppercase(),
+                };
+                matched != name.is_ascii_uppercase()
+            }
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut chars = pattern.chars().peekable();
+        let mut items: Vec<Item> = Vec::new();
+        let anchored_start = chars.next_if_eq(&'^').is_some();
+        let mut anchored_end = false;
+
+        while let Some(ch) = chars.next() {
+            let atom = match ch {
+                '$' if chars.peek().is_none() => {
+                    anchored_end = true;
+                    break;
+                }
+                '.' => Atom::Any,
+                '*' if !items.is_empty() => {
+                    repeat(&mut items, 0, None);
+                    continue;
+                }
+                '[' => parse_class(&mut chars)?,
+                '\\' => match chars.next() {
+                    Some('+') => {
+                        repeat(&mut items, 1, None);
+                        continue;
+                    }
+                    Some('=' | '?') => {
+                        repeat(&mut items, 0, Some(1));
+                        continue;
+                    }
+                    Some(name @ ('d' | 'D' | 's' | 'S' | 'w' | 'W' | 'a' | 'A' | 'x' | 'X'))
+                    | Some(name @ ('l' | 'L' | 'u' | 'U')) => Atom::Named(name),
+                    Some('t') => Atom::Char('\t'),
+                    Some(ch) => Atom::Char(ch),
+                    None => Atom::Char('\\'),
+                },
+                ch => Atom::Char(ch),
+            };
+            items.push(Item {
+                atom,
+                min: 1,
+                max: Some(1),
+            });
+        }
+
+        Ok(Self {
+            items,
+            anchored_start,
+            anchored_end,
+        })
+    }
+
+    /// `text`内で最初に一致する範囲（バイト位置）
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let starts = if self.anchored_start {
+            0..=0
+        } else {
+            0..=chars.len()
+        };
+        let offset = |index: usize| chars.get(index).map_or(text.len(), |&(offset, _)| offset);
+
+        starts.into_iter().find_map(|start| {
+            self.match_at(&chars, 0, start)
+                .map(|end| (offset(start), offset(end)))
+        })
+    }
+
+    /// `items[item..]`が`chars[pos..]`の先頭に一致すれば、一致の終わりを返す
+    fn match_at(&self, chars: &[(usize, char)], item: usize, pos: usize) -> Option<usize> {
+        let Some(Item { atom, min, max }) = self.items.get(item) else {
+            return (!self.anchored_end || pos == chars.len()).then_some(pos);
+        };
+        // 最長一致から順に試す
+        let available = chars[pos..]
+            .iter()
+            .take(max.unwrap_or(usize::MAX))
+            .take_while(|&&(_, ch)| atom.matches(ch))
+            .count();
+        (*min..=available)
+            .rev()
+            .find_map(|count| self.match_at(chars, item + 1, pos + count))
+    }
+}
+
+fn repeat(items: &mut [Item], min: usize, max: Option<usize>) {
+    if let Some(last) = items.last_mut() {
+        last.min = min;
+        last.max = max;
+    }
+}
+
+/// `[abc]`、`[^a-z]`（`[`の直後から`]`まで）
+fn parse_class(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Atom> {
+    let negated = chars.next_if_eq(&'^').is_some();
+    let mut ranges = Vec::new();
+    // 先頭の`]`は文字として扱う
+    if chars.next_if_eq(&']').is_some() {
+        ranges.push((']', ']'));
+    }
+    loop {
+        let ch = match chars.next() {
+            Some(']') => break,
+            Some('\\') => match chars.next() {
+                Some('t') => '\t',
+                Some(ch) => ch,
+                None => '\\',
+            },
+            Some(ch) => ch,
+            None => return Err(EditorError::invalid_command("Missing ] in pattern")),
+        };
+        if chars.peek() == Some(&'-') {
+            chars.next();
+            match chars.next_if(|&next| next != ']') {
+                Some(to) => ranges.push((ch, to)),
+                None => {
+                    ranges.push((ch, ch));
+                    ranges.push(('-', '-'));
+                }
+            }
+        } else {
+            ranges.push((ch, ch));
+        }
+    }
+    Ok(Atom::Class { negated, ranges })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Pattern::new(pattern).unwrap().find(text)
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("b.d", "abcde"), Some((1, 4)));
+        assert_eq!(find("\\d\\+", "ab 123 4"), Some((3, 6)));
+        assert_eq!(find("x*", "abc"), Some((0, 0)));
+        assert_eq!(find("a\\=b", "cb"), Some((1, 2)));
+        assert_eq!(find("[^ ]*,", "one two, three"), Some((4, 8)));
+        assert_eq!(find("[a-c]\\+", "xxbcay"), Some((2, 5)));
+        assert_eq!(find(".*=", "a=b=c"), Some((0, 4)));
+        assert_eq!(find("日本", "この日本語"), Some((6, 12)));
+        assert_eq!(find("\\.", "a.b"), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(find("^a", "ba"), None);
+        assert_eq!(find("^b", "ba"), Some((0, 1)));
+        assert_eq!(find("a$", "aba"), Some((2, 3)));
+        assert_eq!(find("a$b", "a$b"), Some((0, 3)));
+        assert!(Pattern::new("[abc").is_err());
+    }
+}
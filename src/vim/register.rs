@@ -0,0 +1,66 @@
+/// ヤンク・削除したテキスト
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn charwise(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            linewise: false,
+        }
+    }
+
+    pub fn linewise(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            linewise: true,
+        }
+    }
+}
+
+/// レジスタの集合（現在は無名レジスタのみ）
+#[derive(Debug, Clone, Default)]
+pub struct Registers {
+    unnamed: Register,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unnamed(&self) -> &Register {
+        &self.unnamed
+    }
+
+    pub fn set(&mut self, register: Register) {
+        self.unnamed = register;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_kinds() {
+        let register = Register::charwise("word");
+        assert_eq!(register.text, "word");
+        assert!(!register.linewise);
+
+        let register = Register::linewise("line 1\nline 2");
+        assert!(register.linewise);
+    }
+
+    #[test]
+    fn test_registers_set_unnamed() {
+        let mut registers = Registers::new();
+        assert_eq!(registers.unnamed(), &Register::default());
+
+        registers.set(Register::linewise("line"));
+        assert_eq!(registers.unnamed(), &Register::linewise("line"));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::editor::{Buffer, Position};
+use crate::error::Result;
+
+/// Replaceモードで上書きした内容（Backspaceで元に戻すために記録する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replaced {
+    /// 既存の文字を上書きした
+    Char(char),
+    /// 行末を越えて追加した
+    Appended,
+    /// 改行を挿入した
+    LineBreak,
+}
+
+/// カーソル位置の文字を`ch`で上書きする（行末では追加する）
+pub fn overwrite(buffer: &mut Buffer, cursor: &mut Position, ch: char) -> Result<Replaced> {
+    let original = buffer
+        .line(cursor.row)?
+        .get(cursor.col..)
+        .and_then(|rest| rest.chars().next());
+
+    let replaced = match original {
+        Some(original) => {
+            buffer.replace_str(*cursor, original.len_utf8(), &ch.to_string())?;
+            Replaced::Char(original)
+        }
+        None => {
+            buffer.insert_char(*cursor, ch)?;
+            Replaced::Appended
+        }
+    };
+    cursor.col += ch.len_utf8();
+    Ok(replaced)
+}
+
+/// カーソル位置で行を分割する（文字は上書きしない）
+pub fn line_break(buffer: &mut Buffer, cursor: &mut Position) -> Result<Replaced> {
+    *cursor = buffer.insert_text(*cursor, "\n")?;
+    Ok(Replaced::LineBreak)
+}
+
+/// 直前の上書きを取り消し、カーソルを1文字戻す
+pub fn restore(buffer: &mut Buffer, cursor: &mut Position, replaced: Replaced) -> Result<()> {
+    if replaced == Replaced::LineBreak {
+        let row = cursor.row.saturating_sub(1);
+        let end = Position::new(row, buffer.line_length(row)?);
+        buffer.delete_range(end, Position::new(cursor.row, 0))?;
+        *cursor = end;
+        return Ok(());
+    }
+
+    let line = buffer.line(cursor.row)?;
+    let Some(ch) = line[..cursor.col].chars().next_back() else {
+        return Ok(());
+    };
+    cursor.col -= ch.len_utf8();
+
+    match replaced {
+        Replaced::Char(original) => {
+            buffer.replace_str(*cursor, ch.len_utf8(), &original.to_string())?;
+        }
+        _ => {
+            buffer.delete_str(*cursor, ch.len_utf8())?;
+        }
+    }
+    Ok(())
+}
+
+/// `r{ch}`: カーソルから`count`文字を`ch`で置き換える（改行なら`count`文字を1つの改行にする）
+///
+/// 行末までの文字数が足りない場合は何もせず`false`を返す。
+pub fn replace_chars(
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+    ch: char,
+    count: usize,
+) -> Result<bool> {
+    let line = buffer.line(cursor.row)?;
+    let rest = line.get(cursor.col..).unwrap_or("");
+    let Some((index, last)) = rest.char_indices().nth(count.saturating_sub(1)) else {
+        return Ok(false);
+    };
+    let len = index + last.len_utf8();
+
+    if ch == '\n' {
+        buffer.begin_undo_group();
+        let result = buffer
+            .delete_str(*cursor, len)
+            .and_then(|_| buffer.insert_text(*cursor, "\n"));
+        buffer.end_undo_group();
+        *cursor = result?;
+        return Ok(true);
+    }
+
+    let text = ch.to_string().repeat(count);
+    buffer.replace_str(*cursor, len, &text)?;
+    // カーソルは最後に置き換えた文字の上
+    cursor.col += text.len() - ch.len_utf8();
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwrite_and_restore() {
+        let mut buffer = Buffer::from_content("abc");
+        let mut cursor = Position::new(0, 1);
+
+        let mut replaced = Vec::new();
+        for ch in "XYZ".chars() {
+            replaced.push(overwrite(&mut buffer, &mut cursor, ch).unwrap());
+        }
+        assert_eq!(buffer.line(0).unwrap(), "aXYZ");
+        assert_eq!(cursor, Position::new(0, 4));
+        assert_eq!(
+            replaced,
+            vec![Replaced::Char('b'), Replaced::Char('c'), Replaced::Appended]
+        );
+
+        while let Some(last) = replaced.pop() {
+            restore(&mut buffer, &mut cursor, last).unwrap();
+        }
+        assert_eq!(buffer.line(0).unwrap(), "abc");
+        assert_eq!(cursor, Position::new(0, 1));
+    }
+
+    #[test]
+    fn test_line_break_and_restore() {
+        let mut buffer = Buffer::from_content("abcd");
+        let mut cursor = Position::new(0, 2);
+
+        let replaced = line_break(&mut buffer, &mut cursor).unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "ab");
+        assert_eq!(buffer.line(1).unwrap(), "cd");
+        assert_eq!(cursor, Position::new(1, 0));
+
+        restore(&mut buffer, &mut cursor, replaced).unwrap();
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "abcd");
+        assert_eq!(cursor, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_replace_chars_with_count() {
+        let mut buffer = Buffer::from_content("héllo");
+        let mut cursor = Position::new(0, 0);
+
+        assert!(replace_chars(&mut buffer, &mut cursor, 'x', 3).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "xxxlo");
+        assert_eq!(cursor, Position::new(0, 2));
+
+        // 文字数が足りなければ何もしない
+        assert!(!replace_chars(&mut buffer, &mut cursor, 'y', 4).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "xxxlo");
+
+        // 1回のundoで戻る
+        buffer.undo().unwrap();
+        assert_eq!(buffer.line(0).unwrap(), "héllo");
+    }
+
+    #[test]
+    fn test_replace_chars_with_line_break() {
+        let mut buffer = Buffer::from_content("one two");
+        let mut cursor = Position::new(0, 3);
+
+        assert!(replace_chars(&mut buffer, &mut cursor, '\n', 1).unwrap());
+        assert_eq!(buffer.line(0).unwrap(), "one");
+        assert_eq!(buffer.line(1).unwrap(), "two");
+        assert_eq!(cursor, Position::new(1, 0));
+    }
+}
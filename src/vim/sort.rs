@@ -0,0 +1,291 @@
+use crate::editor::Buffer;
+use crate::error::{EditorError, Result};
+use crate::vim::pattern::Pattern;
+use std::cmp::Ordering;
+
+/// `:sort`で数値として比較するときの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `n`: 10進数
+    Decimal,
+    /// `f`: 浮動小数点数
+    Float,
+    /// `x`: 16進数（`0x`は省略可）
+    Hex,
+    /// `o`: 8進数
+    Octal,
+    /// `b`: 2進数
+    Binary,
+}
+
+/// `:sort[!] [b][f][i][n][o][u][x] [/pat/] [r]`の指定
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    /// `!`: 逆順
+    pub reverse: bool,
+    /// `i`: 大文字・小文字を区別しない
+    pub ignore_case: bool,
+    /// `u`: 同じ行は最初の1行だけ残す
+    pub unique: bool,
+    pub number: Option<NumberFormat>,
+    /// `/pat/`: 一致した部分の後ろ（`r`なら一致した部分）で比較する
+    pub pattern: Option<Pattern>,
+    pub use_match: bool,
+}
+
+impl SortOptions {
+    /// `:sort`の引数を解釈する
+    pub fn parse(args: &str, reverse: bool) -> Result<Self> {
+        let mut options = Self {
+            reverse,
+            ..Self::default()
+        };
+        let mut chars = args.chars();
+        while let Some(ch) = chars.next() {
+            let number = match ch {
+                ' ' | '\t' => continue,
+                'i' => {
+                    options.ignore_case = true;
+                    continue;
+                }
+                'u' => {
+                    options.unique = true;
+                    continue;
+                }
+                'r' => {
+                    options.use_match = true;
+                    continue;
+                }
+                'n' => NumberFormat::Decimal,
+                'f' => NumberFormat::Float,
+                'x' => NumberFormat::Hex,
+                'o' => NumberFormat::Octal,
+                'b' => NumberFormat::Binary,
+                // 英数字以外で囲んだパターン（`\`で区切り文字を含められる）
+                delimiter if !delimiter.is_alphanumeric() && delimiter != '"' => {
+                    let mut pattern = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => {
+                                let escaped = chars.next().unwrap_or('\\');
+                                if escaped != delimiter {
+                                    pattern.push('\\');
+                                }
+                                pattern.push(escaped);
+                            }
+                            Some(ch) if ch == delimiter => break,
+                            Some(ch) => pattern.push(ch),
+                            None => break,
+                        }
+                    }
+                    if pattern.is_empty() {
+                        return Err(EditorError::invalid_command("No previous pattern"));
+                    }
+                    options.pattern = Some(Pattern::new(&pattern)?);
+                    continue;
+                }
+                _ => {
+                    return Err(EditorError::invalid_command(format!(
+                        "Invalid argument: {}",
+                        args
+                    )));
+                }
+            };
+            if options.number.is_some_and(|current| current != number) {
+                return Err(EditorError::invalid_command(
+                    "Invalid argument: too many number formats",
+                ));
+            }
+            options.number = Some(number);
+        }
+        Ok(options)
+    }
+}
+
+/// 行を比較するときのキー（数値や一致がない行は`Missing`で、他の行より前に並ぶ）
+#[derive(Debug, Clone, PartialEq)]
+enum SortKey {
+    Missing,
+    Integer(i128),
+    Float(f64),
+    Text(String),
+}
+
+impl SortKey {
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Integer(a), SortKey::Integer(b)) => a.cmp(b),
+            (SortKey::Float(a), SortKey::Float(b)) => a.total_cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            (SortKey::Missing, SortKey::Missing) => Ordering::Equal,
+            (SortKey::Missing, _) => Ordering::Less,
+            (_, SortKey::Missing) => Ordering::Greater,
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// `:[range]sort`: `start`行から`end`行までを並べ替える（同じキーの行は元の順序を保つ）
+pub fn sort_lines(
+    buffer: &mut Buffer,
+    start: usize,
+    end: usize,
+    options: &SortOptions,
+) -> Result<()> {
+    let mut lines = (start..=end)
+        .map(|row| {
+            let line = buffer.line(row)?.to_string();
+            Ok((key(&line, options), line))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    lines.sort_by(|(a, _), (b, _)| a.compare(b));
+    if options.reverse {
+        lines.reverse();
+    }
+    if options.unique {
+        lines.dedup_by(|(a, _), (b, _)| a.compare(b) == Ordering::Equal);
+    }
+
+    let sorted = lines.into_iter().map(|(_, line)| line).collect();
+    buffer.replace_lines(start, end, sorted)
+}
+
+fn key(line: &str, options: &SortOptions) -> SortKey {
+    let text = match &options.pattern {
+        Some(pattern) => match pattern.find(line) {
+            Some((start, end)) if options.use_match => &line[start..end],
+            Some((_, end)) => &line[end..],
+            None if options.use_match => return SortKey::Missing,
+            None => line,
+        },
+        None => line,
+    };
+
+    let Some(format) = options.number else {
+        return SortKey::Text(if options.ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        });
+    };
+    match format {
+        NumberFormat::Float => parse_float(text).map_or(SortKey::Missing, SortKey::Float),
+        _ => parse_integer(text, format).map_or(SortKey::Missing, SortKey::Integer),
+    }
+}
+
+/// 行内で最初に現れる整数（直前の`-`は符号とみなす）
+fn parse_integer(text: &str, format: NumberFormat) -> Option<i128> {
+    let (radix, is_digit): (u32, fn(&char) -> bool) = match format {
+        NumberFormat::Hex => (16, char::is_ascii_hexdigit),
+        NumberFormat::Octal => (8, |ch| ('0'..='7').contains(ch)),
+        NumberFormat::Binary => (2, |ch| matches!(ch, '0' | '1')),
+        _ => (10, char::is_ascii_digit),
+    };
+    let start = text.find(|ch| is_digit(&ch))?;
+    let mut digits = &text[start..];
+    // `0x1F`や`0b101`のプレフィックスを飛ばす
+    let prefix = match format {
+        NumberFormat::Hex => Some(['x', 'X']),
+        NumberFormat::Binary => Some(['b', 'B']),
+        _ => None,
+    };
+    if let Some(prefix) = prefix
+        && let Some(rest) = digits
+            .strip_prefix('0')
+            .and_then(|rest| rest.strip_prefix(prefix))
+        && rest.chars().next().is_some_and(|ch| is_digit(&ch))
+    {
+        digits = rest;
+    }
+    let len = digits.chars().take_while(is_digit).count();
+    let value = i128::from_str_radix(&digits[..len], radix).unwrap_or(i128::MAX);
+    let negative = text[..start].ends_with('-');
+    Some(if negative { -value } else { value })
+}
+
+/// 行内で最初に現れる浮動小数点数（`1.5`、`-2e3`など）
+fn parse_float(text: &str) -> Option<f64> {
+    let start = text.find(|ch: char| ch.is_ascii_digit())?;
+    let start = if text[..start].ends_with('-') {
+        start - 1
+    } else {
+        start
+    };
+    // 数値として解釈できる最長の部分
+    let rest = &text[start..];
+    (1..=rest.len())
+        .rev()
+        .filter(|&len| rest.is_char_boundary(len))
+        .find_map(|len| rest[..len].parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(content: &str, args: &str, reverse: bool) -> String {
+        let mut buffer = Buffer::from_content(content);
+        let options = SortOptions::parse(args, reverse).unwrap();
+        let end = buffer.line_count() - 1;
+        sort_lines(&mut buffer, 0, end, &options).unwrap();
+        buffer.to_string()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = SortOptions::parse("iu /a\\/b/ r", true).unwrap();
+        assert!(options.reverse && options.ignore_case && options.unique && options.use_match);
+        assert_eq!(options.pattern, Some(Pattern::new("a/b").unwrap()));
+        assert_eq!(
+            SortOptions::parse("n", false).unwrap().number,
+            Some(NumberFormat::Decimal)
+        );
+
+        assert!(SortOptions::parse("nx", false).is_err());
+        assert!(SortOptions::parse("z", false).is_err());
+        assert!(SortOptions::parse("//", false).is_err());
+    }
+
+    #[test]
+    fn test_sort_text() {
+        assert_eq!(sort("b\nA\na\nB", "", false), "A\nB\na\nb");
+        assert_eq!(sort("b\nA\na\nB", "", true), "b\na\nB\nA");
+        // 同じキーの行は元の順序を保つ
+        assert_eq!(sort("b\nA\na\nB", "i", false), "A\na\nb\nB");
+        assert_eq!(sort("b\nA\na\nB\nb", "iu", false), "A\nb");
+    }
+
+    #[test]
+    fn test_sort_numbers() {
+        assert_eq!(
+            sort("x10\nnone\nx-2\nx9\nalso none", "n", false),
+            "none\nalso none\nx-2\nx9\nx10"
+        );
+        assert_eq!(sort("0x1F\n0xA\nff", "x", false), "0xA\n0x1F\nff");
+        assert_eq!(sort("0b11\n0b10\n1", "b", false), "1\n0b10\n0b11");
+        assert_eq!(sort("17\n7\n10", "o", false), "7\n10\n17");
+        assert_eq!(
+            sort("v 1.5\nv -2e1\nv 1.25", "f", false),
+            "v -2e1\nv 1.25\nv 1.5"
+        );
+        assert_eq!(sort("2\n02\n1\n2", "nu", false), "1\n2");
+    }
+
+    #[test]
+    fn test_sort_with_pattern() {
+        // 一致した部分の後ろで比較し、一致しない行はそのまま
+        assert_eq!(
+            sort("a=3\nb=1\nskip\nc=2", "/=/ n", false),
+            "skip\nb=1\nc=2\na=3"
+        );
+        // `r`は一致した部分で比較する（一致しない行は先頭）
+        assert_eq!(
+            sort("b 30 x\nc 4\nnone\na 100", "/\\d\\+/ nr", false),
+            "none\nc 4\nb 30 x\na 100"
+        );
+        assert_eq!(sort("x=b\ny=a\nz", "/=./ r", false), "z\ny=a\nx=b");
+    }
+}
@@ -0,0 +1,892 @@
+use crate::editor::cursor::{byte_column, virtual_column};
+use crate::editor::text_object::TextRange;
+use crate::editor::{Buffer, Position};
+use crate::error::{EditorError, Result};
+use crate::vim::command::{self, CommandResult, Operator};
+use crate::vim::increment;
+use crate::vim::mode::VisualKind;
+use crate::vim::register::{Register, Registers};
+
+/// ビジュアルモードで選択範囲に対して行う操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisualAction {
+    Operator(Operator),
+    /// `J`/`gJ`（`spaces`が`false`なら空白を調整しない）
+    Join {
+        spaces: bool,
+    },
+    Replace(char),
+    /// `Ctrl-A`/`Ctrl-X`: 各行の選択範囲内の最初の数値に加算する（`g`付きなら行ごとに増分を増やす）
+    AddToNumber {
+        decrement: bool,
+        progressive: bool,
+    },
+    Put,
+    /// `I`: 選択範囲の前から挿入（矩形では各行に反映）
+    Insert,
+    /// `A`: 選択範囲の後ろから挿入（矩形では各行に反映）
+    Append,
+}
+
+/// 操作後に移るモード
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VisualEffect {
+    Normal,
+    Insert,
+    BlockInsert(BlockInsert),
+    /// 入力済みのテキストでコマンドラインを開く（`!`）
+    CommandLine(String),
+}
+
+/// 矩形の`I`/`A`/`c`で、最初の行に入力したテキストを他の行にも挿入するための情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockInsert {
+    /// 入力を始めた位置（最初の行）
+    pub start: Position,
+    /// 同じテキストを挿入する残りの行
+    pub rows: Vec<usize>,
+    /// 挿入する表示列（短い行は空白で埋める）
+    pub column: usize,
+    /// 各行の行末に挿入するか（`$`で選択した矩形への`A`）
+    pub at_eol: bool,
+}
+
+/// ビジュアルモードの選択範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: Position,
+    pub cursor: Position,
+    pub kind: VisualKind,
+    /// 矩形の右端を各行の行末まで広げる（`$`）
+    pub to_eol: bool,
+}
+
+impl Selection {
+    pub fn new(anchor: Position, cursor: Position, kind: VisualKind) -> Self {
+        Self {
+            anchor,
+            cursor,
+            kind,
+            to_eol: false,
+        }
+    }
+
+    pub fn start(&self) -> Position {
+        self.anchor.min(self.cursor)
+    }
+
+    pub fn end(&self) -> Position {
+        self.anchor.max(self.cursor)
+    }
+
+    pub fn first_row(&self) -> usize {
+        self.start().row
+    }
+
+    pub fn last_row(&self) -> usize {
+        self.end().row
+    }
+
+    /// 矩形の左端と右端の表示列（右端を含む）
+    pub fn block_columns(&self, buffer: &Buffer) -> (usize, usize) {
+        let tabstop = buffer.options().tabstop;
+        let column = |pos: Position| {
+            buffer
+                .line(pos.row)
+                .map(|line| virtual_column(line, pos.col, tabstop))
+                .unwrap_or(0)
+        };
+        let (a, b) = (column(self.anchor), column(self.cursor));
+        (a.min(b), a.max(b))
+    }
+
+    /// `'<`と`'>`に記録する位置（矩形では左上と右下の角）
+    pub fn mark_positions(&self, buffer: &Buffer) -> (Position, Position) {
+        if self.kind != VisualKind::Block {
+            return (self.start(), self.end());
+        }
+        let (left, right) = self.block_columns(buffer);
+        let at_column = |row: usize, col: usize| {
+            let line = buffer.line(row).unwrap_or("");
+            Position::new(row, byte_column(line, col, buffer.options().tabstop))
+        };
+        (
+            at_column(self.first_row(), left),
+            at_column(self.last_row(), right),
+        )
+    }
+
+    /// `row`行で選択されている部分のバイト範囲（終端を含まない）
+    pub fn span(&self, buffer: &Buffer, row: usize) -> Option<(usize, usize)> {
+        if row < self.first_row() || row > self.last_row() {
+            return None;
+        }
+        let line = buffer.line(row).ok()?;
+
+        match self.kind {
+            VisualKind::Line => Some((0, line.len())),
+            VisualKind::Char => {
+                let (start, end) = (self.start(), self.end());
+                let from = if row == start.row { start.col } else { 0 };
+                let to = if row == end.row {
+                    next_boundary(line, end.col)
+                } else {
+                    line.len()
+                };
+                Some((from.min(line.len()), to))
+            }
+            VisualKind::Block => {
+                let (left, right) = self.block_columns(buffer);
+                let tabstop = buffer.options().tabstop;
+                let from = byte_column(line, left, tabstop);
+                let to = if self.to_eol {
+                    line.len()
+                } else {
+                    byte_column(line, right + 1, tabstop)
+                };
+                Some((from, to.max(from)))
+            }
+        }
+    }
+
+    /// 文字単位・行単位の選択をテキスト範囲に変換する
+    pub fn text_range(&self, buffer: &Buffer) -> TextRange {
+        let (start, end) = (self.start(), self.end());
+        if self.kind == VisualKind::Line {
+            return TextRange::lines(start.row, end.row);
+        }
+
+        let line = buffer.line(end.row).unwrap_or("");
+        let end = if end.col < line.len() {
+            Position::new(end.row, next_boundary(line, end.col))
+        } else if end.row + 1 < buffer.line_count() {
+            // 行末を選択している場合は改行も含める
+            Position::new(end.row + 1, 0)
+        } else {
+            Position::new(end.row, line.len())
+        };
+        TextRange::charwise(start, end)
+    }
+
+    fn rows(&self) -> std::ops::RangeInclusive<usize> {
+        self.first_row()..=self.last_row()
+    }
+}
+
+fn next_boundary(line: &str, col: usize) -> usize {
+    line.get(col..)
+        .and_then(|rest| rest.chars().next())
+        .map(|ch| col + ch.len_utf8())
+        .unwrap_or(line.len())
+}
+
+/// 選択範囲に操作を適用する（ヤンク・削除したテキストはレジスタに入る）
+pub fn apply(
+    action: VisualAction,
+    count: usize,
+    selection: &Selection,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+    registers: &mut Registers,
+) -> Result<VisualEffect> {
+    let count = count.max(1);
+
+    match action {
+        VisualAction::Operator(operator @ (Operator::ShiftRight | Operator::ShiftLeft)) => {
+            let amount = if operator == Operator::ShiftRight {
+                1
+            } else {
+                -1
+            };
+            buffer.shift_lines(
+                selection.first_row(),
+                selection.last_row(),
+                amount * count as isize,
+            )?;
+            cursor.row = selection.first_row();
+            cursor.col = buffer.first_non_blank(cursor.row)?;
+            Ok(VisualEffect::Normal)
+        }
+        // 選択した行を`:'<,'>!`で外部コマンドに渡す
+        VisualAction::Operator(Operator::Filter) => {
+            Ok(VisualEffect::CommandLine("'<,'>!".to_string()))
+        }
+        // 整形は矩形選択でも行単位
+        VisualAction::Operator(operator @ (Operator::Format | Operator::FormatKeepCursor)) => {
+            let range = TextRange::lines(selection.first_row(), selection.last_row());
+            command::apply_operator(operator, range, buffer, cursor)?;
+            Ok(VisualEffect::Normal)
+        }
+        VisualAction::Operator(operator) if selection.kind != VisualKind::Block => {
+            let range = selection.text_range(buffer);
+            let result = command::apply_operator(operator, range, buffer, cursor)?;
+            Ok(store_result(result, registers))
+        }
+        VisualAction::Operator(operator) => {
+            apply_block_operator(operator, selection, buffer, cursor, registers)
+        }
+        VisualAction::Join { spaces } => {
+            // 1行だけの選択でも次の行と連結する
+            let start = selection.first_row();
+            let end = selection.last_row().max(start + 1);
+            *cursor = Position::new(start, buffer.join_lines(start, end, spaces)?);
+            Ok(VisualEffect::Normal)
+        }
+        VisualAction::Replace(ch) => {
+            let replace = |text: &str| text.chars().map(|_| ch).collect::<String>();
+            if selection.kind == VisualKind::Block {
+                for_each_span(selection, buffer, |buffer, row, from, to| {
+                    command::transform_span(buffer, row, from, to, replace)
+                })?;
+            } else {
+                command::transform_range(buffer, selection.text_range(buffer), replace)?;
+            }
+            *cursor = block_start(selection, buffer);
+            Ok(VisualEffect::Normal)
+        }
+        VisualAction::AddToNumber {
+            decrement,
+            progressive,
+        } => {
+            let step = if decrement {
+                -(count as i64)
+            } else {
+                count as i64
+            };
+            let nrformats = buffer.options().nrformats.clone();
+            let mut amount = step;
+            for_each_span(selection, buffer, |buffer, row, from, to| {
+                let line = buffer.line(row)?;
+                if let Some((start, end, text)) =
+                    increment::add(&line[from..to], 0, amount, &nrformats)
+                {
+                    buffer.replace_str(Position::new(row, from + start), end - start, &text)?;
+                    if progressive {
+                        amount += step;
+                    }
+                }
+                Ok(())
+            })?;
+            *cursor = block_start(selection, buffer);
+            Ok(VisualEffect::Normal)
+        }
+        VisualAction::Put => put(selection, buffer, cursor, registers),
+        VisualAction::Insert | VisualAction::Append => {
+            start_insert(action, selection, buffer, cursor)
+        }
+    }
+}
+
+/// オペレータの結果をレジスタに格納し、次のモードを決める
+fn store_result(result: CommandResult, registers: &mut Registers) -> VisualEffect {
+    match result {
+        CommandResult::Yanked(register) | CommandResult::Deleted(register) => {
+            registers.set(register);
+            VisualEffect::Normal
+        }
+        CommandResult::Changed(register) => {
+            registers.set(register);
+            VisualEffect::Insert
+        }
+        _ => VisualEffect::Normal,
+    }
+}
+
+/// 矩形の左上の位置
+fn block_start(selection: &Selection, buffer: &Buffer) -> Position {
+    if selection.kind != VisualKind::Block {
+        return selection.start();
+    }
+    let row = selection.first_row();
+    let col = selection.span(buffer, row).map_or(0, |(from, _)| from);
+    Position::new(row, col)
+}
+
+/// 矩形の各行の選択部分に対して`f`を呼ぶ（全体で1回のundo単位）
+fn for_each_span(
+    selection: &Selection,
+    buffer: &mut Buffer,
+    mut f: impl FnMut(&mut Buffer, usize, usize, usize) -> Result<()>,
+) -> Result<()> {
+    // 先に全行の範囲を求めておく（編集で表示列がずれないように）
+    let spans: Vec<(usize, usize, usize)> = selection
+        .rows()
+        .filter_map(|row| {
+            selection
+                .span(buffer, row)
+                .map(|(from, to)| (row, from, to))
+        })
+        .collect();
+
+    buffer.begin_undo_group();
+    let result = spans
+        .into_iter()
+        .try_for_each(|(row, from, to)| f(buffer, row, from, to));
+    buffer.end_undo_group();
+    result
+}
+
+fn block_text(selection: &Selection, buffer: &Buffer) -> String {
+    selection
+        .rows()
+        .map(|row| {
+            let line = buffer.line(row).unwrap_or("");
+            selection
+                .span(buffer, row)
+                .map_or("", |(from, to)| &line[from..to])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn apply_block_operator(
+    operator: Operator,
+    selection: &Selection,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+    registers: &mut Registers,
+) -> Result<VisualEffect> {
+    let start = block_start(selection, buffer);
+
+    match operator {
+        Operator::Yank => {
+            registers.set(Register::charwise(block_text(selection, buffer)));
+            *cursor = start;
+            Ok(VisualEffect::Normal)
+        }
+        Operator::Delete | Operator::Change => {
+            let (left, _) = selection.block_columns(buffer);
+            // 矩形に掛かっていた行だけに入力を反映する
+            let rows: Vec<usize> = selection
+                .rows()
+                .skip(1)
+                .filter(|&row| {
+                    selection
+                        .span(buffer, row)
+                        .is_some_and(|(from, to)| from < to)
+                })
+                .collect();
+            registers.set(Register::charwise(block_text(selection, buffer)));
+            for_each_span(selection, buffer, |buffer, row, from, to| {
+                buffer
+                    .delete_str(Position::new(row, from), to - from)
+                    .map(|_| ())
+            })?;
+            *cursor = start;
+
+            if operator == Operator::Delete {
+                return Ok(VisualEffect::Normal);
+            }
+            Ok(VisualEffect::BlockInsert(BlockInsert {
+                start,
+                rows,
+                column: left,
+                at_eol: false,
+            }))
+        }
+        _ => {
+            for_each_span(selection, buffer, |buffer, row, from, to| {
+                command::transform_span(buffer, row, from, to, |text| {
+                    command::convert_case(operator, text)
+                })
+            })?;
+            *cursor = start;
+            Ok(VisualEffect::Normal)
+        }
+    }
+}
+
+/// 選択範囲をレジスタの内容で置き換える（置き換えたテキストはレジスタに入る）
+fn put(
+    selection: &Selection,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+    registers: &mut Registers,
+) -> Result<VisualEffect> {
+    let register = registers.unnamed().clone();
+    let start = block_start(selection, buffer);
+
+    buffer.begin_undo_group();
+    let result = (|| {
+        let replaced = if selection.kind == VisualKind::Block {
+            let text = block_text(selection, buffer);
+            for_each_span(selection, buffer, |buffer, row, from, to| {
+                buffer
+                    .delete_str(Position::new(row, from), to - from)
+                    .map(|_| ())
+            })?;
+            buffer.insert_text(start, &register.text)?;
+            Register::charwise(text)
+        } else if selection.kind == VisualKind::Line {
+            let (first, last) = (selection.first_row(), selection.last_row());
+            let whole_buffer = first == 0 && last + 1 == buffer.line_count();
+            let lines = buffer.delete_lines(first, last)?;
+
+            // 削除した行の位置に、レジスタの内容を独立した行として入れる
+            for (i, line) in register.text.split('\n').enumerate() {
+                buffer.insert_line_with(first + i, line.to_string())?;
+            }
+            if whole_buffer {
+                buffer.delete_line(buffer.line_count() - 1)?;
+            }
+            Register::linewise(lines.join("\n"))
+        } else {
+            let range = selection.text_range(buffer);
+            let text = buffer.delete_range(range.start, range.end)?;
+            if register.linewise {
+                buffer.insert_text(start, &format!("\n{}\n", register.text))?;
+            } else {
+                buffer.insert_text(start, &register.text)?;
+            }
+            Register::charwise(text)
+        };
+        Ok::<_, EditorError>(replaced)
+    })();
+    buffer.end_undo_group();
+
+    registers.set(result?);
+    *cursor = if selection.kind == VisualKind::Line || register.linewise {
+        let row = if selection.kind == VisualKind::Line {
+            start.row
+        } else {
+            start.row + 1
+        };
+        Position::new(row, buffer.first_non_blank(row)?)
+    } else {
+        start
+    };
+    Ok(VisualEffect::Normal)
+}
+
+fn start_insert(
+    action: VisualAction,
+    selection: &Selection,
+    buffer: &mut Buffer,
+    cursor: &mut Position,
+) -> Result<VisualEffect> {
+    let append = action == VisualAction::Append;
+
+    if selection.kind != VisualKind::Block {
+        *cursor = if append {
+            selection.text_range(buffer).last_position(buffer)
+        } else {
+            selection.start()
+        };
+        if append {
+            let line = buffer.line(cursor.row)?;
+            cursor.col = next_boundary(line, cursor.col);
+        }
+        return Ok(VisualEffect::Insert);
+    }
+
+    let (left, right) = selection.block_columns(buffer);
+    let row = selection.first_row();
+    let column = if append { right + 1 } else { left };
+    let tabstop = buffer.options().tabstop;
+
+    let line = buffer.line(row)?;
+    let col = if append && selection.to_eol {
+        line.len()
+    } else {
+        let width = virtual_column(line, line.len(), tabstop);
+        if append && width < column {
+            // 矩形の右側まで空白で埋める
+            buffer.insert_str(Position::new(row, line.len()), &" ".repeat(column - width))?;
+        }
+        byte_column(buffer.line(row)?, column, tabstop)
+    };
+    *cursor = Position::new(row, col);
+
+    // `I`では矩形に届かない短い行には挿入しない
+    let rows = selection
+        .rows()
+        .skip(1)
+        .filter(|&row| {
+            append
+                || buffer
+                    .line(row)
+                    .is_ok_and(|line| virtual_column(line, line.len(), tabstop) > column)
+        })
+        .collect();
+
+    Ok(VisualEffect::BlockInsert(BlockInsert {
+        start: *cursor,
+        rows,
+        column,
+        at_eol: append && selection.to_eol,
+    }))
+}
+
+/// 矩形挿入の終了時に、最初の行に入力したテキストを残りの行にも挿入する
+pub fn finish_block_insert(
+    block: &BlockInsert,
+    buffer: &mut Buffer,
+    cursor: Position,
+) -> Result<()> {
+    // 改行を入力した場合などは最初の行だけの変更とする
+    if cursor.row != block.start.row || cursor.col <= block.start.col {
+        return Ok(());
+    }
+    let text = buffer.text_range(block.start, cursor)?;
+    let tabstop = buffer.options().tabstop;
+
+    buffer.begin_undo_group();
+    let result = (|| {
+        for &row in &block.rows {
+            let line = buffer.line(row)?;
+            let width = virtual_column(line, line.len(), tabstop);
+
+            let col = if block.at_eol {
+                line.len()
+            } else if width < block.column {
+                buffer.insert_str(
+                    Position::new(row, line.len()),
+                    &" ".repeat(block.column - width),
+                )?;
+                buffer.line_length(row)?
+            } else {
+                byte_column(line, block.column, tabstop)
+            };
+            buffer.insert_str(Position::new(row, col), &text)?;
+        }
+        Ok(())
+    })();
+    buffer.end_undo_group();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(
+        content: &str,
+        anchor: Position,
+        cursor: Position,
+        kind: VisualKind,
+        action: VisualAction,
+    ) -> (Buffer, Position, Registers, VisualEffect) {
+        let mut buffer = Buffer::from_content(content);
+        let mut registers = Registers::new();
+        let mut position = cursor;
+        let selection = Selection::new(anchor, cursor, kind);
+        let effect = apply(
+            action,
+            1,
+            &selection,
+            &mut buffer,
+            &mut position,
+            &mut registers,
+        )
+        .unwrap();
+        (buffer, position, registers, effect)
+    }
+
+    fn lines(buffer: &Buffer) -> Vec<&str> {
+        (0..buffer.line_count())
+            .map(|row| buffer.line(row).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_selection_spans() {
+        let buffer = Buffer::from_content("abcdef\nghijkl\nmn");
+
+        let selection = Selection::new(Position::new(0, 2), Position::new(1, 1), VisualKind::Char);
+        assert_eq!(selection.span(&buffer, 0), Some((2, 6)));
+        assert_eq!(selection.span(&buffer, 1), Some((0, 2)));
+        assert_eq!(selection.span(&buffer, 2), None);
+
+        let selection = Selection::new(Position::new(1, 4), Position::new(0, 2), VisualKind::Line);
+        assert_eq!(selection.span(&buffer, 1), Some((0, 6)));
+
+        let mut selection =
+            Selection::new(Position::new(0, 4), Position::new(2, 1), VisualKind::Block);
+        assert_eq!(selection.span(&buffer, 0), Some((1, 5)));
+        assert_eq!(selection.span(&buffer, 2), Some((1, 2)));
+
+        selection.to_eol = true;
+        assert_eq!(selection.span(&buffer, 1), Some((1, 6)));
+    }
+
+    #[test]
+    fn test_mark_positions() {
+        let buffer = Buffer::from_content("abcdef\nghijkl\nmn");
+
+        let selection = Selection::new(Position::new(1, 4), Position::new(0, 2), VisualKind::Char);
+        assert_eq!(
+            selection.mark_positions(&buffer),
+            (Position::new(0, 2), Position::new(1, 4))
+        );
+
+        // 矩形は左上と右下の角
+        let selection = Selection::new(Position::new(0, 4), Position::new(1, 1), VisualKind::Block);
+        assert_eq!(
+            selection.mark_positions(&buffer),
+            (Position::new(0, 1), Position::new(1, 4))
+        );
+    }
+
+    #[test]
+    fn test_charwise_delete_and_yank() {
+        let (buffer, cursor, registers, effect) = run(
+            "Hello, World!",
+            Position::new(0, 5),
+            Position::new(0, 11),
+            VisualKind::Char,
+            VisualAction::Operator(Operator::Delete),
+        );
+        assert_eq!(lines(&buffer), ["Hello!"]);
+        assert_eq!(cursor, Position::new(0, 5));
+        assert_eq!(registers.unnamed(), &Register::charwise(", World"));
+        assert_eq!(effect, VisualEffect::Normal);
+
+        let (buffer, cursor, registers, _) = run(
+            "one\ntwo\nthree",
+            Position::new(2, 1),
+            Position::new(1, 0),
+            VisualKind::Line,
+            VisualAction::Operator(Operator::Yank),
+        );
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(cursor, Position::new(1, 0));
+        assert_eq!(registers.unnamed(), &Register::linewise("two\nthree"));
+    }
+
+    #[test]
+    fn test_change_enters_insert() {
+        let (buffer, _, _, effect) = run(
+            "one\ntwo\nthree",
+            Position::new(0, 0),
+            Position::new(1, 0),
+            VisualKind::Line,
+            VisualAction::Operator(Operator::Change),
+        );
+        assert_eq!(lines(&buffer), ["", "three"]);
+        assert_eq!(effect, VisualEffect::Insert);
+    }
+
+    #[test]
+    fn test_shift_and_case() {
+        let (buffer, cursor, _, _) = run(
+            "a\n\nb",
+            Position::new(0, 0),
+            Position::new(2, 0),
+            VisualKind::Line,
+            VisualAction::Operator(Operator::ShiftRight),
+        );
+        assert_eq!(lines(&buffer), ["    a", "", "    b"]);
+        assert_eq!(cursor, Position::new(0, 4));
+
+        let (buffer, _, _, _) = run(
+            "hello World",
+            Position::new(0, 3),
+            Position::new(0, 7),
+            VisualKind::Char,
+            VisualAction::Operator(Operator::ToggleCase),
+        );
+        assert_eq!(lines(&buffer), ["helLO wOrld"]);
+
+        let (buffer, _, _, _) = run(
+            "abc\ndef",
+            Position::new(0, 1),
+            Position::new(1, 1),
+            VisualKind::Block,
+            VisualAction::Operator(Operator::Uppercase),
+        );
+        assert_eq!(lines(&buffer), ["aBc", "dEf"]);
+    }
+
+    #[test]
+    fn test_add_to_number() {
+        let (buffer, cursor, _, _) = run(
+            "x 1\nno\nx 1 1\nx 1",
+            Position::new(0, 0),
+            Position::new(3, 0),
+            VisualKind::Line,
+            VisualAction::AddToNumber {
+                decrement: false,
+                progressive: true,
+            },
+        );
+        assert_eq!(lines(&buffer), ["x 2", "no", "x 3 1", "x 4"]);
+        assert_eq!(cursor, Position::new(0, 0));
+
+        // 選択範囲より前の数値は対象外
+        let (buffer, _, _, _) = run(
+            "7 8\n7 8",
+            Position::new(0, 1),
+            Position::new(1, 3),
+            VisualKind::Block,
+            VisualAction::AddToNumber {
+                decrement: true,
+                progressive: false,
+            },
+        );
+        assert_eq!(lines(&buffer), ["7 7", "7 7"]);
+    }
+
+    #[test]
+    fn test_shift_left_with_count() {
+        let mut buffer = Buffer::from_content("        x\n  y");
+        let mut cursor = Position::new(0, 0);
+        let mut registers = Registers::new();
+        let selection = Selection::new(Position::new(0, 0), Position::new(1, 0), VisualKind::Line);
+
+        apply(
+            VisualAction::Operator(Operator::ShiftLeft),
+            2,
+            &selection,
+            &mut buffer,
+            &mut cursor,
+            &mut registers,
+        )
+        .unwrap();
+        assert_eq!(lines(&buffer), ["x", "y"]);
+    }
+
+    #[test]
+    fn test_join_and_replace() {
+        let (buffer, cursor, _, _) = run(
+            "one\n   two\nthree",
+            Position::new(0, 0),
+            Position::new(2, 0),
+            VisualKind::Line,
+            VisualAction::Join { spaces: true },
+        );
+        assert_eq!(lines(&buffer), ["one two three"]);
+        assert_eq!(cursor, Position::new(0, 7));
+
+        let (buffer, _, _, _) = run(
+            "abcd\nefgh",
+            Position::new(0, 2),
+            Position::new(1, 1),
+            VisualKind::Char,
+            VisualAction::Replace('x'),
+        );
+        assert_eq!(lines(&buffer), ["abxx", "xxgh"]);
+    }
+
+    #[test]
+    fn test_block_delete() {
+        let (buffer, cursor, registers, _) = run(
+            "abcdef\nab\nabcdef",
+            Position::new(0, 1),
+            Position::new(2, 3),
+            VisualKind::Block,
+            VisualAction::Operator(Operator::Delete),
+        );
+        assert_eq!(lines(&buffer), ["aef", "a", "aef"]);
+        assert_eq!(cursor, Position::new(0, 1));
+        assert_eq!(registers.unnamed(), &Register::charwise("bcd\nb\nbcd"));
+    }
+
+    #[test]
+    fn test_put_replaces_selection() {
+        let mut buffer = Buffer::from_content("one two three");
+        let mut cursor = Position::new(0, 6);
+        let mut registers = Registers::new();
+        registers.set(Register::charwise("2"));
+        let selection = Selection::new(Position::new(0, 4), Position::new(0, 6), VisualKind::Char);
+
+        apply(
+            VisualAction::Put,
+            1,
+            &selection,
+            &mut buffer,
+            &mut cursor,
+            &mut registers,
+        )
+        .unwrap();
+        assert_eq!(lines(&buffer), ["one 2 three"]);
+        assert_eq!(registers.unnamed(), &Register::charwise("two"));
+
+        // 1回のundoで元に戻る
+        buffer.undo().unwrap();
+        assert_eq!(lines(&buffer), ["one two three"]);
+    }
+
+    #[test]
+    fn test_put_linewise_over_lines() {
+        let mut buffer = Buffer::from_content("a\nb\nc");
+        let mut cursor = Position::new(1, 0);
+        let mut registers = Registers::new();
+        registers.set(Register::linewise("x\ny"));
+        let selection = Selection::new(Position::new(1, 0), Position::new(2, 0), VisualKind::Line);
+
+        apply(
+            VisualAction::Put,
+            1,
+            &selection,
+            &mut buffer,
+            &mut cursor,
+            &mut registers,
+        )
+        .unwrap();
+        assert_eq!(lines(&buffer), ["a", "x", "y"]);
+        assert_eq!(registers.unnamed(), &Register::linewise("b\nc"));
+    }
+
+    #[test]
+    fn test_block_insert_is_repeated_on_each_line() {
+        let (mut buffer, mut cursor, _, effect) = run(
+            "abc\nx\nabc",
+            Position::new(0, 1),
+            Position::new(2, 1),
+            VisualKind::Block,
+            VisualAction::Insert,
+        );
+        let VisualEffect::BlockInsert(block) = effect else {
+            panic!("Expected block insert");
+        };
+        assert_eq!(cursor, Position::new(0, 1));
+
+        buffer.insert_str(cursor, "--").unwrap();
+        cursor.col += 2;
+        finish_block_insert(&block, &mut buffer, cursor).unwrap();
+        // 矩形に届かない行には挿入しない
+        assert_eq!(lines(&buffer), ["a--bc", "x", "a--bc"]);
+    }
+
+    #[test]
+    fn test_block_append_pads_and_to_eol() {
+        let (mut buffer, mut cursor, _, effect) = run(
+            "abc\nx\nabc",
+            Position::new(0, 1),
+            Position::new(2, 2),
+            VisualKind::Block,
+            VisualAction::Append,
+        );
+        let VisualEffect::BlockInsert(block) = effect else {
+            panic!("Expected block insert");
+        };
+        buffer.insert_str(cursor, "!").unwrap();
+        cursor.col += 1;
+        finish_block_insert(&block, &mut buffer, cursor).unwrap();
+        assert_eq!(lines(&buffer), ["abc!", "x  !", "abc!"]);
+
+        // `$`で選択した矩形は各行の行末に追加する
+        let mut buffer = Buffer::from_content("long line\nab");
+        let mut selection =
+            Selection::new(Position::new(0, 0), Position::new(1, 1), VisualKind::Block);
+        selection.to_eol = true;
+        let mut cursor = Position::new(1, 1);
+        let effect = apply(
+            VisualAction::Append,
+            1,
+            &selection,
+            &mut buffer,
+            &mut cursor,
+            &mut Registers::new(),
+        )
+        .unwrap();
+        let VisualEffect::BlockInsert(block) = effect else {
+            panic!("Expected block insert");
+        };
+        assert_eq!(cursor, Position::new(0, 9));
+        buffer.insert_str(cursor, ";").unwrap();
+        cursor.col += 1;
+        finish_block_insert(&block, &mut buffer, cursor).unwrap();
+        assert_eq!(lines(&buffer), ["long line;", "ab;"]);
+    }
+}